@@ -1,128 +1,3006 @@
 // config/mod.rs
 // Handles loading, building, and saving the user's SemanticOS configuration.
-// Config lives at ~/.config/semantic/config.toml
+// Config lives at ~/.config/semantic/config.toml (%APPDATA%\semantic on
+// Windows), or config.json beside it
+// (which wins if both exist; see `config_path`). $SEMANTIC_CONFIG_DIR or
+// $XDG_CONFIG_HOME move the directory (see `config_dir`).
+// A project can layer extra commands/paths on top via .semantic/config.toml
+// (or .semantic.toml) in the current directory or any parent, nearest winning;
+// $SEMANTIC_NO_LOCAL turns that off.
+// Packs in ~/.config/semantic/packs/ add shared commands/paths underneath the user's own.
+// Plugins in plugins/ beside it are the same for mapping sets downloaded from
+// elsewhere, except that one that doesn't load is skipped with a warning.
+// Style templates in templates/ beside it, if there are any, are checked against
+// the built-in styles when the wizard saves and on `semantic reset`.
+// `include = ["base.toml"]` in the user config merges other files in beneath it.
+// [commands.<shell>] tables override individual commands for one shell.
+// `name = { shell = "..." }` runs the value as a shell script (pipes and all).
+// [styles.commands.<style>] and [styles.paths.<style>] add to or replace a
+// style's stock mappings wherever they're used (the wizard, `semantic reset`).
+// [safety] allow_shadowing lists command names allowed to hide a program or
+// shell builtin of the same name without a warning.
+// [overrides.<os>.commands] and [overrides.<os>.paths] (os: linux, macos or
+// windows) replace individual mappings on that OS only, so one config can be
+// shared between machines.
+// Named profiles live in profiles/<name>.toml; the active one (recorded in
+// `profile`, or $SEMANTIC_PROFILE / --profile for one run) stands in for the
+// user config. The plain config file is the "default" profile.
+// Reading and writing the user config take an advisory lock on .lock in the
+// config dir, so two semantic processes can't interleave a save.
+// `SemanticConfig::watch` reloads the config as its files change, for
+// long-running consumers.
+// [general] semantic_bin_dir names the directory the semantic binary is in,
+// which the init code puts at the front of $PATH for installs outside it.
+// The parsed user config is cached in ~/.cache/semantic, one file per config
+// file (see `DiskCache`), so a command run from an alias skips parsing TOML
+// while the file is unchanged; $SEMANTIC_NO_CACHE turns that off.
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::env;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError, mpsc};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::error::SemanticError;
+use crate::shell::{
+    self, ExtraArgs, IntegrationMode, SEPARATOR_MARK, extra_args_mark, find_in_path,
+    normalize_shell_name,
+};
 
 // -- config structs (mirrors config.toml layout) --
 
-/// Top-level config. Serializes directly to/from config.toml.
-#[derive(Debug, Serialize, Deserialize)]
+/// Top-level config. Serializes to/from config.toml by way of `RawConfig`.
+/// Every section defaults to empty so project-local configs can hold just
+/// [commands] and/or [paths].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "RawConfig", into = "RawConfig")]
 pub struct SemanticConfig {
+    /// The config schema the file was written for (see `migrate`); 0 for
+    /// files from before it was recorded.
+    pub version: u32,
+
+    /// Files whose mappings `load()` merges in underneath this one, in order:
+    /// absolute, or relative to `config_dir`.
+    pub include: Vec<String>,
+
     pub general: GeneralConfig,
     pub shells: ShellConfig,
+    pub theme: ThemeConfig,
+    pub styles: StyleOverrides,
+    pub safety: SafetyConfig,
+    pub overrides: OsOverrides,
+    pub commands: HashMap<String, String>,
+
+    /// Per-shell overrides from `[commands.<shell>]` tables, keyed by shell name.
+    /// These win over `commands` for that shell (see `commands_for`).
+    pub shell_commands: HashMap<String, HashMap<String, String>>,
+
+    /// Commands written as an array of steps, run in order by `semantic translate`.
+    pub composite_commands: HashMap<String, Vec<String>>,
+
+    /// Names in `commands` written as `{ shell = "..." }`. Their value runs as a
+    /// script in the default shell, with the user's args as its "$@".
+    pub shell_mode_commands: HashSet<String>,
+
+    /// Names in `commands` written as `{ command = "...", translate_paths = false }`.
+    /// Their arguments are passed on as given, never read as paths (grep's are
+    /// patterns, say).
+    pub untranslated_commands: HashSet<String>,
+
+    pub paths: HashMap<String, String>,
+
+    /// Project-local configs that were merged in by `load()`, outermost first
+    /// (so the last one won any key they share). Never serialized.
+    pub project_configs: Vec<PathBuf>,
+
+    /// Mappings `load()` took from an included file, and which file. Never serialized.
+    pub include_sources: BTreeMap<MappingKey, PathBuf>,
+
+    /// Mappings `load()` took from a pack file, and which file. Never serialized.
+    pub pack_sources: BTreeMap<MappingKey, PathBuf>,
+
+    /// Pack mappings `load()` skipped because the user config (or an earlier
+    /// pack) already maps the key differently. Never serialized.
+    pub pack_collisions: Vec<(PathBuf, MappingKey)>,
+
+    /// Plugin files `load()` left out because they couldn't be read or parsed,
+    /// with the error. Never serialized.
+    pub skipped_plugins: Vec<(PathBuf, String)>,
+
+    /// Mappings `load()` took from this OS's `[overrides.<os>]`. Never serialized.
+    pub os_overridden: Vec<MappingKey>,
+}
+
+/// Where `load_with_source` found a config: the user config file, the nearest
+/// project config merged on top (all of them are in `project_configs`), the
+/// profile if it isn't the default one, and the plugin files merged in.
+#[derive(Debug, Clone)]
+pub struct ConfigSource {
+    pub user_config_path: PathBuf,
+    pub project_config_path: Option<PathBuf>,
+    pub active_profile: Option<String>,
+    pub plugins_loaded: Vec<String>,
+}
+
+/// config.toml as written on disk. `[commands]` mixes plain mappings, step arrays,
+/// and `[commands.<shell>]` tables, which `SemanticConfig` keeps in separate fields.
+#[derive(Serialize, Deserialize)]
+struct RawConfig {
+    #[serde(default)]
+    version: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    include: Vec<String>,
+    #[serde(default)]
+    general: GeneralConfig,
+    #[serde(default)]
+    shells: ShellConfig,
+    #[serde(default, skip_serializing_if = "ThemeConfig::is_empty")]
+    theme: ThemeConfig,
+    #[serde(default, skip_serializing_if = "StyleOverrides::is_empty")]
+    styles: StyleOverrides,
+    #[serde(default, skip_serializing_if = "SafetyConfig::is_empty")]
+    safety: SafetyConfig,
+    #[serde(default, skip_serializing_if = "OsOverrides::is_empty")]
+    overrides: OsOverrides,
+    #[serde(default)]
+    commands: HashMap<String, CommandEntry>,
+    #[serde(default)]
+    paths: HashMap<String, String>,
+}
+
+/// One entry under `[commands]`: a mapping, a list of steps, a shell-mode
+/// mapping, a mapping with options, or a table of overrides for one shell.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum CommandEntry {
+    Command(String),
+    Steps(Vec<String>),
+    Script(ScriptEntry),
+    Options(OptionsEntry),
+    Shell(HashMap<String, String>),
+}
+
+/// `name = { shell = "..." }`. Only a table whose sole key is `shell` counts;
+/// anything else is read as per-shell overrides.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScriptEntry {
+    shell: String,
+}
+
+/// `name = { command = "...", translate_paths = false }`. Both keys are needed,
+/// so a per-shell table with a command named `command` still reads as one.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OptionsEntry {
+    command: String,
+    translate_paths: bool,
+}
+
+impl From<RawConfig> for SemanticConfig {
+    fn from(raw: RawConfig) -> Self {
+        let mut commands = HashMap::new();
+        let mut shell_commands = HashMap::new();
+        let mut composite_commands = HashMap::new();
+        let mut shell_mode_commands = HashSet::new();
+        let mut untranslated_commands = HashSet::new();
+        for (key, entry) in raw.commands {
+            match entry {
+                CommandEntry::Command(real_cmd) => {
+                    commands.insert(key, real_cmd);
+                }
+                CommandEntry::Steps(steps) => {
+                    composite_commands.insert(key, steps);
+                }
+                CommandEntry::Script(ScriptEntry { shell }) => {
+                    shell_mode_commands.insert(key.clone());
+                    commands.insert(key, shell);
+                }
+                CommandEntry::Options(OptionsEntry { command, translate_paths }) => {
+                    if !translate_paths {
+                        untranslated_commands.insert(key.clone());
+                    }
+                    commands.insert(key, command);
+                }
+                CommandEntry::Shell(overrides) => {
+                    shell_commands.insert(key, overrides);
+                }
+            }
+        }
+
+        SemanticConfig {
+            version: raw.version,
+            include: raw.include,
+            general: raw.general,
+            shells: raw.shells,
+            theme: raw.theme,
+            styles: raw.styles,
+            safety: raw.safety,
+            overrides: raw.overrides,
+            commands,
+            shell_commands,
+            composite_commands,
+            shell_mode_commands,
+            untranslated_commands,
+            paths: raw.paths,
+            project_configs: Vec::new(),
+            include_sources: BTreeMap::new(),
+            pack_sources: BTreeMap::new(),
+            pack_collisions: Vec::new(),
+            skipped_plugins: Vec::new(),
+            os_overridden: Vec::new(),
+        }
+    }
+}
+
+impl From<SemanticConfig> for RawConfig {
+    fn from(config: SemanticConfig) -> Self {
+        let shell_mode = config.shell_mode_commands;
+        let untranslated = config.untranslated_commands;
+        let commands = config
+            .commands
+            .into_iter()
+            .map(|(name, real_cmd)| {
+                let entry = if shell_mode.contains(&name) {
+                    CommandEntry::Script(ScriptEntry { shell: real_cmd })
+                } else if untranslated.contains(&name) {
+                    CommandEntry::Options(OptionsEntry { command: real_cmd, translate_paths: false })
+                } else {
+                    CommandEntry::Command(real_cmd)
+                };
+                (name, entry)
+            })
+            .chain(
+                config
+                    .composite_commands
+                    .into_iter()
+                    .map(|(name, steps)| (name, CommandEntry::Steps(steps))),
+            )
+            .chain(
+                config
+                    .shell_commands
+                    .into_iter()
+                    .map(|(shell, overrides)| (shell, CommandEntry::Shell(overrides))),
+            )
+            .collect();
+
+        RawConfig {
+            version: config.version,
+            include: config.include,
+            general: config.general,
+            shells: config.shells,
+            theme: config.theme,
+            styles: config.styles,
+            safety: config.safety,
+            overrides: config.overrides,
+            commands,
+            paths: config.paths,
+        }
+    }
+}
+
+/// Where a single mapping lives in the config.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MappingKey {
+    /// `[commands]`, plain or composite.
+    Command(String),
+    /// `[commands.<shell>]`: shell, then name.
+    ShellCommand(String, String),
+    /// `[paths]`.
+    Path(String),
+}
+
+impl fmt::Display for MappingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MappingKey::Command(name) => write!(f, "commands.{name}"),
+            MappingKey::ShellCommand(shell, name) => write!(f, "commands.{shell}.{name}"),
+            MappingKey::Path(virtual_path) => write!(f, "paths.\"{virtual_path}\""),
+        }
+    }
+}
+
+/// User preferences for command and folder styles.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GeneralConfig {
+    pub command_style: String,
+    pub folder_style: String,
+
+    /// Log every translated command to history.jsonl. Off unless set.
+    #[serde(default)]
+    pub history: bool,
+
+    /// Rotate the history log once it's bigger than this (default: `history::DEFAULT_MAX_KB`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history_max_kb: Option<u64>,
+
+    /// Show real paths in what translated commands print under their virtual
+    /// names (`/usr/bin/vim` as `/apps/vim`; see `PathRewriter`). Their stdout then
+    /// goes through a pipe rather than straight to the terminal, so it's off unless set.
+    #[serde(default)]
+    pub rewrite_output: bool,
+
+    /// Whether moving past either end of a setup wizard list wraps around to
+    /// the other end (default) or stops there.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wrap_navigation: Option<bool>,
+
+    /// Code from `LANGUAGES` that the natural style's names are in (default: English).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+
+    /// The setup wizard's built-in palette ("default", "gruvbox", ...), under
+    /// any colors set in `[theme]`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<String>,
+
+    /// The directory semantic is installed in (`~/.cargo/bin`), which the init
+    /// code puts at the front of $PATH. Unset or empty leaves $PATH alone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub semantic_bin_dir: Option<String>,
+}
+
+impl GeneralConfig {
+    /// Take the settings `overlay` sets: its styles if not empty, its options if
+    /// set, and history/rewrite_output if turned on.
+    pub fn merge(&mut self, overlay: GeneralConfig) {
+        if !overlay.command_style.is_empty() {
+            self.command_style = overlay.command_style;
+        }
+        if !overlay.folder_style.is_empty() {
+            self.folder_style = overlay.folder_style;
+        }
+        self.history |= overlay.history;
+        self.rewrite_output |= overlay.rewrite_output;
+        self.history_max_kb = overlay.history_max_kb.or(self.history_max_kb);
+        self.wrap_navigation = overlay.wrap_navigation.or(self.wrap_navigation);
+        self.language = overlay.language.or(self.language.take());
+        self.theme = overlay.theme.or(self.theme.take());
+        self.semantic_bin_dir = overlay.semantic_bin_dir.or(self.semantic_bin_dir.take());
+    }
+}
+
+/// Shell-related settings: which shell, which are enabled, what to do on new installs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ShellConfig {
+    pub default: String,
+    pub enabled: Vec<String>,
+    pub on_new_shell: String,
+    /// "alias" or "function": how `semantic init` defines commands (see
+    /// `shell::IntegrationMode`). Unset keeps each shell's usual style.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integration_mode: Option<String>,
+}
+
+impl ShellConfig {
+    /// Take the settings `overlay` sets: its default and on_new_shell if not
+    /// empty, its integration mode if set, and its enabled shells on top of
+    /// these (in order, without repeats).
+    pub fn merge(&mut self, overlay: ShellConfig) {
+        if !overlay.default.is_empty() {
+            self.default = overlay.default;
+        }
+        if !overlay.on_new_shell.is_empty() {
+            self.on_new_shell = overlay.on_new_shell;
+        }
+        self.integration_mode = overlay.integration_mode.or(self.integration_mode.take());
+        for shell in overlay.enabled {
+            if !self.enabled.contains(&shell) {
+                self.enabled.push(shell);
+            }
+        }
+    }
+}
+
+/// Colors for the setup wizard, as color names ("cyan", "dark gray"), 256-color
+/// indices, or "#rrggbb". Unset fields keep the colors of the `[general] theme`
+/// palette (see `tui::Theme`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ThemeConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub muted: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Plain text: prompts and unselected options.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Text on the selected row, whose background is `accent`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selected: Option<String>,
+}
+
+impl ThemeConfig {
+    fn is_empty(&self) -> bool {
+        [
+            &self.accent,
+            &self.success,
+            &self.muted,
+            &self.warning,
+            &self.error,
+            &self.text,
+            &self.selected,
+        ]
+        .iter()
+        .all(|color| color.is_none())
+    }
+}
+
+/// `[safety]`: warnings the user has seen and chosen to live with.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SafetyConfig {
+    /// Command names that may hide a program or shell builtin of the same name
+    /// without a warning (see `SemanticConfig::shadowed_commands`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_shadowing: Vec<String>,
+}
+
+impl SafetyConfig {
+    fn is_empty(&self) -> bool {
+        self.allow_shadowing.is_empty()
+    }
+}
+
+/// `[overrides]`: mappings for one OS only, by `std::env::consts::OS` name.
+/// `load()` layers the running OS's on top of `[commands]` and `[paths]`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OsOverrides {
+    #[serde(default, skip_serializing_if = "OsMappings::is_empty")]
+    pub linux: OsMappings,
+    #[serde(default, skip_serializing_if = "OsMappings::is_empty")]
+    pub macos: OsMappings,
+    #[serde(default, skip_serializing_if = "OsMappings::is_empty")]
+    pub windows: OsMappings,
+}
+
+/// The commands and paths in one `[overrides.<os>]`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OsMappings {
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub commands: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub paths: HashMap<String, String>,
 }
 
-/// User preferences for command and folder styles.
-#[derive(Debug, Serialize, Deserialize)]
-pub struct GeneralConfig {
-    pub command_style: String,
-    pub folder_style: String,
+impl OsOverrides {
+    fn is_empty(&self) -> bool {
+        self.linux.is_empty() && self.macos.is_empty() && self.windows.is_empty()
+    }
+
+    /// The overrides for `os`, if it's one that can have them.
+    pub fn for_os(&self, os: &str) -> Option<&OsMappings> {
+        match os {
+            "linux" => Some(&self.linux),
+            "macos" => Some(&self.macos),
+            "windows" => Some(&self.windows),
+            _ => None,
+        }
+    }
+}
+
+impl OsMappings {
+    fn is_empty(&self) -> bool {
+        self.commands.is_empty() && self.paths.is_empty()
+    }
+}
+
+/// `[styles]`: the user's own mappings for a style, by style name. Wherever a
+/// style's defaults are used they're added on top, winning over a stock mapping
+/// of the same name — so `[styles.commands.natural] install = "sudo eopkg install"`
+/// survives `semantic reset`. A name that isn't a built-in style works too,
+/// as a style made only of the user's mappings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StyleOverrides {
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub commands: HashMap<String, HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub paths: HashMap<String, HashMap<String, String>>,
+}
+
+impl StyleOverrides {
+    fn is_empty(&self) -> bool {
+        self.commands.is_empty() && self.paths.is_empty()
+    }
+
+    /// `style_commands` for `style`, with the user's `[styles.commands.<style>]` on top.
+    pub fn commands(&self, style: &str, language: &str) -> HashMap<String, String> {
+        let mut commands = style_commands(style, language);
+        commands.extend(self.own_commands(style));
+        commands
+    }
+
+    /// `style_paths` for `style`, with the user's `[styles.paths.<style>]` on top.
+    pub fn paths(&self, style: &str, language: &str) -> HashMap<String, String> {
+        let mut paths = style_paths(style, language);
+        paths.extend(self.own_paths(style));
+        paths
+    }
+
+    /// Just the user's `[styles.commands.<style>]`.
+    pub fn own_commands(&self, style: &str) -> HashMap<String, String> {
+        self.commands.get(style).cloned().unwrap_or_default()
+    }
+
+    /// Just the user's `[styles.paths.<style>]`.
+    pub fn own_paths(&self, style: &str) -> HashMap<String, String> {
+        self.paths.get(style).cloned().unwrap_or_default()
+    }
+}
+
+impl SemanticConfig {
+    /// Build a config from the TUI installer selections.
+    /// Picks the right command/path mappings based on the chosen styles, with
+    /// the user's `styles` on top (and kept in the result). `semantic_bin_dir`
+    /// is where this semantic runs from (see `detect_bin_dir`).
+    /// `default_shell` should also appear in `enabled_shells`.
+    /// `custom_commands` are added on top and override style commands of the same name.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_selections(
+        default_shell: &str,
+        enabled_shells: &[&str],
+        command_style: &str,
+        folder_style: &str,
+        language: &str,
+        on_new_shell: &str,
+        custom_commands: &[(String, String)],
+        styles: &StyleOverrides,
+    ) -> Self {
+        let mut commands = styles.commands(command_style, language);
+        commands.extend(custom_commands.iter().cloned());
+
+        let paths = styles.paths(folder_style, language);
+
+        SemanticConfig {
+            version: CURRENT_SCHEMA_VERSION,
+            include: Vec::new(),
+            general: GeneralConfig {
+                command_style: command_style.to_string(),
+                folder_style: folder_style.to_string(),
+                history: false,
+                history_max_kb: None,
+                rewrite_output: false,
+                wrap_navigation: None,
+                language: Some(language.to_string()),
+                theme: None,
+                semantic_bin_dir: detect_bin_dir(),
+            },
+            shells: ShellConfig {
+                default: default_shell.to_string(),
+                enabled: enabled_shells.iter().map(|s| s.to_string()).collect(),
+                on_new_shell: on_new_shell.to_string(),
+                integration_mode: None,
+            },
+            theme: ThemeConfig::default(),
+            styles: styles.clone(),
+            safety: SafetyConfig::default(),
+            overrides: OsOverrides::default(),
+            commands,
+            shell_commands: HashMap::new(),
+            composite_commands: HashMap::new(),
+            shell_mode_commands: HashSet::new(),
+            untranslated_commands: HashSet::new(),
+            paths,
+            project_configs: Vec::new(),
+            include_sources: BTreeMap::new(),
+            pack_sources: BTreeMap::new(),
+            pack_collisions: Vec::new(),
+            skipped_plugins: Vec::new(),
+            os_overridden: Vec::new(),
+        }
+    }
+
+    /// Command names `semantic init` would define for `shell` that hide a
+    /// program in $PATH or a shell builtin of the same name, with what they
+    /// hide, sorted by name (see `shell::shadowed`). Names in `[safety]
+    /// allow_shadowing` are left out.
+    pub fn shadowed_commands(&self, shell: &str) -> Vec<(String, String)> {
+        let commands = self.commands_for(shell);
+        let composites = self
+            .composite_commands
+            .iter()
+            .map(|(name, steps)| (name.as_str(), steps.first().map_or("", String::as_str)));
+        shell::shadowed(
+            commands.iter().map(|(name, real_cmd)| (name.as_str(), real_cmd.as_str())).chain(composites),
+        )
+        .into_iter()
+        .filter(|(name, _)| !self.safety.allow_shadowing.contains(name))
+        .collect()
+    }
+
+    /// `[general] semantic_bin_dir` with `~` and environment variables expanded,
+    /// for the init code; None when it's unset or empty.
+    pub fn bin_dir(&self) -> Option<String> {
+        let dir = expand_env(self.general.semantic_bin_dir.as_deref()?.trim());
+        if dir.is_empty() {
+            return None;
+        }
+        match dir.strip_prefix('~') {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+                Some(format!("{}{rest}", dirs::home_dir()?.display()))
+            }
+            _ => Some(dir),
+        }
+    }
+
+    /// `[shells] integration_mode` as a mode; None when unset (or invalid,
+    /// which `validate` reports).
+    pub fn integration_mode(&self) -> Option<IntegrationMode> {
+        self.shells.integration_mode.as_deref().and_then(IntegrationMode::from_name)
+    }
+
+    /// The command mappings as seen from `shell`: the base `[commands]` with
+    /// that shell's `[commands.<shell>]` overrides layered on top.
+    pub fn commands_for(&self, shell: &str) -> HashMap<String, String> {
+        let mut commands = self.commands.clone();
+        if let Some(overrides) = self.shell_commands.get(shell) {
+            commands.extend(overrides.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        commands
+    }
+
+    /// Whether `name` runs in shell mode for `shell`. A plain per-shell override
+    /// replaces the shell-mode mapping for that shell.
+    pub fn is_shell_mode(&self, name: &str, shell: &str) -> bool {
+        self.shell_mode_commands.contains(name)
+            && !self.shell_commands.get(shell).is_some_and(|o| o.contains_key(name))
+    }
+
+    /// `real_cmd` (the mapping for `name`) with a leading reference to another
+    /// semantic command replaced by that command's mapping, repeatedly, so
+    /// `ll = "list -h"` runs whatever `list` maps to plus `-h`.
+    /// A mapping that starts with its own name (`ls = "ls --color"`) means the
+    /// real program, and shell-mode commands are scripts, so neither is followed.
+    /// Errors on a cycle, or a chain through more than `MAX_REFERENCE_DEPTH` commands.
+    pub fn resolve_references(
+        &self,
+        commands: &HashMap<String, String>,
+        name: &str,
+        real_cmd: &str,
+        shell: &str,
+    ) -> Result<String, String> {
+        let mut chain = vec![name];
+        let mut resolved = real_cmd.to_string();
+
+        loop {
+            let trimmed = resolved.trim_start();
+            let head = trimmed.split_whitespace().next().unwrap_or("");
+            let Some((head, target)) = commands.get_key_value(head) else {
+                return Ok(resolved);
+            };
+            if target.split_whitespace().next() == Some(head.as_str())
+                || self.is_shell_mode(head, shell)
+            {
+                return Ok(resolved);
+            }
+
+            if chain.contains(&head.as_str()) {
+                chain.push(head);
+                return Err(format!("refers back to itself ({})", chain.join(" → ")));
+            }
+            if chain.len() > MAX_REFERENCE_DEPTH {
+                return Err(format!(
+                    "goes through more than {MAX_REFERENCE_DEPTH} other commands ({} → ...)",
+                    chain.join(" → ")
+                ));
+            }
+            chain.push(head);
+
+            // the referenced command's `!` or `--` (see `shell::extra_args_mark`)
+            // still applies to what's passed on to it
+            let rest = &trimmed[head.len()..];
+            resolved = match extra_args_mark(target) {
+                (_, ExtraArgs::Allowed) => format!("{target}{rest}"),
+                (_, ExtraArgs::Rejected) if rest.trim().is_empty() => target.clone(),
+                (_, ExtraArgs::Rejected) => {
+                    return Err(format!(
+                        "passes `{}` to `{head}`, which takes no arguments",
+                        rest.trim()
+                    ));
+                }
+                (target, ExtraArgs::AfterSeparator) => format!("{target}{rest} {SEPARATOR_MARK}"),
+            };
+        }
+    }
+
+    /// The semantic command `real_cmd` (a mapping or step of `name`) runs in turn,
+    /// by the rules of `resolve_references`: one starting with another command,
+    /// or any mapping that calls `semantic translate <command>`. Starting with its
+    /// own name means the real program, except in a shell-mode script, where the
+    /// shell may well have `name` defined as the function semantic generated.
+    fn referenced<'a>(
+        &self,
+        commands: &'a HashMap<String, String>,
+        name: &str,
+        real_cmd: &'a str,
+        shell: &str,
+    ) -> Option<&'a str> {
+        let mut words = real_cmd.split_whitespace();
+        let head = words.next()?;
+        let is_semantic = Path::new(head)
+            .file_stem()
+            .is_some_and(|stem| stem == env!("CARGO_PKG_NAME"));
+        if is_semantic {
+            if words.next() != Some("translate") {
+                return None;
+            }
+            let target = words.find(|word| !word.starts_with('-'))?;
+            return (commands.contains_key(target) || self.composite_commands.contains_key(target))
+                .then_some(target);
+        }
+        let shell_mode = self.is_shell_mode(name, shell);
+        if head == name {
+            return shell_mode.then_some(head);
+        }
+        (!shell_mode && commands.contains_key(head)).then_some(head)
+    }
+
+    /// Command names that clash with paths: one that's also a virtual path key,
+    /// or one that looks like a path itself (starts with `/` or `~`), which
+    /// would leave `translate` and `which` guessing which was meant. Each as
+    /// (name, what's wrong), sorted by name; a per-shell override's name says
+    /// which shell (`fish./apps`).
+    pub fn key_collisions(&self) -> Vec<(String, String)> {
+        let names = self
+            .commands
+            .keys()
+            .chain(self.composite_commands.keys())
+            .map(|name| (String::new(), name))
+            .chain(self.shell_commands.iter().flat_map(|(shell, overrides)| {
+                overrides.keys().map(move |name| (format!("{shell}."), name))
+            }));
+        let mut collisions: Vec<(String, String)> = names
+            .filter_map(|(prefix, name)| {
+                let problem = if self.paths.contains_key(name) {
+                    "is both a command and a path; rename one of them"
+                } else if name.starts_with(['/', '~']) {
+                    "looks like a path; commands are typed as words, and paths go under [paths]"
+                } else {
+                    return None;
+                };
+                Some((format!("{prefix}{name}"), problem.to_string()))
+            })
+            .collect();
+        collisions.sort();
+        collisions.dedup();
+        collisions
+    }
+
+    /// Commands that would end up running themselves forever: a chain of mappings
+    /// leading back to where it started (`ll = "list"` with `list = "ll -a"`),
+    /// a shell-mode script starting with its own name, or a mapping calling
+    /// `semantic translate` on itself. Identity mappings (`cd = "cd"`,
+    /// `ls = "ls --color"`) run the real program and aren't loops.
+    /// One message per loop, naming it (`list → ll → list`); a loop that only
+    /// exists in one shell's `[commands.<shell>]` says which shell.
+    pub fn reference_cycles(&self) -> Vec<String> {
+        let mut shells: Vec<&str> = self.shell_commands.keys().map(String::as_str).collect();
+        shells.sort();
+        shells.insert(0, "");
+
+        let mut seen: Vec<Vec<String>> = Vec::new();
+        let mut errors = Vec::new();
+        for shell in shells {
+            let commands = self.commands_for(shell);
+            let mut edges: HashMap<&str, Vec<&str>> = HashMap::new();
+            for (name, real_cmd) in &commands {
+                edges
+                    .entry(name.as_str())
+                    .or_default()
+                    .extend(self.referenced(&commands, name, real_cmd, shell));
+            }
+            for (name, steps) in &self.composite_commands {
+                if commands.contains_key(name) {
+                    continue;
+                }
+                let targets = steps.iter().filter_map(|step| self.referenced(&commands, name, step, shell));
+                edges.entry(name.as_str()).or_default().extend(targets);
+            }
+
+            let mut names: Vec<&str> = edges.keys().copied().collect();
+            names.sort();
+            for start in names {
+                let Some(chain) = cycle_from(&edges, start) else {
+                    continue;
+                };
+                // the same loop is found from each command in it, and in every shell
+                let mut members: Vec<String> = chain[1..].iter().map(|s| s.to_string()).collect();
+                members.sort();
+                if seen.contains(&members) {
+                    continue;
+                }
+                seen.push(members);
+                let what = if chain.len() == 2 { "calls itself" } else { "is part of a loop" };
+                let shell = if shell.is_empty() { String::new() } else { format!(" (in {shell})") };
+                errors.push(format!("command `{start}` {what}{shell}: {}", chain.join(" → ")));
+            }
+        }
+        errors
+    }
+
+    /// Load the effective config: the user config on top of the files it includes,
+    /// with its `[overrides]` for this OS on top of both (see `apply_os_overrides`),
+    /// with the packs in `packs_dir` and then the plugins in `plugins_dir`
+    /// filling in keys it doesn't have (see `with_pack`), and the
+    /// project-local configs of the current directory and its parents merged on
+    /// top, the nearest last so it wins (see `find_project_configs`).
+    /// $SEMANTIC_NO_LOCAL leaves project configs out, for untrusted checkouts.
+    /// Returns `ConfigNotFound` if there's no user config yet, and `ConfigParse`
+    /// if any of those files exists but can't be read or parsed, or if an include
+    /// is missing or includes itself. `BadMapping` if, all merged, a command
+    /// name clashes with a path (see `key_collisions`).
+    /// Mappings are returned as written; environment variables are expanded by
+    /// `expand_env_vars` (or per mapping at translate time).
+    pub fn load() -> Result<Self, SemanticError> {
+        Self::load_with_source().map(|(config, _)| config)
+    }
+
+    /// `load`, along with where the config came from (see `ConfigSource`).
+    pub fn load_with_source() -> Result<(Self, ConfigSource), SemanticError> {
+        let path = Self::config_path();
+        log::info!("config {} (profile {})", path.display(), active_profile());
+        if !path.exists() {
+            return Err(SemanticError::ConfigNotFound { path });
+        }
+        let parse_error = |path: &Path| {
+            let path = path.to_path_buf();
+            move |source| SemanticError::ConfigParse { path, source }
+        };
+
+        let mut config = with_config_lock(Lock::Shared, || {
+            Self::load_with_includes(&path, &mut Vec::new())
+        })
+        .map_err(parse_error(&path))?;
+        // an older file still works as is; `semantic upgrade` writes it migrated
+        migrate(&mut config)
+            .map_err(|e| format!("{}: {e}", path.display()).into())
+            .map_err(parse_error(&path))?;
+        config.apply_os_overrides(env::consts::OS);
+
+        for pack_path in mapping_files(&packs_dir()).map_err(parse_error(&packs_dir()))? {
+            log::debug!("pack {}", pack_path.display());
+            let pack = Self::load_from(&pack_path).map_err(parse_error(&pack_path))?;
+            config = config.with_pack(pack, &pack_path);
+        }
+        // plugins come from elsewhere, so a broken one shouldn't break semantic
+        let mut plugins_loaded = Vec::new();
+        for plugin_path in mapping_files(&plugins_dir()).map_err(parse_error(&plugins_dir()))? {
+            log::debug!("plugin {}", plugin_path.display());
+            match Self::load_from(&plugin_path) {
+                Ok(plugin) => {
+                    let name = plugin_path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                    config = config.with_pack(plugin, &plugin_path);
+                    plugins_loaded.push(name);
+                }
+                Err(e) => config.skipped_plugins.push((plugin_path, e.to_string())),
+            }
+        }
+
+        let project_paths = match env::current_dir() {
+            Ok(dir) if !local_configs_disabled() => find_project_configs(&dir),
+            Ok(_) => {
+                log::debug!("SEMANTIC_NO_LOCAL is set, skipping project configs");
+                Vec::new()
+            }
+            Err(_) => Vec::new(),
+        };
+        for project_path in project_paths {
+            log::info!("project config {}", project_path.display());
+            let overlay = Self::load_from(&project_path).map_err(parse_error(&project_path))?;
+            for key in overlay.mappings().keys() {
+                config.include_sources.remove(key);
+                config.pack_sources.remove(key);
+            }
+            // a checkout only gets to add commands and paths, not change settings
+            config = Self::merge(config, overlay.mappings_only());
+            config.project_configs.push(project_path);
+        }
+
+        // packs and project configs can each be fine alone and clash once merged
+        if let Some((name, message)) = config.key_collisions().into_iter().next() {
+            return Err(SemanticError::BadMapping { name, message });
+        }
+
+        let profile = active_profile();
+        let source = ConfigSource {
+            user_config_path: path,
+            project_config_path: config.project_configs.last().cloned(),
+            active_profile: (profile != DEFAULT_PROFILE).then_some(profile),
+            plugins_loaded,
+        };
+        Ok((config, source))
+    }
+
+    /// Load `path` with the files in its `include` merged in underneath, each in
+    /// turn (later ones win a key both define), and theirs before them.
+    /// Only their mappings count; settings come from `path` itself.
+    /// `chain` holds the files currently being loaded, to catch include cycles.
+    fn load_with_includes(
+        path: &Path,
+        chain: &mut Vec<PathBuf>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        // only the user config itself goes through the disk cache
+        let config = if chain.is_empty() { Self::load_from_cached(path)? } else { Self::load_from(path)? };
+        if config.include.is_empty() {
+            return Ok(config);
+        }
+
+        chain.push(fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()));
+        let mut base: Option<SemanticConfig> = None;
+        for entry in &config.include {
+            // joining an absolute path replaces the config dir
+            let include_path = config_dir().join(expand_env(entry));
+            let include_path = fs::canonicalize(&include_path)
+                .map_err(|e| format!("{}: include `{entry}`: {e}", path.display()))?;
+            log::debug!("include '{entry}' -> {}", include_path.display());
+            if let Some(start) = chain.iter().position(|p| *p == include_path) {
+                let cycle: Vec<_> = chain[start..]
+                    .iter()
+                    .chain([&include_path])
+                    .map(|p| p.display().to_string())
+                    .collect();
+                return Err(format!(
+                    "{}: include `{entry}` forms a cycle ({})",
+                    path.display(),
+                    cycle.join(" → ")
+                )
+                .into());
+            }
+
+            let mut included = Self::load_with_includes(&include_path, chain)?;
+            for key in included.mappings().into_keys() {
+                included.include_sources.entry(key).or_insert_with(|| include_path.clone());
+            }
+            base = Some(match base {
+                Some(base) => base.with_included(included),
+                None => included,
+            });
+        }
+        chain.pop();
+
+        let Some(base) = base else { return Ok(config) };
+        let (version, general, shells, theme, safety, overrides, include) = (
+            config.version,
+            config.general.clone(),
+            config.shells.clone(),
+            config.theme.clone(),
+            config.safety.clone(),
+            config.overrides.clone(),
+            config.include.clone(),
+        );
+        let mut config = base.with_included(config);
+        config.version = version;
+        config.general = general;
+        config.shells = shells;
+        config.theme = theme;
+        config.safety = safety;
+        config.overrides = overrides;
+        config.include = include;
+        Ok(config)
+    }
+
+    /// Put the mappings in `[overrides.<os>]` in place of the plain ones they
+    /// name, recording each in `os_overridden`. A command override is always a
+    /// plain mapping, so it replaces a composite or shell-mode one too.
+    fn apply_os_overrides(&mut self, os: &str) {
+        let Some(mappings) = self.overrides.for_os(os).cloned() else { return };
+        for (name, real_cmd) in mappings.commands {
+            log::debug!("{os} override for command {name}");
+            self.composite_commands.remove(&name);
+            self.shell_mode_commands.remove(&name);
+            self.untranslated_commands.remove(&name);
+            self.include_sources.remove(&MappingKey::Command(name.clone()));
+            self.os_overridden.push(MappingKey::Command(name.clone()));
+            self.commands.insert(name, real_cmd);
+        }
+        for (virtual_path, real_path) in mappings.paths {
+            log::debug!("{os} override for path {virtual_path}");
+            self.include_sources.remove(&MappingKey::Path(virtual_path.clone()));
+            self.os_overridden.push(MappingKey::Path(virtual_path.clone()));
+            self.paths.insert(virtual_path, real_path);
+        }
+        self.os_overridden.sort();
+    }
+
+    /// Layer `overlay` on top with `merge`, keeping track of which mappings still
+    /// come from an included file.
+    fn with_included(mut self, overlay: SemanticConfig) -> Self {
+        for key in overlay.mappings().keys() {
+            self.include_sources.remove(key);
+        }
+        let sources = overlay.include_sources.clone();
+        let mut merged = Self::merge(self, overlay);
+        merged.include_sources.extend(sources);
+        merged
+    }
+
+    /// Add the mappings of one pack (only its [commands] and [paths] count) for keys
+    /// not mapped yet. Keys already mapped keep their value; a different value
+    /// in the pack is recorded in `pack_collisions`.
+    fn with_pack(mut self, mut pack: SemanticConfig, path: &Path) -> Self {
+        let ours = self.mappings();
+        for (key, value) in pack.mappings() {
+            match ours.get(&key) {
+                Some(existing) => {
+                    if *existing != value {
+                        self.pack_collisions.push((path.to_path_buf(), key.clone()));
+                    }
+                    pack.remove_mapping(&key);
+                }
+                None => {
+                    self.pack_sources.insert(key, path.to_path_buf());
+                }
+            }
+        }
+
+        // nothing left in the pack collides, so layering it on top only adds keys
+        Self::merge(self, pack.mappings_only())
+    }
+
+    /// Expand $VAR / ${VAR} in every command value and every path key and value.
+    /// For views of the whole config (init, test, which); translate expands only
+    /// the mapping it runs, so unrelated unset variables aren't logged on every call.
+    /// Shell-mode scripts are left alone: the shell expands them itself, and
+    /// `$1`/`"$@"` there mean the user's args.
+    pub fn expand_env_vars(&mut self) {
+        let shell_mode = &self.shell_mode_commands;
+        for real_cmd in self
+            .commands
+            .iter_mut()
+            .filter(|(name, _)| !shell_mode.contains(*name))
+            .map(|(_, real_cmd)| real_cmd)
+            .chain(self.shell_commands.values_mut().flat_map(|o| o.values_mut()))
+            .chain(self.composite_commands.values_mut().flatten())
+        {
+            *real_cmd = expand_command_env(real_cmd);
+        }
+        self.paths = self
+            .paths
+            .drain()
+            .map(|(virtual_path, real_path)| (expand_env(&virtual_path), expand_env(&real_path)))
+            .collect();
+    }
+
+    /// The real path a virtual path maps to, with environment variables expanded.
+    /// A path under a mapped one keeps its remainder (`/apps/vim` -> `/usr/bin/vim`);
+    /// the longest matching mapping wins, and only whole components match.
+    /// Virtual paths that contain variables are expanded before comparing.
+    pub fn resolve_path(&self, virtual_path: &str) -> Option<String> {
+        let (prefix, rest, real_path) = self
+            .paths
+            .iter()
+            .filter_map(|(key, real_path)| {
+                let key = if key.contains('$') { expand_env(key) } else { key.clone() };
+                let rest = virtual_path.strip_prefix(key.trim_end_matches('/'))?;
+                (rest.is_empty() || rest.starts_with('/')).then_some((key, rest, real_path))
+            })
+            .max_by_key(|(key, _, _)| key.len())?;
+
+        let real_path = expand_env(real_path);
+        let resolved = if rest.is_empty() {
+            real_path
+        } else {
+            format!("{}{rest}", real_path.trim_end_matches('/'))
+        };
+        log::debug!("path '{virtual_path}' matched prefix '{prefix}' -> '{resolved}'");
+        Some(resolved)
+    }
+
+    /// The real path for one argument of a translated command, if it names a
+    /// virtual one (see `resolve_path`). Flags (`-x`, `--dir=/apps`) never do.
+    /// A leading `~` is the home directory. A relative path counts only if it
+    /// doesn't exist from `cwd` but falls under a virtual path when joined to it.
+    pub fn resolve_arg(&self, arg: &str, cwd: Option<&Path>) -> Option<String> {
+        if arg.starts_with('-') {
+            return None;
+        }
+        if arg == "~" || arg.starts_with("~/") {
+            let home = dirs::home_dir()?;
+            return self.resolve_path(&format!("{}{}", home.display(), &arg[1..]));
+        }
+        if Path::new(arg).is_absolute() {
+            return self.resolve_path(arg);
+        }
+        let joined = cwd?.join(arg);
+        if joined.exists() {
+            return None;
+        }
+        self.resolve_path(normalize(&joined).to_str()?)
+    }
+
+    /// The real path for each of `args`, the extra arguments of the semantic
+    /// command `name` (see `resolve_arg`), or None where the argument goes on as
+    /// typed: every one after a `--`, and all of them for a command with
+    /// `translate_paths = false`.
+    pub fn resolve_args(&self, name: &str, args: &[String], cwd: Option<&Path>) -> Vec<Option<String>> {
+        let translate = !self.untranslated_commands.contains(name);
+        let separator = args.iter().position(|arg| arg == "--").unwrap_or(args.len());
+        args.iter()
+            .enumerate()
+            .map(|(i, arg)| if translate && i < separator { self.resolve_arg(arg, cwd) } else { None })
+            .collect()
+    }
+
+    /// `load`, but parsed again only when the user config's modification time
+    /// (or the config path, or the current directory) changed since the last
+    /// call in this process. For long-running batches and sessions that look
+    /// the config up over and over; a single command can just `load`.
+    /// Changes to packs, plugins and project configs alone aren't noticed.
+    pub fn load_cached() -> Result<Arc<Self>, SemanticError> {
+        let path = Self::config_path();
+        let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+        let cwd = env::current_dir().ok();
+
+        let mut cache = CONFIG_CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(cached) = cache.as_ref()
+            && cached.path == path
+            && cached.modified == modified
+            && cached.cwd == cwd
+        {
+            return Ok(Arc::clone(&cached.config));
+        }
+
+        log::debug!("config cache: parsing {}", path.display());
+        let config = Arc::new(Self::load()?);
+        *cache = Some(CachedConfig { path, modified, cwd, config: Arc::clone(&config) });
+        Ok(config)
+    }
+
+    /// `load_from`, but read from the disk cache (see `DiskCache`) while `path`
+    /// still has the size and modification time it had when the cache was
+    /// written, and written to it after parsing otherwise. A cache that's stale,
+    /// unreadable or from another version of semantic is simply replaced, and
+    /// failing to write one only gets logged. $SEMANTIC_NO_CACHE skips it.
+    fn load_from_cached(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let stamp = match FileStamp::of(path) {
+            Some(stamp) if !disk_cache_disabled() => stamp,
+            _ => return Self::load_from(path),
+        };
+        let cache_path = disk_cache_path(path);
+        if let Some(config) = DiskCache::read(&cache_path, path, &stamp) {
+            log::debug!("config cache: using {}", cache_path.display());
+            return Ok(config);
+        }
+
+        let config = Self::load_from(path)?;
+        if let Err(e) = DiskCache::write(&cache_path, path, stamp, &config) {
+            log::debug!("config cache: can't write {}: {e}", cache_path.display());
+        }
+        Ok(config)
+    }
+
+    /// Make the next `load_cached` parse the config again, whatever its
+    /// modification time says (an edit within the same second can keep it).
+    pub fn forget_cached() {
+        *CONFIG_CACHE.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    /// Put `edited`, a changed copy of the user config in the same directory as
+    /// the file itself, in its place. It's renamed over the file, so readers see
+    /// the old config or the new one, never half of one; a symlinked config is
+    /// replaced at the link's target. Returns the path replaced.
+    pub fn replace_user_config(edited: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        with_config_lock(Lock::Exclusive, || {
+            let path = Self::config_path();
+            let path = fs::canonicalize(&path).unwrap_or(path);
+            fs::rename(edited, &path).map_err(|e| format!("{}: {e}", path.display()))?;
+            forget_disk_cache(&path);
+            Ok(path)
+        })
+    }
+
+    /// Load only the user config (see `config_path`), ignoring project-local overrides.
+    /// Returns `ConfigNotFound` if the file doesn't exist, `ConfigParse` if it
+    /// can't be read or parsed.
+    pub fn load_user() -> Result<Self, SemanticError> {
+        let path = Self::config_path();
+        if !path.exists() {
+            return Err(SemanticError::ConfigNotFound { path });
+        }
+        with_config_lock(Lock::Shared, || Self::load_from(&path))
+            .map_err(|source| SemanticError::ConfigParse { path, source })
+    }
+
+    /// Parse a config file at an arbitrary path, as TOML or JSON by its extension.
+    /// A file that doesn't parse gives a `ParseError` pointing at the bad line.
+    pub fn load_from(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content =
+            fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let config = ConfigFormat::of(path)
+            .parse(&content)
+            .map_err(|e| ParseError::new(path, &content, e.as_ref()))?;
+        Ok(config)
+    }
+
+    /// Every mapping in the config, keyed by where it lives, with its value as text
+    /// (composite steps joined with " ; "). Used to compare configs key by key.
+    pub fn mappings(&self) -> BTreeMap<MappingKey, String> {
+        let mut mappings = BTreeMap::new();
+        for (name, real_cmd) in &self.commands {
+            mappings.insert(MappingKey::Command(name.clone()), real_cmd.clone());
+        }
+        for (name, steps) in &self.composite_commands {
+            mappings.insert(MappingKey::Command(name.clone()), steps.join(" ; "));
+        }
+        for (shell, overrides) in &self.shell_commands {
+            for (name, real_cmd) in overrides {
+                mappings.insert(
+                    MappingKey::ShellCommand(shell.clone(), name.clone()),
+                    real_cmd.clone(),
+                );
+            }
+        }
+        for (virtual_path, real_path) in &self.paths {
+            mappings.insert(MappingKey::Path(virtual_path.clone()), real_path.clone());
+        }
+        mappings
+    }
+
+    /// Mappings in `overlay` that would change a value already in `self`.
+    pub fn conflicts_with(&self, overlay: &SemanticConfig) -> Vec<MappingKey> {
+        let ours = self.mappings();
+        overlay
+            .mappings()
+            .into_iter()
+            .filter(|(key, value)| ours.get(key).is_some_and(|v| v != value))
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    /// Drop one mapping, if present.
+    pub fn remove_mapping(&mut self, key: &MappingKey) {
+        match key {
+            MappingKey::Command(name) => {
+                self.commands.remove(name);
+                self.composite_commands.remove(name);
+                self.shell_mode_commands.remove(name);
+                self.untranslated_commands.remove(name);
+            }
+            MappingKey::ShellCommand(shell, name) => {
+                if let Some(overrides) = self.shell_commands.get_mut(shell) {
+                    overrides.remove(name);
+                }
+            }
+            MappingKey::Path(virtual_path) => {
+                self.paths.remove(virtual_path);
+            }
+        }
+    }
+
+    /// Layer `overlay` on top of `base`. Every way two configs are combined goes
+    /// through here (includes, packs, plugins, project configs, import --merge).
+    /// Overlay commands and paths win per key (per-shell overrides per shell and key);
+    /// base fills in everything else.
+    /// `[general]` and `[shells]` settings the overlay sets win (see
+    /// `GeneralConfig::merge` and `ShellConfig::merge`); an overlay that
+    /// shouldn't change settings is passed through `mappings_only` first.
+    /// The base's version, includes, `[theme]`, `[styles]`, `[safety]` and
+    /// `[overrides]` are always kept.
+    pub fn merge(base: SemanticConfig, overlay: SemanticConfig) -> SemanticConfig {
+        let mut merged = base;
+
+        // a name is either plain or composite, so an overlay of one kind replaces the other
+        for name in overlay.commands.keys() {
+            merged.composite_commands.remove(name);
+        }
+        for name in overlay.composite_commands.keys() {
+            merged.commands.remove(name);
+        }
+        // and an overlay mapping brings its own shell mode and options (or lack of them)
+        for name in overlay.commands.keys().chain(overlay.composite_commands.keys()) {
+            merged.shell_mode_commands.remove(name);
+            merged.untranslated_commands.remove(name);
+        }
+        merged.shell_mode_commands.extend(overlay.shell_mode_commands);
+        merged.untranslated_commands.extend(overlay.untranslated_commands);
+
+        merged.commands.extend(overlay.commands);
+        merged.composite_commands.extend(overlay.composite_commands);
+        for (shell, overrides) in overlay.shell_commands {
+            merged.shell_commands.entry(shell).or_default().extend(overrides);
+        }
+        merged.paths.extend(overlay.paths);
+        merged.general.merge(overlay.general);
+        merged.shells.merge(overlay.shells);
+        merged
+    }
+
+    /// This config with its `[general]` and `[shells]` settings cleared, so that
+    /// `merge` only takes its mappings.
+    pub fn mappings_only(mut self) -> Self {
+        self.general = GeneralConfig::default();
+        self.shells = ShellConfig::default();
+        self
+    }
+
+    /// Write the config back to the user config file (see `config_path`),
+    /// in the format that file is in. Creates the directory if it doesn't exist.
+    /// The disk cache of it is removed, so the next load parses the new file.
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = Self::config_path();
+        with_config_lock(Lock::Exclusive, || self.save_to(&path))?;
+        forget_disk_cache(&path);
+        Ok(())
+    }
+
+    /// Write the config to `path`, as TOML or JSON by its extension.
+    /// Refuses a config with a loop in it (see `reference_cycles`) or a command
+    /// name that clashes with a path (see `key_collisions`).
+    /// The new content goes to a temporary file beside it that is then renamed
+    /// over it, so a reader sees the old file or the new one, never half of one.
+    /// A symlinked config (a dotfiles repo, say) is written through the link.
+    pub fn save_to(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        // a config that loops would hang or fork-bomb the next command run from it
+        let cycles = self.reference_cycles();
+        if !cycles.is_empty() {
+            return Err(format!("not saved: {}", cycles.join("; ")).into());
+        }
+        let collisions = self.key_collisions();
+        if !collisions.is_empty() {
+            let collisions: Vec<_> =
+                collisions.iter().map(|(name, problem)| format!("command `{name}` {problem}")).collect();
+            return Err(format!("not saved: {}", collisions.join("; ")).into());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let content = ConfigFormat::of(path).render(self)?;
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        fs::write(&tmp, content)
+            .and_then(|()| fs::rename(&tmp, &path))
+            .map_err(|e| {
+                let _ = fs::remove_file(&tmp);
+                format!("{}: {e}", path.display())
+            })?;
+
+        Ok(())
+    }
+
+    /// Mappings whose value looks destructive or unsafe to take from someone else
+    /// (wiping / or ~, formatting a disk, running a downloaded script...), each
+    /// with what it seems to do. `semantic import` refuses these unless told otherwise.
+    pub fn dangerous_mappings(&self) -> Vec<(MappingKey, &'static str)> {
+        self.mappings()
+            .into_iter()
+            .filter_map(|(key, value)| looks_dangerous(&value).map(|why| (key, why)))
+            .collect()
+    }
+
+    /// Check the config for problems that parse fine but break at runtime.
+    /// Returns every problem found, one message each.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        // per-shell overrides are reported with their shell, e.g. `fish.reload`
+        let mut commands: Vec<_> = self
+            .commands
+            .iter()
+            .map(|(name, real_cmd)| (String::new(), name, real_cmd))
+            .chain(self.shell_commands.iter().flat_map(|(shell, overrides)| {
+                overrides
+                    .iter()
+                    .map(move |(name, real_cmd)| (format!("{shell}."), name, real_cmd))
+            }))
+            .collect();
+        commands.sort();
+        for (prefix, name, real_cmd) in commands {
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                errors.push(format!("command name `{prefix}{name}` must be a single word"));
+            }
+            if real_cmd.trim().is_empty() {
+                errors.push(format!("command `{prefix}{name}` maps to an empty command"));
+            }
+        }
+
+        let mut composites: Vec<_> = self.composite_commands.iter().collect();
+        composites.sort();
+        for (name, steps) in composites {
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                errors.push(format!("command name `{name}` must be a single word"));
+            }
+            if steps.is_empty() {
+                errors.push(format!("command `{name}` has no steps"));
+            }
+            for (i, step) in steps.iter().enumerate() {
+                if step.trim().is_empty() {
+                    errors.push(format!("step {} of command `{name}` is empty", i + 1));
+                }
+            }
+        }
+
+        let mut paths: Vec<_> = self.paths.iter().collect();
+        paths.sort();
+        for (virtual_path, real_path) in paths {
+            if virtual_path.is_empty() {
+                errors.push("path mapping has an empty virtual path".to_string());
+            }
+            if real_path.is_empty() {
+                errors.push(format!("path `{virtual_path}` maps to an empty path"));
+            }
+        }
+
+        errors.extend(self.reference_cycles());
+        errors.extend(
+            self.key_collisions()
+                .into_iter()
+                .map(|(name, problem)| format!("command `{name}` {problem}")),
+        );
+
+        if let Some(code) = &self.general.language
+            && !LANGUAGES.iter().any(|l| l.code == code)
+        {
+            let known: Vec<_> = LANGUAGES.iter().map(|l| l.code).collect();
+            errors.push(format!("unknown language `{code}` (known: {})", known.join(", ")));
+        }
+
+        if let Some(mode) = &self.shells.integration_mode
+            && IntegrationMode::from_name(mode).is_none()
+        {
+            errors.push(format!(
+                "unknown integration_mode `{mode}` (known: {})",
+                IntegrationMode::NAMES.join(", ")
+            ));
+        }
+
+        if !self.shells.default.is_empty() && !self.shells.enabled.contains(&self.shells.default) {
+            errors.push(format!(
+                "default shell `{}` is not in the enabled shells list",
+                self.shells.default
+            ));
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// Returns the full path to the user config: $SEMANTIC_CONFIG if set, then the
+    /// active profile's file (see `active_profile`), otherwise config.json in
+    /// `config_dir` if there is one, else config.toml there.
+    pub fn config_path() -> PathBuf {
+        config_file_override().unwrap_or_else(|| {
+            let json = config_dir().join(ConfigFormat::Json.file_name());
+            if json.is_file() {
+                json
+            } else {
+                config_dir().join(ConfigFormat::Toml.file_name())
+            }
+        })
+    }
+}
+
+/// A config file that exists but doesn't parse, reduced to one line:
+/// `path:line: message`, plus a pointer to `semantic doctor`.
+#[derive(Debug)]
+pub struct ParseError {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    /// Where on `line` the parser gave up (1-based), when it says.
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl ParseError {
+    /// Pull the line and the bare message out of a TOML or JSON parser error,
+    /// whose own text spans several lines (TOML quotes the source with a caret).
+    fn new(path: &Path, content: &str, error: &(dyn std::error::Error + 'static)) -> Self {
+        let (line, column, message) = if let Some(e) = error.downcast_ref::<toml::de::Error>() {
+            let before = e.span().map(|span| &content[..span.start]);
+            let line = before.map(|before| before.matches('\n').count() + 1);
+            let column = before.map(|before| before.rsplit('\n').next().unwrap_or("").chars().count() + 1);
+            (line, column, e.message().trim().to_string())
+        } else if let Some(e) = error.downcast_ref::<serde_json::Error>() {
+            // serde_json appends " at line L column C" to the message itself
+            let text = e.to_string();
+            let message = text.rsplit_once(" at line ").map_or(text.as_str(), |(m, _)| m);
+            let line = Some(e.line()).filter(|&line| line > 0);
+            (line, line.and(Some(e.column())).filter(|&column| column > 0), message.to_string())
+        } else {
+            (None, None, error.to_string())
+        };
+        ParseError { path: path.to_path_buf(), line, column, message }
+    }
+
+    /// The error without the hint: `path:line: message`.
+    pub fn summary(&self) -> String {
+        match self.line {
+            Some(line) => format!("{}:{line}: {}", self.path.display(), self.message),
+            None => format!("{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n(run `semantic doctor` to check the rest of your setup)", self.summary())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// -- schema versions --
+
+/// The config schema this semantic writes. Bump it with a new step in `migrate`
+/// whenever an older file needs rewriting to mean the same thing.
+pub const CURRENT_SCHEMA_VERSION: u32 = 1;
+
+/// Bring a config up to `CURRENT_SCHEMA_VERSION`, one schema step at a time.
+/// Fails for a config from a newer semantic, which this one can't know how to read.
+/// Steps:
+///   0 → 1: `[shells]` names written as paths or with a version
+///          (`/bin/zsh`, `bash5`) become the plain names `init` expects.
+pub fn migrate(config: &mut SemanticConfig) -> Result<(), Box<dyn std::error::Error>> {
+    if config.version > CURRENT_SCHEMA_VERSION {
+        return Err(format!(
+            "written for config version {}, but this semantic only knows up to {}; \
+             update semantic to use it",
+            config.version, CURRENT_SCHEMA_VERSION
+        )
+        .into());
+    }
+
+    if config.version < 1 {
+        let shells = &mut config.shells;
+        if !shells.default.is_empty() {
+            shells.default = normalize_shell_name(&shells.default);
+        }
+        let mut enabled = Vec::new();
+        for shell in shells.enabled.drain(..).map(|s| normalize_shell_name(&s)) {
+            if !shell.is_empty() && !enabled.contains(&shell) {
+                enabled.push(shell);
+            }
+        }
+        shells.enabled = enabled;
+    }
+
+    if config.version != CURRENT_SCHEMA_VERSION {
+        log::info!("migrated config from version {} to {CURRENT_SCHEMA_VERSION}", config.version);
+    }
+    config.version = CURRENT_SCHEMA_VERSION;
+    Ok(())
+}
+
+// -- config file formats --
+
+/// How a config file is written on disk, decided by its extension (.json, or TOML otherwise).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    pub fn of(path: &Path) -> Self {
+        if path.extension().is_some_and(|ext| ext == "json") {
+            ConfigFormat::Json
+        } else {
+            ConfigFormat::Toml
+        }
+    }
+
+    /// Parse a `--format`/`--to` value.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "toml" => Some(ConfigFormat::Toml),
+            "json" => Some(ConfigFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Json => "json",
+        }
+    }
+
+    /// The user config's file name in this format.
+    pub fn file_name(self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => "config.toml",
+            ConfigFormat::Json => "config.json",
+        }
+    }
+
+    pub fn parse(self, content: &str) -> Result<SemanticConfig, Box<dyn std::error::Error>> {
+        Ok(match self {
+            ConfigFormat::Toml => toml::from_str(content)?,
+            ConfigFormat::Json => serde_json::from_str(content)?,
+        })
+    }
+
+    pub fn render(self, config: &SemanticConfig) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(match self {
+            ConfigFormat::Toml => toml::to_string_pretty(config)?,
+            ConfigFormat::Json => serde_json::to_string_pretty(config)? + "\n",
+        })
+    }
+}
+
+/// The version of the `[export]` table `semantic export` writes. Bump it when an
+/// export can no longer be imported by an older semantic.
+pub const EXPORT_VERSION: u32 = 1;
+
+/// `[export]`: what wrote a shared config, and when and where. It heads the file
+/// `semantic export` writes; as a config the file loads like any other, since
+/// loading ignores the table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportInfo {
+    pub version: u32,
+    /// The semantic release that wrote it.
+    pub semantic: String,
+    /// When, as `YYYY-MM-DD HH:MM:SS UTC`.
+    pub created: String,
+    /// The machine it came from.
+    pub host: String,
+}
+
+impl ExportInfo {
+    /// Describe an export made now, on this machine.
+    pub fn now() -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        ExportInfo {
+            version: EXPORT_VERSION,
+            semantic: env!("CARGO_PKG_VERSION").to_string(),
+            created: format!("{} UTC", crate::history::format_timestamp(timestamp)),
+            host: hostname(),
+        }
+    }
+}
+
+/// This machine's name, or "unknown".
+fn hostname() -> String {
+    env::var("COMPUTERNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+impl ConfigFormat {
+    /// `config` as a shared file: `info` as an `[export]` table first, then the
+    /// config exactly as `render` writes it.
+    pub fn render_export(
+        self,
+        config: &SemanticConfig,
+        info: &ExportInfo,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        #[derive(Serialize)]
+        struct Export<'a> {
+            export: &'a ExportInfo,
+            #[serde(flatten)]
+            config: RawConfig,
+        }
+        let export = Export { export: info, config: RawConfig::from(config.clone()) };
+        Ok(match self {
+            ConfigFormat::Toml => toml::to_string_pretty(&export)?,
+            ConfigFormat::Json => serde_json::to_string_pretty(&export)? + "\n",
+        })
+    }
+
+    /// Read a shared file: its `[export]` table, if it has one (plain configs and
+    /// older exports don't), and the config, parsed exactly as `parse` does.
+    pub fn parse_export(
+        self,
+        content: &str,
+    ) -> Result<(Option<ExportInfo>, SemanticConfig), Box<dyn std::error::Error>> {
+        #[derive(Deserialize)]
+        struct Header {
+            #[serde(default)]
+            export: Option<ExportInfo>,
+        }
+        let header: Header = match self {
+            ConfigFormat::Toml => toml::from_str(content)?,
+            ConfigFormat::Json => serde_json::from_str(content)?,
+        };
+        Ok((header.export, self.parse(content)?))
+    }
+}
+
+/// The user config together with the format it's stored in.
+/// Saving writes `config_dir`/config.<format> and then removes the user config
+/// in the other format, so the two can't drift apart or shadow each other.
+pub struct ConfigFile {
+    pub format: ConfigFormat,
+    pub config: SemanticConfig,
+}
+
+impl ConfigFile {
+    /// Load the user config (see `config_path`), remembering its format.
+    /// Fails as `SemanticConfig::load_user` does.
+    pub fn load() -> Result<Self, SemanticError> {
+        let format = ConfigFormat::of(&SemanticConfig::config_path());
+        Ok(ConfigFile { format, config: SemanticConfig::load_user()? })
+    }
+
+    /// Where `save` writes. $SEMANTIC_CONFIG and an active profile name one
+    /// file, so it's always written there, in the format its extension says.
+    pub fn path(&self) -> PathBuf {
+        config_file_override().unwrap_or_else(|| config_dir().join(self.format.file_name()))
+    }
+
+    /// Write the config, check that the file reads back, then delete the
+    /// other-format file if there is one. Returns the path written.
+    pub fn save(&self) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        with_config_lock(Lock::Exclusive, || self.save_unlocked())
+    }
+
+    fn save_unlocked(&self) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let path = self.path();
+        self.config.save_to(&path)?;
+        SemanticConfig::load_from(&path)?;
+
+        if config_file_override().is_none() {
+            let other = match self.format {
+                ConfigFormat::Toml => ConfigFormat::Json,
+                ConfigFormat::Json => ConfigFormat::Toml,
+            };
+            let other = config_dir().join(other.file_name());
+            if other.is_file() {
+                fs::remove_file(&other)
+                    .map_err(|e| format!("wrote {} but couldn't remove {}: {e}", path.display(), other.display()))?;
+            }
+        }
+
+        Ok(path)
+    }
+}
+
+/// What a config has beyond the stock mappings of its styles, so rebuilding
+/// it from style choices (the setup wizard) can carry that over: mappings the
+/// user added or changed, and stock ones they deleted.
+/// A value counts as stock if it's the style's (`[styles]` included) or the
+/// same command for another package manager, as `semantic reset` picks.
+#[derive(Debug, Clone, Default)]
+pub struct Customizations {
+    pub commands: HashMap<String, String>,
+    pub shell_commands: HashMap<String, HashMap<String, String>>,
+    pub composite_commands: HashMap<String, Vec<String>>,
+    pub shell_mode_commands: HashSet<String>,
+    pub untranslated_commands: HashSet<String>,
+    pub paths: HashMap<String, String>,
+    /// Stock command names and virtual paths the config no longer has.
+    pub removed_commands: Vec<String>,
+    pub removed_paths: Vec<String>,
+}
+
+impl Customizations {
+    pub fn of(config: &SemanticConfig) -> Self {
+        let language = config.general.language.as_deref().unwrap_or("en");
+        let stock_commands = config.styles.commands(&config.general.command_style, language);
+        let stock_paths = config.styles.paths(&config.general.folder_style, language);
+        let is_stock = |stock: Option<&String>, value: &str| {
+            stock.is_some_and(|stock| {
+                stock == value
+                    || PACKAGE_MANAGERS.iter().any(|pm| pm.equivalent_of(stock) == Some(value))
+            })
+        };
+
+        let commands = config
+            .commands
+            .iter()
+            .filter(|(name, real_cmd)| {
+                config.shell_mode_commands.contains(*name)
+                    || config.untranslated_commands.contains(*name)
+                    || !is_stock(stock_commands.get(*name), real_cmd)
+            })
+            .map(|(name, real_cmd)| (name.clone(), real_cmd.clone()))
+            .collect();
+        let paths = config
+            .paths
+            .iter()
+            .filter(|(virtual_path, real_path)| !is_stock(stock_paths.get(*virtual_path), real_path))
+            .map(|(virtual_path, real_path)| (virtual_path.clone(), real_path.clone()))
+            .collect();
+
+        let mut removed_commands: Vec<_> = stock_commands
+            .into_iter()
+            // identity mappings (traditional) mean nothing, so they can't be missed
+            .filter(|(name, real_cmd)| name != real_cmd)
+            .map(|(name, _)| name)
+            .filter(|name| {
+                !config.commands.contains_key(name) && !config.composite_commands.contains_key(name)
+            })
+            .collect();
+        removed_commands.sort();
+        let mut removed_paths: Vec<_> = stock_paths
+            .into_keys()
+            .filter(|virtual_path| !config.paths.contains_key(virtual_path))
+            .collect();
+        removed_paths.sort();
+
+        Customizations {
+            commands,
+            shell_commands: config.shell_commands.clone(),
+            composite_commands: config.composite_commands.clone(),
+            shell_mode_commands: config.shell_mode_commands.clone(),
+            untranslated_commands: config.untranslated_commands.clone(),
+            paths,
+            removed_commands,
+            removed_paths,
+        }
+    }
+
+    /// How many added or changed mappings there are to carry over.
+    pub fn len(&self) -> usize {
+        self.commands.len()
+            + self.shell_commands.values().map(HashMap::len).sum::<usize>()
+            + self.composite_commands.len()
+            + self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Put the customizations into a config built from stock mappings: drop the
+    /// stock mappings the user deleted, then lay theirs on top.
+    pub fn apply_to(&self, config: &mut SemanticConfig) {
+        for name in &self.removed_commands {
+            config.commands.remove(name);
+        }
+        for virtual_path in &self.removed_paths {
+            config.paths.remove(virtual_path);
+        }
+        let overlay = SemanticConfig {
+            commands: self.commands.clone(),
+            shell_commands: self.shell_commands.clone(),
+            composite_commands: self.composite_commands.clone(),
+            shell_mode_commands: self.shell_mode_commands.clone(),
+            untranslated_commands: self.untranslated_commands.clone(),
+            paths: self.paths.clone(),
+            ..config.clone()
+        };
+        *config = SemanticConfig::merge(config.clone(), overlay);
+    }
+}
+
+/// One line of a comparison between two configs (see `config_diff`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Added(String, String),
+    Removed(String, String),
+    Changed(String, String, String),
+    Unchanged(String, String),
+}
+
+/// Compare two configs setting by setting and mapping by mapping, in key order.
+pub fn config_diff(old: &SemanticConfig, new: &SemanticConfig) -> Vec<DiffLine> {
+    let flatten = |config: &SemanticConfig| -> BTreeMap<String, String> {
+        let mut entries: BTreeMap<String, String> = [
+            ("version", config.version.to_string()),
+            ("general.command_style", config.general.command_style.clone()),
+            ("general.folder_style", config.general.folder_style.clone()),
+            ("general.history", config.general.history.to_string()),
+            ("general.language", config.general.language.clone().unwrap_or("en".into())),
+            ("general.theme", config.general.theme.clone().unwrap_or("default".into())),
+            ("general.semantic_bin_dir", config.general.semantic_bin_dir.clone().unwrap_or_default()),
+            ("shells.default", config.shells.default.clone()),
+            ("shells.enabled", config.shells.enabled.join(", ")),
+            ("shells.on_new_shell", config.shells.on_new_shell.clone()),
+            (
+                "shells.integration_mode",
+                config.shells.integration_mode.clone().unwrap_or_default(),
+            ),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+        entries.extend(config.mappings().into_iter().map(|(k, v)| (k.to_string(), v)));
+        entries
+    };
+
+    let old = flatten(old);
+    let mut new = flatten(new);
+
+    let mut lines = Vec::new();
+    for (key, old_value) in old {
+        match new.remove(&key) {
+            Some(new_value) if new_value == old_value => {
+                lines.push(DiffLine::Unchanged(key, new_value));
+            }
+            Some(new_value) => lines.push(DiffLine::Changed(key, old_value, new_value)),
+            None => lines.push(DiffLine::Removed(key, old_value)),
+        }
+    }
+    lines.extend(new.into_iter().map(|(key, value)| DiffLine::Added(key, value)));
+
+    lines.sort_by(|a, b| diff_key(a).cmp(diff_key(b)));
+    lines
+}
+
+fn diff_key(line: &DiffLine) -> &str {
+    match line {
+        DiffLine::Added(key, _)
+        | DiffLine::Removed(key, _)
+        | DiffLine::Changed(key, _, _)
+        | DiffLine::Unchanged(key, _) => key,
+    }
+}
+
+/// The config as TOML with every table's keys in order, so that two renderings
+/// of the same config match line for line (`render` keeps map order, which
+/// differs from one load to the next).
+pub fn sorted_toml(config: &SemanticConfig) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(toml::to_string_pretty(&toml::Value::try_from(config)?)?)
+}
+
+/// One line of a line-by-line comparison of two texts (see `line_diff`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineDiff {
+    Same(String),
+    Added(String),
+    Removed(String),
+}
+
+/// Compare two texts line by line, keeping the longest run of lines they share
+/// and marking the rest as removed from `old` or added in `new`, removals first.
+pub fn line_diff(old: &str, new: &str) -> Vec<LineDiff> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // common[i][j]: how many lines old[i..] and new[j..] have in common
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(LineDiff::Same(old[i].to_string()));
+            (i, j) = (i + 1, j + 1);
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(LineDiff::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(LineDiff::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    lines
+}
+
+/// What a command seems to do if it's one nobody should run by accident.
+/// Deliberately narrow: `rm -rf` alone is a stock mapping, `rm -rf ~` is not.
+fn looks_dangerous(command: &str) -> Option<&'static str> {
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let everything = ["/", "/*", "~", "~/", "~/*", "$HOME", "$HOME/", "${HOME}", "*"];
+    for (i, word) in words.iter().enumerate() {
+        let rest = &words[i + 1..];
+        let recursive = rest.iter().any(|w| w.starts_with('-') && w.contains(['r', 'R']));
+        match *word {
+            "rm" if recursive && rest.iter().any(|w| everything.contains(w)) => {
+                return Some("deletes everything under / or your home directory");
+            }
+            "chmod" | "chown" if recursive && rest.contains(&"/") => {
+                return Some("changes the permissions of the whole system");
+            }
+            "dd" if rest.iter().any(|w| w.starts_with("of=/dev/")) => {
+                return Some("writes over a device");
+            }
+            w if w.starts_with("mkfs") => return Some("formats a disk"),
+            _ => {}
+        }
+    }
+    if [">/dev/sd", "> /dev/sd", ">/dev/nvme", "> /dev/nvme"].iter().any(|p| command.contains(p)) {
+        return Some("writes over a device");
+    }
+    if command.contains(":(){") {
+        return Some("is a fork bomb");
+    }
+    let downloads = words.iter().any(|w| matches!(*w, "curl" | "wget"));
+    let pipes_to_shell = command.split('|').skip(1).any(|part| {
+        let program = part.split_whitespace().find(|w| *w != "sudo");
+        matches!(program, Some("sh" | "bash" | "zsh" | "fish"))
+    });
+    if downloads && pipes_to_shell {
+        return Some("runs a script straight from the internet");
+    }
+    None
+}
+
+/// `path` with `.` and `..` components worked out without looking at the
+/// filesystem, the way a virtual path (which may not exist) has to be.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                normal.pop();
+            }
+            other => normal.push(other),
+        }
+    }
+    normal
+}
+
+/// Rewrites real paths in text back to the virtual paths that map to them, the
+/// reverse of `SemanticConfig::resolve_path`: with `"/apps" = "/usr/bin"`,
+/// `/usr/bin/vim` becomes `/apps/vim`. Used on translated commands' output when
+/// `[general] rewrite_output` is set.
+pub struct PathRewriter {
+    /// (real path, virtual path), longest real path first so the most specific wins.
+    paths: Vec<(String, String)>,
+}
+
+impl PathRewriter {
+    /// A rewriter for the config's path mappings, with variables expanded.
+    /// A mapping to `/` is left out, since every absolute path would match it.
+    pub fn new(config: &SemanticConfig) -> Self {
+        let mut paths: Vec<(String, String)> = config
+            .paths
+            .iter()
+            .map(|(virtual_path, real_path)| {
+                let real_path = expand_env(real_path).trim_end_matches('/').to_string();
+                let virtual_path = expand_env(virtual_path).trim_end_matches('/').to_string();
+                (real_path, virtual_path)
+            })
+            .filter(|(real_path, virtual_path)| !real_path.is_empty() && real_path != virtual_path)
+            .collect();
+        // two virtual paths for one real one: the first in order keeps it
+        paths.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.cmp(b)));
+        paths.dedup_by(|a, b| a.0 == b.0);
+        PathRewriter { paths }
+    }
+
+    /// Whether there's nothing to rewrite, so output can go out untouched.
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// `text` with each real path shown as its virtual one. Only whole components
+    /// match: `/usr/bin/vim` is rewritten, `/usr/binaries` and `/opt/usr/bin` aren't.
+    pub fn rewrite(&self, text: &str) -> String {
+        let part_of_name = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/');
+        let mut output = String::with_capacity(text.len());
+        let mut rest = text;
+        let mut previous = None;
+        while let Some(c) = rest.chars().next() {
+            let matches = |real_path: &str| {
+                rest.strip_prefix(real_path).is_some_and(|after| {
+                    after.chars().next().is_none_or(|next| next == '/' || !part_of_name(next))
+                })
+            };
+            let found = if previous.is_none_or(|p| !part_of_name(p)) {
+                self.paths.iter().find(|(real_path, _)| matches(real_path))
+            } else {
+                None
+            };
+            if let Some((real_path, virtual_path)) = found {
+                output.push_str(virtual_path);
+                rest = &rest[real_path.len()..];
+                previous = real_path.chars().last();
+            } else {
+                output.push(c);
+                rest = &rest[c.len_utf8()..];
+                previous = Some(c);
+            }
+        }
+        output
+    }
+}
+
+/// How many other semantic commands one mapping may go through before reaching
+/// a real program (see `SemanticConfig::resolve_references`).
+pub const MAX_REFERENCE_DEPTH: usize = 10;
+
+/// A path through `edges` from `start` back to it (`[start, ..., start]`), if
+/// there is one, each command on it once.
+fn cycle_from<'a>(edges: &HashMap<&'a str, Vec<&'a str>>, start: &'a str) -> Option<Vec<&'a str>> {
+    let mut path = vec![start];
+    // for each command on the path, the index of the next edge to try from it
+    let mut next = vec![0];
+    while let (Some(&here), Some(&i)) = (path.last(), next.last()) {
+        let Some(&target) = edges.get(here).and_then(|targets| targets.get(i)) else {
+            path.pop();
+            next.pop();
+            continue;
+        };
+        *next.last_mut()? += 1;
+        if target == start {
+            path.push(start);
+            return Some(path);
+        }
+        if !path.contains(&target) {
+            path.push(target);
+            next.push(0);
+        }
+    }
+    None
+}
+
+/// Max edit distance for a "did you mean" suggestion.
+const SUGGESTION_MAX_DISTANCE: usize = 3;
+
+/// Pick the known name closest to `name`, if one is close enough to be a likely typo.
+/// A name has to keep at least one character of `name` to count, so a short
+/// name isn't "corrected" to an unrelated one (`ls` to `cd`).
+/// Ties go to the alphabetically first name so suggestions are stable.
+/// Distances count chars (see `strsim::levenshtein`), so non-ASCII names compare sensibly.
+pub fn suggest<'a>(name: &str, known: impl IntoIterator<Item = &'a String>) -> Option<&'a str> {
+    let max_distance = SUGGESTION_MAX_DISTANCE.min(name.chars().count().saturating_sub(1));
+    known
+        .into_iter()
+        .map(|candidate| (strsim::levenshtein(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+/// Expand `$VAR` and `${VAR}` references using the process environment.
+/// Braced names may themselves contain references (`${PREFIX_${ENV}}`).
+/// Unset variables are left as written (logged at debug level).
+pub fn expand_env(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c != '$' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.peek() {
+            // ${VAR} — find the matching close brace, allowing nesting
+            Some(&(_, '{')) => {
+                chars.next();
+                let mut depth = 1;
+                let mut end = None;
+                for (i, c) in chars.by_ref() {
+                    match c {
+                        '{' => depth += 1,
+                        '}' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                end = Some(i);
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+
+                match end {
+                    Some(end) => {
+                        let name = expand_env(&s[start + 2..end]);
+                        out.push_str(&lookup_env(&name).unwrap_or_else(|| s[start..=end].to_string()));
+                    }
+                    // unterminated brace — keep the rest verbatim
+                    None => out.push_str(&s[start..]),
+                }
+            }
+
+            // $VAR — name runs while the characters are alphanumeric or underscore
+            Some(&(_, c)) if c.is_ascii_alphanumeric() || c == '_' => {
+                let mut end = start + 1;
+                while let Some(&(i, c)) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                let name = &s[start + 1..end];
+                out.push_str(&lookup_env(name).unwrap_or_else(|| s[start..end].to_string()));
+            }
+
+            // lone `$` — nothing to expand
+            _ => out.push('$'),
+        }
+    }
+
+    out
+}
+
+/// Like `expand_env`, but for command strings: single-quoted segments are
+/// copied verbatim, the way a shell would treat them.
+/// An unterminated quote runs to the end of the string.
+pub fn expand_command_env(real_cmd: &str) -> String {
+    let mut out = String::with_capacity(real_cmd.len());
+    let mut rest = real_cmd;
+
+    while let Some(open) = rest.find('\'') {
+        out.push_str(&expand_env(&rest[..open]));
+        let end = rest[open + 1..]
+            .find('\'')
+            .map_or(rest.len(), |close| open + close + 2);
+        out.push_str(&rest[open..end]);
+        rest = &rest[end..];
+    }
+
+    out.push_str(&expand_env(rest));
+    out
+}
+
+/// Resolve one variable; None if it's unset (or not UTF-8).
+fn lookup_env(name: &str) -> Option<String> {
+    let value = env::var(name).ok();
+    if value.is_none() {
+        log::debug!("environment variable `{name}` is not set, left as written");
+    }
+    value
+}
+
+/// Directories in every $PATH, which `detect_bin_dir` doesn't bother naming.
+const SYSTEM_BIN_DIRS: &[&str] = &["/bin", "/usr/bin", "/usr/local/bin"];
+
+/// The directory the running semantic is in, written as `[general]
+/// semantic_bin_dir` would be (`~/.cargo/bin`). None if it can't be told, or
+/// it's one of `SYSTEM_BIN_DIRS`.
+pub fn detect_bin_dir() -> Option<String> {
+    let exe = env::current_exe().ok()?;
+    let dir = exe.parent()?;
+    if SYSTEM_BIN_DIRS.iter().any(|system| dir == Path::new(system)) {
+        return None;
+    }
+    Some(display_path(dir))
+}
+
+/// `path` for showing to the user: with the home directory as `~` where that's
+/// how paths are written (not on Windows).
+pub fn display_path(path: &Path) -> String {
+    if !cfg!(windows)
+        && let Some(home) = dirs::home_dir()
+        && let Ok(rest) = path.strip_prefix(&home)
+    {
+        return Path::new("~").join(rest).display().to_string();
+    }
+    path.display().to_string()
+}
+
+/// Where command packs live: packs/ in `config_dir`.
+pub fn packs_dir() -> PathBuf {
+    config_dir().join("packs")
+}
+
+/// Where plugins (packs shared by others, loaded leniently) live: plugins/ in `config_dir`.
+pub fn plugins_dir() -> PathBuf {
+    config_dir().join("plugins")
+}
+
+/// What a file in `pack_sources` is, for messages: "plugin" or "pack".
+pub fn pack_kind(path: &Path) -> &'static str {
+    if path.parent() == Some(plugins_dir().as_path()) { "plugin" } else { "pack" }
+}
+
+/// Where named configs saved by `semantic profile save` live: profiles/ in `config_dir`.
+pub fn profiles_dir() -> PathBuf {
+    config_dir().join("profiles")
+}
+
+/// The file a profile is saved in, or an error if `name` can't be a file name
+/// there (empty, hidden, or with a path separator in it) or is the default one.
+pub fn profile_path(name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(format!("`{name}` can't be a profile name").into());
+    }
+    if name == DEFAULT_PROFILE {
+        return Err(format!("`{name}` is the plain config file, not a saved profile").into());
+    }
+    Ok(profiles_dir().join(format!("{name}.toml")))
+}
+
+/// What `SemanticConfig::load_cached` parsed last, and what from.
+struct CachedConfig {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    cwd: Option<PathBuf>,
+    config: Arc<SemanticConfig>,
+}
+
+static CONFIG_CACHE: Mutex<Option<CachedConfig>> = Mutex::new(None);
+
+/// What tells one version of a config file from another without reading it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct FileStamp {
+    size: u64,
+    /// Modification time since the epoch, as (seconds, nanoseconds).
+    modified: (u64, u32),
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(FileStamp { size: meta.len(), modified: (modified.as_secs(), modified.subsec_nanos()) })
+    }
+}
+
+/// config-<hash>.cache: the user config as `load_from` last parsed it, as JSON (much
+/// quicker to read back than TOML), with the file and `FileStamp` it came from.
+/// `C` is `SemanticConfig` reading it and `&SemanticConfig` writing it.
+#[derive(Serialize, Deserialize)]
+struct DiskCache<C> {
+    /// The semantic that wrote it; any other version's cache is ignored, since
+    /// the config's fields may have changed in between.
+    version: String,
+    path: PathBuf,
+    stamp: FileStamp,
+    config: C,
+}
+
+impl DiskCache<SemanticConfig> {
+    /// The cached config, if `cache_path` holds one for `path` as it is now.
+    fn read(cache_path: &Path, path: &Path, stamp: &FileStamp) -> Option<SemanticConfig> {
+        let content = fs::read(cache_path).ok()?;
+        let cache: Self = serde_json::from_slice(&content).ok()?;
+        (cache.version == env!("CARGO_PKG_VERSION") && cache.path == path && cache.stamp == *stamp)
+            .then_some(cache.config)
+    }
+}
+
+impl DiskCache<&SemanticConfig> {
+    /// Replace the cache with `config`, parsed from `path` when it had `stamp`.
+    /// Written beside it and renamed into place, so a command loading at the
+    /// same time never reads half of one.
+    fn write(
+        cache_path: &Path,
+        path: &Path,
+        stamp: FileStamp,
+        config: &SemanticConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let cache = DiskCache {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            path: path.to_path_buf(),
+            stamp,
+            config,
+        };
+        if let Some(parent) = cache_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = cache_path.with_extension(format!("cache.{}.tmp", std::process::id()));
+        fs::write(&tmp, serde_json::to_vec(&cache)?)
+            .and_then(|()| fs::rename(&tmp, cache_path))
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&tmp);
+            })?;
+        Ok(())
+    }
+}
+
+/// The directory the disk caches are in: ~/.cache/semantic, or under the
+/// platform's cache dir.
+pub fn disk_cache_dir() -> PathBuf {
+    dirs::cache_dir().unwrap_or_else(config_dir).join("semantic")
+}
+
+/// Full path to the disk cache of the config file at `path`, named after a hash
+/// of where that file really is, so the configs $SEMANTIC_CONFIG_DIR and
+/// $SEMANTIC_CONFIG pick each keep their own instead of replacing each other's.
+pub fn disk_cache_path(path: &Path) -> PathBuf {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    disk_cache_dir().join(format!("config-{:016x}.cache", hasher.finish()))
+}
+
+/// Remove the disk cache of the config file at `path`, if there is one.
+pub fn forget_disk_cache(path: &Path) {
+    let _ = fs::remove_file(disk_cache_path(path));
+}
+
+/// Whether $SEMANTIC_NO_CACHE asks to parse the config every time (set, and not "0").
+fn disk_cache_disabled() -> bool {
+    env::var_os("SEMANTIC_NO_CACHE").is_some_and(|v| !v.is_empty() && v != "0")
+}
+
+/// The profile that's just the plain config file.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// The profile picked for this run with `--profile`, which beats everything else.
+static PROFILE_OVERRIDE: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+/// Use profile `name` for the rest of this run, whatever is active.
+pub fn override_profile(name: &str) {
+    let _ = PROFILE_OVERRIDE.set(name.to_string());
+}
+
+/// Where `semantic profile use` records the active profile: `profile` in `config_dir`.
+pub fn active_profile_file() -> PathBuf {
+    config_dir().join("profile")
+}
+
+/// The active profile: the one given with `--profile`, else $SEMANTIC_PROFILE,
+/// else the one `semantic profile use` recorded, else the default.
+pub fn active_profile() -> String {
+    PROFILE_OVERRIDE
+        .get()
+        .cloned()
+        .or_else(|| env::var("SEMANTIC_PROFILE").ok())
+        .or_else(|| fs::read_to_string(active_profile_file()).ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+/// The names of the saved profiles, sorted. A missing directory just means none.
+pub fn profile_names() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let dir = profiles_dir();
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("{}: {e}", dir.display()).into()),
+    };
+
+    let mut names = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "toml")
+            && path.is_file()
+            && let Some(name) = path.file_stem().and_then(|stem| stem.to_str())
+        {
+            names.push(name.to_string());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// The .toml and .json files in a pack or plugin directory, sorted by name
+/// (earlier names win a key both define). A missing directory means none.
+fn mapping_files(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("{}: {e}", dir.display()).into()),
+    };
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let is_config = path
+            .extension()
+            .is_some_and(|ext| ext == "toml" || ext == "json");
+        if is_config && path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Walk up from `start` collecting project configs: .semantic/config.json,
+/// .semantic/config.toml or .semantic.toml, the first of those a directory has.
+/// Returned outermost first, so merging them in order lets the nearest win.
+fn find_project_configs(start: &Path) -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = start
+        .ancestors()
+        .filter_map(|dir| {
+            [ConfigFormat::Json, ConfigFormat::Toml]
+                .map(|format| dir.join(".semantic").join(format.file_name()))
+                .into_iter()
+                .chain([dir.join(".semantic.toml")])
+                .find(|candidate| candidate.is_file())
+        })
+        .collect();
+    found.reverse();
+    found
+}
+
+/// Whether $SEMANTIC_NO_LOCAL asks to ignore project configs (set, and not "0").
+fn local_configs_disabled() -> bool {
+    env::var_os("SEMANTIC_NO_LOCAL").is_some_and(|v| !v.is_empty() && v != "0")
+}
+
+/// Resolves the config directory, first match wins:
+/// the directory holding the file named by $SEMANTIC_CONFIG, $SEMANTIC_CONFIG_DIR,
+/// $XDG_CONFIG_HOME/semantic (on every platform, not just where dirs reads it),
+/// then the platform's config dir from the dirs crate: ~/.config/semantic/, or
+/// %APPDATA%\semantic on Windows.
+pub fn config_dir() -> PathBuf {
+    if let Some(file) = config_override() {
+        // a bare file name has an empty parent, which means the current directory
+        return match file.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+    }
+
+    if let Some(dir) = env::var_os("SEMANTIC_CONFIG_DIR").filter(|v| !v.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    // the XDG spec says to ignore a relative XDG_CONFIG_HOME
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+    {
+        return dir.join("semantic");
+    }
+
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("~/.config"))
+        .join("semantic")
+}
+
+/// The file that stands in for the user config when there is one: $SEMANTIC_CONFIG,
+/// else the active profile's, unless that's the default (see `active_profile`).
+fn config_file_override() -> Option<PathBuf> {
+    config_override().or_else(|| {
+        let name = active_profile();
+        (name != DEFAULT_PROFILE).then(|| profile_path(&name).ok()).flatten()
+    })
+}
+
+/// An alternate config file from $SEMANTIC_CONFIG (empty counts as unset).
+fn config_override() -> Option<PathBuf> {
+    env::var_os("SEMANTIC_CONFIG")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
+// -- locking --
+// An advisory lock on `config_dir`/.lock: shared while the user config is read,
+// exclusive while it's written. The lock file is only ever closed, never
+// deleted, so every process locks the same file.
+
+/// How long to wait for the lock when $SEMANTIC_LOCK_TIMEOUT_MS doesn't say.
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lock {
+    Shared,
+    Exclusive,
+}
+
+/// $SEMANTIC_LOCK_TIMEOUT_MS, or `DEFAULT_LOCK_TIMEOUT` if it's unset or not a number.
+fn lock_timeout() -> Duration {
+    env::var("SEMANTIC_LOCK_TIMEOUT_MS")
+        .ok()
+        .and_then(|ms| ms.trim().parse().ok())
+        .map_or(DEFAULT_LOCK_TIMEOUT, Duration::from_millis)
+}
+
+/// Run `f` holding the config lock, waiting up to `lock_timeout` for it.
+/// Only writers create the lock file. A shared lock opens it read-only, and is
+/// skipped when there's none yet: nothing has written there through us.
+fn with_config_lock<T>(
+    kind: Lock,
+    f: impl FnOnce() -> Result<T, Box<dyn std::error::Error>>,
+) -> Result<T, Box<dyn std::error::Error>> {
+    let dir = config_dir();
+    let path = dir.join(".lock");
+    let file = match kind {
+        Lock::Shared => match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(_) => return f(),
+        },
+        Lock::Exclusive => {
+            fs::create_dir_all(&dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+            fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)
+                .map_err(|e| format!("{}: {e}", path.display()))?
+        }
+    };
+
+    let mut lock = fd_lock::RwLock::new(file);
+    let timeout = lock_timeout();
+    let start = Instant::now();
+    // taken by whichever attempt gets the lock, which then returns
+    let mut f = Some(f);
+    let mut run = || f.take().map_or_else(|| unreachable!("ran twice"), |f| f());
+    loop {
+        // each arm keeps its guard alive until `f` is done with the file
+        let attempt = match kind {
+            Lock::Shared => lock.try_read().map(|_guard| run()),
+            Lock::Exclusive => lock.try_write().map(|_guard| run()),
+        };
+        match attempt {
+            Ok(result) => return result,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock && start.elapsed() < timeout => {
+                thread::sleep(Duration::from_millis(20));
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                return Err(format!(
+                    "another semantic process is holding {} (waited {}ms; \
+                     set SEMANTIC_LOCK_TIMEOUT_MS to wait longer)",
+                    path.display(),
+                    timeout.as_millis()
+                )
+                .into());
+            }
+            Err(e) => return Err(format!("{}: {e}", path.display()).into()),
+        }
+    }
+}
+
+// Hot reload for long-running consumers like `semantic watch`: a notify watcher
+// on the directories holding the files `load()` reads, and a thread that
+// reloads once a burst of changes has settled.
+
+/// How long `SemanticConfig::watch` waits for changes to settle before reloading.
+pub const WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// What a `ConfigWatcher`'s thread hears: a file event, or that it should stop.
+enum WatchMessage {
+    Event(notify::Result<notify::Event>),
+    Stop,
+}
+
+/// Returned by `SemanticConfig::watch`. The config is watched until it's dropped.
+pub struct ConfigWatcher {
+    tx: mpsc::Sender<WatchMessage>,
+    thread: Option<thread::JoinHandle<()>>,
 }
 
-/// Shell-related settings: which shell, which are enabled, what to do on new installs.
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ShellConfig {
-    pub default: String,
-    pub enabled: Vec<String>,
-    pub on_new_shell: String,
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        let _ = self.tx.send(WatchMessage::Stop);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
 }
 
 impl SemanticConfig {
-    /// Build a config from the TUI installer selections.
-    /// Picks the right command/path mappings based on the chosen styles.
-    pub fn from_selections(
-        shell: &str,
-        command_style: &str,
-        folder_style: &str,
-        on_new_shell: &str,
-    ) -> Self {
-        // pick command mappings based on style
-        let commands = match command_style {
-            "natural" => natural_commands(),
-            "verbose" => verbose_commands(),
-            _ => traditional_commands(),
-        };
+    /// Watch the files `load()` reads (the user config or active profile, its
+    /// includes, the packs and plugins, the project configs) and call `on_change` with the
+    /// reloaded config whenever they change. A burst of events, like an editor's
+    /// save, is one reload once `WATCH_DEBOUNCE` passes without another.
+    /// A config that no longer loads or validates goes to `on_error` instead, and
+    /// the last good one stays in effect. Returns an error if the config dir
+    /// can't be watched; a packs dir created later isn't noticed.
+    pub fn watch(
+        on_change: impl Fn(SemanticConfig) + Send + 'static,
+        on_error: impl Fn(String) + Send + 'static,
+    ) -> Result<ConfigWatcher, Box<dyn std::error::Error>> {
+        let (tx, rx) = mpsc::channel();
+        let events = tx.clone();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = events.send(WatchMessage::Event(event));
+        })?;
+        let mut watched = WatchedFiles::default();
+        watched.update(&mut watcher, Self::load().ok().as_ref())?;
 
-        // pick path mappings based on style
-        let paths = match folder_style {
-            "natural" => natural_paths(),
-            "verbose" => verbose_paths(),
-            _ => traditional_paths(),
-        };
+        let thread = thread::spawn(move || {
+            while let Ok(WatchMessage::Event(event)) = rx.recv() {
+                if !event.is_ok_and(|event| watched.affected_by(&event)) {
+                    continue;
+                }
+                loop {
+                    match rx.recv_timeout(WATCH_DEBOUNCE) {
+                        Ok(WatchMessage::Event(_)) => {}
+                        Ok(WatchMessage::Stop) | Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                        Err(mpsc::RecvTimeoutError::Timeout) => break,
+                    }
+                }
 
-        SemanticConfig {
-            general: GeneralConfig {
-                command_style: command_style.to_string(),
-                folder_style: folder_style.to_string(),
-            },
-            shells: ShellConfig {
-                default: shell.to_string(),
-                enabled: vec![shell.to_string()],
-                on_new_shell: on_new_shell.to_string(),
-            },
-            commands,
-            paths,
+                let reloaded = Self::load()
+                    .map_err(|e| e.to_string())
+                    .and_then(|config| match config.validate() {
+                        Ok(()) => Ok(config),
+                        Err(errors) => Err(errors.join("; ")),
+                    });
+                match reloaded {
+                    Ok(config) => {
+                        // an include or project config it picked up is worth watching too
+                        let _ = watched.update(&mut watcher, Some(&config));
+                        on_change(config);
+                    }
+                    Err(e) => on_error(e),
+                }
+            }
+        });
+        Ok(ConfigWatcher { tx, thread: Some(thread) })
+    }
+}
+
+/// The files a `ConfigWatcher` reloads for, and the directories it watches to see them.
+#[derive(Default)]
+struct WatchedFiles {
+    files: HashSet<PathBuf>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl WatchedFiles {
+    /// Add the files `config` was loaded from (plus the ones that would replace
+    /// them, like a new config.json or a switched profile), and watch their
+    /// directories. Returns the first directory that couldn't be watched.
+    fn update(
+        &mut self,
+        watcher: &mut impl notify::Watcher,
+        config: Option<&SemanticConfig>,
+    ) -> notify::Result<()> {
+        let dir = config_dir();
+        let mut files: Vec<PathBuf> = [ConfigFormat::Json, ConfigFormat::Toml]
+            .map(|format| dir.join(format.file_name()))
+            .into_iter()
+            .chain([active_profile_file()])
+            .chain(config_file_override())
+            .collect();
+        if let Some(config) = config {
+            files.extend(config.include.iter().map(|entry| dir.join(expand_env(entry))));
+            files.extend(config.include_sources.values().cloned());
+            files.extend(config.project_configs.iter().cloned());
+        }
+
+        let mut dirs = vec![packs_dir(), plugins_dir()];
+        for file in files {
+            if let Some(parent) = file.parent() {
+                dirs.push(parent.to_path_buf());
+            }
+            if let Ok(canonical) = fs::canonicalize(&file) {
+                self.files.insert(canonical);
+            }
+            self.files.insert(file);
         }
+
+        let mut result = Ok(());
+        for dir in dirs {
+            if !dir.is_dir() || self.dirs.contains(&dir) {
+                continue;
+            }
+            match watcher.watch(&dir, notify::RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    self.dirs.insert(dir);
+                }
+                Err(e) if result.is_ok() => result = Err(e),
+                Err(_) => {}
+            }
+        }
+        result
     }
 
-    /// Load config from ~/.config/semantic/config.toml.
-    /// Returns an error if the file doesn't exist or can't be parsed.
-    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
-        let config_path = Self::config_path();
-        let content = fs::read_to_string(&config_path)
-            .map_err(|e| format!("{}: {e}", config_path.display()))?;
-        let config: SemanticConfig = toml::from_str(&content)?;
-        Ok(config)
+    /// Whether `event` changed one of the files, or a pack or plugin.
+    fn affected_by(&self, event: &notify::Event) -> bool {
+        // reading the config is an access too, and must not reload it again
+        if matches!(event.kind, notify::EventKind::Access(_)) {
+            return false;
+        }
+        let pack_dirs = [packs_dir(), plugins_dir()];
+        event.paths.iter().any(|path| {
+            self.files.contains(path)
+                || (path.parent().is_some_and(|dir| pack_dirs.iter().any(|d| d == dir))
+                    && path.extension().is_some_and(|ext| ext == "toml" || ext == "json"))
+        })
     }
+}
 
-    /// Write the config to ~/.config/semantic/config.toml.
-    /// Creates the directory if it doesn't exist.
-    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let config_dir = config_dir();
-        fs::create_dir_all(&config_dir)?;
+/// Returns the program a real command actually runs, skipping any leading `sudo`.
+/// e.g. "sudo pacman -S" -> "pacman", "ls -la" -> "ls".
+pub fn real_program(real_cmd: &str) -> Option<&str> {
+    real_cmd.split_whitespace().find(|token| *token != "sudo")
+}
 
-        let config_path = config_dir.join("config.toml");
-        let content = toml::to_string_pretty(self)?;
-        fs::write(&config_path, content)?;
+// -- package managers --
+// Known package managers and the commands the semantic install/remove/update map to.
 
-        Ok(())
+/// Install/remove/update commands for one package manager.
+pub struct PackageManager {
+    pub name: &'static str,
+    pub install: &'static str,
+    pub remove: &'static str,
+    pub update: &'static str,
+}
+
+pub const PACKAGE_MANAGERS: &[PackageManager] = &[
+    PackageManager {
+        name: "pacman",
+        install: "sudo pacman -S",
+        remove: "sudo pacman -R",
+        update: "sudo pacman -Syu",
+    },
+    PackageManager {
+        name: "apt",
+        install: "sudo apt install",
+        remove: "sudo apt remove",
+        update: "sudo apt upgrade",
+    },
+    PackageManager {
+        name: "dnf",
+        install: "sudo dnf install",
+        remove: "sudo dnf remove",
+        update: "sudo dnf upgrade",
+    },
+    PackageManager {
+        name: "zypper",
+        install: "sudo zypper install",
+        remove: "sudo zypper remove",
+        update: "sudo zypper update",
+    },
+    PackageManager {
+        name: "xbps-install",
+        install: "sudo xbps-install -S",
+        remove: "sudo xbps-remove",
+        update: "sudo xbps-install -Su",
+    },
+    PackageManager {
+        name: "brew",
+        install: "brew install",
+        remove: "brew uninstall",
+        update: "brew upgrade",
+    },
+    PackageManager {
+        name: "winget",
+        install: "winget install",
+        remove: "winget uninstall",
+        update: "winget upgrade --all",
+    },
+    PackageManager {
+        name: "scoop",
+        install: "scoop install",
+        remove: "scoop uninstall",
+        update: "scoop update --all",
+    },
+];
+
+impl PackageManager {
+    /// Find the known package manager whose program matches `program`.
+    pub fn by_program(program: &str) -> Option<&'static PackageManager> {
+        PACKAGE_MANAGERS.iter().find(|pm| {
+            [pm.install, pm.remove, pm.update]
+                .iter()
+                .any(|cmd| real_program(cmd) == Some(program))
+        })
     }
 
-    /// Returns the full path to config.toml (for display purposes).
-    pub fn config_path() -> PathBuf {
-        config_dir().join("config.toml")
+    /// Given a real command from another package manager, return this one's equivalent.
+    /// Matches on the action (install/remove/update) of whichever manager `real_cmd` uses.
+    pub fn equivalent_of(&self, real_cmd: &str) -> Option<&'static str> {
+        let other = PackageManager::by_program(real_program(real_cmd)?)?;
+        if real_cmd == other.install {
+            Some(self.install)
+        } else if real_cmd == other.remove {
+            Some(self.remove)
+        } else if real_cmd == other.update {
+            Some(self.update)
+        } else {
+            None
+        }
     }
 }
 
-/// Resolves ~/.config/semantic/ using the dirs crate.
-fn config_dir() -> PathBuf {
-    dirs::config_dir()
-        .unwrap_or_else(|| PathBuf::from("~/.config"))
-        .join("semantic")
+/// Detect which package manager is available on this system by checking $PATH.
+/// Returns the first match in PACKAGE_MANAGERS order, or None if none are installed.
+pub fn detect_package_manager() -> Option<&'static PackageManager> {
+    PACKAGE_MANAGERS
+        .iter()
+        .find(|pm| real_program(pm.install).is_some_and(|p| find_in_path(p).is_some()))
+}
+
+// -- languages --
+// The natural style's names in each language semantic ships. Verbose names are
+// English only, emoji need no language, and traditional names are the real
+// commands in any language.
+
+/// One bundled language: its `[general] language` code, its own name for
+/// itself, and the natural-style commands and paths in it.
+pub struct Language {
+    pub code: &'static str,
+    pub name: &'static str,
+    pub command_example: &'static str, // for the style pickers
+    pub path_example: &'static str,
+    commands: &'static [(&'static str, &'static str)],
+    paths: &'static [(&'static str, &'static str)],
+}
+
+/// Every bundled language, English (the default) first.
+pub const LANGUAGES: &[Language] = &[
+    Language {
+        code: "en",
+        name: "English",
+        command_example: "goto, list, install, delete",
+        path_example: "/apps, /settings, /logs",
+        commands: &[
+            ("goto", "cd"),
+            ("back", "cd .."),
+            ("list", "ls -la"),
+            ("delete", "rm -rf"),
+            ("copy", "cp -r"),
+            ("move", "mv"),
+            ("install", "sudo pacman -S"),
+            ("remove", "sudo pacman -R"),
+            ("update", "sudo pacman -Syu"),
+        ],
+        paths: &[("/apps", "/usr/bin"), ("/settings", "/etc"), ("/logs", "/var/log")],
+    },
+    Language {
+        code: "es",
+        name: "Español",
+        command_example: "ir, listar, instalar, borrar",
+        path_example: "/programas, /ajustes, /registros",
+        commands: &[
+            ("ir", "cd"),
+            ("atrás", "cd .."),
+            ("listar", "ls -la"),
+            ("borrar", "rm -rf"),
+            ("copiar", "cp -r"),
+            ("mover", "mv"),
+            ("instalar", "sudo pacman -S"),
+            ("quitar", "sudo pacman -R"),
+            ("actualizar", "sudo pacman -Syu"),
+        ],
+        paths: &[("/programas", "/usr/bin"), ("/ajustes", "/etc"), ("/registros", "/var/log")],
+    },
+    Language {
+        code: "de",
+        name: "Deutsch",
+        command_example: "gehe, liste, installieren, löschen",
+        path_example: "/programme, /einstellungen, /protokolle",
+        commands: &[
+            ("gehe", "cd"),
+            ("zurück", "cd .."),
+            ("liste", "ls -la"),
+            ("löschen", "rm -rf"),
+            ("kopieren", "cp -r"),
+            ("verschieben", "mv"),
+            ("installieren", "sudo pacman -S"),
+            ("entfernen", "sudo pacman -R"),
+            ("aktualisieren", "sudo pacman -Syu"),
+        ],
+        paths: &[
+            ("/programme", "/usr/bin"),
+            ("/einstellungen", "/etc"),
+            ("/protokolle", "/var/log"),
+        ],
+    },
+];
+
+/// The bundled language with this code, or English for an unknown one.
+pub fn language(code: &str) -> &'static Language {
+    LANGUAGES.iter().find(|l| l.code == code).unwrap_or(&LANGUAGES[0])
+}
+
+/// The bundled language matching the locale in $LC_ALL, $LC_MESSAGES or $LANG
+/// (e.g. `es_ES.UTF-8`), if there is one.
+pub fn language_from_env() -> Option<&'static Language> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .find_map(|var| env::var(var).ok().filter(|v| !v.is_empty()))?;
+    let code = locale.split(['_', '.', '@']).next()?;
+    LANGUAGES.iter().find(|l| l.code == code)
 }
 
 // -- command mappings --
 // Each style returns a map of semantic_command -> real_command.
-// These match the templates in templates/*.toml.
+// These match the templates in templates/*.toml (see `template_drift`).
 
-fn natural_commands() -> HashMap<String, String> {
-    HashMap::from([
-        ("goto".into(), "cd".into()),
-        ("back".into(), "cd ..".into()),
-        ("list".into(), "ls -la".into()),
-        ("delete".into(), "rm -rf".into()),
-        ("copy".into(), "cp -r".into()),
-        ("move".into(), "mv".into()),
-        ("install".into(), "sudo pacman -S".into()),
-        ("remove".into(), "sudo pacman -R".into()),
-        ("update".into(), "sudo pacman -Syu".into()),
-    ])
+/// The stock command mappings for a style, with natural names in `language`.
+/// Unknown styles get traditional (identity mappings).
+pub fn style_commands(style: &str, language: &str) -> HashMap<String, String> {
+    match style {
+        "natural" => natural_commands(language),
+        "verbose" => verbose_commands(),
+        "emoji" => emoji_commands(),
+        _ => traditional_commands(),
+    }
+}
+
+fn natural_commands(code: &str) -> HashMap<String, String> {
+    language(code)
+        .commands
+        .iter()
+        .map(|(name, real_cmd)| (name.to_string(), real_cmd.to_string()))
+        .collect()
 }
 
 fn verbose_commands() -> HashMap<String, String> {
@@ -139,6 +3017,22 @@ fn verbose_commands() -> HashMap<String, String> {
     ])
 }
 
+/// One emoji per verb. All of them are single code points that show as emoji
+/// without a variation selector, so what's typed is exactly the key.
+fn emoji_commands() -> HashMap<String, String> {
+    HashMap::from([
+        ("📂".into(), "cd".into()),
+        ("🔙".into(), "cd ..".into()),
+        ("📋".into(), "ls -la".into()),
+        ("🚮".into(), "rm -rf".into()),
+        ("📑".into(), "cp -r".into()),
+        ("🚚".into(), "mv".into()),
+        ("📦".into(), "sudo pacman -S".into()),
+        ("🧹".into(), "sudo pacman -R".into()),
+        ("🔄".into(), "sudo pacman -Syu".into()),
+    ])
+}
+
 fn traditional_commands() -> HashMap<String, String> {
     // identity mappings — real commands map to themselves
     HashMap::from([
@@ -151,16 +3045,155 @@ fn traditional_commands() -> HashMap<String, String> {
     ])
 }
 
+// -- command descriptions --
+// What each stock command does, keyed by the real command so every style and
+// language shares them. Read by `semantic help` and the TUI's command preview.
+
+/// Description of one stock real command. `example` is what follows the
+/// semantic name in a usage example (`goto /apps`).
+pub struct CommandDoc {
+    pub real: &'static str,
+    pub category: &'static str,
+    pub description: &'static str,
+    pub example: &'static str,
+}
+
+/// Every described command, grouped by category in the order `semantic help` lists them.
+pub const COMMAND_DOCS: &[CommandDoc] = &[
+    CommandDoc { real: "cd", category: "Navigation", description: "go to a directory", example: "/apps" },
+    CommandDoc { real: "cd ..", category: "Navigation", description: "go up to the parent directory", example: "" },
+    CommandDoc { real: "ls -la", category: "Files", description: "list files, hidden ones included, with details", example: "/settings" },
+    CommandDoc { real: "ls", category: "Files", description: "list files", example: "/settings" },
+    CommandDoc { real: "rm -rf", category: "Files", description: "delete files and directories, without asking", example: "old-notes.txt" },
+    CommandDoc { real: "rm", category: "Files", description: "delete files", example: "old-notes.txt" },
+    CommandDoc { real: "cp -r", category: "Files", description: "copy files and directories", example: "notes.txt notes-backup.txt" },
+    CommandDoc { real: "cp", category: "Files", description: "copy files", example: "notes.txt notes-backup.txt" },
+    CommandDoc { real: "mv", category: "Files", description: "move or rename files", example: "draft.txt final.txt" },
+    CommandDoc { real: "sudo pacman -S", category: "Packages", description: "install packages", example: "firefox" },
+    CommandDoc { real: "sudo pacman -R", category: "Packages", description: "remove packages", example: "firefox" },
+    CommandDoc { real: "sudo pacman -Syu", category: "Packages", description: "update every installed package", example: "" },
+    CommandDoc { real: "pacman", category: "Packages", description: "manage packages", example: "-Qi firefox" },
+];
+
+/// Category for commands `COMMAND_DOCS` doesn't describe.
+pub const CUSTOM_CATEGORY: &str = "Custom";
+
+/// The description of `real_cmd`, if it's a stock command.
+pub fn command_doc(real_cmd: &str) -> Option<&'static CommandDoc> {
+    COMMAND_DOCS.iter().find(|doc| doc.real == real_cmd.trim())
+}
+
+/// One line saying what a mapping to `real_cmd` does: the stock description,
+/// or `runs: <real_cmd>` for anything else.
+pub fn describe_command(real_cmd: &str) -> String {
+    match command_doc(real_cmd) {
+        Some(doc) => doc.description.to_string(),
+        None => format!("runs: {real_cmd}"),
+    }
+}
+
 // -- path mappings --
 // Each style returns a map of virtual_path -> real_path.
-// Used by the FUSE layer to remap directory names.
+// Translated in command arguments, and served as directories by `semantic mount`.
 
-fn natural_paths() -> HashMap<String, String> {
-    HashMap::from([
-        ("/apps".into(), "/usr/bin".into()),
-        ("/settings".into(), "/etc".into()),
-        ("/logs".into(), "/var/log".into()),
-    ])
+/// Where style templates are looked for: templates/ in `config_dir`, where
+/// the templates/*.toml that come with semantic's source can be copied to have
+/// the built-in styles checked against them (see `template_drift`).
+pub fn templates_dir() -> PathBuf {
+    config_dir().join("templates")
+}
+
+/// One templates/<style>.toml: a style's stock mappings written out as a file.
+/// A table the template doesn't have isn't compared.
+#[derive(Deserialize)]
+struct Template {
+    commands: Option<HashMap<String, String>>,
+    paths: Option<HashMap<String, String>>,
+}
+
+/// Where the built-in mappings of `command_style` and `folder_style` (natural
+/// names in `language`) differ from their templates in `dir`, one line per
+/// mapping, so a template edit that never made it into the code isn't silently
+/// ignored. The natural style's template is natural.<language>.toml, except in
+/// English. A style without a template, or no `dir` at all, has nothing to report.
+pub fn template_drift(
+    dir: &Path,
+    command_style: &str,
+    folder_style: &str,
+    language: &str,
+) -> Vec<String> {
+    let file_name = |style: &str| match style {
+        "natural" if language != "en" => format!("natural.{language}.toml"),
+        _ => format!("{style}.toml"),
+    };
+    let mut drift = Vec::new();
+    let tables = [
+        ("commands", command_style, style_commands(command_style, language)),
+        ("paths", folder_style, style_paths(folder_style, language)),
+    ];
+    for (table, style, built_in) in tables {
+        let path = dir.join(file_name(style));
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                drift.push(format!("{}: {e}", display_path(&path)));
+                continue;
+            }
+        };
+        let template: Template = match toml::from_str(&content) {
+            Ok(template) => template,
+            Err(e) => {
+                drift.push(format!("{}: {}", display_path(&path), e.message()));
+                continue;
+            }
+        };
+        let in_template = match table {
+            "commands" => template.commands,
+            _ => template.paths,
+        };
+        let Some(mut in_template) = in_template else {
+            continue;
+        };
+
+        let mut keys: Vec<&String> = built_in.keys().collect();
+        keys.sort();
+        for key in keys {
+            let at = format!("{}: [{table}] \"{key}\"", display_path(&path));
+            match in_template.remove(key) {
+                Some(value) if value == built_in[key] => {}
+                Some(value) => {
+                    drift.push(format!("{at} is \"{value}\" there but \"{}\" built in", built_in[key]));
+                }
+                None => drift.push(format!("{at} is built in but not in the template")),
+            }
+        }
+        let mut extra: Vec<String> = in_template.into_keys().collect();
+        extra.sort();
+        for key in extra {
+            let at = format!("{}: [{table}] \"{key}\"", display_path(&path));
+            drift.push(format!("{at} is in the template but not built in"));
+        }
+    }
+    drift
+}
+
+/// The stock path mappings for a style, with natural names in `language`.
+/// Unknown styles get traditional (none).
+pub fn style_paths(style: &str, language: &str) -> HashMap<String, String> {
+    match style {
+        "natural" => natural_paths(language),
+        "verbose" => verbose_paths(),
+        _ => traditional_paths(),
+    }
+}
+
+fn natural_paths(code: &str) -> HashMap<String, String> {
+    language(code)
+        .paths
+        .iter()
+        .map(|(virtual_path, real_path)| (virtual_path.to_string(), real_path.to_string()))
+        .collect()
 }
 
 fn verbose_paths() -> HashMap<String, String> {
@@ -175,3 +3208,750 @@ fn traditional_paths() -> HashMap<String, String> {
     // no remapping — use real paths as-is
     HashMap::new()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, Sandbox};
+
+    #[test]
+    fn expand_env_reads_plain_and_braced_variables() {
+        let mut sandbox = Sandbox::new();
+        sandbox.set("SEMANTIC_TEST_DIR", "/opt/tools");
+        assert_eq!(expand_env("$SEMANTIC_TEST_DIR/bin"), "/opt/tools/bin");
+        assert_eq!(expand_env("${SEMANTIC_TEST_DIR}bin"), "/opt/toolsbin");
+        assert_eq!(expand_env("cd $SEMANTIC_TEST_DIR"), "cd /opt/tools");
+    }
+
+    #[test]
+    fn expand_env_leaves_unset_variables_as_written() {
+        let _sandbox = Sandbox::new();
+        assert_eq!(expand_env("$SEMANTIC_TEST_UNSET/bin"), "$SEMANTIC_TEST_UNSET/bin");
+        assert_eq!(expand_env("${SEMANTIC_TEST_UNSET}/bin"), "${SEMANTIC_TEST_UNSET}/bin");
+    }
+
+    #[test]
+    fn expand_env_keeps_dollars_that_start_no_name() {
+        let _sandbox = Sandbox::new();
+        assert_eq!(expand_env("echo $$"), "echo $$");
+        assert_eq!(expand_env("costs 5$"), "costs 5$");
+        assert_eq!(expand_env("${unterminated"), "${unterminated");
+    }
+
+    #[test]
+    fn expand_env_resolves_nested_braces() {
+        let mut sandbox = Sandbox::new();
+        sandbox.set("SEMANTIC_TEST_ENV", "prod");
+        sandbox.set("SEMANTIC_TEST_DIR_prod", "/srv/prod");
+        assert_eq!(expand_env("${SEMANTIC_TEST_DIR_${SEMANTIC_TEST_ENV}}/logs"), "/srv/prod/logs");
+    }
+
+    #[test]
+    fn expanded_paths_keep_their_spaces() {
+        let mut sandbox = Sandbox::new();
+        sandbox.set("SEMANTIC_TEST_DIR", "/opt/my tools");
+        let mut config = testutil::config(
+            "[commands]\ntools = \"ls $SEMANTIC_TEST_DIR\"\n[paths]\n\"/tools\" = \"$SEMANTIC_TEST_DIR\"",
+        );
+        assert_eq!(config.resolve_path("/tools/bin").as_deref(), Some("/opt/my tools/bin"));
+
+        config.expand_env_vars();
+        assert_eq!(config.paths["/tools"], "/opt/my tools");
+        assert_eq!(config.commands["tools"], "ls /opt/my tools");
+    }
+
+    #[test]
+    fn expand_command_env_skips_single_quotes() {
+        let mut sandbox = Sandbox::new();
+        sandbox.set("SEMANTIC_TEST_DIR", "/opt/tools");
+        assert_eq!(
+            expand_command_env("echo '$SEMANTIC_TEST_DIR' $SEMANTIC_TEST_DIR"),
+            "echo '$SEMANTIC_TEST_DIR' /opt/tools"
+        );
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn suggest_finds_a_near_miss() {
+        let known = names(&["install", "remove", "update"]);
+        assert_eq!(suggest("instal", &known), Some("install"));
+        assert_eq!(suggest("upadte", &known), Some("update"));
+    }
+
+    #[test]
+    fn suggest_stays_quiet_without_a_close_name() {
+        let known = names(&["install", "remove", "update"]);
+        assert_eq!(suggest("firefox", &known), None);
+        assert_eq!(suggest("x", &known), None);
+        assert_eq!(suggest("instal", &names(&[])), None);
+    }
+
+    #[test]
+    fn suggest_allows_three_edits_or_one_less_than_the_name() {
+        let known = names(&["install", "ls"]);
+        // 3 edits away is close enough, 4 isn't
+        assert_eq!(suggest("inst", &known), Some("install"));
+        assert_eq!(suggest("ins", &names(&["install"])), None);
+        // a two-letter name may only be one edit off
+        assert_eq!(suggest("lx", &known), Some("ls"));
+        assert_eq!(suggest("xy", &names(&["ls"])), None);
+    }
+
+    #[test]
+    fn suggest_breaks_ties_alphabetically() {
+        let known = names(&["stop", "stat", "star"]);
+        assert_eq!(suggest("sta", &known), Some("star"));
+    }
+
+    #[test]
+    fn pack_commands_fill_in_under_the_user_config() {
+        let sandbox = Sandbox::new();
+        sandbox.write_config("[commands]\ninstall = \"sudo pacman -S\"");
+        let pack = sandbox.write(
+            "config/packs/team.toml",
+            "[commands]\ndeploy = \"make deploy\"\ninstall = \"apt install\"\n\
+             [paths]\n\"/team\" = \"/srv/team\"",
+        );
+
+        let config = SemanticConfig::load().unwrap();
+        assert_eq!(config.commands["deploy"], "make deploy");
+        assert_eq!(config.paths["/team"], "/srv/team");
+        assert_eq!(config.pack_sources[&MappingKey::Command("deploy".to_string())], pack);
+        // the user's own mapping wins, and the clash is noted
+        assert_eq!(config.commands["install"], "sudo pacman -S");
+        assert_eq!(config.pack_collisions, [(pack, MappingKey::Command("install".to_string()))]);
+    }
+
+    #[test]
+    fn a_malformed_config_names_the_offending_line() {
+        let sandbox = Sandbox::new();
+        let path = sandbox.write_config("[commands]\ninstall = \"sudo pacman -S\nremove = \"rm\"");
+        // after the base config and the blank line that ends it
+        let line = testutil::BASE_CONFIG.lines().count() + 3;
+
+        let message = SemanticConfig::load().unwrap_err().to_string();
+        assert!(message.starts_with(&format!("{}:{line}: ", path.display())), "{message}");
+        assert!(message.contains("semantic doctor"), "{message}");
+    }
+
+    #[test]
+    fn a_value_of_the_wrong_type_names_its_line() {
+        let sandbox = Sandbox::new();
+        let content = "[general]\ncommand_style = \"natural\"\nfolder_style = 12\n";
+        let path = sandbox.write("config/config.toml", content);
+
+        let error = SemanticConfig::load_from(&path).unwrap_err();
+        let error = error.downcast_ref::<ParseError>().expect("a ParseError");
+        assert_eq!(error.line, Some(3));
+        assert!(error.summary().starts_with(&format!("{}:3: ", path.display())), "{}", error.summary());
+    }
+
+    #[test]
+    fn semantic_config_dir_comes_first() {
+        let mut sandbox = Sandbox::new();
+        let dir = sandbox.path("elsewhere");
+        sandbox.set("SEMANTIC_CONFIG_DIR", &dir);
+        sandbox.set("XDG_CONFIG_HOME", sandbox.path("xdg"));
+        assert_eq!(config_dir(), dir);
+        assert_eq!(SemanticConfig::config_path(), dir.join("config.toml"));
+    }
+
+    #[test]
+    fn xdg_config_home_is_next_if_absolute() {
+        let mut sandbox = Sandbox::new();
+        sandbox.unset("SEMANTIC_CONFIG_DIR");
+        sandbox.set("XDG_CONFIG_HOME", sandbox.path("xdg"));
+        assert_eq!(config_dir(), sandbox.path("xdg/semantic"));
+
+        sandbox.set("XDG_CONFIG_HOME", "relative/xdg");
+        assert_ne!(config_dir(), PathBuf::from("relative/xdg/semantic"));
+    }
+
+    #[test]
+    fn semantic_config_overrides_both_and_sets_the_dir() {
+        let mut sandbox = Sandbox::new();
+        let file = sandbox.path("work/semantic.toml");
+        sandbox.set("SEMANTIC_CONFIG", &file);
+        assert_eq!(SemanticConfig::config_path(), file);
+        assert_eq!(config_dir(), sandbox.path("work"));
+    }
+
+    #[test]
+    fn an_include_is_merged_under_the_including_file() {
+        let sandbox = Sandbox::new();
+        sandbox.write(
+            "config/base.toml",
+            "[general]\ncommand_style = \"verbose\"\nfolder_style = \"verbose\"\n\
+             [commands]\ninstall = \"apt install\"\nupdate = \"apt update\"",
+        );
+        let base = testutil::BASE_CONFIG;
+        sandbox.write(
+            "config/config.toml",
+            &format!("include = [\"base.toml\"]\n{base}[commands]\ninstall = \"sudo pacman -S\""),
+        );
+
+        let config = SemanticConfig::load().unwrap();
+        assert_eq!(config.commands["update"], "apt update");
+        assert_eq!(config.commands["install"], "sudo pacman -S");
+        // settings come from the including file
+        assert_eq!(config.general.command_style, "natural");
+        let update = MappingKey::Command("update".to_string());
+        let base = fs::canonicalize(sandbox.path("config/base.toml")).unwrap();
+        assert_eq!(config.include_sources[&update], base);
+    }
+
+    #[test]
+    fn a_cyclic_include_is_an_error() {
+        let sandbox = Sandbox::new();
+        let base = testutil::BASE_CONFIG;
+        sandbox.write("config/a.toml", &format!("include = [\"b.toml\"]\n{base}"));
+        sandbox.write("config/b.toml", &format!("include = [\"a.toml\"]\n{base}"));
+        sandbox.write("config/config.toml", &format!("include = [\"a.toml\"]\n{base}"));
+
+        let message = SemanticConfig::load().unwrap_err().to_string();
+        assert!(message.contains("forms a cycle"), "{message}");
+    }
+
+    #[test]
+    fn reading_the_config_never_creates_the_lock_file() {
+        let sandbox = Sandbox::new();
+        sandbox.write_config("[commands]\ninstall = \"sudo pacman -S\"");
+        SemanticConfig::load().unwrap();
+        assert!(!sandbox.path("config/.lock").exists());
+    }
+
+    #[test]
+    fn saving_creates_the_lock_file_and_keeps_it() {
+        let sandbox = Sandbox::new();
+        testutil::config("[commands]\ninstall = \"sudo pacman -S\"").save().unwrap();
+        assert!(sandbox.path("config/.lock").exists());
+        // readers take the existing one
+        assert_eq!(SemanticConfig::load().unwrap().commands["install"], "sudo pacman -S");
+        assert!(sandbox.path("config/.lock").exists());
+    }
+
+    #[test]
+    fn a_held_lock_times_out_with_a_clear_error() {
+        let mut sandbox = Sandbox::new();
+        sandbox.write_config("");
+        let lock_file = fs::File::create(sandbox.path("config/.lock")).unwrap();
+        let mut held = fd_lock::RwLock::new(lock_file);
+        let _guard = held.try_write().unwrap();
+        sandbox.set("SEMANTIC_LOCK_TIMEOUT_MS", "50");
+
+        let message = SemanticConfig::load().unwrap_err().to_string();
+        assert!(message.contains("another semantic process is holding"), "{message}");
+    }
+
+    /// What the setup wizard writes for verbose commands and `folder_style`.
+    fn wizard_config(folder_style: &str) -> SemanticConfig {
+        let styles = StyleOverrides::default();
+        SemanticConfig::from_selections(
+            "bash",
+            &["bash"],
+            "verbose",
+            folder_style,
+            "en",
+            "ignore",
+            &[],
+            &styles,
+        )
+    }
+
+    #[test]
+    fn customizations_survive_rebuilding_from_styles() {
+        let mut config = wizard_config("natural");
+        config.commands.insert("serve".to_string(), "python -m http.server".to_string());
+        config.commands.insert("list-files".to_string(), "eza -la".to_string());
+        config.commands.remove("delete-file");
+
+        let custom = Customizations::of(&config);
+        assert_eq!(custom.len(), 2);
+        assert_eq!(custom.removed_commands, ["delete-file"]);
+
+        let mut rebuilt = wizard_config("verbose");
+        custom.apply_to(&mut rebuilt);
+        assert_eq!(rebuilt.commands["serve"], "python -m http.server");
+        assert_eq!(rebuilt.commands["list-files"], "eza -la");
+        assert!(!rebuilt.commands.contains_key("delete-file"));
+        assert_eq!(rebuilt.commands["move-file"], "mv");
+    }
+
+    #[test]
+    fn a_stock_config_has_nothing_to_carry_over() {
+        let custom = Customizations::of(&wizard_config("natural"));
+        assert!(custom.is_empty());
+        assert!(custom.removed_commands.is_empty() && custom.removed_paths.is_empty());
+    }
+
+    #[test]
+    fn project_configs_shadow_the_global_one_nearest_last() {
+        let mut sandbox = Sandbox::new();
+        sandbox.write_config("[commands]\ninstall = \"sudo pacman -S\"\nupdate = \"sudo pacman -Syu\"");
+        let outer = "[commands]\ninstall = \"npm install\"\ndeploy = \"make deploy\"";
+        sandbox.write("project/.semantic.toml", outer);
+        sandbox.write("project/app/.semantic.toml", "[commands]\ninstall = \"cargo add\"");
+        sandbox.chdir("project/app/src");
+        sandbox.unset("SEMANTIC_NO_LOCAL");
+
+        let config = SemanticConfig::load().unwrap();
+        assert_eq!(config.commands["install"], "cargo add");
+        assert_eq!(config.commands["deploy"], "make deploy");
+        assert_eq!(config.commands["update"], "sudo pacman -Syu");
+        assert_eq!(config.project_configs.len(), 2);
+    }
+
+    #[test]
+    fn semantic_no_local_leaves_project_configs_out() {
+        let sandbox = Sandbox::new();
+        sandbox.write_config("[commands]\ninstall = \"sudo pacman -S\"");
+        sandbox.write("project/.semantic.toml", "[commands]\ninstall = \"npm install\"");
+        sandbox.chdir("project");
+
+        let config = SemanticConfig::load().unwrap();
+        assert_eq!(config.commands["install"], "sudo pacman -S");
+        assert!(config.project_configs.is_empty());
+    }
+
+    #[test]
+    fn line_diff_marks_what_changed_between_two_configs() {
+        let old = testutil::config("[commands]\ninstall = \"sudo pacman -S\"\nupdate = \"pacman -Syu\"");
+        let new = testutil::config("[commands]\ninstall = \"sudo pacman -S\"\nsearch = \"pacman -Ss\"");
+        let diff = line_diff(&sorted_toml(&old).unwrap(), &sorted_toml(&new).unwrap());
+
+        let changed: Vec<_> = diff.iter().filter(|line| !matches!(line, LineDiff::Same(_))).collect();
+        assert_eq!(
+            changed,
+            [
+                &LineDiff::Removed("update = \"pacman -Syu\"".to_string()),
+                &LineDiff::Added("search = \"pacman -Ss\"".to_string()),
+            ]
+        );
+        assert!(diff.contains(&LineDiff::Same("install = \"sudo pacman -S\"".to_string())));
+    }
+
+    #[test]
+    fn line_diff_of_identical_configs_is_all_the_same() {
+        let config = testutil::config("[commands]\ninstall = \"sudo pacman -S\"");
+        let text = sorted_toml(&config).unwrap();
+        let diff = line_diff(&text, &text);
+        assert_eq!(diff.len(), text.lines().count());
+        assert!(diff.iter().all(|line| matches!(line, LineDiff::Same(_))));
+    }
+
+    #[test]
+    fn load_cached_reuses_the_parsed_config_until_the_file_changes() {
+        let sandbox = Sandbox::new();
+        let path = sandbox.write_config("[commands]\ninstall = \"sudo pacman -S\"");
+
+        // a parse makes a new Arc, so the same one back means it wasn't read again
+        let first = SemanticConfig::load_cached().unwrap();
+        let second = SemanticConfig::load_cached().unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        sandbox.write_config("[commands]\ninstall = \"apt install\"");
+        let later = SystemTime::now() + std::time::Duration::from_secs(10);
+        fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        let edited = SemanticConfig::load_cached().unwrap();
+        assert!(!Arc::ptr_eq(&first, &edited));
+        assert_eq!(edited.commands["install"], "apt install");
+    }
+
+    #[test]
+    fn forget_cached_forces_a_reload() {
+        let sandbox = Sandbox::new();
+        sandbox.write_config("");
+        let first = SemanticConfig::load_cached().unwrap();
+        SemanticConfig::forget_cached();
+        assert!(!Arc::ptr_eq(&first, &SemanticConfig::load_cached().unwrap()));
+    }
+
+    /// The config as JSON, to compare two configs field by field.
+    fn json(config: &SemanticConfig) -> serde_json::Value {
+        serde_json::to_value(config).unwrap()
+    }
+
+    #[test]
+    fn the_disk_cache_gives_the_same_config_as_parsing() {
+        let sandbox = Sandbox::new();
+        let mappings = "[commands]\ninstall = \"sudo pacman -S\"\n[paths]\n\"/apps\" = \"/usr/bin\"";
+        let path = sandbox.write_config(mappings);
+
+        let parsed = SemanticConfig::load_from_cached(&path).unwrap();
+        assert!(disk_cache_path(&path).exists());
+        let cached = SemanticConfig::load_from_cached(&path).unwrap();
+        assert_eq!(json(&cached), json(&parsed));
+        assert_eq!(json(&cached), json(&SemanticConfig::load_from(&path).unwrap()));
+    }
+
+    #[test]
+    fn editing_the_config_misses_the_disk_cache() {
+        let sandbox = Sandbox::new();
+        let path = sandbox.write_config("[commands]\ninstall = \"sudo pacman -S\"");
+        SemanticConfig::load_from_cached(&path).unwrap();
+
+        // a different size is a different stamp, whatever the modification time
+        sandbox.write_config("[commands]\ninstall = \"sudo apt install\"");
+        let config = SemanticConfig::load_from_cached(&path).unwrap();
+        assert_eq!(config.commands["install"], "sudo apt install");
+
+        // and a cache that doesn't parse is replaced rather than failing the load
+        fs::write(disk_cache_path(&path), "{ not json").unwrap();
+        let config = SemanticConfig::load_from_cached(&path).unwrap();
+        assert_eq!(config.commands["install"], "sudo apt install");
+        assert!(fs::read_to_string(disk_cache_path(&path)).unwrap().starts_with("{\""));
+    }
+
+    #[test]
+    fn each_config_dir_has_its_own_disk_cache() {
+        let mut sandbox = Sandbox::new();
+        let user = sandbox.write_config("[commands]\ninstall = \"sudo pacman -S\"");
+        let other = sandbox.write("other/config.toml", &format!("{}\n", testutil::BASE_CONFIG));
+        assert_ne!(disk_cache_path(&user), disk_cache_path(&other));
+
+        assert_eq!(SemanticConfig::load().unwrap().commands["install"], "sudo pacman -S");
+        sandbox.set("SEMANTIC_CONFIG_DIR", sandbox.path("other"));
+        assert!(!SemanticConfig::load().unwrap().commands.contains_key("install"));
+        assert!(disk_cache_path(&user).exists() && disk_cache_path(&other).exists());
+    }
+
+    #[test]
+    fn semantic_no_cache_skips_the_disk_cache() {
+        let mut sandbox = Sandbox::new();
+        let path = sandbox.write_config("");
+        sandbox.set("SEMANTIC_NO_CACHE", "1");
+        SemanticConfig::load_from_cached(&path).unwrap();
+        assert!(!disk_cache_path(&path).exists());
+    }
+
+    #[test]
+    fn saving_removes_the_disk_cache() {
+        let sandbox = Sandbox::new();
+        let path = sandbox.write_config("");
+        let config = SemanticConfig::load_from_cached(&path).unwrap();
+        config.save().unwrap();
+        assert!(!disk_cache_path(&path).exists());
+    }
+
+    #[test]
+    fn key_collisions_finds_commands_that_clash_with_paths() {
+        let config = testutil::config(
+            "[commands]\n\"/apps\" = \"ls /usr/bin\"\nlogs = \"journalctl\"\n\
+             [commands.fish]\n\"~home\" = \"cd ~\"\n\
+             [paths]\n\"/apps\" = \"/usr/bin\"\nlogs = \"/var/log\"",
+        );
+        let collisions = config.key_collisions();
+        let names: Vec<&str> = collisions.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["/apps", "fish.~home", "logs"]);
+        assert!(collisions[2].1.contains("both a command and a path"));
+        assert!(collisions[1].1.contains("looks like a path"));
+    }
+
+    #[test]
+    fn a_clean_config_has_no_key_collisions() {
+        let mappings = "[commands]\ninstall = \"sudo pacman -S\"\n[paths]\n\"/apps\" = \"/usr/bin\"";
+        let config = testutil::config(mappings);
+        assert!(config.key_collisions().is_empty());
+    }
+
+    #[test]
+    fn a_config_with_key_collisions_isnt_saved_or_loaded() {
+        let sandbox = Sandbox::new();
+        let config = testutil::config("[commands]\nlogs = \"journalctl\"\n[paths]\nlogs = \"/var/log\"");
+        let path = sandbox.path("saved.toml");
+        let error = config.save_to(&path).unwrap_err().to_string();
+        assert!(error.starts_with("not saved: command `logs` is both"), "{error}");
+        assert!(!path.exists());
+
+        sandbox.write_config("[commands]\nlogs = \"journalctl\"\n[paths]\nlogs = \"/var/log\"");
+        let error = SemanticConfig::load().unwrap_err();
+        assert!(matches!(&error, SemanticError::BadMapping { name, .. } if name == "logs"), "{error}");
+    }
+
+    /// A config from just `toml`, without `BASE_CONFIG`'s settings.
+    fn bare(toml: &str) -> SemanticConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn merging_an_empty_overlay_keeps_the_base() {
+        let mappings = "[commands]\ninstall = \"sudo pacman -S\"\n[paths]\n\"/apps\" = \"/usr/bin\"";
+        let base = testutil::config(mappings);
+        let merged = SemanticConfig::merge(base.clone(), bare(""));
+        assert_eq!(json(&merged), json(&base));
+    }
+
+    #[test]
+    fn merging_onto_an_empty_base_takes_the_overlay() {
+        let mappings = "[commands]\ninstall = \"sudo pacman -S\"\n[paths]\n\"/apps\" = \"/usr/bin\"";
+        let overlay = testutil::config(mappings);
+        let merged = SemanticConfig::merge(bare(""), overlay.clone());
+        assert_eq!(json(&merged), json(&overlay));
+    }
+
+    #[test]
+    fn merging_disjoint_mappings_keeps_both() {
+        let base = bare("[commands]\ninstall = \"sudo pacman -S\"\n[paths]\n\"/apps\" = \"/usr/bin\"");
+        let overlay = bare("[commands]\ndeploy = \"make deploy\"\n[paths]\n\"/logs\" = \"/var/log\"");
+        let merged = SemanticConfig::merge(base, overlay);
+        assert_eq!(merged.commands.len(), 2);
+        assert_eq!(merged.paths["/apps"], "/usr/bin");
+        assert_eq!(merged.paths["/logs"], "/var/log");
+    }
+
+    #[test]
+    fn the_overlay_wins_conflicting_keys() {
+        let base = bare(
+            "[commands]\ninstall = \"sudo pacman -S\"\nship = [\"make\", \"make deploy\"]\n\
+             [commands.fish]\nll = \"ls -l\"\n[paths]\n\"/apps\" = \"/usr/bin\"",
+        );
+        let overlay = bare(
+            "[commands]\ninstall = \"apt install\"\nship = \"./ship.sh\"\n\
+             [commands.fish]\nla = \"ls -a\"\n[paths]\n\"/apps\" = \"/opt/bin\"",
+        );
+        let merged = SemanticConfig::merge(base, overlay);
+        assert_eq!(merged.commands["install"], "apt install");
+        // a plain command replaces a composite one of the same name
+        assert_eq!(merged.commands["ship"], "./ship.sh");
+        assert!(!merged.composite_commands.contains_key("ship"));
+        assert_eq!(merged.shell_commands["fish"].len(), 2);
+        assert_eq!(merged.paths["/apps"], "/opt/bin");
+    }
+
+    #[test]
+    fn merging_settings_takes_what_the_overlay_sets() {
+        let base = bare(
+            "[general]\ncommand_style = \"natural\"\nfolder_style = \"natural\"\n\
+             [shells]\ndefault = \"bash\"\nenabled = [\"bash\", \"zsh\"]\non_new_shell = \"ignore\"",
+        );
+        let overlay = bare(
+            "[general]\ncommand_style = \"verbose\"\nfolder_style = \"\"\n\
+             [shells]\ndefault = \"fish\"\nenabled = [\"zsh\", \"fish\"]\non_new_shell = \"\"",
+        );
+        let merged = SemanticConfig::merge(base, overlay);
+        assert_eq!(merged.general.command_style, "verbose");
+        assert_eq!(merged.general.folder_style, "natural");
+        assert_eq!(merged.shells.default, "fish");
+        assert_eq!(merged.shells.enabled, ["bash", "zsh", "fish"]);
+        assert_eq!(merged.shells.on_new_shell, "ignore");
+
+        // unless it's only there for its mappings
+        let overlay = testutil::config("[commands]\ndeploy = \"make deploy\"").mappings_only();
+        let merged = SemanticConfig::merge(testutil::config(""), overlay);
+        assert_eq!(merged.shells.enabled, ["bash"]);
+        assert_eq!(merged.commands["deploy"], "make deploy");
+    }
+
+    #[test]
+    fn load_with_source_reports_where_each_part_came_from() {
+        let mut sandbox = Sandbox::new();
+        let include = sandbox.write("shared/base.toml", "[commands]\nupdate = \"sudo pacman -Syu\"");
+        let profile = sandbox.write(
+            "config/profiles/work.toml",
+            &format!(
+                "include = [{:?}]\n{}\n[commands]\nclean = \"make clean\"\n\
+                 [overrides.{os}.commands]\nclean = \"cargo clean\"",
+                include.display().to_string(),
+                testutil::BASE_CONFIG,
+                os = env::consts::OS
+            ),
+        );
+        let pack = sandbox.write("config/packs/team.toml", "[commands]\ndeploy = \"make deploy\"");
+        sandbox.write("config/plugins/docker.toml", "[commands]\nup = \"docker compose up\"");
+        let project = sandbox.write("project/.semantic.toml", "[commands]\ntest = \"cargo test\"");
+        sandbox.set("SEMANTIC_PROFILE", "work");
+        sandbox.unset("SEMANTIC_NO_LOCAL");
+        sandbox.chdir("project");
+
+        let (config, source) = SemanticConfig::load_with_source().unwrap();
+        assert_eq!(source.user_config_path, profile);
+        assert_eq!(source.active_profile.as_deref(), Some("work"));
+        assert_eq!(source.plugins_loaded, ["docker.toml"]);
+        assert_eq!(source.project_config_path, Some(project.clone()));
+
+        let command = |name: &str| MappingKey::Command(name.to_string());
+        assert_eq!(config.include_sources[&command("update")], include);
+        assert_eq!(config.os_overridden, [command("clean")]);
+        assert_eq!(config.commands["clean"], "cargo clean");
+        assert_eq!(config.pack_sources[&command("deploy")], pack);
+        assert!(config.pack_sources[&command("up")].ends_with("plugins/docker.toml"));
+        assert_eq!(config.project_configs, [project]);
+        assert_eq!(config.commands["test"], "cargo test");
+    }
+
+    #[test]
+    fn built_in_styles_match_their_templates() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("templates");
+        let templates = [
+            ("emoji", "en", "emoji.toml"),
+            ("natural", "de", "natural.de.toml"),
+            ("natural", "es", "natural.es.toml"),
+            ("natural", "en", "natural.toml"),
+            ("verbose", "en", "verbose.toml"),
+        ];
+        // every template is compared
+        let mut files: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        assert_eq!(files, templates.map(|(_, _, file)| file));
+
+        for (style, language, file) in templates {
+            assert_eq!(template_drift(&dir, style, style, language), Vec::<String>::new(), "{file}");
+        }
+    }
+
+    #[test]
+    fn template_drift_lists_changed_missing_and_extra_keys() {
+        let sandbox = Sandbox::new();
+        let template =
+            sandbox.write("config/templates/verbose.toml", testutil::DRIFTED_VERBOSE_TEMPLATE);
+
+        let at = format!("{}: [commands]", display_path(&template));
+        assert_eq!(
+            template_drift(&templates_dir(), "verbose", "verbose", "en"),
+            [
+                format!(r#"{at} "list-files" is "ls -al" there but "ls -la" built in"#),
+                format!(r#"{at} "update-system" is built in but not in the template"#),
+                format!(r#"{at} "search-package" is in the template but not built in"#),
+            ]
+        );
+        // nor does a style without a template report anything
+        assert!(template_drift(&templates_dir(), "emoji", "traditional", "en").is_empty());
+
+        sandbox.write("config/templates/emoji.toml", "[commands\n");
+        let drift = template_drift(&templates_dir(), "emoji", "traditional", "en");
+        assert_eq!(drift.len(), 1);
+        assert!(drift[0].starts_with(&display_path(&templates_dir().join("emoji.toml"))), "{drift:?}");
+    }
+
+    #[test]
+    fn without_templates_nothing_drifts() {
+        let _sandbox = Sandbox::new();
+        assert!(!templates_dir().exists());
+        for style in ["natural", "verbose", "emoji", "traditional"] {
+            assert!(template_drift(&templates_dir(), style, style, "en").is_empty(), "{style}");
+        }
+    }
+
+    /// How `name` resolves in bash, following the commands it refers to.
+    fn resolved(config: &SemanticConfig, name: &str) -> Result<String, String> {
+        let commands = config.commands_for("bash");
+        config.resolve_references(&commands, name, &commands[name], "bash")
+    }
+
+    #[test]
+    fn a_command_resolves_through_the_one_it_starts_with() {
+        let config =
+            testutil::config("[commands]\nlist = \"ls -la\"\nll = \"list -h\"\nls = \"ls --color\"");
+        assert_eq!(resolved(&config, "ll").unwrap(), "ls -la -h");
+        // starting with its own name runs the real program
+        assert_eq!(resolved(&config, "ls").unwrap(), "ls --color");
+        assert!(config.reference_cycles().is_empty());
+    }
+
+    #[test]
+    fn a_chain_of_commands_resolves_to_the_end() {
+        let config =
+            testutil::config("[commands]\nlist = \"ls -la\"\nll = \"list -h\"\nl = \"ll --sort=size\"");
+        assert_eq!(resolved(&config, "l").unwrap(), "ls -la -h --sort=size");
+
+        // c0 → c1 → ... → c11, one more than may be followed
+        let last = MAX_REFERENCE_DEPTH + 1;
+        let chain: String = (0..last).map(|i| format!("c{i} = \"c{}\"\n", i + 1)).collect();
+        let config = testutil::config(&format!("[commands]\n{chain}c{last} = \"true\""));
+        let error = resolved(&config, "c0").unwrap_err();
+        assert!(error.starts_with("goes through more than 10 other commands"), "{error}");
+    }
+
+    #[test]
+    fn a_command_calling_itself_is_a_cycle() {
+        let config = testutil::config(
+            "[commands]\nagain = \"semantic translate again\"\nloop = { shell = \"loop --again\" }",
+        );
+        assert_eq!(
+            config.reference_cycles(),
+            [
+                "command `again` calls itself: again → again",
+                "command `loop` calls itself: loop → loop",
+            ]
+        );
+    }
+
+    #[test]
+    fn commands_referring_to_each_other_are_a_cycle() {
+        let config = testutil::config("[commands]\nll = \"list\"\nlist = \"ll -a\"");
+        assert_eq!(resolved(&config, "ll").unwrap_err(), "refers back to itself (ll → list → ll)");
+        let cycles = config.reference_cycles();
+        assert_eq!(cycles, ["command `list` is part of a loop: list → ll → list"]);
+
+        // only in one shell's overrides
+        let overrides = "[commands.fish]\nlist = \"ll -a\"";
+        let config = testutil::config(&format!("[commands]\nll = \"list\"\nlist = \"ls\"\n{overrides}"));
+        assert_eq!(
+            config.reference_cycles(),
+            ["command `list` is part of a loop (in fish): list → ll → list"]
+        );
+    }
+
+    #[test]
+    fn flags_are_never_paths() {
+        let config = testutil::config("[paths]\n\"/apps\" = \"/usr/bin\"");
+        assert_eq!(config.resolve_arg("/apps/vim", None).as_deref(), Some("/usr/bin/vim"));
+        for flag in ["-x", "--opt=/apps", "-/apps"] {
+            assert_eq!(config.resolve_arg(flag, None), None, "{flag}");
+        }
+    }
+
+    #[test]
+    fn a_leading_tilde_is_the_home_directory() {
+        let sandbox = Sandbox::new();
+        let home = sandbox.path("home");
+        let paths = format!("[paths]\n\"{}/projects\" = \"/srv/projects\"", home.display());
+        let config = testutil::config(&paths);
+        assert_eq!(config.resolve_arg("~/projects/site", None).as_deref(), Some("/srv/projects/site"));
+        // another user's home, or a tilde further in, isn't expanded
+        assert_eq!(config.resolve_arg("~other/projects", None), None);
+        assert_eq!(config.resolve_arg("a~/projects", Some(&home)), None);
+    }
+
+    #[test]
+    fn relative_paths_count_only_under_a_virtual_path_that_isnt_there() {
+        let sandbox = Sandbox::new();
+        let home = sandbox.path("home");
+        let paths = format!("[paths]\n\"{}/data\" = \"/srv/data\"", home.display());
+        let config = testutil::config(&paths);
+        assert_eq!(config.resolve_arg("data/logs", Some(&home)).as_deref(), Some("/srv/data/logs"));
+        assert_eq!(config.resolve_arg("./data", Some(&home)).as_deref(), Some("/srv/data"));
+        assert_eq!(config.resolve_arg("notes.txt", Some(&home)), None);
+        // no current directory to resolve it from
+        assert_eq!(config.resolve_arg("data/logs", None), None);
+
+        // a real file or directory by that name wins
+        sandbox.write("home/data/logs", "");
+        assert_eq!(config.resolve_arg("data/logs", Some(&home)), None);
+    }
+
+    #[test]
+    fn arguments_after_a_separator_go_on_as_typed() {
+        let config = testutil::config("[commands]\ncopy = \"cp\"\n[paths]\n\"/apps\" = \"/usr/bin\"");
+        let typed = names(&["/apps/a", "-r", "--", "/apps/b", "--"]);
+        let resolved = config.resolve_args("copy", &typed, None);
+        assert_eq!(resolved, [Some("/usr/bin/a".to_string()), None, None, None, None]);
+    }
+
+    #[test]
+    fn translate_paths_false_leaves_every_argument_alone() {
+        let config = testutil::config(
+            "[commands]\nsearch = { command = \"grep -rn\", translate_paths = false }\nshow = \"cat\"\n\
+             [paths]\n\"/apps\" = \"/usr/bin\"",
+        );
+        assert_eq!(config.resolve_args("search", &names(&["/apps", "/apps/vim"]), None), [None, None]);
+        let resolved = config.resolve_args("show", &names(&["/apps/vim"]), None);
+        assert_eq!(resolved, [Some("/usr/bin/vim".to_string())]);
+    }
+}
@@ -0,0 +1,289 @@
+// doctor/mod.rs
+// Health checks behind `semantic doctor`.
+// Each check is a plain function that takes what it inspects and returns a `Check`,
+// so they can run (and be reasoned about) independently of the others.
+
+use std::env;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::config::{ParseError, SemanticConfig};
+use crate::error::SemanticError;
+use crate::link::{self, Manifest};
+use crate::shell::{self, ShellSource};
+use crate::watch;
+
+/// Env var the generated init code sets, so we can tell whether it has been sourced.
+pub const INIT_MARKER: &str = "SEMANTIC_INIT";
+
+/// Outcome of one check. Only `Fail` makes `semantic doctor` exit non-zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl Status {
+    pub fn label(self) -> &'static str {
+        match self {
+            Status::Pass => "PASS",
+            Status::Warn => "WARN",
+            Status::Fail => "FAIL",
+        }
+    }
+}
+
+/// The result of one check: what was checked, how it went, and why.
+/// Serialized as-is for `semantic --json doctor`.
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, status: Status, detail: impl Into<String>) -> Self {
+        Check { name, status, detail: detail.into() }
+    }
+}
+
+/// Run every check against the user's setup.
+/// If the config can't be loaded, the checks that need it are skipped.
+pub fn run_all() -> Vec<Check> {
+    let path = SemanticConfig::config_path();
+    let loaded = SemanticConfig::load().map(|mut config| {
+        config.expand_env_vars();
+        config
+    });
+
+    // a parse error's hint points back here, so leave it out
+    let error = loaded.as_ref().map_err(|e| match e {
+        SemanticError::ConfigParse { source, .. } => {
+            source.downcast_ref::<ParseError>().map_or_else(|| e.to_string(), ParseError::summary)
+        }
+        _ => e.to_string(),
+    });
+    let mut checks = vec![check_config(&path, error)];
+
+    if let Ok(config) = &loaded {
+        let detected = shell::detect_shell_with_source();
+        checks.push(check_default_shell(config, detected.as_ref()));
+        let shell = detected.map_or_else(|| config.shells.default.clone(), |(shell, _)| shell);
+        checks.push(check_programs(config, &shell));
+        checks.push(check_shadowing(config, &shell));
+        // fish loads semantic from conf.d rather than config.fish
+        let rc = shell::rc_file_for(&shell);
+        let rc_contents: Vec<String> = [rc.clone(), shell::install_path(&shell)]
+            .into_iter()
+            .flatten()
+            .filter_map(|path| std::fs::read_to_string(path).ok())
+            .collect();
+        checks.push(check_rc_file(&shell, rc.as_deref(), &rc_contents));
+        if let Ok(installed) = watch::installed_shells(Path::new(watch::ETC_SHELLS)) {
+            checks.push(check_new_shells(config, &installed));
+        }
+        checks.push(check_key_collisions(config));
+        checks.push(check_loops(config));
+        // only for people who use `semantic link`
+        if let Ok(manifest) = Manifest::load()
+            && !manifest.links.is_empty()
+        {
+            checks.push(check_links(config, &manifest));
+        }
+    }
+    checks.push(check_init_sourced(env::var(INIT_MARKER).ok().as_deref()));
+
+    checks
+}
+
+/// The config file exists and parses.
+pub fn check_config(path: &Path, loaded: Result<&SemanticConfig, String>) -> Check {
+    match loaded {
+        Ok(_) => Check::new("config", Status::Pass, format!("{} parses", path.display())),
+        Err(_) if !path.exists() => Check::new(
+            "config",
+            Status::Fail,
+            format!("{} not found — run `semantic` to create it", path.display()),
+        ),
+        Err(e) => Check::new("config", Status::Fail, e),
+    }
+}
+
+/// The shell we're running under is the one the config treats as default.
+/// The detail says how the running shell was detected.
+pub fn check_default_shell(
+    config: &SemanticConfig,
+    detected: Option<&(String, ShellSource)>,
+) -> Check {
+    let default = &config.shells.default;
+    let Some((running, source)) = detected else {
+        return Check::new("shell", Status::Warn, "couldn't detect the running shell");
+    };
+    let source = source.describe();
+
+    if default.is_empty() {
+        Check::new(
+            "shell",
+            Status::Warn,
+            format!("no default shell set (detected {running} {source})"),
+        )
+    } else if default == running {
+        Check::new(
+            "shell",
+            Status::Pass,
+            format!("running the default shell ({default}, {source})"),
+        )
+    } else {
+        Check::new(
+            "shell",
+            Status::Warn,
+            format!("detected {running} {source}, but the default shell is {default}"),
+        )
+    }
+}
+
+/// Every mapping's program resolves in $PATH (or is a shell builtin).
+pub fn check_programs(config: &SemanticConfig, shell: &str) -> Check {
+    let commands = config.commands_for(shell);
+    let missing = shell::missing_programs(&commands);
+
+    if missing.is_empty() {
+        return Check::new(
+            "programs",
+            Status::Pass,
+            format!("all {} mapped programs found", commands.len()),
+        );
+    }
+
+    let list: Vec<String> = missing
+        .iter()
+        .map(|(name, program)| format!("{name} → {program}"))
+        .collect();
+    Check::new(
+        "programs",
+        Status::Fail,
+        format!("not in $PATH: {}", list.join(", ")),
+    )
+}
+
+/// Every installed shell semantic supports is enabled. One that isn't gets
+/// nothing from semantic until `semantic scan-shells` (or the user) enables it.
+pub fn check_new_shells(config: &SemanticConfig, installed: &[&str]) -> Check {
+    let new = watch::new_shells(installed, &config.shells.enabled);
+    if new.is_empty() {
+        Check::new("new shells", Status::Pass, "every installed shell is enabled")
+    } else {
+        Check::new(
+            "new shells",
+            Status::Warn,
+            format!(
+                "installed but not enabled: {} (`semantic scan-shells` sets them up)",
+                new.join(", ")
+            ),
+        )
+    }
+}
+
+/// No command name hides a program or builtin of the same name in `shell`
+/// (see `SemanticConfig::shadowed_commands`), unless `[safety]` allows it.
+pub fn check_shadowing(config: &SemanticConfig, shell: &str) -> Check {
+    let shadowed = config.shadowed_commands(shell);
+    if shadowed.is_empty() {
+        return Check::new("shadowing", Status::Pass, "no command hides a program or builtin");
+    }
+    let hidden: Vec<String> =
+        shadowed.iter().map(|(name, hidden)| format!("`{name}` hides {hidden}")).collect();
+    Check::new(
+        "shadowing",
+        Status::Warn,
+        format!(
+            "{} — rename them (`semantic edit`), or list them in [safety] allow_shadowing",
+            hidden.join(", ")
+        ),
+    )
+}
+
+/// No name is both a command and a virtual path, which would make `semantic which` ambiguous.
+pub fn check_key_collisions(config: &SemanticConfig) -> Check {
+    let mut collisions: Vec<&str> = config
+        .paths
+        .keys()
+        .filter(|key| config.commands.contains_key(*key))
+        .map(String::as_str)
+        .collect();
+    collisions.sort();
+
+    if collisions.is_empty() {
+        Check::new("names", Status::Pass, "no command shares a name with a path")
+    } else {
+        Check::new(
+            "names",
+            Status::Warn,
+            format!("both a command and a path: {}", collisions.join(", ")),
+        )
+    }
+}
+
+/// No command ends up running itself (see `SemanticConfig::reference_cycles`).
+pub fn check_loops(config: &SemanticConfig) -> Check {
+    let cycles = config.reference_cycles();
+    if cycles.is_empty() {
+        Check::new("loops", Status::Pass, "no command runs itself")
+    } else {
+        Check::new("loops", Status::Fail, cycles.join("; "))
+    }
+}
+
+/// The symlinks `semantic link` made are all still in place and lead somewhere.
+pub fn check_links(config: &SemanticConfig, manifest: &Manifest) -> Check {
+    let problems = link::problems(manifest, &config.paths);
+    if problems.is_empty() {
+        Check::new(
+            "links",
+            Status::Pass,
+            format!("all {} links from `semantic link` in place", manifest.links.len()),
+        )
+    } else {
+        Check::new(
+            "links",
+            Status::Warn,
+            format!("{} — run `semantic link` to fix", problems.join("; ")),
+        )
+    }
+}
+
+/// The shell's rc file (`rc`, or the file `semantic init --install` writes to)
+/// loads semantic. `contents` is what could be read of those files.
+pub fn check_rc_file(shell: &str, rc: Option<&Path>, contents: &[String]) -> Check {
+    let Some(rc) = rc else {
+        return Check::new("rc file", Status::Warn, format!("don't know where {shell} keeps its rc file"));
+    };
+    if contents.iter().any(|content| shell::loads_semantic(shell, content)) {
+        Check::new("rc file", Status::Pass, format!("{} runs `semantic init`", rc.display()))
+    } else {
+        Check::new(
+            "rc file",
+            Status::Warn,
+            format!(
+                "{} doesn't run `semantic init` — run `semantic init --install {shell}`",
+                rc.display()
+            ),
+        )
+    }
+}
+
+/// The init code has been sourced into this shell (it sets `INIT_MARKER`).
+pub fn check_init_sourced(marker: Option<&str>) -> Check {
+    match marker {
+        Some(shell) => Check::new("init", Status::Pass, format!("init code loaded ({shell})")),
+        None => Check::new(
+            "init",
+            Status::Warn,
+            "init code not loaded in this shell — add `semantic init` to your rc file",
+        ),
+    }
+}
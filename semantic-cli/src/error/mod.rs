@@ -0,0 +1,146 @@
+// error/mod.rs
+// The failures semantic tells apart, and the exit code each one ends with, so
+// the generated shell functions (and scripts) can tell "no config" from "no such
+// command" from "the program isn't installed":
+//   1   anything else (bad usage, a failed save, ...)
+//   2   the config is missing, unreadable, or maps something impossibly
+//   3   no semantic command by that name
+//   126 the program was found but couldn't be run
+//   127 the program wasn't found
+// A command that did run passes on its own exit code.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_CONFIG: i32 = 2;
+pub const EXIT_UNKNOWN_COMMAND: i32 = 3;
+pub const EXIT_NOT_EXECUTABLE: i32 = 126;
+pub const EXIT_NOT_FOUND: i32 = 127;
+
+/// Something semantic couldn't do, by what went wrong (see `exit_code`).
+#[derive(Debug)]
+pub enum SemanticError {
+    /// There's no config file at `path` yet.
+    ConfigNotFound { path: PathBuf },
+    /// A config file (`path`, or one it includes) couldn't be read or parsed.
+    ConfigParse { path: PathBuf, source: Box<dyn Error> },
+    /// A mapping that can't be run as written: a loop, an empty command,
+    /// unbalanced quotes.
+    BadMapping { name: String, message: String },
+    /// No semantic command `name`; `suggestions` are close names that do exist.
+    UnknownCommand { name: String, suggestions: Vec<String> },
+    /// `name` was used as a command but is a virtual path.
+    PathAlias { name: String },
+    /// A shell semantic can't generate code for.
+    UnknownShell { name: String },
+    /// `program` couldn't be started.
+    ExecFailed { program: String, source: io::Error },
+    Io(io::Error),
+}
+
+impl SemanticError {
+    /// The exit code this error ends semantic with (see the table at the top).
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SemanticError::ConfigNotFound { .. }
+            | SemanticError::ConfigParse { .. }
+            | SemanticError::BadMapping { .. } => EXIT_CONFIG,
+            SemanticError::UnknownCommand { .. } | SemanticError::PathAlias { .. } => {
+                EXIT_UNKNOWN_COMMAND
+            }
+            SemanticError::ExecFailed { source, .. } if source.kind() == io::ErrorKind::NotFound => {
+                EXIT_NOT_FOUND
+            }
+            SemanticError::ExecFailed { .. } => EXIT_NOT_EXECUTABLE,
+            SemanticError::UnknownShell { .. } | SemanticError::Io(_) => EXIT_FAILURE,
+        }
+    }
+
+    /// The closest existing name, for a "did you mean" hint.
+    pub fn suggestion(&self) -> Option<&str> {
+        match self {
+            SemanticError::UnknownCommand { suggestions, .. } => suggestions.first().map(String::as_str),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SemanticError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SemanticError::ConfigNotFound { path } => write!(f, "{} doesn't exist", path.display()),
+            // most sources say which file already
+            SemanticError::ConfigParse { path, source } => {
+                let message = source.to_string();
+                if message.contains(&path.display().to_string()) {
+                    write!(f, "{message}")
+                } else {
+                    write!(f, "{}: {message}", path.display())
+                }
+            }
+            SemanticError::BadMapping { name, message } => write!(f, "`{name}` {message}"),
+            SemanticError::UnknownCommand { name, .. } => write!(f, "Unknown semantic command: {name}"),
+            SemanticError::PathAlias { name } => {
+                write!(f, "`{name}` is a path alias — use `semantic path {name}`")
+            }
+            SemanticError::UnknownShell { name } => write!(f, "Unsupported shell: {name}"),
+            SemanticError::ExecFailed { program, source } => write!(f, "Failed to run `{program}`: {source}"),
+            SemanticError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for SemanticError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SemanticError::ConfigParse { source, .. } => Some(source.as_ref()),
+            SemanticError::ExecFailed { source, .. } | SemanticError::Io(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SemanticError {
+    fn from(e: io::Error) -> Self {
+        SemanticError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_kind_of_failure_has_its_exit_code() {
+        let not_found = SemanticError::ConfigNotFound { path: PathBuf::from("/c/config.toml") };
+        assert_eq!(not_found.exit_code(), EXIT_CONFIG);
+        assert_eq!(not_found.to_string(), "/c/config.toml doesn't exist");
+
+        let suggestions = vec!["install".to_string()];
+        let unknown = SemanticError::UnknownCommand { name: "instal".into(), suggestions };
+        assert_eq!(unknown.exit_code(), EXIT_UNKNOWN_COMMAND);
+        assert_eq!(unknown.to_string(), "Unknown semantic command: instal");
+        assert_eq!(unknown.suggestion(), Some("install"));
+
+        let missing = io::Error::from(io::ErrorKind::NotFound);
+        let exec = SemanticError::ExecFailed { program: "pacman".into(), source: missing };
+        assert_eq!(exec.exit_code(), EXIT_NOT_FOUND);
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        let exec = SemanticError::ExecFailed { program: "./run.sh".into(), source: denied };
+        assert_eq!(exec.exit_code(), EXIT_NOT_EXECUTABLE);
+
+        assert_eq!(SemanticError::UnknownShell { name: "tcsh".into() }.exit_code(), EXIT_FAILURE);
+    }
+
+    #[test]
+    fn a_parse_error_names_its_file_once() {
+        let path = PathBuf::from("/c/config.toml");
+        let error = SemanticError::ConfigParse { path: path.clone(), source: "bad value".into() };
+        assert_eq!(error.to_string(), "/c/config.toml: bad value");
+        let error = SemanticError::ConfigParse { path, source: "/c/config.toml:3: bad value".into() };
+        assert_eq!(error.to_string(), "/c/config.toml:3: bad value");
+    }
+}
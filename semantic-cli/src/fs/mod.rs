@@ -0,0 +1,515 @@
+// fs/mod.rs
+// The virtual-path filesystem behind `semantic mount`.
+// Exposes config.paths as a read-only FUSE filesystem, so that with /apps → /usr/bin
+// mapped, `ls <mountpoint>/apps` lists the contents of /usr/bin.
+//
+// The root (and any intermediate directory of a nested virtual path such as
+// /user/applications) is synthesised from the config; everything below a mapped
+// path is passed straight through to the real filesystem. The mount is read-only,
+// so the kernel answers any write with EROFS.
+// Only built with the `fuse` feature (on by default).
+
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File, Metadata};
+use std::io;
+use std::os::unix::fs::{FileExt, FileTypeExt, MetadataExt};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use fuser::{
+    Config, Errno, FileAttr, FileHandle, FileType, Filesystem, Generation, INodeNo, LockOwner,
+    MountOption, OpenFlags, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry, Request,
+};
+
+/// How long the kernel may cache attributes and lookups.
+/// Short, since the real directories can change underneath us.
+const TTL: Duration = Duration::from_secs(1);
+
+/// A node in the mounted tree.
+enum Node {
+    /// A directory made up from virtual path components, mapping child names to inodes.
+    Virtual(BTreeMap<OsString, u64>),
+    /// A file or directory on the real filesystem.
+    Real(PathBuf),
+}
+
+/// Inode table. Real paths get an inode the first time they're looked up.
+/// Inode numbers are indices into `nodes` plus one (FUSE reserves 0, the root is 1).
+struct Inodes {
+    nodes: Vec<Node>,
+    by_path: HashMap<PathBuf, u64>,
+}
+
+impl Inodes {
+    fn get(&self, ino: u64) -> Option<&Node> {
+        self.nodes.get(ino.checked_sub(1)? as usize)
+    }
+
+    fn push(&mut self, node: Node) -> u64 {
+        self.nodes.push(node);
+        self.nodes.len() as u64
+    }
+
+    /// The inode for a real path, allocating one if it hasn't been seen yet.
+    fn real(&mut self, path: PathBuf) -> u64 {
+        if let Some(&ino) = self.by_path.get(&path) {
+            return ino;
+        }
+        let ino = self.push(Node::Real(path.clone()));
+        self.by_path.insert(path, ino);
+        ino
+    }
+}
+
+pub struct VirtualFs {
+    inodes: Mutex<Inodes>,
+    /// Owner reported for the synthesised directories (taken from the mountpoint).
+    uid: u32,
+    gid: u32,
+}
+
+impl VirtualFs {
+    /// Build the virtual tree from path mappings (virtual path → real path).
+    /// Mappings that don't start with `/` or name the root itself are skipped.
+    pub fn new(paths: &HashMap<String, String>, uid: u32, gid: u32) -> Self {
+        let mut inodes = Inodes {
+            nodes: vec![Node::Virtual(BTreeMap::new())],
+            by_path: HashMap::new(),
+        };
+
+        // sorted so /user is created before /user/applications is attached to it
+        let mut sorted: Vec<_> = paths.iter().collect();
+        sorted.sort();
+
+        for (virtual_path, real_path) in sorted {
+            let components: Vec<&OsStr> = Path::new(virtual_path)
+                .components()
+                .filter_map(|c| match c {
+                    Component::Normal(name) => Some(name),
+                    _ => None,
+                })
+                .collect();
+            let Some((leaf, parents)) = components.split_last() else {
+                continue;
+            };
+
+            // walk (and create) the intermediate virtual directories
+            let mut dir = INodeNo::ROOT.0;
+            for name in parents {
+                let existing = match inodes.get(dir) {
+                    Some(Node::Virtual(children)) => children.get(*name).copied(),
+                    _ => None,
+                };
+                dir = match existing {
+                    Some(ino) => ino,
+                    None => {
+                        let ino = inodes.push(Node::Virtual(BTreeMap::new()));
+                        if let Some(Node::Virtual(children)) = inodes.nodes.get_mut(dir as usize - 1) {
+                            children.insert(name.to_os_string(), ino);
+                        }
+                        ino
+                    }
+                };
+            }
+
+            let ino = inodes.real(PathBuf::from(real_path));
+            if let Some(Node::Virtual(children)) = inodes.nodes.get_mut(dir as usize - 1) {
+                children.insert(leaf.to_os_string(), ino);
+            }
+        }
+
+        VirtualFs {
+            inodes: Mutex::new(inodes),
+            uid,
+            gid,
+        }
+    }
+
+    /// Attributes for an inode: synthesised for virtual dirs, lstat'd for real paths.
+    fn attr(&self, ino: u64) -> Result<FileAttr, Errno> {
+        let inodes = self.inodes.lock().unwrap();
+        match inodes.get(ino) {
+            Some(Node::Virtual(children)) => Ok(FileAttr {
+                ino: INodeNo(ino),
+                size: 0,
+                blocks: 0,
+                atime: UNIX_EPOCH,
+                mtime: UNIX_EPOCH,
+                ctime: UNIX_EPOCH,
+                crtime: UNIX_EPOCH,
+                kind: FileType::Directory,
+                perm: 0o555,
+                nlink: 2 + children.len() as u32,
+                uid: self.uid,
+                gid: self.gid,
+                rdev: 0,
+                blksize: 4096,
+                flags: 0,
+            }),
+            Some(Node::Real(path)) => {
+                let meta = fs::symlink_metadata(path).map_err(io_errno)?;
+                Ok(real_attr(ino, &meta))
+            }
+            None => Err(Errno::ENOENT),
+        }
+    }
+
+    /// The real path behind an inode, if it isn't a virtual directory.
+    fn real_path(&self, ino: u64) -> Result<PathBuf, Errno> {
+        match self.inodes.lock().unwrap().get(ino) {
+            Some(Node::Real(path)) => Ok(path.clone()),
+            Some(Node::Virtual(_)) => Err(Errno::EISDIR),
+            None => Err(Errno::ENOENT),
+        }
+    }
+
+    /// Resolve `name` inside directory `parent` to an inode.
+    fn child(&self, parent: u64, name: &OsStr) -> Result<u64, Errno> {
+        let mut inodes = self.inodes.lock().unwrap();
+        let path = match inodes.get(parent) {
+            Some(Node::Virtual(children)) => {
+                return children.get(name).copied().ok_or(Errno::ENOENT);
+            }
+            Some(Node::Real(path)) => path.join(name),
+            None => return Err(Errno::ENOENT),
+        };
+
+        // symlink_metadata so dangling symlinks still show up (and readlink can explain them)
+        fs::symlink_metadata(&path).map_err(io_errno)?;
+        Ok(inodes.real(path))
+    }
+
+    /// Every entry of a directory, including `.` and `..`.
+    fn entries(&self, ino: u64) -> Result<Vec<(u64, FileType, OsString)>, Errno> {
+        let mut inodes = self.inodes.lock().unwrap();
+        let mut entries = vec![
+            (ino, FileType::Directory, OsString::from(".")),
+            // the kernel fills in the real parent; any valid inode will do here
+            (INodeNo::ROOT.0, FileType::Directory, OsString::from("..")),
+        ];
+
+        let path = match inodes.get(ino) {
+            Some(Node::Virtual(children)) => {
+                let children: Vec<_> = children.iter().map(|(n, &i)| (n.clone(), i)).collect();
+                for (name, child) in children {
+                    let kind = match inodes.get(child) {
+                        Some(Node::Real(path)) => fs::symlink_metadata(path)
+                            .map(|m| file_type(&m))
+                            .unwrap_or(FileType::Directory),
+                        _ => FileType::Directory,
+                    };
+                    entries.push((child, kind, name));
+                }
+                return Ok(entries);
+            }
+            Some(Node::Real(path)) => path.clone(),
+            None => return Err(Errno::ENOENT),
+        };
+
+        for entry in fs::read_dir(&path).map_err(io_errno)? {
+            let Ok(entry) = entry else { continue };
+            let kind = entry
+                .file_type()
+                .map(|t| std_file_type(&t))
+                .unwrap_or(FileType::RegularFile);
+            let child = inodes.real(entry.path());
+            entries.push((child, kind, entry.file_name()));
+        }
+        Ok(entries)
+    }
+}
+
+impl Filesystem for VirtualFs {
+    fn lookup(&self, _req: &Request, parent: INodeNo, name: &OsStr, reply: ReplyEntry) {
+        match self.child(parent.0, name).and_then(|ino| self.attr(ino)) {
+            Ok(attr) => reply.entry(&TTL, &attr, Generation(0)),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn getattr(&self, _req: &Request, ino: INodeNo, _fh: Option<FileHandle>, reply: ReplyAttr) {
+        match self.attr(ino.0) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn readlink(&self, _req: &Request, ino: INodeNo, reply: ReplyData) {
+        match self.real_path(ino.0).and_then(|p| fs::read_link(p).map_err(io_errno)) {
+            Ok(target) => reply.data(target.as_os_str().as_encoded_bytes()),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn read(
+        &self,
+        _req: &Request,
+        ino: INodeNo,
+        _fh: FileHandle,
+        offset: u64,
+        size: u32,
+        _flags: OpenFlags,
+        _lock_owner: Option<LockOwner>,
+        reply: ReplyData,
+    ) {
+        let result = self.real_path(ino.0).and_then(|path| {
+            let file = File::open(path).map_err(io_errno)?;
+            read_full(&file, offset, size as usize).map_err(io_errno)
+        });
+        match result {
+            Ok(data) => reply.data(&data),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn readdir(
+        &self,
+        _req: &Request,
+        ino: INodeNo,
+        _fh: FileHandle,
+        offset: u64,
+        mut reply: ReplyDirectory,
+    ) {
+        let entries = match self.entries(ino.0) {
+            Ok(entries) => entries,
+            Err(e) => {
+                reply.error(e);
+                return;
+            }
+        };
+
+        for (i, (child, kind, name)) in entries.into_iter().enumerate().skip(offset as usize) {
+            // the offset passed back is where the next call should resume
+            if reply.add(INodeNo(child), (i + 1) as u64, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+/// Read up to `size` bytes at `offset`, stopping early only at end of file.
+/// FUSE pads short reads with zeroes, so a single partial `read_at` isn't enough.
+fn read_full(file: &File, offset: u64, size: usize) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0; size];
+    let mut filled = 0;
+    while filled < size {
+        match file.read_at(&mut buf[filled..], offset + filled as u64) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    buf.truncate(filled);
+    Ok(buf)
+}
+
+fn real_attr(ino: u64, meta: &Metadata) -> FileAttr {
+    FileAttr {
+        ino: INodeNo(ino),
+        size: meta.size(),
+        blocks: meta.blocks(),
+        atime: meta.accessed().unwrap_or(UNIX_EPOCH),
+        mtime: meta.modified().unwrap_or(UNIX_EPOCH),
+        ctime: UNIX_EPOCH + Duration::new(meta.ctime().max(0) as u64, meta.ctime_nsec() as u32),
+        crtime: meta.created().unwrap_or(UNIX_EPOCH),
+        kind: file_type(meta),
+        // the mount is read-only, so drop the write bits rather than advertise them
+        perm: (meta.mode() & 0o7555) as u16,
+        nlink: meta.nlink() as u32,
+        uid: meta.uid(),
+        gid: meta.gid(),
+        rdev: meta.rdev() as u32,
+        blksize: meta.blksize() as u32,
+        flags: 0,
+    }
+}
+
+fn file_type(meta: &Metadata) -> FileType {
+    std_file_type(&meta.file_type())
+}
+
+fn std_file_type(t: &fs::FileType) -> FileType {
+    if t.is_dir() {
+        FileType::Directory
+    } else if t.is_symlink() {
+        FileType::Symlink
+    } else if t.is_fifo() {
+        FileType::NamedPipe
+    } else if t.is_socket() {
+        FileType::Socket
+    } else if t.is_block_device() {
+        FileType::BlockDevice
+    } else if t.is_char_device() {
+        FileType::CharDevice
+    } else {
+        FileType::RegularFile
+    }
+}
+
+/// Turn an io::Error into the errno FUSE should report for it.
+fn io_errno(e: io::Error) -> Errno {
+    e.raw_os_error().map(Errno::from_i32).unwrap_or(Errno::EIO)
+}
+
+/// Whether `path` is on a different device from its parent, i.e. something is
+/// mounted there (the root always counts).
+fn is_mount_point(path: &Path) -> io::Result<bool> {
+    let meta = fs::metadata(path)?;
+    let parent = fs::metadata(path.join(".."))?;
+    Ok(meta.dev() != parent.dev() || meta.ino() == parent.ino())
+}
+
+/// Whether the mount at `path` is one of ours, going by /proc/self/mountinfo.
+/// Other systems have no cheap way to tell, so any mount counts there.
+fn is_semantic_mount(path: &Path) -> bool {
+    if !cfg!(target_os = "linux") {
+        return true;
+    }
+    let Ok(mountinfo) = fs::read_to_string("/proc/self/mountinfo") else {
+        return true;
+    };
+    let path = fs::canonicalize(path)
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf());
+
+    // fields: id parent dev root mountpoint options [optional...] - fstype source superoptions
+    mountinfo.lines().any(|line| {
+        let fields: Vec<&str> = line.split(' ').collect();
+        let Some(dash) = fields.iter().position(|f| *f == "-") else {
+            return false;
+        };
+        let mountpoint = fields.get(4).map(|m| unescape_mountinfo(m));
+        mountpoint.is_some_and(|m| Path::new(&m) == path)
+            && fields.get(dash + 1).is_some_and(|t| t.starts_with("fuse"))
+            && fields.get(dash + 2) == Some(&"semantic")
+    })
+}
+
+/// mountinfo writes space, tab, newline and backslash in paths as octal escapes.
+fn unescape_mountinfo(field: &str) -> String {
+    field
+        .replace("\\040", " ")
+        .replace("\\011", "\t")
+        .replace("\\012", "\n")
+        .replace("\\134", "\\")
+}
+
+/// Explain a failed mount in terms of what to do about it.
+fn mount_error(e: &io::Error, mountpoint: &Path) -> String {
+    let has_fusermount = ["fusermount3", "fusermount"]
+        .iter()
+        .any(|bin| crate::shell::find_in_path(bin).is_some());
+    let hint = if cfg!(target_os = "linux") && !Path::new("/dev/fuse").exists() {
+        "/dev/fuse doesn't exist; load the fuse kernel module (`sudo modprobe fuse`)"
+    } else if e.kind() == io::ErrorKind::NotFound && !has_fusermount {
+        "mounting without root goes through fusermount3, which isn't installed \
+         (it comes with your distribution's fuse3 package)"
+    } else if e.kind() == io::ErrorKind::PermissionDenied {
+        "FUSE needs read/write access to /dev/fuse and a mountpoint you own"
+    } else {
+        return format!("failed to mount {}: {e}", mountpoint.display());
+    };
+    format!("failed to mount {}: {e}\n{hint}", mountpoint.display())
+}
+
+/// Mount the virtual filesystem at `mountpoint` and serve it until Ctrl-C
+/// (or until it's unmounted from outside, e.g. with `semantic umount`).
+pub fn mount(paths: &HashMap<String, String>, mountpoint: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let shown = mountpoint.display();
+    let meta = match fs::metadata(mountpoint) {
+        Ok(meta) => meta,
+        // what a mount looks like once the process serving it is gone
+        Err(e) if e.kind() == io::ErrorKind::NotConnected => {
+            return Err(format!(
+                "{shown} is a dead FUSE mount; clear it with `semantic umount {shown}` first"
+            )
+            .into());
+        }
+        Err(e) => return Err(format!("{shown}: {e}").into()),
+    };
+    if !meta.is_dir() {
+        return Err(format!("{shown} is not a directory").into());
+    }
+    if is_mount_point(mountpoint).unwrap_or(false) {
+        let hint = if is_semantic_mount(mountpoint) {
+            format!("; unmount it with `semantic umount {shown}` first")
+        } else {
+            String::new()
+        };
+        return Err(format!("something is already mounted at {shown}{hint}").into());
+    }
+
+    let filesystem = VirtualFs::new(paths, meta.uid(), meta.gid());
+
+    let mut options = Config::default();
+    options.mount_options.extend([
+        MountOption::RO,
+        MountOption::FSName("semantic".to_string()),
+        MountOption::Subtype("semantic".to_string()),
+    ]);
+
+    let session = fuser::spawn_mount(filesystem, mountpoint, &options)
+        .map_err(|e| mount_error(&e, mountpoint))?;
+
+    let (tx, rx) = mpsc::channel();
+    ctrlc::set_handler(move || {
+        let _ = tx.send(());
+    })?;
+
+    // wake up now and then to notice an unmount that didn't come from us
+    loop {
+        match rx.recv_timeout(Duration::from_millis(500)) {
+            Ok(()) => break,
+            Err(mpsc::RecvTimeoutError::Timeout) if !session.guard.is_finished() => continue,
+            Err(_) => return session.join().map_err(Into::into),
+        }
+    }
+
+    session.umount_and_join()?;
+    Ok(())
+}
+
+/// Unmount a `semantic mount` from outside, e.g. one whose process was killed
+/// before it could clean up. Uses fusermount3 or fusermount where there is one
+/// (so no root is needed on Linux), and umount otherwise.
+pub fn unmount(mountpoint: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let shown = mountpoint.display();
+    match is_mount_point(mountpoint) {
+        Ok(true) => {}
+        Ok(false) => return Err(format!("nothing is mounted at {shown}").into()),
+        // a dead mount is exactly what this is for
+        Err(e) if e.kind() == io::ErrorKind::NotConnected => {}
+        Err(e) => return Err(format!("{shown}: {e}").into()),
+    }
+    if !is_semantic_mount(mountpoint) {
+        return Err(format!("{shown} wasn't mounted by semantic; leaving it alone").into());
+    }
+
+    let helpers: &[(&str, &[&str])] = if cfg!(target_os = "linux") {
+        &[("fusermount3", &["-u"]), ("fusermount", &["-u"]), ("umount", &[])]
+    } else {
+        &[("umount", &[])]
+    };
+    let Some((program, args)) = helpers
+        .iter()
+        .find(|(program, _)| crate::shell::find_in_path(program).is_some())
+    else {
+        let names: Vec<_> = helpers.iter().map(|(program, _)| *program).collect();
+        return Err(format!("can't unmount: none of {} is installed", names.join(", ")).into());
+    };
+
+    let status = std::process::Command::new(program)
+        .args(*args)
+        .arg(mountpoint)
+        .status()
+        .map_err(|e| format!("failed to run {program}: {e}"))?;
+    if !status.success() {
+        return Err(format!("{program} couldn't unmount {shown}").into());
+    }
+    Ok(())
+}
@@ -0,0 +1,234 @@
+// history/mod.rs
+// Records every command run through `semantic translate`, and reads it back
+// for `semantic history` and `semantic stats`.
+// History lives at ~/.local/state/semantic/history.jsonl, one JSON object per line:
+//   {"timestamp": <unix secs>, "name": ..., "resolved": [argv...], "exit_code": .., "duration_ms": ..,
+//    "cwd": ...}
+// (cwd, the directory it ran in, is missing from entries written before it was recorded.)
+// Only written when `[general] history = true` is set in config.toml.
+// Once the file grows past `[general] history_max_kb` it's rotated to history.jsonl.1.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// Rotation threshold when `[general] history_max_kb` isn't set.
+pub const DEFAULT_MAX_KB: u64 = 1024;
+
+/// One executed command.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub timestamp: u64,
+    pub name: String,
+    pub resolved: Vec<String>,
+    pub exit_code: i32,
+    pub duration_ms: u64,
+    /// Where it ran, for `semantic undo` to find relative paths again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+}
+
+/// Full path to history.jsonl.
+/// Uses the XDG state dir where there is one, otherwise the local data dir.
+pub fn history_path() -> PathBuf {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .unwrap_or_else(config::config_dir)
+        .join("semantic")
+        .join("history.jsonl")
+}
+
+/// Append one executed command to the history log, rotating it first if it's
+/// grown past `max_kb`.
+/// Returns an error instead of panicking so the caller can decide whether to mention it.
+pub fn append(
+    name: &str,
+    resolved: &[String],
+    exit_code: i32,
+    duration: Duration,
+    max_kb: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = history_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if fs::metadata(&path).is_ok_and(|m| m.len() > max_kb * 1024) {
+        fs::rename(&path, path.with_extension("jsonl.1"))?;
+    }
+
+    let entry = Entry {
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        name: name.to_string(),
+        resolved: resolved.to_vec(),
+        exit_code,
+        duration_ms: duration.as_millis() as u64,
+        cwd: env::current_dir().ok(),
+    };
+
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+
+    Ok(())
+}
+
+/// Every entry in the current log, oldest first.
+/// A missing file is just an empty history; lines that don't parse are skipped.
+pub fn read() -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
+    let content = match fs::read_to_string(history_path()) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Empty the log, including the rotated file, for `semantic stats --reset`.
+/// A log that doesn't exist yet is already empty.
+pub fn clear() -> Result<(), Box<dyn std::error::Error>> {
+    let path = history_path();
+    if path.exists() {
+        fs::write(&path, "")?;
+    }
+    let rotated = path.with_extension("jsonl.1");
+    if rotated.exists() {
+        fs::remove_file(rotated)?;
+    }
+    Ok(())
+}
+
+/// How one semantic command has been used.
+#[derive(Debug)]
+pub struct Usage<'a> {
+    pub name: &'a str,
+    pub runs: usize,
+    pub successes: usize,
+    pub last_used: u64,
+}
+
+/// Usage per semantic command, most used first (ties alphabetical).
+pub fn stats(entries: &[Entry]) -> Vec<Usage<'_>> {
+    let mut usage: HashMap<&str, Usage> = HashMap::new();
+    for entry in entries {
+        let u = usage.entry(&entry.name).or_insert(Usage {
+            name: &entry.name,
+            runs: 0,
+            successes: 0,
+            last_used: 0,
+        });
+        u.runs += 1;
+        u.successes += usize::from(entry.exit_code == 0);
+        u.last_used = u.last_used.max(entry.timestamp);
+    }
+
+    let mut usage: Vec<_> = usage.into_values().collect();
+    usage.sort_by(|a, b| b.runs.cmp(&a.runs).then(a.name.cmp(b.name)));
+    usage
+}
+
+/// Parse a duration like `30d`, `12h`, `45m`, `90s` or `2w`.
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let split = s.find(|c: char| !c.is_ascii_digit())?;
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        "w" => 604_800,
+        _ => return None,
+    };
+    Some(Duration::from_secs(number.checked_mul(seconds)?))
+}
+
+/// Parse a date like `2026-03-01` as the unix timestamp of its midnight (UTC).
+pub fn parse_date(s: &str) -> Option<u64> {
+    let mut parts = s.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    let days_in_month = match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return None,
+    };
+    if !(1..=days_in_month).contains(&day) {
+        return None;
+    }
+
+    // a civil date to days since 1970-01-01 (the inverse of format_timestamp's)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    u64::try_from(days).ok().map(|days| days * 86_400)
+}
+
+/// Format a unix timestamp as `YYYY-MM-DD HH:MM:SS` (UTC).
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let secs = timestamp % 86_400;
+
+    // days since 1970-01-01 to a civil date (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Sandbox;
+
+    #[test]
+    fn append_adds_one_line_per_command() {
+        let _sandbox = Sandbox::new();
+        let argv = ["echo".to_string(), "hi".to_string()];
+        append("say", &argv, 0, Duration::from_millis(5), DEFAULT_MAX_KB).unwrap();
+        append("say", &argv, 2, Duration::from_millis(5), DEFAULT_MAX_KB).unwrap();
+
+        let content = fs::read_to_string(history_path()).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        let entries = read().unwrap();
+        assert_eq!(entries[0].name, "say");
+        assert_eq!(entries[0].resolved, argv);
+        assert_eq!((entries[0].exit_code, entries[1].exit_code), (0, 2));
+        assert_eq!(entries[0].cwd, env::current_dir().ok());
+    }
+
+    #[test]
+    fn missing_log_is_empty_history() {
+        let _sandbox = Sandbox::new();
+        assert!(read().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,314 @@
+// link/mod.rs
+// The symlink alternative to `semantic mount`, behind `semantic link`.
+// Each virtual path becomes a symlink to its real path: under ~/Semantic/ by
+// default (~/Semantic/apps → /usr/bin), or at the virtual path itself with
+// --system. Every link made is recorded in a manifest (links.json in the config
+// dir), so later runs update or remove exactly what semantic created and never
+// touch anything else.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// One symlink semantic created.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Link {
+    pub path: PathBuf,
+    pub target: PathBuf,
+    /// Made with --system (at the virtual path itself) rather than under ~/Semantic.
+    #[serde(default)]
+    pub system: bool,
+}
+
+impl Link {
+    fn describe(&self) -> String {
+        format!("{} → {}", self.path.display(), self.target.display())
+    }
+}
+
+/// links.json: every link semantic has made, and the directories it had to
+/// create to hold them (removed again once they're empty).
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub links: Vec<Link>,
+    #[serde(default)]
+    pub dirs: Vec<PathBuf>,
+}
+
+impl Manifest {
+    /// links.json in `config_dir`.
+    pub fn path() -> PathBuf {
+        config::config_dir().join("links.json")
+    }
+
+    /// The manifest, or an empty one if nothing has been linked yet.
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        let path = Self::path();
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                serde_json::from_str(&content).map_err(|e| format!("{}: {e}", path.display()).into())
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{}: {e}", path.display()).into()),
+        }
+    }
+
+    /// Write the manifest back, or delete it once it has nothing left to track.
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = Self::path();
+        if self.links.is_empty() && self.dirs.is_empty() {
+            return match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    Err(format!("{}: {e}", path.display()).into())
+                }
+                _ => Ok(()),
+            };
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)? + "\n";
+        fs::write(&path, content).map_err(|e| format!("{}: {e}", path.display()).into())
+    }
+
+    fn owns(&self, path: &Path) -> bool {
+        self.links.iter().any(|link| link.path == path)
+    }
+
+    fn record(&mut self, link: Link) {
+        self.links.retain(|l| l.path != link.path);
+        self.links.push(link);
+    }
+
+    /// Create the missing parent directories of `path`, remembering each one.
+    fn create_parents(&mut self, path: &Path) -> io::Result<()> {
+        let Some(parent) = path.parent() else {
+            return Ok(());
+        };
+        let missing: Vec<PathBuf> = parent
+            .ancestors()
+            .take_while(|dir| !dir.exists())
+            .map(Path::to_path_buf)
+            .collect();
+        fs::create_dir_all(parent)?;
+        self.dirs.extend(missing);
+        Ok(())
+    }
+
+    /// Remove the directories semantic created that have nothing left in them,
+    /// deepest first. Ones still in use stay (and stay recorded).
+    fn remove_empty_dirs(&mut self) {
+        self.dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+        self.dirs.retain(|dir| match fs::remove_dir(dir) {
+            Ok(()) => false,
+            Err(e) => e.kind() != io::ErrorKind::NotFound,
+        });
+    }
+}
+
+/// What a `link_all` or `remove_all` run did, one "link → target" line per entry.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub removed: Vec<String>,
+    /// Left alone, with the reason.
+    pub skipped: Vec<String>,
+}
+
+/// Where links go: ~/Semantic, or the root for --system.
+pub fn link_root(system: bool) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if system {
+        return Ok(PathBuf::from("/"));
+    }
+    dirs::home_dir()
+        .map(|home| home.join("Semantic"))
+        .ok_or_else(|| "no home directory to put ~/Semantic in".into())
+}
+
+/// Make (or fix) a symlink for every path mapping, and remove the links an
+/// earlier run of the same kind made for mappings that no longer exist.
+/// Anything at a link's place that semantic didn't create is left alone.
+pub fn link_all(
+    paths: &HashMap<String, String>,
+    system: bool,
+) -> Result<Report, Box<dyn std::error::Error>> {
+    let root = link_root(system)?;
+    let mut manifest = Manifest::load()?;
+    let mut report = Report::default();
+
+    let mut sorted: Vec<_> = paths.iter().collect();
+    sorted.sort();
+
+    let mut wanted = Vec::new();
+    for (virtual_path, real_path) in sorted {
+        let relative = virtual_path.trim_start_matches('/');
+        if relative.is_empty() {
+            report.skipped.push(format!("{virtual_path}: can't link the root itself"));
+            continue;
+        }
+        let link = Link { path: root.join(relative), target: PathBuf::from(real_path), system };
+        wanted.push(link.path.clone());
+
+        match fs::symlink_metadata(&link.path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                match manifest.create_parents(&link.path).and_then(|()| make_symlink(&link)) {
+                    Ok(()) => {
+                        report.created.push(link.describe());
+                        manifest.record(link);
+                    }
+                    Err(e) => report.skipped.push(link_error(&link, &e)),
+                }
+            }
+            Err(e) => report.skipped.push(link_error(&link, &e)),
+            Ok(meta) if meta.file_type().is_symlink() => {
+                let current = fs::read_link(&link.path).unwrap_or_default();
+                if current == link.target {
+                    report.unchanged.push(link.describe());
+                } else if manifest.owns(&link.path) {
+                    match fs::remove_file(&link.path).and_then(|()| make_symlink(&link)) {
+                        Ok(()) => {
+                            report.updated.push(link.describe());
+                            manifest.record(link);
+                        }
+                        Err(e) => report.skipped.push(link_error(&link, &e)),
+                    }
+                } else {
+                    report.skipped.push(format!(
+                        "{}: already a symlink to {}, which semantic didn't make",
+                        link.path.display(),
+                        current.display()
+                    ));
+                }
+            }
+            Ok(_) => report.skipped.push(format!(
+                "{}: already exists and isn't a symlink",
+                link.path.display()
+            )),
+        }
+    }
+
+    // links of the same kind whose mapping has gone since
+    let stale: Vec<Link> = manifest
+        .links
+        .iter()
+        .filter(|link| link.system == system && !wanted.contains(&link.path))
+        .cloned()
+        .collect();
+    for link in stale {
+        remove_link(&mut manifest, &link, &mut report);
+    }
+
+    manifest.remove_empty_dirs();
+    manifest.save()?;
+    Ok(report)
+}
+
+/// Remove every link in the manifest (both kinds), then the directories
+/// semantic created for them.
+pub fn remove_all() -> Result<Report, Box<dyn std::error::Error>> {
+    let mut manifest = Manifest::load()?;
+    let mut report = Report::default();
+    for link in manifest.links.clone() {
+        remove_link(&mut manifest, &link, &mut report);
+    }
+    manifest.remove_empty_dirs();
+    manifest.save()?;
+    Ok(report)
+}
+
+/// Delete one recorded link if it's still a symlink, and forget it unless that failed.
+fn remove_link(manifest: &mut Manifest, link: &Link, report: &mut Report) {
+    let forget = match fs::symlink_metadata(&link.path) {
+        Ok(meta) if meta.file_type().is_symlink() => match fs::remove_file(&link.path) {
+            Ok(()) => {
+                report.removed.push(link.describe());
+                true
+            }
+            Err(e) => {
+                report.skipped.push(link_error(link, &e));
+                false
+            }
+        },
+        Ok(_) => {
+            report.skipped.push(format!(
+                "{}: replaced by something that isn't a symlink; leaving it",
+                link.path.display()
+            ));
+            true
+        }
+        // already gone, so there's nothing left to track
+        Err(_) => true,
+    };
+    if forget {
+        manifest.links.retain(|l| l.path != link.path);
+    }
+}
+
+fn link_error(link: &Link, e: &io::Error) -> String {
+    let hint = if e.kind() == io::ErrorKind::PermissionDenied && link.system {
+        " (--system needs write access there)"
+    } else {
+        ""
+    };
+    format!("{}: {e}{hint}", link.path.display())
+}
+
+#[cfg(unix)]
+fn make_symlink(link: &Link) -> io::Result<()> {
+    std::os::unix::fs::symlink(&link.target, &link.path)
+}
+
+#[cfg(windows)]
+fn make_symlink(link: &Link) -> io::Result<()> {
+    std::os::windows::fs::symlink_dir(&link.target, &link.path)
+}
+
+/// Problems with recorded links, one line each: gone, replaced, pointing
+/// somewhere else, or pointing at a path that doesn't exist.
+/// Path mappings without a link (for a kind of link that's in use) count too.
+pub fn problems(manifest: &Manifest, paths: &HashMap<String, String>) -> Vec<String> {
+    let mut problems = Vec::new();
+    for link in &manifest.links {
+        let shown = link.path.display();
+        match fs::read_link(&link.path) {
+            Err(_) if fs::symlink_metadata(&link.path).is_ok() => {
+                problems.push(format!("{shown} was replaced by something else"));
+            }
+            Err(_) => problems.push(format!("{shown} is missing")),
+            Ok(target) if target != link.target => problems.push(format!(
+                "{shown} points to {} instead of {}",
+                target.display(),
+                link.target.display()
+            )),
+            Ok(_) if !link.path.exists() => {
+                problems.push(format!("{shown} is broken ({} doesn't exist)", link.target.display()));
+            }
+            Ok(_) => {}
+        }
+    }
+
+    for system in [false, true] {
+        if !manifest.links.iter().any(|link| link.system == system) {
+            continue;
+        }
+        let Ok(root) = link_root(system) else { continue };
+        let mut unlinked: Vec<_> = paths
+            .keys()
+            .filter(|v| !v.trim_start_matches('/').is_empty())
+            .filter(|v| !manifest.owns(&root.join(v.trim_start_matches('/'))))
+            .collect();
+        unlinked.sort();
+        for virtual_path in unlinked {
+            problems.push(format!("{virtual_path} has no link yet"));
+        }
+    }
+    problems
+}
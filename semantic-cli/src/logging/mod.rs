@@ -0,0 +1,97 @@
+// logging/mod.rs
+// The `log` backend behind -v/-vv and $SEMANTIC_LOG.
+// Off unless one of them asks for it: the aliases run semantic constantly, so by
+// default a log call costs one comparison against the max level and nothing more.
+// Lines look like `debug: path '/apps/vim' matched prefix '/apps' -> '/usr/bin/vim'`
+// and go to stderr, so a command's stdout stays clean for pipes, or to a file
+// while the TUI owns the terminal (see `to_file`).
+// Only semantic's own lines are shown, not those of the crates it uses.
+
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// What $SEMANTIC_LOG accepts, quietest first.
+pub const LEVELS: &[&str] = &["off", "error", "warn", "info", "debug", "trace"];
+
+/// Where lines go instead of stderr, once `to_file` has opened one.
+static FILE: Mutex<Option<fs::File>> = Mutex::new(None);
+
+struct Logger;
+
+static LOGGER: Logger = Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level() && metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!("{}: {}\n", record.level().as_str().to_lowercase(), record.args());
+        let mut file = FILE.lock().unwrap_or_else(|e| e.into_inner());
+        // a line that can't be written is dropped; logging never fails a command
+        let _ = match file.as_mut() {
+            Some(file) => file.write_all(line.as_bytes()),
+            None => io::stderr().write_all(line.as_bytes()),
+        };
+    }
+
+    fn flush(&self) {}
+}
+
+/// The level for `verbosity` -v flags (1 is debug, 2 or more trace), or, with
+/// none, for $SEMANTIC_LOG. Off when neither asks for anything. A $SEMANTIC_LOG
+/// that isn't one of `LEVELS` is an error, and logging stays off.
+pub fn level(verbosity: u8) -> Result<LevelFilter, String> {
+    match verbosity {
+        0 => {}
+        1 => return Ok(LevelFilter::Debug),
+        _ => return Ok(LevelFilter::Trace),
+    }
+    let Ok(value) = env::var("SEMANTIC_LOG") else {
+        return Ok(LevelFilter::Off);
+    };
+    if value.is_empty() {
+        return Ok(LevelFilter::Off);
+    }
+    value.parse().map_err(|_| {
+        format!("SEMANTIC_LOG is \"{value}\"; it should be one of {}", LEVELS.join(", "))
+    })
+}
+
+/// Install the logger at `level`. Nothing is installed for Off, so the
+/// default path never touches it.
+pub fn init(level: LevelFilter) {
+    if level == LevelFilter::Off {
+        return;
+    }
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Send lines to the end of the file at `path` from now on, if logging is on.
+/// For the TUI, where stderr would draw over the alternate screen.
+pub fn to_file(path: &Path) -> io::Result<()> {
+    if log::max_level() == LevelFilter::Off {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    *FILE.lock().unwrap_or_else(|e| e.into_inner()) = Some(file);
+    Ok(())
+}
+
+/// Send lines back to stderr, e.g. once the TUI has left the alternate screen.
+pub fn to_stderr() {
+    *FILE.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
@@ -3,115 +3,4660 @@
 //
 // Subcommands:
 //   (no args)           — launch the TUI installer
-//   init                — print shell aliases to stdout (user evals this)
-//   translate <cmd> ... — look up a semantic command and run the real one
+//   init [--force] [--shell <shell> | <shell> | --all]
+//                       — print shell aliases to stdout (user evals this); --force
+//                         drops the guard that skips it in an already set-up shell;
+//                         --all prints one snippet for every enabled fish, zsh and
+//                         bash, each running only its own part
+//   init --install [--inline] [--dry-run] [shell]
+//                       — add a marked block that evals it to each enabled shell's
+//                         rc file (fish: conf.d/semantic.fish), unless one is there;
+//                         --inline writes the code itself, replacing its old block
+//   alias [--shell <shell>] <name> <real cmd...>
+//                       — print one mapping as an alias or function to eval, for
+//                         this session only (the config isn't read or written)
+//   alias-file [--output <file>] [--shell <shell>]
+//                       — write every mapping as plain aliases and functions in a
+//                         standalone script, for where semantic won't be installed
+//   translate <cmd> ... — look up a semantic command and run the real one;
+//                         "<cmd> && <cmd> | <cmd>", as one quoted argument,
+//                         chains several through sh
+//                         (-v/--verbose traces it on stderr, --dry-run skips running it,
+//                         --timeout <seconds> kills it after that long (exit 124),
+//                         --resolve-path <path> only prints the path it translates to,
+//                         --batch [--keep-going] reads one command per line from stdin,
+//                         and a `--` ends these options: the words after it go on
+//                         as typed, never read as options, chains or paths);
+//                         virtual paths among the args are translated, but not flags,
+//                         anything after a `--`, or the args of a mapping written as
+//                         { command = "...", translate_paths = false }
+//   translate-batch [--input <file>] [--keep-going] [--parallel <n>] [--dry-run]
+//                       — run one semantic command per line (stdin by default),
+//                         echoing each resolved command to stderr
+//   run [--keep-going] <script>
+//                       — run a semantic script (see script/mod.rs) line by line,
+//                         stopping at the first failure; scripts can start with
+//                         #!/usr/bin/env -S semantic run
+//   test [--fix]        — check that every mapped binary and path exists
+//   edit [--commands | --paths]
+//                       — open a copy of config.toml in $VISUAL/$EDITOR (creating it
+//                         if needed), and replace the config with it only once it
+//                         parses and validates; --commands/--paths start at that table
+//   config path | dir   — print where the active config file (or its directory) is,
+//                         whether or not it exists yet, for scripts and editors
+//   list [--commands | --paths | --all] [--filter <glob>] [--json | --format <template>]
+//                       — print the command names (or the paths, or both), one a line,
+//                         or each mapping through a template of {name}, {command} and
+//                         {kind} (see ListField); --format text, tsv and json are plain
+//                         ones for scripts
+//   info [--json]       — show the config file, shell settings and every mapping,
+//                         or that there is no config yet (exit 0 either way); with
+//                         -v, also the project config and plugins merged in
+//   which <name|path>   — show what a semantic command or virtual path resolves to
+//                         (--plain for just the expansion, --json for scripts)
+//   path <path> | --all — print the real path a virtual path translates to
+//                         (exit 2 if nothing maps it), or the whole path table
+//   history [--stats]   — show recent translated commands, or usage counts per command
+//   stats [--since <duration|date>] [--top <n>] [--reset]
+//                       — runs, success rate and last use per command (from history),
+//                         and the failure rate overall; --top lists only the most used
+//   undo [--yes] [--dry-run]
+//                       — reverse the last command in the history where possible
+//                         (see undo/mod.rs), after showing how and asking
+//   explain <real cmd>  — find the semantic command or path that maps to something real
+//   help [<command>]    — what each semantic command does, by category, or one
+//                         command's real command, description and an example
+//   completions <shell> — print a completion script for semantic itself (bash, zsh,
+//                         fish): its subcommands, and the semantic commands after
+//                         translate/which/help, described by their real commands
+//   uninstall [--purge] [--yes] [--dry-run]
+//                       — remove semantic's block from shell rc files
+//                         (--purge also deletes its config, history and cache)
+//   reset [--yes]       — replace all mappings with the styles' defaults, keeping settings
+//   diff [--commands | --paths] [--style <style>]
+//                       — show which mappings differ from the styles' defaults (or
+//                         what switching to <style> would change); exit 1 if any do
+//   upgrade             — migrate the config to the current schema version and save
+//                         it (backed up to <file>.bak first)
+//   profile list | create <name> [--copy] | use <name> | delete <name> | save|load <name>
+//                       — keep whole configs as named profiles (work, personal, ...)
+//                         in profiles/ and switch the active one ("default" is
+//                         config.toml itself); save/load copy the active config
+//                         to a profile and back
+//   doctor              — check the setup and report PASS/WARN/FAIL per check
+//   export [--format toml|json] [--output <file> | <file>]
+//                       — print (or write) the active config for sharing, headed
+//                         by an [export] table (version, date, host)
+//   import <path|url|-> [--merge [--strategy replace|extend|ask]] [--allow-dangerous] [--yes]
+//                       — replace your config with a shared one (--merge: only
+//                         add its mappings), after showing the diff and asking
+//   convert --to json|toml
+//                       — rewrite config.toml as config.json (or back)
+//   mount <mountpoint>  — serve the virtual paths as a read-only FUSE filesystem
+//                         (unix, `fuse` feature)
+//   umount <mountpoint> — unmount it from outside, e.g. after the mount was killed
+//   link [--system] | --remove
+//                       — expose the virtual paths as symlinks under ~/Semantic
+//                         (or at the virtual paths themselves), or remove them again
+//   scan-shells         — set up (or just report) installed shells (in /etc/shells or
+//                         $PATH) that aren't enabled yet, per [shells] on_new_shell;
+//                         quiet when there's nothing new, for a login hook or timer
+//                         (check-shells is the old name)
+//   watch               — watch $PATH for newly installed shells and set them up
+//                         (or just report them), per [shells] on_new_shell
+//
+// Global flags (before the subcommand):
+//   -q, --quiet         — don't print notices (e.g. project-local config in effect)
+//   -v, -vv, --verbose  — log what semantic does to stderr, at debug (-v) or trace
+//                         (-vv) level (see SEMANTIC_LOG); implies translate -v
+//   --json              — JSON on stdout from list, info, which, diff, doctor and
+//                         translate --dry-run; their errors go to stderr as
+//                         {"error": ...}, exit codes unchanged
+//   --profile <name>    — use that profile instead of the active one for this run
+//                         (init also exports it, so the shell keeps using it)
+//   -h, --help          — print the full reference (HELP below)
+//   -V, --version       — print the version
+//
+// Environment:
+//   SEMANTIC_CONFIG     — use this config file instead of ~/.config/semantic/config.toml
+//                         (or config.json, which is used instead if it exists)
+//   SEMANTIC_CONFIG_DIR — the config directory (packs/, plugins/ and all), instead of
+//                         $XDG_CONFIG_HOME/semantic or ~/.config/semantic
+//                         (%APPDATA%\semantic on Windows)
+//   SEMANTIC_SHELL      — the shell to assume instead of detecting it (set by the aliases)
+//   SEMANTIC_NO_LOCAL   — ignore project configs (.semantic/config.toml, .semantic.toml),
+//                         e.g. in a checkout you don't trust
+//   SEMANTIC_NO_CACHE   — parse the config every time instead of reading the parsed copy
+//                         in ~/.cache/semantic (see config::DiskCache)
+//   SEMANTIC_PROFILE    — the profile to use instead of the active one (see --profile)
+//   SEMANTIC_LOCK_TIMEOUT_MS
+//                       — how long to wait for another process's config lock
+//                         (default 5000)
+//   SEMANTIC_LOG        — log at this level when no -v is given: off, error, warn,
+//                         info, debug or trace (default off)
+//
+// Exit codes (see error::SemanticError):
+//   1 — any other failure; 2 — the config is missing, broken or maps something
+//   that can't run; 3 — unknown semantic command; 126/127 — the real program
+//   couldn't be run / wasn't found. Otherwise translate exits with the program's code.
 
 mod config;
+mod doctor;
+mod error;
+#[cfg(all(unix, feature = "fuse"))]
+mod fs;
+mod history;
+mod link;
+mod logging;
+mod script;
 mod shell;
+#[cfg(test)]
+mod testutil;
 mod tui;
+mod undo;
+mod watch;
 
 use std::env;
-use std::process::{Command, exit};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+use std::process::{Command, Stdio, exit};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use error::SemanticError;
+
+/// Every subcommand, for `semantic completions`. Keep in sync with `main`.
+const SUBCOMMANDS: &[&str] = &[
+    "init", "alias", "alias-file", "translate", "translate-batch", "run", "test", "edit", "config",
+    "list", "info", "which", "path", "explain", "help", "completions", "export", "import", "convert",
+    "history", "stats", "undo", "uninstall", "reset", "diff", "profile", "upgrade", "doctor", "mount",
+    "umount", "link", "scan-shells", "watch",
+];
+
+/// Full reference printed by `semantic --help`. Keep in sync with the header above.
+const HELP: &str = "\
+semantic — plain-language aliases for shell commands and paths
+
+Usage: semantic [-q] [<command> [args...]]
+
+Commands:
+  (none)                     run the setup wizard
+  init [--force] [shell]     print shell init code (eval it in your rc file)
+      --force                  re-apply even in a shell that already ran it
+      --shell <shell>          for this enabled shell (the same as [shell])
+      --all                    one snippet for all enabled shells among fish,
+                               zsh and bash, to share an rc file between them
+  init --install [--dry-run] [shell]
+                             add the line that loads semantic to the rc file of
+                             each enabled shell (or just [shell]), once
+      --inline                 write the init code itself there instead,
+                               replacing what an earlier run wrote
+  alias <name> <real command...>
+                             print a one-off alias for this session only, e.g.
+                             eval \"$(semantic alias deploy 'ssh user@prod')\"
+      --shell <shell>          define it for this shell instead of the current one
+  alias-file [--output <file>] [--shell <shell>]
+                             write your commands as a standalone script of
+                             aliases and functions that doesn't need semantic
+  translate <cmd> [args...]  run the real command a semantic command maps to
+      -v, --verbose            trace the resolved command on stderr
+      --dry-run                show what would run without running it
+      --timeout <seconds>      kill it if it runs longer, and exit with 124
+      --resolve-path <path>    just print the real path <path> translates to
+                               (unchanged if no mapping applies)
+      --batch                  read one command per line from stdin instead
+      --keep-going             with --batch, carry on after a command fails
+      -- <cmd> [args...]       the command and args as typed: nothing after the
+                               -- is read as a translate option, chain or path
+      \"<cmd> && <cmd> | <cmd>\" chain semantic commands with &&, || and |, quoted
+                               as one argument; each is translated and the whole
+                               line runs through sh
+      virtual paths in [args...] are translated (~/... and relative ones too),
+      but not flags or anything after a --, which is passed on as given
+  translate-batch [--input <file>] [--keep-going] [--parallel <n>] [--dry-run]
+                             run one semantic command per line of stdin (or the
+                             file), echoing each resolved command to stderr
+  run [--keep-going] <script>
+                             run a file of semantic commands, one per line, in
+                             order; it stops at the first failure unless
+                             --keep-going (start one with
+                             #!/usr/bin/env -S semantic run to chmod +x it)
+  test [--fix]               check that every mapped program and path exists
+  edit [--commands | --paths]
+                             edit your config in $VISUAL or $EDITOR; it's only
+                             saved once it's valid (start at a table with --commands
+                             or --paths, in editors that take +<line>)
+  config path | dir          print where your config file (or its directory) is,
+                             even before there is one
+  list [--json]              print your command names, one a line
+      --paths | --all          the virtual paths instead, or both
+      --filter <glob>          only names matching it, e.g. \"git-*\"
+      --format <template>      one line per mapping instead, e.g. \"{name}\\t{command}\"
+                               ({kind} is command or path; \\t, \\n, {{ and }} work),
+                               or text (names only), tsv (name, tab, mapping) or json
+  info [--json]              show the config file, shell settings and mappings
+                             (with -v, also the project config and plugins)
+  which [--plain | --json] <name>
+                             show what a command or virtual path resolves to
+  path <virtual-path> | --all
+                             print the real path a path translates to (exit 2 if
+                             no mapping applies), or every path mapping
+  history [-n <count>] [--stats]
+                             show recent commands, or how often each is used
+  stats [--since <duration|date>] [--top <n>] [--reset]
+                             runs, success rate and last use per command (the
+                             top <n>), and how many runs failed in all
+  undo [--yes] [--dry-run]   reverse the last command you ran (a move, copy or
+                             mkdir), after showing how; rm can't be undone
+  explain [--plain] <real command|path>
+                             find the semantic name for a real command or path
+  help [<command>]           what your semantic commands do, or how to use one
+  completions <shell>        print tab completion for semantic (bash, zsh, fish)
+  uninstall [--purge] [--yes] [--dry-run]
+                             remove semantic's block from your shell rc files
+      --purge                  also delete your config, history and cache
+  reset [--yes]              replace your mappings with your styles' defaults
+                             (settings are kept, the old file backed up)
+  diff [--commands | --paths]
+                             show the mappings you added, removed or changed
+                             from your styles' defaults (exit 1 if there are any)
+      --style <style>          what switching to that style would change instead
+  upgrade                    update your config for this version of semantic
+                             (the old file is backed up)
+  profile list               list the profiles, marking the active one
+  profile create <name>      make a profile from the default settings
+      --copy                   start from a copy of the active config instead
+  profile use <name>         switch to a profile (\"default\" is config.toml)
+  profile delete <name>      delete a profile that isn't active
+  profile save <name>        save the active config as a named profile
+  profile load <name>        copy a saved profile over the active config (the
+                             old one is backed up)
+  doctor                     check the setup and report PASS/WARN/FAIL
+  export [--format toml|json] [--output <file> | <file>]
+                             print (or write) the active config, with an
+                             [export] header saying where it came from
+  import <path|url|->        replace your config with a shared one, after
+                             showing what changes and asking
+      --merge                  only add its commands and paths to yours
+      --strategy <s>           with --merge, conflicting keys: replace (theirs,
+                               the default), extend (yours) or ask
+      --allow-dangerous        import mappings that look destructive anyway
+      --yes                    don't ask (needed when reading from stdin)
+  convert --to json|toml     rewrite your config file in the other format
+  mount <mountpoint>         serve the virtual paths as a read-only filesystem
+  umount <mountpoint>        unmount it (Ctrl-C in `semantic mount` does too)
+  link [--system] | --remove
+                             symlink each virtual path to its real path under
+                             ~/Semantic (--system: at the virtual path itself);
+                             --remove deletes every link semantic made
+  scan-shells                set up (or, with on_new_shell = \"notify\", report)
+                             shells in /etc/shells or $PATH that aren't enabled
+  watch                      set up (or, with on_new_shell = \"notify\", report)
+                             shells installed while it runs
+
+Options:
+  -q, --quiet                don't print notices
+  -v, --verbose              log what semantic does to stderr (-vv for more);
+                             implies translate -v
+      --json                 print JSON from list, info, which, diff, doctor
+                             and translate --dry-run; errors go to stderr as
+                             {\"error\": ...} with the usual exit codes
+      --profile <name>       use that profile for this run instead of the
+                             active one
+      --no-color             draw the setup wizard without colors
+  -h, --help                 print this help
+  -V, --version              print the version
+
+Environment:
+  SEMANTIC_CONFIG            use this config file instead of ~/.config/semantic/config.toml
+  SEMANTIC_CONFIG_DIR        use this directory instead of ~/.config/semantic
+                             (%APPDATA%\\semantic on Windows)
+  SEMANTIC_SHELL             assume this shell instead of detecting it
+  SEMANTIC_NO_LOCAL          ignore project configs like ./.semantic.toml
+  SEMANTIC_NO_CACHE          don't cache the parsed config in ~/.cache/semantic
+  SEMANTIC_PROFILE           use this profile instead of the active one
+  SEMANTIC_LOCK_TIMEOUT_MS   how long to wait for the config lock (default 5000)
+  SEMANTIC_LOG               log level without -v: error, warn, info, debug, trace
+  NO_COLOR                   if set and not empty, the same as --no-color
+
+Exit codes:
+  1                          any other failure
+  2                          no config, a config that doesn't parse, or a mapping
+                             that can't run as written
+  3                          no semantic command by that name
+  126, 127                   the real program couldn't be run, or wasn't found
+                             (otherwise translate exits with the program's code)
+";
+
+/// Flags that apply to every subcommand. Parsed from the front of the args.
+struct GlobalFlags {
+    quiet: bool,
+    json: bool,
+    profile: Option<String>, // --profile: use this profile instead of the active one
+    verbosity: u8,           // how many -v: 1 logs at debug level, 2 at trace
+    no_color: bool,          // --no-color or $NO_COLOR: no colors in the wizard
+}
 
 fn main() {
-    let args: Vec<String> = env::args().skip(1).collect();
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let flags = parse_global_flags(&mut args);
+    match logging::level(flags.verbosity) {
+        Ok(level) => logging::init(level),
+        Err(e) => eprintln!("semantic: {e}"),
+    }
 
     match args.first().map(|s| s.as_str()) {
         // no args — run the TUI installer
-        None => tui::run(),
+        None => tui::run(flags.no_color),
 
         // print shell init code to stdout
-        Some("init") => cmd_init(),
+        Some("init") => cmd_init(&args[1..], &flags),
+
+        // a one-off mapping for the current session, without touching the config
+        Some("alias") => cmd_alias(&args[1..]),
+        Some("alias-file") => cmd_alias_file(&args[1..], &flags),
 
         // translate and execute a semantic command
-        Some("translate") => cmd_translate(&args[1..]),
+        Some("translate") => cmd_translate(&args[1..], &flags),
+
+        // run a list of semantic commands, optionally several at once
+        Some("translate-batch") => cmd_translate_batch(&args[1..], &flags),
+        Some("run") => cmd_run(&args[1..], &flags),
+
+        // verify the config against the current system
+        Some("test") => cmd_test(&args[1..], &flags),
+
+        // open the config in the user's editor
+        Some("edit") => cmd_edit(&args[1..]),
+        Some("config") => cmd_config(&args[1..]),
+
+        // show every mapping
+        Some("list") => cmd_list(&args[1..], &flags),
+
+        // what's configured, at a glance
+        Some("info") => cmd_info(&args[1..], &flags),
+
+        // explain how a command or path resolves
+        Some("which") => cmd_which(&args[1..], &flags),
+
+        // translate a path the way translate does its arguments
+        Some("path") => cmd_path(&args[1..], &flags),
+
+        // the reverse of which: find the semantic name for a real command or path
+        Some("explain") => cmd_explain(&args[1..], &flags),
+
+        // what each semantic command does, for learning them
+        Some("help") => cmd_help(&args[1..], &flags),
+        Some("completions") => cmd_completions(&args[1..], &flags),
+
+        // share a config with other people
+        Some("export") => cmd_export(&args[1..], &flags),
+        Some("import") => cmd_import(&args[1..]),
+
+        // switch the config file between TOML and JSON
+        Some("convert") => cmd_convert(&args[1..]),
+
+        // show what's been run through translate
+        Some("history") => cmd_history(&args[1..]),
+        Some("stats") => cmd_stats(&args[1..]),
+        Some("undo") => cmd_undo(&args[1..]),
+
+        // undo semantic's setup: its rc-file blocks, and with --purge its own files
+        Some("uninstall") => cmd_uninstall(&args[1..]),
+
+        // go back to the stock mappings for the configured styles
+        Some("reset") => cmd_reset(&args[1..]),
+
+        // compare the mappings with the stock ones for the styles
+        Some("diff") => cmd_diff(&args[1..], &flags),
+
+        Some("profile") => cmd_profile(&args[1..]),
+
+        // rewrite an older config for this version of semantic
+        Some("upgrade") => cmd_upgrade(),
+
+        // summarise what's wrong with the setup
+        Some("doctor") => cmd_doctor(&flags),
+
+        // expose the virtual paths as a real filesystem
+        Some("mount") => cmd_mount(&args[1..], &flags),
+        Some("umount") => cmd_umount(&args[1..]),
+        Some("link") => cmd_link(&args[1..], &flags),
+        Some("scan-shells" | "check-shells") => cmd_scan_shells(&flags),
+
+        // act on shells installed while this runs
+        Some("watch") => cmd_watch(&flags),
+
+        Some("-h" | "--help") => print!("{HELP}"),
+        Some("-V" | "--version") => println!("semantic {}", env!("CARGO_PKG_VERSION")),
 
         // unknown subcommand
         Some(other) => {
             eprintln!("Unknown command: {other}");
-            eprintln!("Usage: semantic [init | translate <command> ...]");
+            eprintln!("Usage: semantic [--quiet] <command> [args...]");
+            eprintln!("Run `semantic --help` for details.");
             exit(1);
         }
     }
 }
 
+/// Strip global flags from the front of `args`, stopping at the first non-flag.
+fn parse_global_flags(args: &mut Vec<String>) -> GlobalFlags {
+    let mut flags = GlobalFlags {
+        quiet: false,
+        json: false,
+        profile: None,
+        verbosity: 0,
+        // https://no-color.org: present and not empty
+        no_color: env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()),
+    };
+
+    while let Some(arg) = args.first() {
+        match arg.as_str() {
+            "-q" | "--quiet" => flags.quiet = true,
+            "--json" => flags.json = true,
+            "--no-color" => flags.no_color = true,
+            "-v" | "--verbose" => flags.verbosity = flags.verbosity.saturating_add(1),
+            "-vv" => flags.verbosity = flags.verbosity.saturating_add(2),
+            "--profile" => {
+                let Some(name) = args.get(1).cloned() else {
+                    eprintln!("--profile needs a profile name");
+                    exit(1);
+                };
+                if name != config::DEFAULT_PROFILE
+                    && !config::profile_path(&name).is_ok_and(|path| path.is_file())
+                {
+                    eprintln!("No saved profile named `{name}`.");
+                    let saved = config::profile_names().unwrap_or_default();
+                    if !saved.is_empty() {
+                        eprintln!("Saved profiles: {}", saved.join(", "));
+                    }
+                    exit(1);
+                }
+                config::override_profile(&name);
+                flags.profile = Some(name);
+                args.remove(0);
+            }
+            _ => break,
+        }
+        args.remove(0);
+    }
+
+    flags
+}
+
+/// Load the effective config, noting on stderr when a project-local override
+/// applies, when a pack's mapping lost to one already defined, and when a
+/// plugin couldn't be loaded.
+fn load_config(flags: &GlobalFlags) -> Result<config::SemanticConfig, SemanticError> {
+    load_config_with_source(flags).map(|(config, _)| config)
+}
+
+/// `load_config`, along with where the config came from (see `print_config_source`).
+fn load_config_with_source(
+    flags: &GlobalFlags,
+) -> Result<(config::SemanticConfig, config::ConfigSource), SemanticError> {
+    let (config, source) = config::SemanticConfig::load_with_source()?;
+    if flags.quiet {
+        return Ok((config, source));
+    }
+
+    for project in &config.project_configs {
+        eprintln!("semantic: using project config {}", project.display());
+    }
+    for (pack, key) in &config.pack_collisions {
+        eprintln!(
+            "semantic: warning: {key} from {} {} is already mapped, keeping the existing one",
+            config::pack_kind(pack),
+            pack.display()
+        );
+    }
+    for (plugin, e) in &config.skipped_plugins {
+        eprintln!("semantic: warning: skipping plugin {}: {e}", plugin.display());
+    }
+    // translate refuses to run these; say why before it does
+    for cycle in config.reference_cycles() {
+        eprintln!("semantic: warning: {cycle}");
+    }
+
+    Ok((config, source))
+}
+
+/// Say on stderr which files the config was loaded from: the user config and
+/// its profile, the project config on top, and the plugins. For -v.
+fn print_config_source(source: &config::ConfigSource) {
+    let profile = source.active_profile.as_deref().unwrap_or(config::DEFAULT_PROFILE);
+    eprintln!("semantic: config {} (profile {profile})", source.user_config_path.display());
+    if let Some(project) = &source.project_config_path {
+        eprintln!("semantic: project config {}", project.display());
+    }
+    if !source.plugins_loaded.is_empty() {
+        eprintln!("semantic: plugins {}", source.plugins_loaded.join(", "));
+    }
+}
+
+/// Load the effective config with environment variables expanded throughout.
+/// For subcommands that show or use the whole config rather than one mapping.
+fn load_expanded_config(flags: &GlobalFlags) -> Result<config::SemanticConfig, SemanticError> {
+    let mut config = load_config(flags)?;
+    config.expand_env_vars();
+    Ok(config)
+}
+
 /// Load the user's config, detect their shell, and print init code.
-fn cmd_init() {
-    let config = match config::SemanticConfig::load() {
-        Ok(c) => c,
+/// The code is guarded so evaluating it twice in one shell is harmless;
+/// --force leaves the guard out, to re-apply it after editing the config.
+/// --install instead adds the line that loads it to the rc files (see `install_init`).
+/// Called as: semantic init [--force] [shell] | --install [--dry-run] [shell]
+/// An explicit shell must be one of the enabled shells in config.
+fn cmd_init(args: &[String], flags: &GlobalFlags) {
+    let mut config = match load_config_with_source(flags) {
+        Ok((c, source)) => {
+            if flags.verbosity > 0 {
+                print_config_source(&source);
+            }
+            c
+        }
         Err(e) => {
             eprintln!("Failed to load config: {e}");
             eprintln!("Run `semantic` (no args) to set up your config first.");
+            exit(e.exit_code());
+        }
+    };
+    config.expand_env_vars();
+
+    if args.iter().any(|a| a == "--install") {
+        install_init(args, &config, flags);
+        return;
+    }
+
+    let force = args.iter().any(|a| a == "--force");
+    if args.iter().any(|a| a == "--all") {
+        print!("{}", polyglot_init_code(&config, !force, flags));
+        return;
+    }
+
+    // an explicit shell wins, then the shell we're actually running in (if enabled),
+    // then the configured default, and only then the login shell from $SHELL
+    let requested = requested_shell(args);
+    let detected = shell::detect_shell_with_source();
+    let (shell, why) = match (requested, &detected) {
+        (Some(requested), _) => {
+            if !config.shells.enabled.contains(requested) {
+                eprintln!("Shell `{requested}` is not enabled in your config.");
+                eprintln!("Enabled shells: {}", config.shells.enabled.join(", "));
+                exit(1);
+            }
+            (requested, "as asked")
+        }
+        (None, Some((running, source)))
+            if matches!(source, shell::ShellSource::Override | shell::ShellSource::ParentProcess)
+                && config.shells.enabled.contains(running) =>
+        {
+            (running, source.describe())
+        }
+        (None, _) if !config.shells.default.is_empty() => {
+            (&config.shells.default, "the configured default")
+        }
+        (None, Some((login, source))) => (login, source.describe()),
+        (None, None) => {
+            eprintln!("Couldn't tell which shell this is; run `semantic init <shell>`.");
             exit(1);
         }
     };
 
-    let detected_shell = shell::detect_shell();
+    log::debug!("init for {shell}, {why}");
+    // warn, but still print everything: a missing program may be installed later
+    if !flags.quiet {
+        for (name, program) in shell::missing_programs(&config.commands_for(shell)) {
+            eprintln!("semantic: warning: `{name}` runs `{program}`, which isn't in $PATH");
+        }
+        for (name, hidden) in config.shadowed_commands(shell) {
+            eprintln!(
+                "semantic: warning: `{name}` hides {hidden} (list it in [safety] allow_shadowing \
+                 if that's intended)"
+            );
+        }
+    }
 
-    // use the configured default shell if set, otherwise use the detected one
-    let shell = if config.shells.default.is_empty() {
-        &detected_shell
-    } else {
-        &config.shells.default
+    match init_code(&config, shell, !force, flags) {
+        Ok(code) => print!("{code}"),
+        Err(e) => {
+            eprintln!("{e}");
+            exit(e.exit_code());
+        }
+    }
+}
+
+/// The shell named by `--shell <name>` or as a plain argument, if any.
+fn requested_shell(args: &[String]) -> Option<&String> {
+    match args.iter().position(|a| a == "--shell") {
+        Some(pos) => Some(args.get(pos + 1).unwrap_or_else(|| {
+            eprintln!("--shell needs a value");
+            exit(1);
+        })),
+        None => args.iter().find(|a| !a.starts_with("--")),
+    }
+}
+
+/// What `semantic init --all` prints: init code for every enabled shell that
+/// can share one snippet, each run only by its own shell
+/// (see `shell::generate_init_polyglot`).
+fn polyglot_init_code(config: &config::SemanticConfig, guard: bool, flags: &GlobalFlags) -> String {
+    let enabled = &config.shells.enabled;
+    let left_out: Vec<&str> = enabled
+        .iter()
+        .map(String::as_str)
+        .filter(|shell| !matches!(*shell, "fish" | "zsh" | "bash"))
+        .collect();
+    if !left_out.is_empty() && !flags.quiet {
+        eprintln!(
+            "semantic: warning: --all covers fish, zsh and bash; run `semantic init <shell>` for {}",
+            left_out.join(", ")
+        );
+    }
+
+    let commands = enabled.iter().map(|shell| (shell.clone(), config.commands_for(shell))).collect();
+    let mut output = String::new();
+    // every branch calls back into semantic later, so they all need telling
+    if let Some(profile) = &flags.profile {
+        output.push_str(&shell::generate_env_export("sh", "SEMANTIC_PROFILE", profile));
+    }
+    output.push_str(&shell::generate_init_polyglot(
+        &commands,
+        &config.composite_commands,
+        &config.paths,
+        enabled,
+        &shell::InitOptions {
+            guard,
+            mode: config.integration_mode(),
+            shell_mode: &config.shell_mode_commands,
+            bin_dir: config.bin_dir().as_deref(),
+        },
+    ));
+    output
+}
+
+/// What `semantic init` prints for `shell` (see `shell::generate_init`).
+fn init_code(
+    config: &config::SemanticConfig,
+    shell: &str,
+    guard: bool,
+    flags: &GlobalFlags,
+) -> Result<String, SemanticError> {
+    let mut output = String::new();
+    // the functions call back into semantic later, so they need telling too
+    if let Some(profile) = &flags.profile {
+        output.push_str(&shell::generate_env_export(shell, "SEMANTIC_PROFILE", profile));
+    }
+    output.push_str(&shell::generate_init(
+        &config.commands_for(shell),
+        &config.composite_commands,
+        &config.paths,
+        shell,
+        &shell::InitOptions {
+            guard,
+            mode: config.integration_mode(),
+            shell_mode: &config.shell_mode_commands,
+            bin_dir: config.bin_dir().as_deref(),
+        },
+    )?);
+    Ok(output)
+}
+
+/// Add a marked block that loads semantic to the rc file of each enabled shell
+/// (or only the one given), never twice, and list the files it changed.
+/// --inline writes the init code itself instead, replacing the block each time
+/// (see `shell::install_init_block`), for rc files that shouldn't run semantic.
+/// With --dry-run, list the files it would change instead.
+/// Called as: semantic init --install [--inline] [--dry-run] [shell]
+fn install_init(args: &[String], config: &config::SemanticConfig, flags: &GlobalFlags) {
+    let dry_run = args.iter().any(|a| a == "--dry-run");
+    let inline = args.iter().any(|a| a == "--inline");
+    let shells: Vec<&String> = match requested_shell(args) {
+        Some(requested) if !config.shells.enabled.contains(requested) => {
+            eprintln!("Shell `{requested}` is not enabled in your config.");
+            eprintln!("Enabled shells: {}", config.shells.enabled.join(", "));
+            exit(1);
+        }
+        Some(requested) => vec![requested],
+        None => config.shells.enabled.iter().collect(),
     };
 
-    let output = shell::generate_init(&config.commands, &config.paths, shell);
-    print!("{output}");
+    let mut changed = Vec::new();
+    let mut unchanged = Vec::new();
+    let mut failed = Vec::new();
+    for shell in shells {
+        let installed = if inline {
+            init_code(config, shell, true, flags)
+                .map_err(Into::into)
+                .and_then(|code| shell::install_init_block(shell, &code, dry_run))
+        } else {
+            shell::install_rc_block(shell, dry_run)
+        };
+        match installed {
+            Ok(Some(path)) => changed.push(format!("{} ({shell})", path.display())),
+            Ok(None) if inline => unchanged.push(format!("{shell}: its rc file has this code already")),
+            Ok(None) => unchanged.push(format!("{shell}: its rc file already loads semantic")),
+            Err(e) => failed.push(format!("{shell}: {e}")),
+        }
+    }
+
+    print_list(if dry_run { "Would add semantic to" } else { "Added semantic to" }, &changed);
+    print_list("Left as they are", &unchanged);
+    if !failed.is_empty() {
+        print_list("Failed", &failed);
+        exit(1);
+    }
+    if changed.is_empty() {
+        println!("Nothing to change.");
+    } else if !dry_run {
+        println!("Open a new shell to start using semantic.");
+    }
 }
 
-/// Look up a semantic command in config and execute the real command.
-/// Called as: semantic translate <semantic_cmd> [args...]
-fn cmd_translate(args: &[String]) {
-    if args.is_empty() {
-        eprintln!("Usage: semantic translate <command> [args...]");
+/// Print a definition of one mapping for the current shell session only, to eval:
+/// `eval "$(semantic alias deploy 'ssh user@prod')"`. The config is neither read
+/// nor written, so translate, list and other shells never hear of it.
+/// The shell is the one given with --shell, else the one we're running in.
+/// Called as: semantic alias [--shell <shell>] <name> <real command...>
+fn cmd_alias(args: &[String]) {
+    const USAGE: &str = "Usage: semantic alias [--shell <shell>] <name> <real command...>";
+    let (requested, args) = match args {
+        [flag, shell, rest @ ..] if flag == "--shell" => (Some(shell), rest),
+        [flag] if flag == "--shell" => {
+            eprintln!("--shell needs a value");
+            eprintln!("{USAGE}");
+            exit(1);
+        }
+        _ => (None, args),
+    };
+    let [name, real @ ..] = args else {
+        eprintln!("{USAGE}");
+        exit(1);
+    };
+    let real_cmd = real.join(" ");
+    if real_cmd.trim().is_empty() {
+        eprintln!("{USAGE}");
+        exit(1);
+    }
+    if name.starts_with('-')
+        || !name.chars().all(|c| {
+            c.is_alphanumeric() || matches!(c, '-' | '_' | '.') || !(c.is_ascii() || c.is_whitespace())
+        })
+    {
+        eprintln!("`{name}` can't be a command name; use letters, digits, emoji, -, _ and .");
         exit(1);
     }
 
-    let config = match config::SemanticConfig::load() {
+    let shell = match requested {
+        Some(requested) => {
+            let shell = shell::normalize_shell_name(requested);
+            if !shell::KNOWN_SHELLS.contains(&shell.as_str()) {
+                eprintln!("Unknown shell `{requested}`.");
+                eprintln!("Known shells: {}", shell::KNOWN_SHELLS.join(", "));
+                exit(1);
+            }
+            shell
+        }
+        None => shell::detect_shell().unwrap_or_else(|| {
+            eprintln!("Couldn't tell which shell this is; pass --shell <shell>.");
+            exit(1);
+        }),
+    };
+
+    print!("{}", shell::generate_session_alias(name, real_cmd.trim(), &shell));
+}
+
+/// Write every mapping as a standalone script of plain aliases and functions
+/// (see `shell::generate_alias_file`), for machines where semantic itself won't
+/// be around to run them: containers, minimal images and the like.
+/// The shell is the one given with --shell, else the one we're running in if
+/// it can be written for, else sh. Prints the script unless --output names a file.
+/// Called as: semantic alias-file [--output <file>] [--shell <name>]
+fn cmd_alias_file(args: &[String], flags: &GlobalFlags) {
+    const USAGE: &str = "Usage: semantic alias-file [--output <file>] [--shell <name>]";
+    let output = flag_value(args, "--output", USAGE);
+    let shell = match flag_value(args, "--shell", USAGE) {
+        Some(requested) => {
+            let shell = shell::normalize_shell_name(requested);
+            if !shell::ALIAS_FILE_SHELLS.contains(&shell.as_str()) {
+                eprintln!("alias-file can't write for `{requested}`.");
+                eprintln!("Shells it can: {}", shell::ALIAS_FILE_SHELLS.join(", "));
+                exit(1);
+            }
+            shell
+        }
+        None => shell::detect_shell()
+            .filter(|shell| shell::ALIAS_FILE_SHELLS.contains(&shell.as_str()))
+            .unwrap_or_else(|| "sh".to_string()),
+    };
+
+    let config = match load_expanded_config(flags) {
         Ok(c) => c,
         Err(e) => {
             eprintln!("Failed to load config: {e}");
+            exit(e.exit_code());
+        }
+    };
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let header = format!(
+        "# Generated by `semantic alias-file` from {}\n\
+         # on {} UTC. Nothing here calls semantic, so run it again after editing the config.\n\
+         # Load it with `source` (or `.`) from your shell's rc file.\n",
+        config::SemanticConfig::config_path().display(),
+        history::format_timestamp(now)
+    );
+    let script = shell::generate_alias_file(
+        &config.commands_for(&shell),
+        &config.composite_commands,
+        &config.paths,
+        &config.shell_mode_commands,
+        &shell,
+        &header,
+    );
+
+    match output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, script) {
+                eprintln!("Failed to write {path}: {e}");
+                exit(1);
+            }
+            println!("Wrote {shell} definitions for your commands to {path}.");
+        }
+        None => print!("{script}"),
+    }
+}
+
+/// Print the most recent history entries, or per-command usage counts with --stats.
+/// Called as: semantic history [-n <count>] [--stats]
+fn cmd_history(args: &[String]) {
+    const USAGE: &str = "Usage: semantic history [-n <count>] [--stats]";
+    let stats = args.iter().any(|a| a == "--stats");
+    let count = match flag_value(args, "-n", USAGE).map(str::parse::<usize>) {
+        None => 20,
+        Some(Ok(n)) => n,
+        Some(Err(_)) => {
+            eprintln!("{USAGE}");
             exit(1);
         }
     };
 
-    let semantic_cmd = &args[0];
-    let extra_args = &args[1..];
+    let entries = match history::read() {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Failed to read {}: {e}", history::history_path().display());
+            exit(1);
+        }
+    };
 
-    // look up the semantic command in the config
-    let real_cmd = match config.commands.get(semantic_cmd.as_str()) {
-        Some(cmd) => cmd,
-        None => {
-            eprintln!("Unknown semantic command: {semantic_cmd}");
+    if entries.is_empty() {
+        println!("No history yet. Set `history = true` under [general] in your config to record it.");
+        return;
+    }
+
+    if stats {
+        let usage = history::stats(&entries);
+        let width = usage.iter().map(|u| display_width(u.name)).max().unwrap_or(0);
+        for u in usage {
+            println!("  {}  {}", pad(u.name, width), u.runs);
+        }
+        return;
+    }
+
+    let recent = &entries[entries.len().saturating_sub(count)..];
+    let width = recent.iter().map(|e| display_width(&e.name)).max().unwrap_or(0);
+    for entry in recent {
+        println!(
+            "{}  {}  {}  (exit {}, {}ms)",
+            history::format_timestamp(entry.timestamp),
+            pad(&entry.name, width),
+            entry.resolved.join(" "),
+            entry.exit_code,
+            entry.duration_ms
+        );
+    }
+}
+
+/// Print how each semantic command has been used, most used first: run count,
+/// success rate and when it last ran, then the total runs and how many failed.
+/// Reads the same log as `semantic history`. --since takes a duration back from
+/// now or a date (UTC); --top only lists that many commands (the total is of all).
+/// Called as: semantic stats [--since <duration|date>] [--top <n>] [--reset]
+fn cmd_stats(args: &[String]) {
+    const USAGE: &str = "Usage: semantic stats [--since <duration|date>] [--top <n>] [--reset]";
+
+    if args.iter().any(|a| a == "--reset") {
+        if !confirm(&format!("Clear {}?", history::history_path().display())) {
+            println!("Nothing cleared.");
+            return;
+        }
+        if let Err(e) = history::clear() {
+            eprintln!("Failed to clear history: {e}");
+            exit(1);
+        }
+        println!("Usage history cleared.");
+        return;
+    }
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let cutoff = flag_value(args, "--since", USAGE).map(|s| {
+        since_cutoff(s, now).unwrap_or_else(|| {
+            eprintln!("Invalid --since `{s}` (expected e.g. 30d, 12h, 45m or a date like 2026-03-01)");
+            exit(1);
+        })
+    });
+    let top = match flag_value(args, "--top", USAGE).map(str::parse::<usize>) {
+        None => None,
+        Some(Ok(n)) if n > 0 => Some(n),
+        Some(_) => {
+            eprintln!("--top needs a number of at least 1");
             exit(1);
         }
     };
 
-    // the real command might have multiple parts (e.g. "sudo pacman -S")
-    let parts: Vec<&str> = real_cmd.split_whitespace().collect();
-    let (program, builtin_args) = parts.split_first().expect("empty command mapping");
+    let mut entries = match history::read() {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Failed to read {}: {e}", history::history_path().display());
+            exit(1);
+        }
+    };
+    if let Some(cutoff) = cutoff {
+        entries.retain(|entry| entry.timestamp >= cutoff);
+    }
 
-    // translate any path arguments (e.g. /apps -> /usr/bin)
-    let translated_args: Vec<String> = extra_args
+    if entries.is_empty() && cutoff.is_some() {
+        println!("No history since then.");
+        return;
+    }
+    if entries.is_empty() {
+        println!("No history yet. Set `history = true` under [general] in your config to record it.");
+        return;
+    }
+
+    print!("{}", stats_report(&entries, top));
+}
+
+/// The unix timestamp `--since` means at `now`: a duration back from it, or the
+/// midnight a date starts at. `None` for anything else.
+fn since_cutoff(since: &str, now: std::time::Duration) -> Option<u64> {
+    history::parse_duration(since)
+        .map(|ago| now.saturating_sub(ago).as_secs())
+        .or_else(|| history::parse_date(since))
+}
+
+/// The `semantic stats` table over `entries` (at least one), showing the `top`
+/// most used commands or all of them, and a total line with the failures.
+fn stats_report(entries: &[history::Entry], top: Option<usize>) -> String {
+    use std::fmt::Write;
+
+    let usage = history::stats(entries);
+    let commands = usage.len();
+    let shown = &usage[..top.unwrap_or(commands).min(commands)];
+    let width = shown.iter().map(|u| display_width(u.name)).max().unwrap_or(0).max("COMMAND".len());
+    let mut out = format!("{:<width$}  {:>5}  {:>7}  LAST USED\n", "COMMAND", "RUNS", "SUCCESS");
+    for u in shown {
+        let success = u.successes * 100 / u.runs;
+        let _ = writeln!(
+            out,
+            "{}  {:>5}  {:>6}%  {}",
+            pad(u.name, width),
+            u.runs,
+            success,
+            history::format_timestamp(u.last_used)
+        );
+    }
+
+    let runs = entries.len();
+    let failures = entries.iter().filter(|entry| entry.exit_code != 0).count();
+    let _ = write!(out, "\nTotal runs: {runs}, failed: {failures} ({}%)\n", failures * 100 / runs);
+    out
+}
+
+/// Reverse the last command in the history log, where that's possible: a move
+/// is moved back, what a copy or mkdir made is removed. Shows the commands first
+/// and asks before running them. Refuses commands that failed, ones whose
+/// effects can't be taken back (rm), and programs it doesn't know.
+/// Called as: semantic undo [--yes] [--dry-run]
+fn cmd_undo(args: &[String]) {
+    let yes = args.iter().any(|a| a == "--yes" || a == "-y");
+    let dry_run = args.iter().any(|a| a == "--dry-run");
+
+    let entries = match history::read() {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Failed to read {}: {e}", history::history_path().display());
+            exit(1);
+        }
+    };
+    let Some(entry) = entries.last() else {
+        eprintln!("Nothing to undo: there's no history yet. Set `history = true` under [general] to record it.");
+        exit(1);
+    };
+
+    let command = entry.resolved.join(" ");
+    println!(
+        "Last command: {} ({command}), {}",
+        entry.name,
+        history::format_timestamp(entry.timestamp)
+    );
+    if entry.exit_code != 0 {
+        eprintln!(
+            "Can't undo it: it failed (exit {}), so what it changed is anyone's guess.",
+            entry.exit_code
+        );
+        exit(1);
+    }
+
+    let cwd = match (&entry.cwd, env::current_dir()) {
+        (Some(cwd), _) => cwd.clone(),
+        (None, Ok(here)) => {
+            println!("(Its directory wasn't recorded; assuming {}.)", here.display());
+            here
+        }
+        (None, Err(e)) => {
+            eprintln!("Can't undo it: its directory wasn't recorded and the current one is unreadable: {e}");
+            exit(1);
+        }
+    };
+    let inverse = match undo::inverse(&entry.resolved, &cwd) {
+        Ok(inverse) => inverse,
+        Err(reason) => {
+            eprintln!("Can't undo it: {reason}.");
+            exit(1);
+        }
+    };
+
+    println!("To undo it, semantic would run:");
+    for step in &inverse.steps {
+        println!("  {}", step.join(" "));
+    }
+    if let Some(warning) = inverse.warning {
+        println!("Note: {warning}.");
+    }
+    if dry_run || (!yes && !confirm("Run it?")) {
+        println!("Nothing undone.");
+        return;
+    }
+
+    for step in &inverse.steps {
+        let status = Command::new(&step[0])
+            .args(&step[1..])
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .status();
+        let status = match status {
+            Ok(status) => status,
+            Err(source) => {
+                let e = SemanticError::ExecFailed { program: step[0].clone(), source };
+                eprintln!("{e}");
+                exit(e.exit_code());
+            }
+        };
+        if !status.success() {
+            eprintln!("`{}` failed; stopped there.", step.join(" "));
+            exit(exit_code(status));
+        }
+    }
+    println!("Undone.");
+}
+
+/// Undo semantic's setup: cut the marked semantic block out of each shell rc
+/// file, and with --purge delete the config directory, history and cache as well.
+/// Shows the plan and asks first unless --yes. rc files that load semantic
+/// outside a marked block are reported but never edited. Nothing here needs root.
+/// Called as: semantic uninstall [--purge] [--yes] [--dry-run]
+fn cmd_uninstall(args: &[String]) {
+    let purge = args.iter().any(|a| a == "--purge");
+    let yes = args.iter().any(|a| a == "--yes");
+    let dry_run = args.iter().any(|a| a == "--dry-run");
+
+    // with SEMANTIC_CONFIG the "config dir" is wherever that file lives
+    // (often a dotfiles repo), which is not ours to delete
+    if purge && env::var_os("SEMANTIC_CONFIG").is_some_and(|v| !v.is_empty()) {
+        eprintln!(
+            "SEMANTIC_CONFIG is set; remove {} yourself if you no longer need it.",
+            config::SemanticConfig::config_path().display()
+        );
+        exit(1);
+    }
+
+    // every shell we know, not just the enabled ones — a block may outlive its config
+    let mut edits = Vec::new(); // (rc file, contents without the block)
+    let mut left = Vec::new(); // what stays, and why
+    let mut seen = Vec::new();
+    // fish's block is in conf.d/semantic.fish, but an older one may be in config.fish;
+    // nu and PowerShell may report a config file other than the usual one
+    let rc_files = shell::KNOWN_SHELLS.iter().flat_map(|&name| {
+        [shell::rc_path(name), shell::rc_file_for(name), shell::install_path(name)].map(|path| (name, path))
+    });
+    for (name, path) in rc_files {
+        let Some(path) = path else { continue };
+        // pwsh and powershell share a profile outside Windows
+        if seen.contains(&path) {
+            continue;
+        }
+        seen.push(path.clone());
+        let Ok(content) = std::fs::read_to_string(&path) else { continue };
+        match shell::strip_rc_block(&content) {
+            Some(stripped) => edits.push((path, stripped)),
+            None => {
+                if let Some((_, line)) = shell::rc_setup(name)
+                    && content.contains(line)
+                {
+                    left.push(format!(
+                        "{}: `{line}` isn't inside a semantic block, remove it yourself",
+                        path.display()
+                    ));
+                }
+            }
+        }
+    }
+
+    if let Ok(manifest) = link::Manifest::load()
+        && !manifest.links.is_empty()
+    {
+        let count = manifest.links.len();
+        left.push(format!(
+            "{count} {} from `semantic link` (`semantic link --remove` deletes them)",
+            if count == 1 { "symlink" } else { "symlinks" }
+        ));
+    }
+
+    // history lives in the state dir, not next to the config
+    let config_dir = config::config_dir();
+    let history_dir = history::history_path().parent().map(Path::to_path_buf);
+    let cache_dir = Some(config::disk_cache_dir());
+    let dirs: Vec<_> = std::iter::once(config_dir)
+        .chain(history_dir)
+        .chain(cache_dir)
+        .filter(|dir| dir.exists())
+        .collect();
+    let (purged, kept) = if purge { (dirs, Vec::new()) } else { (Vec::new(), dirs) };
+    for dir in kept {
+        left.push(format!("{} (--purge deletes it)", dir.display()));
+    }
+
+    if edits.is_empty() && purged.is_empty() {
+        println!("Nothing to remove.");
+        print_list("Left in place", &left);
+        return;
+    }
+
+    let mut planned: Vec<String> = edits
         .iter()
-        .map(|arg| {
-            config.paths.get(arg.as_str())
-                .cloned()
-                .unwrap_or_else(|| arg.clone())
-        })
+        .map(|(path, _)| format!("semantic block in {}", path.display()))
+        .collect();
+    planned.extend(purged.iter().map(|dir| dir.display().to_string()));
+
+    print_list(if dry_run { "Would remove" } else { "This will remove" }, &planned);
+    print_list("Left in place", &left);
+    if dry_run {
+        return;
+    }
+
+    if !yes && !confirm("Continue?") {
+        println!("Nothing was removed.");
+        return;
+    }
+
+    let mut removed = Vec::new();
+    let mut failed = Vec::new();
+    for ((path, content), what) in edits.iter().zip(&planned) {
+        match std::fs::write(path, content) {
+            Ok(()) => removed.push(what.clone()),
+            Err(e) => failed.push(format!("{}: {e}", path.display())),
+        }
+    }
+    for dir in &purged {
+        match std::fs::remove_dir_all(dir) {
+            Ok(()) => removed.push(dir.display().to_string()),
+            Err(e) => failed.push(format!("{}: {e}", dir.display())),
+        }
+    }
+
+    print_list("Removed", &removed);
+    print_list("Left in place", &left);
+    if !failed.is_empty() {
+        print_list("Failed", &failed);
+        exit(1);
+    }
+    println!("Open a new shell for the change to take effect.");
+}
+
+/// Replace every command and path mapping with the stock ones for the current
+/// `command_style`/`folder_style`, with package commands for the detected
+/// package manager. `[general]`, `[shells]` and the rest of the settings stay.
+/// Mappings that would be lost are listed first, and the old file is copied to
+/// <file>.bak before saving. Asks first unless --yes.
+/// Called as: semantic reset [--yes]
+fn cmd_reset(args: &[String]) {
+    let yes = args.iter().any(|a| a == "--yes");
+
+    let mut file = match config::ConfigFile::load() {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Failed to load config: {e}");
+            exit(e.exit_code());
+        }
+    };
+
+    let general = &file.config.general;
+    let fresh = stock_config(&file.config, &general.command_style, &general.folder_style);
+    let language = general.language.as_deref().unwrap_or("en");
+    let templates = config::templates_dir();
+    let (command_style, folder_style) = (&general.command_style, &general.folder_style);
+    for drift in config::template_drift(&templates, command_style, folder_style, language) {
+        eprintln!("semantic: warning: {drift}");
+    }
+
+    let fresh_mappings = fresh.mappings();
+    let old_mappings = file.config.mappings();
+    if old_mappings == fresh_mappings {
+        println!("Your mappings are already the defaults for your styles.");
+        return;
+    }
+    let lost: Vec<String> = old_mappings
+        .iter()
+        .filter(|(key, value)| fresh_mappings.get(key) != Some(value))
+        .map(|(key, value)| format!("{key} → {value}"))
         .collect();
 
-    // combine: program + builtin args from mapping + user's extra args
-    let status = Command::new(program)
-        .args(builtin_args)
-        .args(&translated_args)
-        .status();
+    println!(
+        "This resets [commands] and [paths] to the {} command style and {} folder style.",
+        general.command_style, general.folder_style
+    );
+    println!("Your other settings, [shells] included, stay as they are.");
+    println!();
+    print_list("These mappings will be lost (copy any you want to keep)", &lost);
+
+    if !yes && !confirm("Continue?") {
+        println!("Nothing was changed.");
+        return;
+    }
+
+    let path = file.path();
+    let mut backup = path.clone().into_os_string();
+    backup.push(".bak");
+    let backup = Path::new(&backup);
+    if let Err(e) = std::fs::copy(&path, backup) {
+        eprintln!("Failed to back up {} to {}: {e}", path.display(), backup.display());
+        exit(1);
+    }
 
-    match status {
-        Ok(s) => exit(s.code().unwrap_or(1)),
+    file.config = fresh;
+    match file.save() {
+        Ok(path) => {
+            println!("Reset {} (the old one is in {}).", path.display(), backup.display());
+            println!("Open a new shell (or re-run `semantic init`) to pick up the new mappings.");
+        }
         Err(e) => {
-            eprintln!("Failed to run `{real_cmd}`: {e}");
+            eprintln!("Failed to save config: {e}");
             exit(1);
         }
     }
 }
+
+/// `config` with its mappings replaced by the stock ones for `command_style`
+/// and `folder_style` (package commands for the detected package manager, the
+/// user's `[styles]` on top), as `semantic reset` leaves it. Settings are kept.
+fn stock_config(
+    config: &config::SemanticConfig,
+    command_style: &str,
+    folder_style: &str,
+) -> config::SemanticConfig {
+    let language = config.general.language.as_deref().unwrap_or("en");
+    let mut commands = config::style_commands(command_style, language);
+    use_detected_package_manager(&mut commands);
+    // the user's own [styles.commands.<style>] beat the detected package manager
+    commands.extend(config.styles.own_commands(command_style));
+    config::SemanticConfig {
+        commands,
+        shell_commands: Default::default(),
+        composite_commands: Default::default(),
+        shell_mode_commands: Default::default(),
+        untranslated_commands: Default::default(),
+        paths: config.styles.paths(folder_style, language),
+        ..config.clone()
+    }
+}
+
+/// Show how the user config's mappings differ from the stock ones for its
+/// styles: what the user added, stock mappings they removed, and ones they
+/// changed (`commands.list: ls -la → eza -la --git`, stock value first).
+/// With --style, show instead what switching both styles to <style> in the
+/// wizard would do to the mappings, customizations carried over as it does.
+/// Exits 1 when there are differences, so it can be a drift check in scripts,
+/// and 2 when the config can't be loaded.
+/// Called as: semantic diff [--commands | --paths] [--style <style>] [--json]
+fn cmd_diff(args: &[String], flags: &GlobalFlags) {
+    const USAGE: &str = "Usage: semantic diff [--commands | --paths] [--style <style>] [--json]";
+    let json = flags.json || args.iter().any(|a| a == "--json");
+    let only_commands = args.iter().any(|a| a == "--commands");
+    let only_paths = args.iter().any(|a| a == "--paths");
+    let style = flag_value(args, "--style", USAGE);
+    if only_commands && only_paths {
+        eprintln!("--commands and --paths can't be used together");
+        eprintln!("{USAGE}");
+        exit(1);
+    }
+
+    let file = match config::ConfigFile::load() {
+        Ok(file) => file,
+        Err(e) => {
+            print_error(json, &format!("Failed to load config: {e}"), None);
+            exit(e.exit_code());
+        }
+    };
+    let live = &file.config;
+    let general = &live.general;
+
+    let (old, new) = match style {
+        None => (stock_config(live, &general.command_style, &general.folder_style), live.clone()),
+        Some(style) => {
+            let mut switched = stock_config(live, style, style);
+            config::Customizations::of(live).apply_to(&mut switched);
+            (live.clone(), switched)
+        }
+    };
+    let in_scope = |key: &config::MappingKey| match key {
+        config::MappingKey::Path(_) => !only_commands,
+        config::MappingKey::Command(_) | config::MappingKey::ShellCommand(..) => !only_paths,
+    };
+    let scoped = |config: config::SemanticConfig| -> std::collections::BTreeMap<_, _> {
+        config.mappings().into_iter().filter(|(key, _)| in_scope(key)).collect()
+    };
+    let old = scoped(old);
+    let mut new = scoped(new);
+
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut changed = Vec::new();
+    for (key, old_value) in old {
+        match new.remove(&key) {
+            Some(new_value) if new_value == old_value => {}
+            Some(new_value) => changed.push((key, old_value, new_value)),
+            None => removed.push((key, old_value)),
+        }
+    }
+    added.extend(new);
+    let differs = !(added.is_empty() && removed.is_empty() && changed.is_empty());
+
+    if json {
+        let values = |entries: &[(config::MappingKey, String)]| {
+            entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.as_str().into()))
+                .collect::<serde_json::Map<_, _>>()
+        };
+        let changed: serde_json::Map<_, _> = changed
+            .iter()
+            .map(|(key, old, new)| (key.to_string(), serde_json::json!({ "old": old, "new": new })))
+            .collect();
+        print_json(&serde_json::json!({
+            "command_style": style.unwrap_or(&general.command_style),
+            "folder_style": style.unwrap_or(&general.folder_style),
+            "added": values(&added),
+            "removed": values(&removed),
+            "changed": changed,
+        }));
+        exit(i32::from(differs));
+    }
+
+    let added: Vec<String> = added.iter().map(|(key, value)| format!("{key}: {value}")).collect();
+    let removed: Vec<String> = removed.iter().map(|(key, value)| format!("{key}: {value}")).collect();
+    let changed: Vec<String> =
+        changed.iter().map(|(key, old, new)| format!("{key}: {old} → {new}")).collect();
+    match style {
+        None if !differs => println!(
+            "Your mappings are the stock ones for the {} command style and {} folder style.",
+            general.command_style, general.folder_style
+        ),
+        None => {
+            println!(
+                "Compared with the stock {} command style and {} folder style:",
+                general.command_style, general.folder_style
+            );
+            println!();
+            print_list("Added by you", &added);
+            print_list("Stock, but removed from your config", &removed);
+            print_list("Changed from stock", &changed);
+        }
+        Some(style) if !differs => println!("Switching to the {style} style wouldn't change your mappings."),
+        Some(style) => {
+            println!("Switching both styles to {style} (your own mappings kept):");
+            println!();
+            print_list("Would be added", &added);
+            print_list("Would be removed", &removed);
+            print_list("Would change", &changed);
+        }
+    }
+    if differs {
+        exit(1);
+    }
+}
+
+/// Bring the user config up to the current schema version (see `config::migrate`)
+/// and write it back, after backing it up to <file>.bak like `reset` does.
+/// Doesn't ask: there's nothing to choose, and it's meant to run after an update.
+/// A .bak newer than the config suggests an earlier upgrade stopped halfway,
+/// so that's pointed out before it's overwritten.
+/// Called as: semantic upgrade
+fn cmd_upgrade() {
+    let mut file = match config::ConfigFile::load() {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Failed to load config: {e}");
+            exit(e.exit_code());
+        }
+    };
+    if file.config.version == config::CURRENT_SCHEMA_VERSION {
+        println!("Config is up to date (version {}).", file.config.version);
+        return;
+    }
+
+    let old = file.config.clone();
+    if let Err(e) = config::migrate(&mut file.config) {
+        eprintln!("Can't upgrade {}: {e}", file.path().display());
+        exit(1);
+    }
+    println!(
+        "Upgrading {} from version {} to {}.",
+        file.path().display(),
+        old.version,
+        file.config.version
+    );
+    print_list("Changes to your config", &diff_changes(&old, &file.config));
+
+    let path = file.path();
+    let mut backup = path.clone().into_os_string();
+    backup.push(".bak");
+    let backup = Path::new(&backup);
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    if let (Some(backed_up), Some(written)) = (modified(backup), modified(&path))
+        && backed_up > written
+    {
+        eprintln!(
+            "semantic: warning: {} is newer than the config, so a previous upgrade may have \
+             failed; replacing it with a fresh backup",
+            backup.display()
+        );
+    }
+    if let Err(e) = std::fs::copy(&path, backup) {
+        eprintln!("Failed to back up {} to {}: {e}", path.display(), backup.display());
+        exit(1);
+    }
+
+    match file.save() {
+        Ok(path) => println!("Upgraded {} (the old one is in {}).", path.display(), backup.display()),
+        Err(e) => {
+            eprintln!("Failed to save config: {e}");
+            exit(1);
+        }
+    }
+}
+
+/// Manage named profiles: whole configs in profiles/<name>.toml, of which the
+/// active one is used wherever the user config would be ("default" is the plain
+/// config file). `create` starts one from the wizard's defaults, or with --copy
+/// from the active config; `use` switches to one; `delete` removes one that
+/// isn't active. `save` copies the active config to a profile and `load` copies
+/// one back over it, backing it up to <file>.bak first like `reset` does.
+/// Called as: semantic profile list | create <name> [--copy] | use <name>
+///            | delete <name> | save <name> | load <name>
+fn cmd_profile(args: &[String]) {
+    const USAGE: &str = "Usage: semantic profile list | create <name> [--copy] | use <name> \
+                         | delete <name> | save <name> | load <name>";
+    let copy = args.get(2).is_some_and(|a| a == "--copy");
+    let (action, name) = match (args.first().map(String::as_str), args.get(1), args.len()) {
+        (Some("list"), None, 1) => ("list", ""),
+        (Some("create"), Some(name), 2) => ("create", name.as_str()),
+        (Some("create"), Some(name), 3) if copy => ("create", name.as_str()),
+        (Some(action @ ("use" | "delete" | "save" | "load")), Some(name), 2) => (action, name.as_str()),
+        _ => {
+            eprintln!("{USAGE}");
+            exit(1);
+        }
+    };
+    let active = config::active_profile();
+
+    if action == "list" {
+        let names = match config::profile_names() {
+            Ok(names) => names,
+            Err(e) => {
+                eprintln!("Failed to list profiles: {e}");
+                exit(1);
+            }
+        };
+        let lines: Vec<String> = std::iter::once(config::DEFAULT_PROFILE.to_string())
+            .chain(names)
+            .map(|name| if name == active { format!("{name} (active)") } else { name })
+            .collect();
+        print_list("Profiles", &lines);
+        return;
+    }
+
+    if action == "use" && name == config::DEFAULT_PROFILE {
+        match std::fs::remove_file(config::active_profile_file()) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                eprintln!("Failed to switch profiles: {e}");
+                exit(1);
+            }
+            _ => {}
+        }
+        println!("Now using the default profile ({}).", config::SemanticConfig::config_path().display());
+        println!("Open a new shell (or re-run `semantic init`) to pick up its mappings.");
+        return;
+    }
+
+    let profile = match config::profile_path(name) {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("semantic profile: {e}");
+            exit(1);
+        }
+    };
+
+    if action == "create" {
+        if profile.exists() {
+            eprintln!("There's already a profile named `{name}`; `semantic profile use {name}` switches to it.");
+            exit(1);
+        }
+        let config = if copy {
+            match config::ConfigFile::load() {
+                Ok(file) => file.config,
+                Err(e) => {
+                    eprintln!("Failed to load config: {e}");
+                    exit(e.exit_code());
+                }
+            }
+        } else {
+            default_config()
+        };
+        if let Err(e) = config.save_to(&profile) {
+            eprintln!("Failed to create profile `{name}`: {e}");
+            exit(1);
+        }
+        let from = if copy { format!("a copy of `{active}`") } else { "the default settings".to_string() };
+        println!("Created profile `{name}` from {from} ({}).", profile.display());
+        println!("`semantic profile use {name}` switches to it.");
+        return;
+    }
+
+    if matches!(action, "use" | "delete" | "load") && !profile.is_file() {
+        let saved = config::profile_names().unwrap_or_default();
+        eprintln!("No saved profile named `{name}`.");
+        if saved.is_empty() {
+            eprintln!("`semantic profile create {name}` makes one.");
+        } else {
+            eprintln!("Saved profiles: {}", saved.join(", "));
+        }
+        exit(1);
+    }
+
+    if action == "use" {
+        let pointer = config::active_profile_file();
+        let written = pointer
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| std::fs::write(&pointer, format!("{name}\n")));
+        if let Err(e) = written {
+            eprintln!("Failed to switch profiles: {}: {e}", pointer.display());
+            exit(1);
+        }
+        println!("Now using profile `{name}` ({}).", profile.display());
+        if env::var("SEMANTIC_PROFILE").is_ok_and(|p| !p.is_empty() && p != name) {
+            println!("$SEMANTIC_PROFILE is set, so this shell keeps using its own profile.");
+        }
+        println!("Open a new shell (or re-run `semantic init`) to pick up its mappings.");
+        return;
+    }
+
+    if action == "delete" {
+        if name == active {
+            eprintln!("`{name}` is the active profile; `semantic profile use default` first.");
+            exit(1);
+        }
+        if let Err(e) = std::fs::remove_file(&profile) {
+            eprintln!("Failed to delete profile `{name}`: {e}");
+            exit(1);
+        }
+        println!("Deleted profile `{name}`.");
+        return;
+    }
+
+    if action == "save" {
+        let file = match config::ConfigFile::load() {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("Failed to load config: {e}");
+                exit(e.exit_code());
+            }
+        };
+        let replaced = profile.exists();
+        if let Err(e) = file.config.save_to(&profile) {
+            eprintln!("Failed to save profile `{name}`: {e}");
+            exit(1);
+        }
+        let verb = if replaced { "Replaced" } else { "Saved" };
+        println!("{verb} profile `{name}` ({}).", profile.display());
+        return;
+    }
+
+    let loaded = match config::SemanticConfig::load_from(&profile) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to load profile `{name}`: {e}");
+            exit(1);
+        }
+    };
+
+    // keep the format the config is in now, so nothing else moves around
+    let path = config::SemanticConfig::config_path();
+    let file = config::ConfigFile { format: config::ConfigFormat::of(&path), config: loaded };
+    let path = file.path();
+    let mut backup = path.clone().into_os_string();
+    backup.push(".bak");
+    let backup = Path::new(&backup);
+    let backed_up = path.is_file();
+    if backed_up && let Err(e) = std::fs::copy(&path, backup) {
+        eprintln!("Failed to back up {} to {}: {e}", path.display(), backup.display());
+        exit(1);
+    }
+
+    match file.save() {
+        Ok(path) => {
+            if backed_up {
+                println!(
+                    "Loaded profile `{name}` into {} (the old one is in {}).",
+                    path.display(),
+                    backup.display()
+                );
+            } else {
+                println!("Loaded profile `{name}` into {}.", path.display());
+            }
+            println!("Open a new shell (or re-run `semantic init`) to pick up its mappings.");
+        }
+        Err(e) => {
+            eprintln!("Failed to save config: {e}");
+            exit(1);
+        }
+    }
+}
+
+/// Point package commands (install, remove, update) at the package manager
+/// found on this system, if it's one semantic knows.
+fn use_detected_package_manager(commands: &mut std::collections::HashMap<String, String>) {
+    let Some(pm) = config::detect_package_manager() else {
+        return;
+    };
+    for real_cmd in commands.values_mut() {
+        if let Some(replacement) = pm.equivalent_of(real_cmd) {
+            *real_cmd = replacement.to_string();
+        }
+    }
+}
+
+/// Print a titled, indented list, followed by a blank line. Prints nothing when empty.
+fn print_list(title: &str, items: &[String]) {
+    if items.is_empty() {
+        return;
+    }
+    println!("{title}:");
+    for item in items {
+        println!("  {item}");
+    }
+    println!();
+}
+
+/// Run the health checks and print one PASS/WARN/FAIL line per check.
+/// Exits non-zero if any check fails.
+/// Called as: semantic doctor
+fn cmd_doctor(flags: &GlobalFlags) {
+    let checks = doctor::run_all();
+    let failed = checks.iter().any(|c| c.status == doctor::Status::Fail);
+
+    if flags.json {
+        print_json(&serde_json::to_value(&checks).expect("checks always serialize"));
+        exit(if failed { 1 } else { 0 });
+    }
+
+    let name_width = checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+
+    for check in &checks {
+        println!(
+            "{}  {:<name_width$}  {}",
+            check.status.label(),
+            check.name,
+            check.detail
+        );
+    }
+
+    exit(if failed { 1 } else { 0 });
+}
+
+/// Look up a semantic command in config and execute the real command.
+/// With --resolve-path it only translates one path argument the way a command's
+/// arguments are translated, printing it unchanged if no mapping applies. The
+/// init code's cd-like functions use it, since they run the builtin themselves.
+/// Commands joined by `&&`, `||` or `|` in one quoted argument are each
+/// translated and run together through sh; see `translate_chain`. Given as
+/// arguments of their own, the operators are passed on like any other argument.
+/// A `--` ends translate's own options: the words after it are the command and
+/// its args exactly as typed, never read as flags, chains or virtual paths.
+/// Called as: semantic translate [-v|--verbose] [--dry-run] [--] <semantic_cmd> [args...]
+///        or: semantic translate [-v] [--dry-run] --batch [--keep-going] < commands
+///        or: semantic translate [-v] --resolve-path <path>
+/// Verbose and dry-run output goes to stderr so the command's stdout stays pipeable.
+fn cmd_translate(args: &[String], flags: &GlobalFlags) {
+    const USAGE: &str = "Usage: semantic translate [-v|--verbose] [--dry-run] [--timeout <seconds>] \
+                         [--] <command> [args...]\n       \
+                         semantic translate [-v|--verbose] [--dry-run] [--timeout <seconds>] \
+                         --batch [--keep-going]\n       \
+                         semantic translate [-v|--verbose] --resolve-path <path>";
+    let mut options = TranslateOptions {
+        // the global -v means the same here
+        verbose: flags.verbosity > 0,
+        dry_run: false,
+        exec: true,
+        echo: false,
+        json: flags.json,
+        timeout: None,
+        literal_args: false,
+    };
+    let mut batch = false;
+    let mut keep_going = false;
+    let mut resolve_path = None;
+    let mut args = args;
+    while let Some(arg) = args.first() {
+        match arg.as_str() {
+            "-v" | "--verbose" => options.verbose = true,
+            "--dry-run" => options.dry_run = true,
+            "--batch" => batch = true,
+            "--keep-going" => keep_going = true,
+            // the end of translate's own options: the rest is the command, as typed
+            "--" => {
+                options.literal_args = true;
+                args = &args[1..];
+                break;
+            }
+            "--resolve-path" => {
+                let Some(path) = args.get(1) else {
+                    print_error(flags.json, USAGE, None);
+                    exit(1);
+                };
+                resolve_path = Some(path);
+                args = &args[1..];
+            }
+            "--timeout" => {
+                // whole seconds only, so a typo can't silently mean something else
+                match args.get(1).map(|s| s.parse::<u64>()) {
+                    Some(Ok(seconds)) if seconds > 0 => {
+                        options.timeout = Some(Duration::from_secs(seconds));
+                    }
+                    _ => {
+                        print_error(
+                            flags.json,
+                            &format!("--timeout needs a whole number of seconds (at least 1)\n{USAGE}"),
+                            None,
+                        );
+                        exit(1);
+                    }
+                }
+                args = &args[1..];
+            }
+            _ => break,
+        }
+        args = &args[1..];
+    }
+
+    let misused = match resolve_path {
+        Some(_) => batch || !args.is_empty(),
+        None => batch != args.is_empty(),
+    };
+    if misused || (keep_going && !batch) {
+        print_error(flags.json, USAGE, None);
+        exit(1);
+    }
+
+    let config = match load_config_with_source(flags) {
+        Ok((c, source)) => {
+            if options.verbose {
+                print_config_source(&source);
+            }
+            c
+        }
+        Err(e) => {
+            print_error(flags.json, &format!("Failed to load config: {e}"), None);
+            exit(e.exit_code());
+        }
+    };
+
+    if let Some(path) = resolve_path {
+        match config.resolve_arg(path, env::current_dir().ok().as_deref()) {
+            Some(real_path) => {
+                if options.verbose {
+                    eprintln!("semantic: path {path} → {real_path}");
+                }
+                println!("{real_path}");
+            }
+            None => println!("{path}"),
+        }
+        return;
+    }
+    check_translate_depth(flags.json);
+
+    // the generated aliases say which shell they were defined in (via $SEMANTIC_SHELL)
+    let shell = shell::detect_shell().unwrap_or_else(|| config.shells.default.clone());
+
+    if !batch && options.literal_args {
+        exit(translate(&config, &shell, args, &options));
+    }
+    if !batch {
+        exit(match chain_of(args) {
+            Some(Ok((commands, operators))) if commands.len() > 1 => {
+                translate_chain(&config, &shell, &commands, &operators, &options)
+            }
+            Some(Ok((commands, _))) => translate(&config, &shell, &commands[0], &options),
+            Some(Err(e)) => {
+                print_error(flags.json, &format!("`{}` {e}", args.join(" ")), None);
+                1
+            }
+            None => translate(&config, &shell, args, &options),
+        });
+    }
+
+    // later lines still need us, so nothing may exec
+    options.exec = false;
+    exit(run_batch(&config, &shell, io::stdin().lock(), &options, keep_going, 1));
+}
+
+/// Run a list of semantic commands, one per line, like `translate --batch` but
+/// from a file too, and up to `--parallel` of them at once. Each resolved
+/// command is echoed to stderr before it runs.
+/// Called as: semantic translate-batch [--input <file>] [--keep-going] [--parallel <n>] [--dry-run]
+fn cmd_translate_batch(args: &[String], flags: &GlobalFlags) {
+    const USAGE: &str =
+        "Usage: semantic translate-batch [--input <file>] [--keep-going] [--parallel <n>] [--dry-run]";
+    let input = flag_value(args, "--input", USAGE);
+    let parallel = match flag_value(args, "--parallel", USAGE).map(str::parse::<usize>) {
+        None => 1,
+        Some(Ok(n)) if n > 0 => n,
+        Some(_) => {
+            eprintln!("--parallel needs a number of at least 1");
+            exit(1);
+        }
+    };
+    let options = TranslateOptions {
+        verbose: flags.verbosity > 0,
+        dry_run: args.iter().any(|a| a == "--dry-run"),
+        exec: false,
+        echo: true,
+        json: false,
+        timeout: None,
+        literal_args: false,
+    };
+    let keep_going = args.iter().any(|a| a == "--keep-going");
+
+    let config = match load_config(flags) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Failed to load config: {e}");
+            exit(e.exit_code());
+        }
+    };
+    check_translate_depth(flags.json);
+    let shell = shell::detect_shell().unwrap_or_else(|| config.shells.default.clone());
+
+    let code = match input {
+        Some(path) => match std::fs::File::open(path) {
+            Ok(file) => {
+                let reader = io::BufReader::new(file);
+                run_batch(&config, &shell, reader, &options, keep_going, parallel)
+            }
+            Err(e) => {
+                eprintln!("Failed to open {path}: {e}");
+                exit(1);
+            }
+        },
+        None => run_batch(&config, &shell, io::stdin().lock(), &options, keep_going, parallel),
+    };
+    exit(code);
+}
+
+/// Run a semantic script: each command in it in turn, translated like
+/// `semantic translate` does, stopping at the first that fails unless
+/// --keep-going. Exits with the last failing command's code (0 if none failed).
+/// A script that doesn't parse runs nothing.
+/// Called as: semantic run [--keep-going] <script>, or by a script's
+/// `#!/usr/bin/env -S semantic run` line
+fn cmd_run(args: &[String], flags: &GlobalFlags) {
+    const USAGE: &str = "Usage: semantic run [--keep-going] <script>";
+    let keep_going = args.iter().any(|a| a == "--keep-going");
+    let Some(path) = args.iter().find(|a| !a.starts_with("--")) else {
+        eprintln!("{USAGE}");
+        exit(1);
+    };
+
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Failed to read {path}: {e}");
+            exit(1);
+        }
+    };
+    let lines = match script::SemanticScript::parse(&source) {
+        Ok(lines) => lines,
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            exit(1);
+        }
+    };
+
+    let config = match load_config(flags) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Failed to load config: {e}");
+            exit(e.exit_code());
+        }
+    };
+    check_translate_depth(flags.json);
+    let shell = shell::detect_shell().unwrap_or_else(|| config.shells.default.clone());
+    let options = TranslateOptions {
+        verbose: flags.verbosity > 0,
+        dry_run: false,
+        exec: false,
+        echo: false,
+        json: false,
+        timeout: None,
+        literal_args: false,
+    };
+
+    let mut code = 0;
+    for (i, line) in lines.into_iter().enumerate() {
+        let script::ScriptLine::Command { name, args } = line else {
+            continue;
+        };
+        let words: Vec<String> = [name].into_iter().chain(args).collect();
+        let line_code = translate(&config, &shell, &words, &options);
+        if line_code == 0 {
+            continue;
+        }
+        code = line_code;
+        if !keep_going {
+            eprintln!("{path}:{}: `{}` failed (exit code {code}), stopping", i + 1, words.join(" "));
+            break;
+        }
+    }
+    exit(code);
+}
+
+/// Run each line of `input` as a semantic command with args, up to `parallel`
+/// at a time. Blank lines and `#` comments are skipped. A failure stops any
+/// more commands from starting unless `keep_going` (running ones still finish).
+/// Returns 0 if every command succeeded, otherwise the last failing exit code.
+/// Each line uses the config as it is when the line is read (see
+/// `SemanticConfig::load_cached`); `config` is for when that fails to load.
+fn run_batch(
+    config: &config::SemanticConfig,
+    shell: &str,
+    input: impl BufRead,
+    options: &TranslateOptions,
+    keep_going: bool,
+    parallel: usize,
+) -> i32 {
+    let options = *options;
+    let mut config = Arc::new(config.clone());
+
+    // each finished command sends back its exit code, which frees its slot
+    let (done_tx, done_rx) = mpsc::channel();
+    thread::scope(move |scope| {
+        let mut code = 0;
+        let mut running = 0;
+        for line in input.lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    eprintln!("Failed to read input: {e}");
+                    code = 1;
+                    break;
+                }
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if running == parallel {
+                running -= 1;
+                if let Ok(line_code) = done_rx.recv()
+                    && line_code != 0
+                {
+                    code = line_code;
+                }
+            }
+            if code != 0 && !keep_going {
+                break;
+            }
+
+            // a long batch (or someone typing at it) sees the config as edited since,
+            // and it's only parsed again when the file has changed
+            match config::SemanticConfig::load_cached() {
+                Ok(latest) => config = latest,
+                Err(e) => log::warn!("keeping the config as it was: {e}"),
+            }
+
+            let words: Vec<String> = line.split_whitespace().map(String::from).collect();
+            let done_tx = done_tx.clone();
+            let config = Arc::clone(&config);
+            scope.spawn(move || done_tx.send(translate(&config, shell, &words, &options)));
+            running += 1;
+        }
+
+        // a command that panicked never reports back; stop waiting once all senders are gone
+        drop(done_tx);
+        for _ in 0..running {
+            if let Ok(line_code) = done_rx.recv()
+                && line_code != 0
+            {
+                code = line_code;
+            }
+        }
+        code
+    })
+}
+
+/// `args` split into semantic commands and the operators joining them, if it's
+/// a single quoted line with more than one word ("update && install firefox", or
+/// just "install firefox"). Operators among several arguments are ordinary
+/// arguments, as the shell already left them after removing quotes: `search '|'`
+/// reaches us as `search` and `|`.
+fn chain_of(args: &[String]) -> Option<Result<shell::Chain, String>> {
+    let [line] = args else { return None };
+    match shell::split_chain(line) {
+        Ok((commands, _)) if commands.len() == 1 && commands[0].len() == 1 => None,
+        // a quote in an ordinary command name is for the lookup to reject
+        Err(_) if !shell::CHAIN_OPERATORS.iter().any(|op| line.contains(op)) => None,
+        result => Some(result),
+    }
+}
+
+/// Run semantic commands joined by `&&`, `||` and `|` as one `sh -c` script:
+/// each command is resolved like `translate` does (a composite one becomes its
+/// steps joined by `&&`), and the operators stay between them. A command that
+/// isn't a semantic one but is in $PATH (`show | grep foo`) is passed through.
+fn translate_chain(
+    config: &config::SemanticConfig,
+    shell: &str,
+    commands: &[Vec<String>],
+    operators: &[&str],
+    options: &TranslateOptions,
+) -> i32 {
+    let quote = |argv: &[String]| {
+        argv.iter().map(|arg| shell_escape::unix::escape(arg.into())).collect::<Vec<_>>().join(" ")
+    };
+
+    let mut script = String::new();
+    for (i, words) in commands.iter().enumerate() {
+        if i > 0 {
+            script.push_str(&format!(" {} ", operators[i - 1]));
+        }
+        let is_semantic = config.commands_for(shell).contains_key(&words[0])
+            || config.composite_commands.contains_key(&words[0]);
+        if !is_semantic && shell::find_in_path(&words[0]).is_some() {
+            script.push_str(&quote(words));
+            continue;
+        }
+        let resolved = resolve_steps(config, shell, words, options.verbose, options.literal_args);
+        let steps = match resolved {
+            Ok(steps) => steps,
+            Err(e) => {
+                print_error(options.json, &e.to_string(), e.suggestion());
+                return e.exit_code();
+            }
+        };
+        let steps: Vec<String> = steps.iter().map(|(_, argv)| quote(argv)).collect();
+        match steps.as_slice() {
+            [step] => script.push_str(step),
+            _ => script.push_str(&format!("{{ {}; }}", steps.join(" && "))),
+        }
+    }
+
+    let line = commands
+        .iter()
+        .map(|words| words.join(" "))
+        .zip(operators.iter().map(|op| format!(" {op} ")).chain([String::new()]))
+        .map(|(command, op)| command + &op)
+        .collect::<String>();
+    if options.verbose {
+        eprintln!("semantic: {line} → {script}");
+    }
+    log::info!("chain '{line}' runs as sh script '{script}'");
+
+    let argv = ["sh".to_string(), "-c".to_string(), script.clone()];
+    if options.dry_run && options.json {
+        print_json(&serde_json::json!({ "program": argv[0], "args": &argv[1..] }));
+        return 0;
+    }
+    if options.verbose || options.dry_run || options.echo {
+        eprintln!("semantic: argv {argv:?}");
+    }
+    if options.dry_run {
+        return 0;
+    }
+    let deadline = options.timeout.map(|t| Instant::now() + t);
+    run_step(config, &line, &script, &argv, options.exec, options, deadline)
+}
+
+/// Set for everything translate runs, to one more than it is for translate itself.
+/// Mappings that reach `semantic translate` again without a visible loop in the
+/// config (a script calling the shell's function for its own name, say) then
+/// stop after `config::MAX_REFERENCE_DEPTH` rounds instead of spawning forever.
+const DEPTH_VAR: &str = "SEMANTIC_TRANSLATE_DEPTH";
+
+/// How many translates this one runs inside: 0 when started from a shell.
+fn translate_depth() -> usize {
+    env::var(DEPTH_VAR).ok().and_then(|depth| depth.parse().ok()).unwrap_or(0)
+}
+
+/// Exit with an error if translate has called itself too many times over.
+fn check_translate_depth(json: bool) {
+    let depth = translate_depth();
+    if depth >= config::MAX_REFERENCE_DEPTH {
+        print_error(
+            json,
+            &format!(
+                "semantic translate is running {depth} levels inside itself; \
+                 a mapping probably calls itself (`semantic doctor` looks for loops)"
+            ),
+            None,
+        );
+        exit(1);
+    }
+}
+
+/// How `translate` runs what it resolves.
+#[derive(Clone, Copy)]
+struct TranslateOptions {
+    verbose: bool,
+    dry_run: bool,
+    exec: bool, // the last step may replace this process (unix)
+    echo: bool, // print each step's argv to stderr before running it
+    json: bool, // dry runs print {"program", "args"} on stdout, errors are JSON
+    timeout: Option<Duration>, // kill the command if it's still running after this long
+    literal_args: bool, // the args came after `translate --`: no paths are translated in them
+}
+
+/// Resolve and run one semantic command with its args, returning the exit code
+/// to pass on. `args[0]` is the semantic command.
+fn translate(
+    config: &config::SemanticConfig,
+    shell: &str,
+    args: &[String],
+    options: &TranslateOptions,
+) -> i32 {
+    let TranslateOptions { verbose, dry_run, exec, echo, json, timeout, literal_args } = *options;
+    // one deadline for the whole command, however many steps it has
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let semantic_cmd = &args[0];
+    let steps = match resolve_steps(config, shell, args, verbose, literal_args) {
+        Ok(steps) => steps,
+        Err(e) => {
+            print_error(json, &e.to_string(), e.suggestion());
+            return e.exit_code();
+        }
+    };
+
+    let mut code = 0;
+    let mut planned = Vec::new(); // for a JSON dry run
+    for (i, (real_cmd, argv)) in steps.iter().enumerate() {
+        let (program, program_args) = (&argv[0], &argv[1..]);
+
+        if dry_run && json {
+            planned.push(serde_json::json!({ "program": program, "args": program_args }));
+            continue;
+        }
+        if verbose || dry_run || echo {
+            eprintln!("semantic: argv {argv:?}");
+        }
+        log::info!("step {} of {}: argv {argv:?}", i + 1, steps.len());
+        if dry_run {
+            continue;
+        }
+
+        let last = exec && i + 1 == steps.len();
+        code = run_step(config, semantic_cmd, real_cmd, argv, last, options, deadline);
+
+        // a failed step stops the sequence, and its code becomes ours
+        if code != 0 {
+            break;
+        }
+    }
+
+    // one step is an object; a composite command's steps are an array of them
+    if dry_run && json {
+        if planned.len() == 1 {
+            print_json(&planned[0]);
+        } else {
+            print_json(&planned.into());
+        }
+    }
+
+    code
+}
+
+/// Look up a semantic command (`args[0]`) and work out what each of its steps
+/// runs with the rest of `args`: (the mapping, its argv) per step. With
+/// `literal_args`, the rest of `args` go on as typed, virtual paths and all.
+/// Fails with `UnknownCommand` (`PathAlias` if it's a virtual path instead),
+/// or `BadMapping` for one that can't run as written.
+fn resolve_steps(
+    config: &config::SemanticConfig,
+    shell: &str,
+    args: &[String],
+    verbose: bool,
+    literal_args: bool,
+) -> Result<Vec<(String, Vec<String>)>, SemanticError> {
+    let semantic_cmd = &args[0];
+    let extra_args = &args[1..];
+    let commands = config.commands_for(shell);
+
+    // shell-mode scripts go to the shell as written — it expands them itself
+    let shell_mode = config.is_shell_mode(semantic_cmd, shell);
+
+    // look up the semantic command in the config; composite commands run several steps
+    let steps: Vec<String> = if let Some(cmd) = commands.get(semantic_cmd.as_str()) {
+        vec![cmd.clone()]
+    } else if let Some(steps) = config.composite_commands.get(semantic_cmd.as_str()) {
+        steps.clone()
+    } else if config.resolve_path(semantic_cmd).is_some() {
+        return Err(SemanticError::PathAlias { name: semantic_cmd.clone() });
+    } else {
+        let known = commands.keys().chain(config.composite_commands.keys());
+        return Err(SemanticError::UnknownCommand {
+            name: semantic_cmd.clone(),
+            suggestions: config::suggest(semantic_cmd, known).map(String::from).into_iter().collect(),
+        });
+    };
+
+    // a step may start with another semantic command (`ll = "list -h"`)
+    let steps: Vec<String> = if shell_mode {
+        steps
+    } else {
+        let resolved = steps
+            .iter()
+            .map(|step| config.resolve_references(&commands, semantic_cmd, step, shell))
+            .collect::<Result<Vec<_>, _>>()
+            .map(|steps| steps.iter().map(|step| config::expand_command_env(step)).collect());
+        match resolved {
+            Ok(steps) => steps,
+            Err(e) => {
+                return Err(SemanticError::BadMapping { name: semantic_cmd.clone(), message: e.to_string() });
+            }
+        }
+    };
+
+    if verbose {
+        eprintln!("semantic: {semantic_cmd} → {}", steps.join(" ; "));
+    }
+    if log::log_enabled!(log::Level::Debug) {
+        let source = mapping_source(config, semantic_cmd, &steps.join(" ; "));
+        log::debug!("command '{semantic_cmd}' matched -> '{}' from {source}", steps.join(" ; "));
+    }
+
+    // translate any path arguments (e.g. /apps -> /usr/bin), up to a `--`; after
+    // it, after `translate --`, and for commands with `translate_paths = false`,
+    // they go on as typed
+    let real_paths = if literal_args {
+        vec![None; extra_args.len()]
+    } else {
+        config.resolve_args(semantic_cmd, extra_args, env::current_dir().ok().as_deref())
+    };
+    let translated_args: Vec<String> = extra_args
+        .iter()
+        .zip(real_paths)
+        .map(|(arg, real_path)| match real_path {
+            Some(real_path) => {
+                if verbose {
+                    eprintln!("semantic: path {arg} → {real_path}");
+                }
+                real_path
+            }
+            None => {
+                log::trace!("arg '{arg}' left as is");
+                arg.clone()
+            }
+        })
+        .collect();
+
+    // extra args go to the last step, unless some step asks for them with {args} or {N}
+    let placeholder = !shell_mode && steps.iter().any(|step| shell::takes_args(step));
+    let no_args: &[String] = &[];
+
+    let mut resolved = Vec::new();
+    for (i, real_cmd) in steps.iter().enumerate() {
+        let step_args = if placeholder {
+            if shell::takes_args(real_cmd) { &translated_args[..] } else { no_args }
+        } else if i + 1 == steps.len() {
+            &translated_args[..]
+        } else {
+            no_args
+        };
+
+        // pipes, && and friends only work inside a shell; everything else runs directly
+        let argv = if shell_mode {
+            Ok(shell::shell_mode_argv(&config.shells.default, real_cmd, semantic_cmd, step_args))
+        } else {
+            shell::command_argv(&config.shells.default, real_cmd, step_args)
+        };
+        let argv = argv
+            .map_err(|e| SemanticError::BadMapping { name: semantic_cmd.clone(), message: e.to_string() })?;
+        if argv.is_empty() {
+            return Err(SemanticError::BadMapping {
+                name: semantic_cmd.clone(),
+                message: "maps to an empty command".to_string(),
+            });
+        }
+        resolved.push((real_cmd.clone(), argv));
+    }
+    Ok(resolved)
+}
+
+/// Run one resolved step, returning its exit code. `last` (with
+/// `TranslateOptions::exec`) lets it take over this process when nothing is
+/// left to do afterwards. `deadline` is when the whole command runs out of time.
+fn run_step(
+    config: &config::SemanticConfig,
+    semantic_cmd: &str,
+    real_cmd: &str,
+    argv: &[String],
+    last: bool,
+    options: &TranslateOptions,
+    deadline: Option<Instant>,
+) -> i32 {
+    let Some((program, program_args)) = argv.split_first() else {
+        return 1;
+    };
+
+    // once the last step starts there's nothing left for us to do, unless we still
+    // need its exit code — so hand the process over to it. Signals and the tty then
+    // reach the program directly, and it's the shell's child rather than ours.
+    // A timeout needs us around to enforce it.
+    // [general] rewrite_output: stdout comes back through us to be rewritten
+    let rewriter = config
+        .general
+        .rewrite_output
+        .then(|| config::PathRewriter::new(config))
+        .filter(|rewriter| !rewriter.is_empty());
+
+    #[cfg(unix)]
+    if last && !options.verbose && !config.general.history && deadline.is_none() && rewriter.is_none() {
+        use std::os::unix::process::CommandExt;
+        log::debug!("exec {program}, replacing semantic");
+        // exec only returns if it failed
+        let source = Command::new(program)
+            .args(program_args)
+            .env(DEPTH_VAR, (translate_depth() + 1).to_string())
+            .exec();
+        let e = SemanticError::ExecFailed { program: real_cmd.to_string(), source };
+        eprintln!("{e}");
+        exit(e.exit_code());
+    }
+
+    // Command finds only .exe files by itself; a .cmd or .bat wrapper (npm,
+    // code) has to be given by its full path
+    #[cfg(windows)]
+    let program = &shell::find_in_path(program)
+        .map_or_else(|| program.clone(), |path| path.to_string_lossy().into_owned());
+
+    let started = Instant::now();
+    let mut command = Command::new(program);
+    // the terminal itself, so editors and other full-screen programs work
+    command
+        .args(program_args)
+        .env(DEPTH_VAR, (translate_depth() + 1).to_string())
+        .stdin(Stdio::inherit())
+        .stdout(if rewriter.is_some() { Stdio::piped() } else { Stdio::inherit() })
+        .stderr(Stdio::inherit());
+    let status = command.spawn().and_then(|mut child| {
+        let copier = child
+            .stdout
+            .take()
+            .zip(rewriter)
+            .map(|(stdout, rewriter)| {
+                thread::spawn(move || copy_rewritten(stdout, &mut io::stdout(), &rewriter))
+            });
+        let status = match deadline {
+            Some(deadline) => wait_before(&mut child, deadline),
+            None => child.wait().map(Some),
+        };
+        // the rest of the output, up to the point the program closed its stdout
+        if let Some(copier) = copier {
+            let _ = copier.join();
+        }
+        status
+    });
+    let elapsed = started.elapsed();
+
+    let code = match status {
+        // fed from a pipe whose reader quit early (`semantic translate show | head`):
+        // that's how pipelines end, not a failure worth reporting
+        Ok(Some(s)) if !is_interactive_command() && killed_by_sigpipe(s) => {
+            if options.verbose {
+                eprintln!("semantic: `{real_cmd}` stopped at a closed pipe");
+            }
+            141
+        }
+        Ok(Some(s)) => {
+            if let Some(signal) = terminating_signal(s)
+                && options.verbose
+            {
+                eprintln!("semantic: `{real_cmd}` was killed by signal {signal}");
+            }
+            exit_code(s)
+        }
+        // 124, as GNU timeout exits with
+        Ok(None) => {
+            eprintln!("Command timed out after {}s", options.timeout.unwrap_or_default().as_secs());
+            124
+        }
+        Err(source) => {
+            let e = SemanticError::ExecFailed { program: real_cmd.to_string(), source };
+            eprintln!("{e}");
+            e.exit_code()
+        }
+    };
+
+    if options.verbose {
+        eprintln!("semantic: exit code {code}");
+    }
+    log::debug!("`{real_cmd}` exited with {code} after {}ms", elapsed.as_millis());
+
+    // logging is best-effort — a failed append must never change the outcome,
+    // and is only worth mentioning when the user asked for details
+    if config.general.history {
+        let max_kb = config.general.history_max_kb.unwrap_or(history::DEFAULT_MAX_KB);
+        if let Err(e) = history::append(semantic_cmd, argv, code, elapsed, max_kb)
+            && options.verbose
+        {
+            eprintln!("semantic: failed to write history: {e}");
+        }
+    }
+
+    code
+}
+
+/// Wait for `child` to exit or `deadline` to pass, whichever comes first.
+/// Returns None if it was still running at the deadline and had to be killed.
+fn wait_before(
+    child: &mut std::process::Child,
+    deadline: Instant,
+) -> io::Result<Option<std::process::ExitStatus>> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            // it may have exited in the meantime, which is fine too
+            let _ = child.kill();
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep((deadline - now).min(Duration::from_millis(50)));
+    }
+}
+
+/// The most of an unfinished line `copy_rewritten` holds back waiting for its
+/// newline; output without one goes out in pieces this big. No path is longer.
+const MAX_PENDING_LINE: usize = 64 * 1024;
+
+/// Copy a program's `output` to `out` with real paths shown as virtual ones
+/// (see `config::PathRewriter`), a line at a time as it arrives, so a path
+/// split between two writes is still whole when it's rewritten. What isn't
+/// UTF-8 passes through untouched. Stops once `out` is closed, which closes the
+/// program's pipe in turn.
+fn copy_rewritten(mut output: impl io::Read, out: &mut impl Write, rewriter: &config::PathRewriter) {
+    let mut buffer = [0; 8192];
+    let mut pending = Vec::new();
+    loop {
+        let read = match output.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        };
+        pending.extend_from_slice(&buffer[..read]);
+        // an unfinished line waits for the rest, unless it's grown too long
+        let end = match pending.iter().rposition(|&b| b == b'\n') {
+            Some(newline) => newline + 1,
+            None if pending.len() >= MAX_PENDING_LINE => pending.len(),
+            None => 0,
+        };
+        if write_rewritten(out, &pending[..end], rewriter).is_err() {
+            return;
+        }
+        pending.drain(..end);
+    }
+    let _ = write_rewritten(out, &pending, rewriter);
+}
+
+/// Write `bytes` to `out` rewritten, for `copy_rewritten`, and flush them.
+/// Each run of valid UTF-8 is rewritten; invalid bytes between them are kept.
+fn write_rewritten(
+    out: &mut impl Write,
+    bytes: &[u8],
+    rewriter: &config::PathRewriter,
+) -> io::Result<()> {
+    if bytes.is_empty() {
+        return Ok(());
+    }
+    for chunk in bytes.utf8_chunks() {
+        out.write_all(rewriter.rewrite(chunk.valid()).as_bytes())?;
+        out.write_all(chunk.invalid())?;
+    }
+    out.flush()
+}
+
+/// Whether stdin is a terminal, so the command may be interactive (an editor,
+/// a pager, htop) rather than reading from a pipe or file.
+fn is_interactive_command() -> bool {
+    io::stdin().is_terminal()
+}
+
+/// The signal that killed a child, if one did. Always None off Unix.
+fn terminating_signal(status: std::process::ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.signal()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        None
+    }
+}
+
+/// Whether a child was killed by SIGPIPE, i.e. wrote to a pipe nobody reads any more.
+fn killed_by_sigpipe(status: std::process::ExitStatus) -> bool {
+    const SIGPIPE: i32 = 13;
+    terminating_signal(status) == Some(SIGPIPE)
+}
+
+/// The exit code to pass on for a finished child.
+/// A child killed by a signal reports 128 + the signal number, as shells do;
+/// 1 when there's neither a code nor a signal.
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    match terminating_signal(status) {
+        Some(signal) => 128 + signal,
+        None => status.code().unwrap_or(1),
+    }
+}
+
+/// Check every command mapping and path mapping against the current system.
+/// Exits 0 only if all mapped programs are in $PATH and all real paths exist.
+/// Mappings from a pack or plugin say which one.
+/// Called as: semantic test [--fix]
+fn cmd_test(args: &[String], flags: &GlobalFlags) {
+    let fix = args.iter().any(|a| a == "--fix");
+
+    let config = match load_expanded_config(flags) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Failed to load config: {e}");
+            exit(e.exit_code());
+        }
+    };
+
+    let mut all_ok = true;
+    let mut missing = Vec::new();
+
+    // one row per command, or per step for composite commands (numbered, name on the first)
+    let mut rows: Vec<(&String, String, &String)> = config
+        .commands
+        .iter()
+        .map(|(name, real_cmd)| (name, real_cmd.clone(), real_cmd))
+        .collect();
+    for (name, steps) in &config.composite_commands {
+        for (i, step) in steps.iter().enumerate() {
+            rows.push((name, format!("{}. {step}", i + 1), step));
+        }
+    }
+
+    // sort so the table is stable between runs
+    rows.sort();
+    let name_width = rows.iter().map(|(k, _, _)| display_width(k)).max().unwrap_or(0);
+    let cmd_width = rows.iter().map(|(_, v, _)| display_width(v)).max().unwrap_or(0);
+
+    println!("Commands:");
+    let mut previous = None;
+    for (name, shown, real_cmd) in rows {
+        let status = match config::real_program(real_cmd) {
+            Some(program) if shell::SHELL_BUILTINS.contains(&program) => "OK (builtin)",
+            Some(program) if shell::find_in_path(program).is_some() => "OK",
+            _ => {
+                all_ok = false;
+                missing.push((name, real_cmd));
+                "MISSING"
+            }
+        };
+        let label = if previous == Some(name) { "" } else { name.as_str() };
+        let note = if label.is_empty() {
+            String::new()
+        } else {
+            pack_note(&config, &config::MappingKey::Command(name.clone()))
+        };
+        previous = Some(name);
+        println!("  {}  {}  {status}{note}", pad(label, name_width), pad(&shown, cmd_width));
+    }
+
+    let mut paths: Vec<_> = config.paths.iter().collect();
+    paths.sort();
+    let virtual_width = paths.iter().map(|(k, _)| display_width(k)).max().unwrap_or(0);
+    let real_width = paths.iter().map(|(_, v)| display_width(v)).max().unwrap_or(0);
+
+    if !paths.is_empty() {
+        println!();
+        println!("Paths:");
+    }
+    for (virtual_path, real_path) in paths {
+        let status = if Path::new(real_path).exists() {
+            "OK"
+        } else {
+            all_ok = false;
+            "MISSING"
+        };
+        let note = pack_note(&config, &config::MappingKey::Path(virtual_path.clone()));
+        println!(
+            "  {}  {}  {status}{note}",
+            pad(virtual_path, virtual_width),
+            pad(real_path, real_width)
+        );
+    }
+
+    // suggest the detected package manager's equivalent for missing package commands
+    if fix && !missing.is_empty() {
+        println!();
+        match config::detect_package_manager() {
+            Some(pm) => {
+                println!("Suggested fixes (detected package manager: {}):", pm.name);
+                let mut suggested = false;
+                for (name, real_cmd) in &missing {
+                    if let Some(replacement) = pm.equivalent_of(real_cmd) {
+                        println!("  {name} = \"{replacement}\"  (was \"{real_cmd}\")");
+                        suggested = true;
+                    }
+                }
+                if !suggested {
+                    println!("  No package manager substitutions apply.");
+                }
+            }
+            None => println!("No supported package manager detected; no fixes to suggest."),
+        }
+    }
+
+    exit(if all_ok { 0 } else { 1 });
+}
+
+/// The value following `flag` in `args` (e.g. `--format json`), if given.
+/// Exits with a usage error if the flag is the last argument.
+fn flag_value<'a>(args: &'a [String], flag: &str, usage: &str) -> Option<&'a str> {
+    let pos = args.iter().position(|a| a == flag)?;
+    match args.get(pos + 1) {
+        Some(value) => Some(value),
+        None => {
+            eprintln!("{flag} needs a value");
+            eprintln!("{usage}");
+            exit(1);
+        }
+    }
+}
+
+/// Serialize the active config (user plus any project overlay) as TOML or JSON.
+/// Env vars are left unexpanded, since their values belong to this machine.
+/// Included files are already merged in, so `include` itself is left out.
+/// An `[export]` table at the top says which semantic wrote it, when, and on
+/// which machine; `semantic import` checks it, loading the file ignores it.
+/// The format defaults to the output file's extension, else TOML.
+/// Called as: semantic export [--format toml|json] [--output <file> | <file>]
+fn cmd_export(args: &[String], flags: &GlobalFlags) {
+    const USAGE: &str = "Usage: semantic export [--format toml|json] [--output <file> | <file>]";
+    let output = flag_value(args, "--output", USAGE).or_else(|| {
+        args.iter()
+            .enumerate()
+            .find(|(i, a)| !a.starts_with("--") && (*i == 0 || !args[i - 1].starts_with("--")))
+            .map(|(_, a)| a.as_str())
+    });
+    let format = flag_value(args, "--format", USAGE).unwrap_or_else(|| {
+        output.map_or("toml", |path| config::ConfigFormat::of(Path::new(path)).name())
+    });
+
+    let mut config = match load_config(flags) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Failed to load config: {e}");
+            exit(e.exit_code());
+        }
+    };
+    config.include.clear();
+
+    let Some(format) = config::ConfigFormat::from_name(format) else {
+        eprintln!("Unknown format `{format}` (expected toml or json)");
+        exit(1);
+    };
+    let content = format.render_export(&config, &config::ExportInfo::now()).unwrap_or_else(|e| {
+        eprintln!("Failed to serialize config: {e}");
+        exit(1);
+    });
+
+    match output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, content) {
+                eprintln!("Failed to write {path}: {e}");
+                exit(1);
+            }
+        }
+        None => print!("{content}"),
+    }
+}
+
+/// Rewrite the user config in the other format, then delete the old file.
+/// The new file is read back before the old one goes, so a bad write loses nothing.
+/// Called as: semantic convert --to json|toml
+fn cmd_convert(args: &[String]) {
+    const USAGE: &str = "Usage: semantic convert --to json|toml";
+    let Some(to) = flag_value(args, "--to", USAGE) else {
+        eprintln!("{USAGE}");
+        exit(1);
+    };
+    let Some(format) = config::ConfigFormat::from_name(to) else {
+        eprintln!("Unknown format `{to}` (expected toml or json)");
+        exit(1);
+    };
+
+    // SEMANTIC_CONFIG names one file whose extension picks its format
+    if env::var_os("SEMANTIC_CONFIG").is_some_and(|v| !v.is_empty()) {
+        eprintln!("SEMANTIC_CONFIG is set; convert that file yourself with `semantic export`.");
+        exit(1);
+    }
+
+    let mut file = match config::ConfigFile::load() {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Failed to load config: {e}");
+            exit(e.exit_code());
+        }
+    };
+    if file.format == format {
+        println!("{} is already {}.", file.path().display(), format.name());
+        return;
+    }
+
+    let old_path = file.path();
+    file.format = format;
+    match file.save() {
+        Ok(path) => println!("Converted {} to {}", old_path.display(), path.display()),
+        Err(e) => {
+            eprintln!("Failed to convert config: {e}");
+            exit(1);
+        }
+    }
+}
+
+/// Take a shared config (local file, http(s) URL or `-` for stdin, TOML or JSON).
+/// By default it replaces the user config as a whole; `--merge` only layers its
+/// commands and paths over yours, keeping your settings and shells, with
+/// `--strategy` deciding conflicting keys: `replace` (imported wins, the
+/// default), `extend` (only add new keys), or `ask` (prompt per conflict).
+/// An export from a newer semantic, a full config without shells, or mappings
+/// that look destructive (unless `--allow-dangerous`) are refused. The result
+/// is validated and shown as a diff, and asks before saving unless `--yes`.
+/// Called as: semantic import <path|url|-> [--merge [--strategy replace|extend|ask]]
+///            [--allow-dangerous] [--yes]
+fn cmd_import(args: &[String]) {
+    const USAGE: &str = "Usage: semantic import <path|url|-> [--merge [--strategy replace|extend|ask]] \
+                         [--allow-dangerous] [--yes]";
+    let strategy = flag_value(args, "--strategy", USAGE);
+    // --strategy only means something when merging, so it implies --merge
+    let merge = strategy.is_some() || args.iter().any(|a| a == "--merge");
+    let strategy = strategy.unwrap_or("replace");
+    let allow_dangerous = args.iter().any(|a| a == "--allow-dangerous");
+    let yes = args.iter().any(|a| a == "--yes");
+    let source = args
+        .iter()
+        .enumerate()
+        .find(|(i, a)| {
+            (*a == "-" || !a.starts_with('-')) && (*i == 0 || args[i - 1] != "--strategy")
+        })
+        .map(|(_, a)| a);
+    let Some(source) = source else {
+        eprintln!("{USAGE}");
+        exit(1);
+    };
+    if !matches!(strategy, "replace" | "extend" | "ask") {
+        eprintln!("Unknown strategy `{strategy}` (expected replace, extend or ask)");
+        exit(1);
+    }
+    // stdin carries the config, so there's nothing left to read answers from
+    if source == "-" && (!yes || strategy == "ask") {
+        eprintln!("Importing from stdin can't ask anything; add --yes (and don't use --strategy ask).");
+        exit(1);
+    }
+
+    let (info, mut imported) = match fetch_config(source) {
+        Ok(import) => import,
+        Err(e) => {
+            eprintln!("Failed to import {source}: {e}");
+            exit(1);
+        }
+    };
+    if let Some(info) = &info
+        && info.version > config::EXPORT_VERSION
+    {
+        eprintln!(
+            "{source} was exported by semantic {} (export version {}); this semantic only reads \
+             version {} and older.",
+            info.semantic,
+            info.version,
+            config::EXPORT_VERSION
+        );
+        exit(1);
+    }
+    let dangerous: Vec<String> = imported
+        .dangerous_mappings()
+        .into_iter()
+        .map(|(key, why)| format!("{key} {why}"))
+        .collect();
+    if !dangerous.is_empty() && !allow_dangerous {
+        eprintln!("{source} has mappings that look dangerous, nothing was imported:");
+        for line in &dangerous {
+            eprintln!("  - {line}");
+        }
+        eprintln!("Check them, then run again with --allow-dangerous to import them anyway.");
+        exit(1);
+    }
+    if !merge && imported.shells.enabled.is_empty() {
+        eprintln!("{source} has no enabled shells ([shells] enabled), so it can't be your whole config.");
+        eprintln!("Use --merge to take only its commands and paths.");
+        exit(1);
+    }
+
+    // a whole-config import may be the first config there is
+    let path = config::SemanticConfig::config_path();
+    let current = match config::SemanticConfig::load_user() {
+        Ok(c) => Some(c),
+        Err(_) if !merge && !path.exists() => None,
+        Err(e) => {
+            eprintln!("Failed to load config: {e}");
+            eprintln!("Run `semantic` (no args) to set up your config first.");
+            exit(e.exit_code());
+        }
+    };
+
+    let new = match current.clone() {
+        Some(current) if merge => {
+            // strip the imported side of any conflict that shouldn't win
+            let conflicts = current.conflicts_with(&imported);
+            match strategy {
+                "extend" => {
+                    for key in &conflicts {
+                        imported.remove_mapping(key);
+                    }
+                }
+                "ask" => {
+                    let ours = current.mappings();
+                    let theirs = imported.mappings();
+                    for key in &conflicts {
+                        println!("{key}");
+                        println!("  current:  {}", ours[key]);
+                        println!("  imported: {}", theirs[key]);
+                        if !confirm("Use the imported value?") {
+                            imported.remove_mapping(key);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            config::SemanticConfig::merge(current, imported.mappings_only())
+        }
+        _ => imported,
+    };
+
+    if let Err(errors) = new.validate() {
+        eprintln!("The imported config has problems, nothing was written:");
+        for error in errors {
+            eprintln!("  - {error}");
+        }
+        exit(1);
+    }
+
+    let changes: Vec<String> = match &current {
+        Some(current) => diff_changes(current, &new),
+        None => vec![format!("(new) {}", path.display())],
+    };
+    if changes.is_empty() {
+        println!("{source} matches your config already; nothing to import.");
+        return;
+    }
+    if let Some(info) = &info {
+        println!("Exported by semantic {} on {} at {}.", info.semantic, info.host, info.created);
+    }
+    print_list("Changes to your config", &changes);
+    if !yes && !confirm("Save these changes?") {
+        println!("Nothing was changed.");
+        return;
+    }
+
+    if let Err(e) = new.save() {
+        eprintln!("Failed to write config: {e}");
+        exit(1);
+    }
+    println!("Imported {source} into {}", path.display());
+}
+
+/// What differs between two configs, one `+`/`-`/`~` line per setting or mapping.
+fn diff_changes(old: &config::SemanticConfig, new: &config::SemanticConfig) -> Vec<String> {
+    config::config_diff(old, new)
+        .into_iter()
+        .filter_map(|line| match line {
+            config::DiffLine::Added(key, value) => Some(format!("+ {key} = {value}")),
+            config::DiffLine::Removed(key, value) => Some(format!("- {key} = {value}")),
+            config::DiffLine::Changed(key, old, value) => Some(format!("~ {key} = {old} → {value}")),
+            config::DiffLine::Unchanged(..) => None,
+        })
+        .collect()
+}
+
+/// Read a shared config from a file, an http(s) URL, or stdin (`-`), along with
+/// its `[export]` table if it has one. It's parsed just as the user config is.
+/// JSON is recognised by a `.json` suffix or a leading `{`; anything else is TOML.
+fn fetch_config(
+    source: &str,
+) -> Result<(Option<config::ExportInfo>, config::SemanticConfig), Box<dyn std::error::Error>> {
+    let content = if source == "-" {
+        io::read_to_string(io::stdin())?
+    } else if source.starts_with("http://") || source.starts_with("https://") {
+        ureq::get(source).call()?.body_mut().read_to_string()?
+    } else {
+        std::fs::read_to_string(source)?
+    };
+
+    let format = if source.ends_with(".json") || content.trim_start().starts_with('{') {
+        config::ConfigFormat::Json
+    } else {
+        config::ConfigFormat::Toml
+    };
+    format.parse_export(&content)
+}
+
+/// Print where the active config lives, as `config_path` works it out (with
+/// $SEMANTIC_CONFIG, $SEMANTIC_CONFIG_DIR, $XDG_CONFIG_HOME and the profile
+/// taken into account). Never reads the config, so it works before there is one.
+/// Called as: semantic config path | dir
+fn cmd_config(args: &[String]) {
+    const USAGE: &str = "Usage: semantic config path | dir";
+    match args {
+        [what] if what == "path" => println!("{}", config::SemanticConfig::config_path().display()),
+        [what] if what == "dir" => println!("{}", config::config_dir().display()),
+        _ => {
+            eprintln!("{USAGE}");
+            exit(1);
+        }
+    }
+}
+
+/// Editors known to take `+<line>` before a file to open it at that line.
+const LINE_ARG_EDITORS: &[&str] = &["vi", "vim", "nvim", "nano", "emacs", "micro", "kak", "mg", "ne", "joe"];
+
+/// Open a copy of the user config in the user's editor, and put it in place of
+/// the config only once it parses, migrates and validates, so a typo never
+/// reaches the shell. A missing config is first created with the wizard's
+/// defaults, so there's something to edit. If the copy has problems, they're
+/// shown (a parse error with the line it's on) and the editor can be re-opened
+/// on the copy, at that line; otherwise the changes are dropped.
+/// --commands and --paths open it at that table, in editors in `LINE_ARG_EDITORS`.
+/// Called as: semantic edit [--commands | --paths]
+fn cmd_edit(args: &[String]) {
+    let path = config::SemanticConfig::config_path();
+    let table = if args.iter().any(|a| a == "--commands") {
+        Some("commands")
+    } else if args.iter().any(|a| a == "--paths") {
+        Some("paths")
+    } else {
+        None
+    };
+
+    let Some(editor) = find_editor() else {
+        eprintln!("No editor found. Set $VISUAL or $EDITOR (e.g. `export EDITOR=nano`) and try again.");
+        exit(1);
+    };
+
+    if !path.exists() {
+        if let Err(e) = default_config().save_to(&path) {
+            eprintln!("Failed to create {}: {e}", path.display());
+            exit(1);
+        }
+        println!("Created {} with the default settings.", path.display());
+    }
+
+    // the copy sits beside the real file (past any symlink), so it can be renamed over it
+    let target = std::fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+    let copy = edit_copy_path(&target);
+    let original = match std::fs::read_to_string(&target) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("Failed to read {}: {e}", target.display());
+            exit(1);
+        }
+    };
+    if let Err(e) = std::fs::copy(&target, &copy) {
+        eprintln!("Failed to copy {} to {}: {e}", target.display(), copy.display());
+        exit(1);
+    }
+    let discard = || {
+        let _ = std::fs::remove_file(&copy);
+    };
+
+    let mut line = table.and_then(|table| table_line(&original, table));
+    loop {
+        // $EDITOR may carry its own arguments, e.g. "code --wait"
+        let mut parts = editor.split_whitespace();
+        let program = parts.next().unwrap_or("vi");
+        let takes_line = Path::new(program)
+            .file_name()
+            .is_some_and(|name| LINE_ARG_EDITORS.iter().any(|e| name == *e));
+        let line_arg = line.filter(|_| takes_line).map(|line| format!("+{line}"));
+
+        if let Err(e) = Command::new(program).args(parts).args(line_arg).arg(&copy).status() {
+            discard();
+            eprintln!("Failed to start editor `{editor}`: {e}");
+            eprintln!("Set $VISUAL or $EDITOR to an editor that's installed (e.g. `export EDITOR=nano`).");
+            exit(1);
+        }
+
+        let edited = std::fs::read_to_string(&copy).unwrap_or_default();
+        if edited == original {
+            discard();
+            println!("No changes.");
+            return;
+        }
+
+        let (errors, error_line) = match check_edited_config(&copy) {
+            Ok(()) => match config::SemanticConfig::replace_user_config(&copy) {
+                Ok(replaced) => {
+                    config::SemanticConfig::forget_cached();
+                    println!("Config OK, saved: {}", replaced.display());
+                    return;
+                }
+                Err(e) => {
+                    eprintln!("Failed to save the config: {e}");
+                    eprintln!("Your changes are in {}.", copy.display());
+                    exit(1);
+                }
+            },
+            Err(problems) => problems,
+        };
+
+        eprintln!("The config has problems:");
+        for error in &errors {
+            eprintln!("  - {error}");
+        }
+        if let Some((number, column)) = error_line
+            && let Some(text) = edited.lines().nth(number - 1)
+        {
+            eprintln!("  {number:>4} | {text}");
+            if let Some(column) = column {
+                eprintln!("       | {}^", " ".repeat(column.saturating_sub(1)));
+            }
+        }
+
+        if !confirm("Re-open the editor? (no drops your changes)") {
+            discard();
+            eprintln!("Changes dropped; {} is as it was.", path.display());
+            exit(1);
+        }
+        line = error_line.map(|(number, _)| number).or(line);
+    }
+}
+
+/// Where `semantic edit` keeps the copy being edited: config.edit.toml beside
+/// config.toml (the extension kept, so it parses in the same format).
+fn edit_copy_path(path: &Path) -> std::path::PathBuf {
+    let stem = path.file_stem().map_or_else(|| "config".into(), |s| s.to_string_lossy());
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}.edit.{}", ext.to_string_lossy()),
+        None => format!("{stem}.edit"),
+    };
+    path.with_file_name(name)
+}
+
+/// The 1-based line a top-level table starts on: `[table]` in TOML,
+/// `"table":` in JSON.
+fn table_line(content: &str, table: &str) -> Option<usize> {
+    let toml_header = format!("[{table}]");
+    let json_key = format!("\"{table}\"");
+    content
+        .lines()
+        .position(|line| {
+            let line = line.trim();
+            line == toml_header || line.starts_with(&json_key)
+        })
+        .map(|i| i + 1)
+}
+
+/// A 1-based line in a file, and the column on it if known.
+type LineColumn = (usize, Option<usize>);
+
+/// Everything wrong with an edited config at `path`, the way loading it would
+/// see it: a parse error, a schema it can't migrate, mappings that can't run or
+/// that loop. With a parse error, also the (line, column) it points at.
+fn check_edited_config(path: &Path) -> Result<(), (Vec<String>, Option<LineColumn>)> {
+    let mut config = config::SemanticConfig::load_from(path).map_err(|e| {
+        let position = e
+            .downcast_ref::<config::ParseError>()
+            .and_then(|e| e.line.map(|line| (line, e.column)));
+        let message = match e.downcast_ref::<config::ParseError>() {
+            Some(e) => e.summary(),
+            None => e.to_string(),
+        };
+        (vec![message], position)
+    })?;
+    config::migrate(&mut config).map_err(|e| (vec![e.to_string()], None))?;
+    config.validate().map_err(|errors| (errors, None))?;
+    let cycles = config.reference_cycles();
+    if cycles.is_empty() { Ok(()) } else { Err((cycles, None)) }
+}
+
+/// What the wizard would write if every step were left at its default:
+/// the current shell, natural names in the locale's language, and package
+/// commands for the detected package manager.
+fn default_config() -> config::SemanticConfig {
+    let shell = shell::detect_shell()
+        .filter(|s| shell::KNOWN_SHELLS.contains(&s.as_str()))
+        .unwrap_or_else(|| "bash".to_string());
+    let language = config::language_from_env().map_or("en", |l| l.code);
+    let mut config = config::SemanticConfig::from_selections(
+        &shell,
+        &[shell.as_str()],
+        "natural",
+        "natural",
+        language,
+        "auto-setup",
+        &[],
+        &Default::default(),
+    );
+    use_detected_package_manager(&mut config.commands);
+    config
+}
+
+/// Pick an editor: $VISUAL, then $EDITOR, then the first common editor found in $PATH.
+fn find_editor() -> Option<String> {
+    for var in ["VISUAL", "EDITOR"] {
+        if let Ok(editor) = env::var(var)
+            && !editor.trim().is_empty()
+        {
+            return Some(editor);
+        }
+    }
+
+    let fallbacks: &[&str] = if cfg!(windows) { &["notepad.exe"] } else { &["vi", "nano"] };
+    fallbacks
+        .iter()
+        .find(|e| shell::find_in_path(e).is_some())
+        .map(|e| e.to_string())
+}
+
+/// Ask a y/n question on stdin. Anything other than y/yes counts as no.
+fn confirm(question: &str) -> bool {
+    print!("{question} [y/n] ");
+    let _ = io::stdout().flush();
+
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// List the semantic command names, one per line, for scripts: nothing else
+/// goes to stdout. --paths lists the virtual paths instead, and --all (or
+/// --commands with --paths) both, commands first. Per-shell overrides for the
+/// current shell apply. --json prints an object with `commands` and `paths`
+/// maps instead, composite commands as arrays of steps, along with the pack or
+/// plugin each mapping came from (under `packs`). --format prints each mapping
+/// through a template (see `parse_list_format`), composite steps joined with
+/// " ; "; `text`, `tsv` and `json` name the usual ones, so scripts needn't spell
+/// them out. --filter keeps to the names matching a glob. An empty list is
+/// still a success.
+/// Called as: semantic list [--commands | --paths | --all] [--filter <glob>]
+///                          [--json | --format <template>]
+fn cmd_list(args: &[String], flags: &GlobalFlags) {
+    const USAGE: &str = "Usage: semantic list [--commands | --paths | --all] [--filter <glob>] \
+                         [--json | --format <template>]";
+    let mut json = flags.json || args.iter().any(|a| a == "--json");
+    let template = match flag_value(args, "--format", USAGE) {
+        Some("json") => {
+            json = true;
+            None
+        }
+        None | Some("text") => Some("{name}"),
+        Some("tsv") => Some("{name}\\t{command}"),
+        other => other,
+    };
+    let format = template.map(|template| {
+        parse_list_format(template).unwrap_or_else(|e| {
+            eprintln!("Invalid --format: {e}");
+            exit(1);
+        })
+    });
+    let filter = flag_value(args, "--filter", USAGE).map(|pattern| {
+        glob::Pattern::new(pattern).unwrap_or_else(|e| {
+            eprintln!("Invalid --filter: {e}");
+            exit(1);
+        })
+    });
+    let (show_commands, show_paths) = list_sections(args);
+
+    let mut config = match load_expanded_config(flags) {
+        Ok(c) => c,
+        Err(e) => {
+            print_error(json, &format!("Failed to load config: {e}"), None);
+            exit(e.exit_code());
+        }
+    };
+
+    let shell = shell::detect_shell().unwrap_or_else(|| config.shells.default.clone());
+    let commands = list_filtered(&mut config, &shell, show_commands, show_paths, filter.as_ref());
+
+    if json {
+        print_json(&list_json(&config, &commands, &shell, show_commands, show_paths));
+        return;
+    }
+
+    if let Some(format) = format {
+        print!("{}", list_formatted(&config, &commands, &format));
+    }
+}
+
+/// Which of commands and paths `semantic list` shows for its `args`: commands
+/// alone unless --paths or --all says otherwise.
+fn list_sections(args: &[String]) -> (bool, bool) {
+    let flag = |name: &str| args.iter().any(|a| a == name);
+    if flag("--all") {
+        return (true, true);
+    }
+    match (flag("--commands"), flag("--paths")) {
+        (false, false) => (true, false),
+        chosen => chosen,
+    }
+}
+
+/// Cut `config` down to what `semantic list` shows: its commands for `shell`
+/// (returned) and composite commands only with `show_commands`, its paths only
+/// with `show_paths`, and of those only the names `filter` matches.
+fn list_filtered(
+    config: &mut config::SemanticConfig,
+    shell: &str,
+    show_commands: bool,
+    show_paths: bool,
+    filter: Option<&glob::Pattern>,
+) -> std::collections::HashMap<String, String> {
+    let matches = |name: &str| filter.is_none_or(|pattern| pattern.matches(name));
+    let mut commands = config.commands_for(shell);
+    commands.retain(|name, _| show_commands && matches(name));
+    config.composite_commands.retain(|name, _| show_commands && matches(name));
+    config.paths.retain(|virtual_path, _| show_paths && matches(virtual_path));
+    commands
+}
+
+/// `semantic list --json`: `commands` (a shell-mode command is `{"shell": ...}`
+/// and a composite one its list of steps) and `paths`, each left out when not
+/// shown, and the packs mappings came from. `commands` is `config`'s commands for `shell`.
+fn list_json(
+    config: &config::SemanticConfig,
+    commands: &std::collections::HashMap<String, String>,
+    shell: &str,
+    show_commands: bool,
+    show_paths: bool,
+) -> serde_json::Value {
+    let mut command_map = serde_json::Map::new();
+    for (name, real_cmd) in commands {
+        let value = if config.is_shell_mode(name, shell) {
+            serde_json::json!({ "shell": real_cmd })
+        } else {
+            real_cmd.as_str().into()
+        };
+        command_map.insert(name.clone(), value);
+    }
+    for (name, steps) in &config.composite_commands {
+        if !commands.contains_key(name) {
+            command_map.insert(name.clone(), serde_json::json!(steps));
+        }
+    }
+    let packs: serde_json::Map<_, _> = config
+        .pack_sources
+        .iter()
+        .map(|(key, pack)| (key.to_string(), pack.display().to_string().into()))
+        .collect();
+    let mut out = serde_json::Map::new();
+    if show_commands {
+        out.insert("commands".to_string(), command_map.into());
+    }
+    if show_paths {
+        out.insert("paths".to_string(), serde_json::json!(config.paths));
+    }
+    out.insert("packs".to_string(), packs.into());
+    out.into()
+}
+
+/// `semantic list --format`: one line per mapping, commands (composite ones with
+/// their steps joined by ` ; `) sorted by name and then paths, each laid out as
+/// `format` says. `commands` is `config`'s commands for the shell.
+fn list_formatted(
+    config: &config::SemanticConfig,
+    commands: &std::collections::HashMap<String, String>,
+    format: &[FormatPart],
+) -> String {
+    let mut mappings: Vec<(&str, String, &str)> = commands
+        .iter()
+        .map(|(name, real_cmd)| ("command", real_cmd.clone(), name.as_str()))
+        .chain(
+            config
+                .composite_commands
+                .iter()
+                .filter(|(name, _)| !commands.contains_key(*name))
+                .map(|(name, steps)| ("command", steps.join(" ; "), name.as_str())),
+        )
+        .collect();
+    mappings.sort_by_key(|(_, _, name)| *name);
+    let mut paths: Vec<_> = config.paths.iter().collect();
+    paths.sort();
+    mappings.extend(paths.into_iter().map(|(k, v)| ("path", v.clone(), k.as_str())));
+
+    let mut out = String::new();
+    for (kind, real, name) in mappings {
+        for part in format {
+            out.push_str(match part {
+                FormatPart::Text(text) => text,
+                FormatPart::Field(ListField::Name) => name,
+                FormatPart::Field(ListField::Command) => &real,
+                FormatPart::Field(ListField::Kind) => kind,
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// A placeholder in a `list --format` template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListField {
+    /// `{name}`: the semantic command or virtual path.
+    Name,
+    /// `{command}`: what it maps to (the real path, for a path).
+    Command,
+    /// `{kind}`: `command` or `path`.
+    Kind,
+}
+
+/// A `list --format` template, split into literal text and placeholders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FormatPart {
+    Text(String),
+    Field(ListField),
+}
+
+/// Parse a `list --format` template. `\t`, `\n` and `\\` are a tab, a newline
+/// and a backslash; `{{` and `}}` are literal braces. A placeholder other than
+/// those in `ListField`, or an unclosed `{`, is an error naming it.
+fn parse_list_format(template: &str) -> Result<Vec<FormatPart>, String> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('t') => text.push('\t'),
+                Some('n') => text.push('\n'),
+                Some('\\') => text.push('\\'),
+                Some(other) => return Err(format!("unknown escape `\\{other}`")),
+                None => return Err("it ends in a lone `\\`".to_string()),
+            },
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(format!("unclosed `{{{name}`")),
+                    }
+                }
+                let field = match name.as_str() {
+                    "name" => ListField::Name,
+                    "command" => ListField::Command,
+                    "kind" => ListField::Kind,
+                    _ => {
+                        return Err(format!(
+                            "unknown placeholder `{{{name}}}` (expected {{name}}, {{command}} or {{kind}})"
+                        ));
+                    }
+                };
+                parts.push(FormatPart::Text(std::mem::take(&mut text)));
+                parts.push(FormatPart::Field(field));
+            }
+            _ => text.push(c),
+        }
+    }
+    parts.push(FormatPart::Text(text));
+    parts.retain(|part| *part != FormatPart::Text(String::new()));
+    Ok(parts)
+}
+
+/// Show the config file in use, the shell settings and every mapping as written
+/// (per-shell overrides as `<shell>.<name>`), for a quick look at a setup.
+/// Without a config it says so and points at the wizard; both exit 0. With -v
+/// it also says which project config and plugins were merged in.
+/// Called as: semantic info [--json]
+fn cmd_info(args: &[String], flags: &GlobalFlags) {
+    let json = flags.json || args.iter().any(|a| a == "--json");
+    let path = config::SemanticConfig::config_path();
+
+    if !path.is_file() {
+        if json {
+            print_json(&serde_json::json!({ "configured": false, "config": path }));
+        } else {
+            println!("Not configured: there is no {}.", config::display_path(&path));
+            println!("Run `semantic` (no args) to set it up.");
+        }
+        return;
+    }
+
+    let (config, source) = match load_config_with_source(flags) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            print_error(json, &format!("Failed to load config: {e}"), None);
+            exit(e.exit_code());
+        }
+    };
+
+    let mut commands: Vec<(String, String)> = config
+        .commands
+        .iter()
+        .map(|(name, real_cmd)| (name.clone(), real_cmd.clone()))
+        .chain(config.shell_commands.iter().flat_map(|(shell, overrides)| {
+            overrides.iter().map(move |(name, real_cmd)| (format!("{shell}.{name}"), real_cmd.clone()))
+        }))
+        .chain(
+            config
+                .composite_commands
+                .iter()
+                .filter(|(name, _)| !config.commands.contains_key(*name))
+                .map(|(name, steps)| (name.clone(), steps.join(" ; "))),
+        )
+        .collect();
+    commands.sort();
+    let mut paths: Vec<_> = config.paths.iter().collect();
+    paths.sort();
+
+    let verbose = flags.verbosity > 0;
+    if json {
+        let mut info = serde_json::json!({
+            "configured": true,
+            "config": path,
+            "profile": config::active_profile(),
+            "shells": {
+                "default": config.shells.default,
+                "enabled": config.shells.enabled,
+                "on_new_shell": config.shells.on_new_shell,
+            },
+            "commands": commands
+                .into_iter()
+                .map(|(name, real_cmd)| (name, real_cmd.into()))
+                .collect::<serde_json::Map<_, _>>(),
+            "paths": config.paths,
+            "os_overridden": config.os_overridden.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        });
+        if verbose {
+            info["project_config"] = serde_json::json!(source.project_config_path);
+            info["plugins"] = serde_json::json!(source.plugins_loaded);
+        }
+        print_json(&info);
+        return;
+    }
+
+    println!("Config:     {}", config::display_path(&path));
+    println!("Profile:    {}", config::active_profile());
+    println!("Shell:      {}", config.shells.default);
+    println!("Enabled:    {}", config.shells.enabled.join(", "));
+    println!("New shells: {}", config.shells.on_new_shell);
+    // where the mappings came from besides the config file
+    if verbose {
+        let project = source.project_config_path.as_deref().map(config::display_path);
+        println!("Project:    {}", project.unwrap_or_else(|| "(none)".to_string()));
+        let plugins = if source.plugins_loaded.is_empty() {
+            "(none)".to_string()
+        } else {
+            source.plugins_loaded.join(", ")
+        };
+        println!("Plugins:    {plugins}");
+    }
+
+    println!();
+    println!("Commands:");
+    if commands.is_empty() {
+        println!("  (none)");
+    }
+    let width = commands.iter().map(|(name, _)| display_width(name)).max().unwrap_or(0);
+    for (name, real_cmd) in &commands {
+        let key = config::MappingKey::Command(name.clone());
+        println!("  {}  {real_cmd}{}", pad(name, width), os_override_note(&config, &key));
+    }
+
+    println!();
+    println!("Paths:");
+    if paths.is_empty() {
+        println!("  (none)");
+    }
+    let width = paths.iter().map(|(virtual_path, _)| display_width(virtual_path)).max().unwrap_or(0);
+    for (virtual_path, real_path) in paths {
+        let key = config::MappingKey::Path(virtual_path.clone());
+        println!("  {}  {real_path}{}", pad(virtual_path, width), os_override_note(&config, &key));
+    }
+}
+
+/// For a mapping this OS's `[overrides.<os>]` replaced, "  (linux override)";
+/// empty for the rest.
+fn os_override_note(config: &config::SemanticConfig, key: &config::MappingKey) -> String {
+    if config.os_overridden.contains(key) {
+        format!("  ({} override)", env::consts::OS)
+    } else {
+        String::new()
+    }
+}
+
+/// For a mapping from a pack or plugin, which one by file name, e.g.
+/// "  (plugin: docker)"; empty for the rest.
+fn pack_note(config: &config::SemanticConfig, key: &config::MappingKey) -> String {
+    match config.pack_sources.get(key) {
+        Some(pack) => format!(
+            "  ({}: {})",
+            config::pack_kind(pack),
+            pack.file_stem().unwrap_or_default().display()
+        ),
+        None => String::new(),
+    }
+}
+
+/// How many terminal columns `text` takes: emoji and CJK take two, so table
+/// columns are measured with this rather than a char count.
+fn display_width(text: &str) -> usize {
+    unicode_width::UnicodeWidthStr::width(text)
+}
+
+/// `text` with spaces after it to fill `width` columns (see `display_width`).
+fn pad(text: &str, width: usize) -> String {
+    format!("{text}{}", " ".repeat(width.saturating_sub(display_width(text))))
+}
+
+/// Pretty-print a JSON value to stdout.
+fn print_json(value: &serde_json::Value) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).expect("JSON values always serialize")
+    );
+}
+
+/// Report an error on stderr, with a "did you mean" hint if there is one.
+/// With `json` it's a single `{"error": ..., "suggestion": ...}` object instead,
+/// so a program reading stderr doesn't have to parse the human wording.
+fn print_error(json: bool, message: &str, suggestion: Option<&str>) {
+    if json {
+        let mut error = serde_json::json!({ "error": message });
+        if let Some(suggestion) = suggestion {
+            error["suggestion"] = suggestion.into();
+        }
+        eprintln!("{error}");
+        return;
+    }
+    eprintln!("{message}");
+    if let Some(suggestion) = suggestion {
+        eprintln!("did you mean `{suggestion}`?");
+    }
+}
+
+/// Show how a semantic command or virtual path resolves.
+/// For commands: the real command, where the mapping came from, and the binary that runs.
+/// Called as: semantic which [--plain] <name|virtual_path>
+fn cmd_which(args: &[String], flags: &GlobalFlags) {
+    let plain = args.iter().any(|a| a == "--plain");
+    let json = (flags.json && !plain) || args.iter().any(|a| a == "--json");
+    let Some(name) = args.iter().find(|a| !a.starts_with("--")) else {
+        print_error(json, "Usage: semantic which [--plain | --json] <command|path>", None);
+        exit(1);
+    };
+
+    let config = match load_expanded_config(flags) {
+        Ok(c) => c,
+        Err(e) => {
+            print_error(json, &format!("Failed to load config: {e}"), None);
+            exit(e.exit_code());
+        }
+    };
+
+    if let Some(real_cmd) = config.commands.get(name) {
+        if plain {
+            println!("{real_cmd}");
+            return;
+        }
+
+        if json {
+            print_json(&which_json(&config, name).expect("a mapped command"));
+            return;
+        }
+
+        let source = mapping_source(&config, name, real_cmd);
+        let program = config::real_program(real_cmd);
+        let path = program
+            .filter(|p| !shell::SHELL_BUILTINS.contains(p))
+            .and_then(shell::find_in_path);
+
+        println!("{name} → {real_cmd}");
+        println!("  source: {source}");
+
+        let binary = match (program, path) {
+            (Some(program), _) if shell::SHELL_BUILTINS.contains(&program) => {
+                "shell builtin".to_string()
+            }
+            (Some(_), Some(path)) => path.display().to_string(),
+            (Some(program), None) => format!("{program} (not found in $PATH)"),
+            (None, _) => "(empty command)".to_string(),
+        };
+        println!("  binary: {binary}");
+        return;
+    }
+
+    if let Some(steps) = config.composite_commands.get(name) {
+        if plain {
+            for step in steps {
+                println!("{step}");
+            }
+            return;
+        }
+        if json {
+            print_json(&which_json(&config, name).expect("a composite command"));
+            return;
+        }
+
+        println!("{name} → {} steps", steps.len());
+        for (i, step) in steps.iter().enumerate() {
+            println!("  {}. {step}", i + 1);
+        }
+        return;
+    }
+
+    if let Some(real_path) = config.paths.get(name) {
+        if plain {
+            println!("{real_path}");
+        } else if json {
+            print_json(&which_json(&config, name).expect("a virtual path"));
+        } else {
+            println!("{name} → {real_path}");
+        }
+        return;
+    }
+
+    let known = config
+        .commands
+        .keys()
+        .chain(config.composite_commands.keys())
+        .chain(config.paths.keys());
+    print_error(
+        json,
+        &format!("Unknown semantic command or path: {name}"),
+        config::suggest(name, known),
+    );
+    exit(1);
+}
+
+/// `semantic which --json`: the name, the map it's in (`commands` or `paths`)
+/// and what it expands to, plus the source and binary for a single command.
+/// None if `name` isn't mapped.
+fn which_json(config: &config::SemanticConfig, name: &str) -> Option<serde_json::Value> {
+    if let Some(real_cmd) = config.commands.get(name) {
+        let path = config::real_program(real_cmd)
+            .filter(|p| !shell::SHELL_BUILTINS.contains(p))
+            .and_then(shell::find_in_path);
+        return Some(serde_json::json!({
+            "name": name,
+            "map": "commands",
+            "expansion": real_cmd,
+            "source": mapping_source(config, name, real_cmd),
+            "binary": path,
+        }));
+    }
+    if let Some(steps) = config.composite_commands.get(name) {
+        return Some(serde_json::json!({ "name": name, "map": "commands", "expansion": steps }));
+    }
+    let real_path = config.paths.get(name)?;
+    Some(serde_json::json!({ "name": name, "map": "paths", "expansion": real_path }))
+}
+
+/// Describe the semantic commands: with no name, every one by category with a
+/// line on what it does (see `config::COMMAND_DOCS`; anything else is "runs: ...");
+/// with a name, its real command, description and a usage example.
+/// Called as: semantic help [<command>]
+fn cmd_help(args: &[String], flags: &GlobalFlags) {
+    let config = match load_expanded_config(flags) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Failed to load config: {e}");
+            exit(e.exit_code());
+        }
+    };
+
+    let Some(name) = args.first() else {
+        print_command_overview(&config);
+        return;
+    };
+
+    if let Some(real_cmd) = config.commands.get(name) {
+        let doc = config::command_doc(real_cmd);
+        // the runs: line below already says all we know about a custom one
+        match doc {
+            Some(doc) => println!("{name} — {}", doc.description),
+            None => println!("{name}"),
+        }
+        println!("  runs:     {real_cmd}");
+        let example = doc.map_or("", |doc| doc.example);
+        println!("  example:  {}", format!("{name} {example}").trim_end());
+        println!();
+        println!("Anything after `{name}` is passed on to `{real_cmd}`, so its own flags work too.");
+        return;
+    }
+
+    if let Some(steps) = config.composite_commands.get(name) {
+        println!("{name} — runs {} commands in turn, stopping at the first that fails", steps.len());
+        for (i, step) in steps.iter().enumerate() {
+            println!("  {}. {step}", i + 1);
+        }
+        return;
+    }
+
+    eprintln!("Unknown semantic command: {name}");
+    let known = config.commands.keys().chain(config.composite_commands.keys());
+    if let Some(suggestion) = config::suggest(name, known) {
+        eprintln!("Did you mean `{suggestion}`?");
+    }
+    eprintln!("Run `semantic --help` for semantic's own subcommands.");
+    exit(1);
+}
+
+/// Print a completion script for `semantic` in `shell` (see
+/// `shell::generate_cli_completions`), with the current semantic commands.
+/// Called as: semantic completions <shell>
+fn cmd_completions(args: &[String], flags: &GlobalFlags) {
+    let Some(shell) = args.first() else {
+        eprintln!("Usage: semantic completions <{}>", shell::CLI_COMPLETION_SHELLS.join("|"));
+        exit(1);
+    };
+    let shell = shell::normalize_shell_name(shell);
+    if !shell::CLI_COMPLETION_SHELLS.contains(&shell.as_str()) {
+        eprintln!(
+            "No completions for {shell}; semantic completes in {}.",
+            shell::CLI_COMPLETION_SHELLS.join(", ")
+        );
+        exit(1);
+    }
+
+    let config = match load_expanded_config(flags) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Failed to load config: {e}");
+            exit(e.exit_code());
+        }
+    };
+    let mut commands: Vec<(String, String)> = config
+        .commands_for(&shell)
+        .into_iter()
+        .chain(config.composite_commands.iter().map(|(name, steps)| (name.clone(), steps.join(" && "))))
+        .collect();
+    commands.sort();
+    print!("{}", shell::generate_cli_completions(SUBCOMMANDS, &commands, &shell));
+}
+
+/// The no-argument `semantic help`: every semantic command under its category,
+/// categories in `COMMAND_DOCS` order and the user's own commands last.
+fn print_command_overview(config: &config::SemanticConfig) {
+    let mut rows: Vec<(&str, &str, String)> = config
+        .commands
+        .iter()
+        // traditional mappings run themselves; there's nothing to learn
+        .filter(|(name, real_cmd)| name != real_cmd)
+        .map(|(name, real_cmd)| {
+            let category = config::command_doc(real_cmd).map_or(config::CUSTOM_CATEGORY, |doc| doc.category);
+            (category, name.as_str(), config::describe_command(real_cmd))
+        })
+        .chain(config.composite_commands.iter().map(|(name, steps)| {
+            (config::CUSTOM_CATEGORY, name.as_str(), format!("runs: {}", steps.join(" && ")))
+        }))
+        .collect();
+    if rows.is_empty() {
+        println!("No semantic commands configured. Run `semantic` to pick a style.");
+        return;
+    }
+
+    let rank = |category: &str| {
+        config::COMMAND_DOCS
+            .iter()
+            .position(|doc| doc.category == category)
+            .unwrap_or(config::COMMAND_DOCS.len())
+    };
+    rows.sort_by(|a, b| rank(a.0).cmp(&rank(b.0)).then(a.1.cmp(b.1)));
+    let width = rows.iter().map(|row| row.1.chars().count()).max().unwrap_or(0);
+
+    let mut current = None;
+    for (category, name, description) in &rows {
+        if current != Some(category) {
+            if current.is_some() {
+                println!();
+            }
+            println!("{category}");
+            current = Some(category);
+        }
+        println!("  {name:width$}  {description}");
+    }
+    println!();
+    println!("Run `semantic help <command>` for its real command and an example.");
+}
+
+/// Reverse lookup: which semantic commands (or virtual paths) map to a real one.
+/// A command matches if its mapping equals the query or is a prefix of it
+/// (`rm -rf /tmp` is explained by `delete → rm -rf`). With no match, mappings
+/// that run the same program are listed as hints and the exit code is 1.
+/// `--plain` must come first, since anything after it is part of the real command.
+/// Called as: semantic explain [--plain] <real command...|path>
+fn cmd_explain(args: &[String], flags: &GlobalFlags) {
+    let (plain, query) = match args.split_first() {
+        Some((first, rest)) if first == "--plain" => (true, rest),
+        _ => (false, args),
+    };
+    if query.is_empty() {
+        eprintln!("Usage: semantic explain [--plain] <real command|path>");
+        exit(1);
+    }
+    let query: Vec<&str> = query.iter().flat_map(|a| a.split_whitespace()).collect();
+    let query_text = query.join(" ");
+
+    let config = match load_expanded_config(flags) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Failed to load config: {e}");
+            exit(e.exit_code());
+        }
+    };
+
+    let print = |name: &str, real: &str| {
+        if plain {
+            println!("{name}");
+        } else {
+            println!("{name} → {real}");
+        }
+    };
+
+    let mut paths: Vec<_> = config
+        .paths
+        .iter()
+        .filter(|(_, real_path)| **real_path == query_text)
+        .collect();
+    paths.sort();
+
+    // compare token by token so `rm -r` doesn't count as a prefix of `rm -rf`
+    let mut commands: Vec<_> = config
+        .commands
+        .iter()
+        .filter(|(_, real_cmd)| {
+            let tokens: Vec<&str> = real_cmd.split_whitespace().collect();
+            !tokens.is_empty() && query.starts_with(&tokens)
+        })
+        .collect();
+    // the most specific mapping first
+    commands.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then(a.cmp(b)));
+
+    if !paths.is_empty() || !commands.is_empty() {
+        for (virtual_path, real_path) in paths {
+            print(virtual_path, real_path);
+        }
+        for (name, real_cmd) in commands {
+            print(name, real_cmd);
+        }
+        return;
+    }
+
+    let program = config::real_program(&query_text).unwrap_or_default();
+    let mut related: Vec<_> = config
+        .commands
+        .iter()
+        .filter(|(_, real_cmd)| config::real_program(real_cmd) == Some(program))
+        .collect();
+    related.sort();
+
+    eprintln!("No semantic command or path maps to `{query_text}`.");
+    if !related.is_empty() {
+        eprintln!("Mappings that also run `{program}`:");
+        for (name, real_cmd) in related {
+            print(name, real_cmd);
+        }
+    }
+    exit(1);
+}
+
+/// Describe where a command mapping comes from: a project config, a pack, an
+/// included file, the style defaults, or the user's own edits.
+fn mapping_source(config: &config::SemanticConfig, name: &str, real_cmd: &str) -> String {
+    // project configs are merged last, nearest last, so the nearest one that
+    // defines a key is where it comes from
+    if let Some(project) = config.project_configs.iter().rev().find(|project| {
+        config::SemanticConfig::load_from(project).is_ok_and(|p| p.commands.contains_key(name))
+    }) {
+        return format!("project config ({})", project.display());
+    }
+    if config.os_overridden.contains(&config::MappingKey::Command(name.to_string())) {
+        return format!("OS override ([overrides.{}])", env::consts::OS);
+    }
+    if let Some(pack) = config.pack_sources.get(&config::MappingKey::Command(name.to_string())) {
+        return format!("{} ({})", config::pack_kind(pack), pack.display());
+    }
+    if let Some(file) = config.include_sources.get(&config::MappingKey::Command(name.to_string())) {
+        return format!("included file ({})", file.display());
+    }
+
+    let style = &config.general.command_style;
+    let language = config.general.language.as_deref().unwrap_or("en");
+    let own = config.styles.own_commands(style).contains_key(name);
+    match config.styles.commands(style, language).get(name) {
+        Some(default) if default == real_cmd && own => {
+            format!("style default ({style}, from [styles.commands.{style}])")
+        }
+        Some(default) if default == real_cmd => format!("style default ({style})"),
+        Some(default) => format!("user override (style default is `{default}`)"),
+        None => "user-defined".to_string(),
+    }
+}
+
+/// Print the real path a virtual path translates to, for scripts:
+/// `real=$(semantic path /logs)`. Paths under a mapping keep their remainder.
+/// Exits 2 (printing the path unchanged) when no mapping applies, so that
+/// case can be told apart from errors. --all prints the whole path table.
+/// Called as: semantic path <virtual-path> | semantic path --all
+fn cmd_path(args: &[String], flags: &GlobalFlags) {
+    let all = args.iter().any(|a| a == "--all");
+    let virtual_path = args.iter().find(|a| !a.starts_with("--"));
+    if all == virtual_path.is_some() {
+        eprintln!("Usage: semantic path <virtual-path> | semantic path --all");
+        exit(1);
+    }
+
+    let config = match load_config(flags) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Failed to load config: {e}");
+            exit(e.exit_code());
+        }
+    };
+
+    if all {
+        let mut paths: Vec<_> = config.paths.keys().collect();
+        paths.sort();
+        for virtual_path in paths {
+            let real_path = config.resolve_path(virtual_path).unwrap_or_default();
+            println!("{virtual_path} -> {real_path}");
+        }
+        return;
+    }
+
+    let virtual_path = virtual_path.expect("checked above");
+    match config.resolve_path(virtual_path) {
+        Some(real_path) => println!("{real_path}"),
+        None => {
+            println!("{virtual_path}");
+            exit(2);
+        }
+    }
+}
+
+/// Mount the virtual paths as a read-only filesystem and serve it until Ctrl-C.
+/// Called as: semantic mount <mountpoint>
+#[cfg(all(unix, feature = "fuse"))]
+fn cmd_mount(args: &[String], flags: &GlobalFlags) {
+    let Some(mountpoint) = args.first() else {
+        eprintln!("Usage: semantic mount <mountpoint>");
+        exit(1);
+    };
+
+    let config = match load_expanded_config(flags) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Failed to load config: {e}");
+            exit(e.exit_code());
+        }
+    };
+
+    if config.paths.is_empty() {
+        eprintln!("No virtual paths configured; nothing to mount.");
+        exit(1);
+    }
+
+    if !flags.quiet {
+        eprintln!("semantic: mounting at {mountpoint} (Ctrl-C to unmount)");
+    }
+
+    if let Err(e) = fs::mount(&config.paths, Path::new(mountpoint)) {
+        eprintln!("semantic mount: {e}");
+        exit(1);
+    }
+}
+
+/// Unmount a `semantic mount`, e.g. one left behind by a killed process.
+/// Called as: semantic umount <mountpoint>
+#[cfg(all(unix, feature = "fuse"))]
+fn cmd_umount(args: &[String]) {
+    let Some(mountpoint) = args.first() else {
+        eprintln!("Usage: semantic umount <mountpoint>");
+        exit(1);
+    };
+    if let Err(e) = fs::unmount(Path::new(mountpoint)) {
+        eprintln!("semantic umount: {e}");
+        exit(1);
+    }
+}
+
+#[cfg(not(all(unix, feature = "fuse")))]
+fn cmd_mount(_args: &[String], _flags: &GlobalFlags) {
+    eprintln!("{NO_FUSE}");
+    exit(1);
+}
+
+#[cfg(not(all(unix, feature = "fuse")))]
+fn cmd_umount(_args: &[String]) {
+    eprintln!("{NO_FUSE}");
+    exit(1);
+}
+
+#[cfg(not(all(unix, feature = "fuse")))]
+const NO_FUSE: &str = if cfg!(unix) {
+    "This semantic was built without FUSE support; rebuild it with `--features fuse`."
+} else {
+    "semantic mount needs FUSE, which isn't available on this platform."
+};
+
+/// Expose the virtual paths as symlinks to their real paths, or remove the
+/// ones made before. Exits 1 if any link had to be left alone.
+/// Called as: semantic link [--system] | --remove
+fn cmd_link(args: &[String], flags: &GlobalFlags) {
+    let system = args.iter().any(|a| a == "--system");
+    let remove = args.iter().any(|a| a == "--remove");
+    if let Some(arg) = args.iter().find(|a| *a != "--system" && *a != "--remove") {
+        eprintln!("semantic link: unknown argument `{arg}`");
+        eprintln!("Usage: semantic link [--system] | --remove");
+        exit(1);
+    }
+    if system && remove {
+        eprintln!("semantic link: --remove already removes both kinds of link; drop --system");
+        exit(1);
+    }
+
+    let result = if remove {
+        link::remove_all()
+    } else {
+        let config = match load_expanded_config(flags) {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("Failed to load config: {e}");
+                exit(e.exit_code());
+            }
+        };
+        if config.paths.is_empty() {
+            eprintln!("No virtual paths configured; nothing to link.");
+            exit(1);
+        }
+        link::link_all(&config.paths, system)
+    };
+    let report = match result {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("semantic link: {e}");
+            exit(1);
+        }
+    };
+
+    print_list("Created", &report.created);
+    print_list("Updated", &report.updated);
+    print_list("Removed", &report.removed);
+    if !flags.quiet {
+        print_list("Already in place", &report.unchanged);
+    }
+    if report.skipped.is_empty() {
+        if remove && report.removed.is_empty() {
+            println!("No links to remove.");
+        }
+    } else {
+        print_list("Left alone", &report.skipped);
+        exit(1);
+    }
+}
+
+/// Compare the installed shells (see `watch::installed_shells`) with
+/// `[shells] enabled` once, and set up or report each one that's missing, as
+/// `[shells] on_new_shell` says. Prints nothing when there's nothing new, and
+/// setting up is idempotent, so it can run at every login or from a timer.
+/// Called as: semantic scan-shells (or check-shells, its old name)
+fn cmd_scan_shells(flags: &GlobalFlags) {
+    let config = match load_config(flags) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Failed to load config: {e}");
+            exit(e.exit_code());
+        }
+    };
+
+    let on_new_shell = config.shells.on_new_shell.as_str();
+    let Some(mode) = watch::Mode::from_config(on_new_shell) else {
+        if on_new_shell != "ignore" && !flags.quiet {
+            eprintln!(
+                "semantic: [shells] on_new_shell is \"{on_new_shell}\" \
+                 (expected auto-setup, notify or ignore); not checking"
+            );
+        }
+        return;
+    };
+
+    let scanned = match watch::scan(Path::new(watch::ETC_SHELLS), &config.shells.enabled, Some(mode)) {
+        Ok(scanned) => scanned,
+        Err(e) => {
+            eprintln!("semantic scan-shells: {}: {e}", watch::ETC_SHELLS);
+            exit(1);
+        }
+    };
+
+    let mut failed = false;
+    for (name, scanned) in scanned {
+        match scanned {
+            watch::Scanned::SetUp(message) => println!("semantic: new shell {name}: {message}"),
+            watch::Scanned::Failed(e) => {
+                eprintln!("semantic: couldn't set up {name}: {e}");
+                failed = true;
+            }
+            watch::Scanned::Reported => {
+                println!("semantic: {name} is installed but not enabled");
+                if let Some((rc, line)) = shell::rc_setup(name) {
+                    println!("  to use semantic in it, add `{line}` to {rc}");
+                    println!("  and `{name}` to [shells] enabled");
+                }
+            }
+        }
+    }
+    if failed {
+        exit(1);
+    }
+}
+
+/// Watch $PATH in the foreground for shells installed while it runs, and set
+/// each one up or report it, as `[shells] on_new_shell` says.
+/// Called as: semantic watch
+fn cmd_watch(flags: &GlobalFlags) {
+    let config = match load_config(flags) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Failed to load config: {e}");
+            exit(e.exit_code());
+        }
+    };
+
+    let on_new_shell = &config.shells.on_new_shell;
+    let Some(mode) = watch::Mode::from_config(on_new_shell) else {
+        eprintln!("[shells] on_new_shell is \"{on_new_shell}\", so there's nothing to watch for.");
+        eprintln!("Set it to \"auto-setup\" or \"notify\" to use semantic watch.");
+        exit(1);
+    };
+
+    if let Err(e) = watch::run(mode) {
+        eprintln!("semantic watch: {e}");
+        exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, Sandbox};
+
+    /// Options for running a command in-process: never exec, print nothing extra.
+    fn run_options() -> TranslateOptions {
+        TranslateOptions {
+            verbose: false,
+            dry_run: false,
+            exec: false,
+            echo: false,
+            json: false,
+            timeout: None,
+            literal_args: false,
+        }
+    }
+
+    fn words(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn translate_appends_to_history() {
+        let _sandbox = Sandbox::new();
+        let mut config = testutil::config("[commands]\nok = \"true\"");
+        config.general.history = true;
+
+        assert_eq!(translate(&config, "bash", &words("ok"), &run_options()), 0);
+
+        let entries = history::read().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "ok");
+        assert_eq!(entries[0].resolved, ["true"]);
+        assert_eq!(entries[0].exit_code, 0);
+    }
+
+    #[test]
+    fn translate_leaves_history_alone_when_off() {
+        let _sandbox = Sandbox::new();
+        let config = testutil::config("[commands]\nok = \"true\"");
+
+        assert_eq!(translate(&config, "bash", &words("ok"), &run_options()), 0);
+        assert!(!history::history_path().exists());
+    }
+
+    #[test]
+    fn unknown_command_suggests_the_closest_name() {
+        let config = testutil::config("[commands]\ninstall = \"true\"\nremove = \"true\"");
+        let error = resolve_steps(&config, "bash", &words("instal firefox"), false, false).unwrap_err();
+        assert!(matches!(&error, SemanticError::UnknownCommand { name, .. } if name == "instal"));
+        assert_eq!(error.suggestion(), Some("install"));
+
+        let error = resolve_steps(&config, "bash", &words("firefox"), false, false).unwrap_err();
+        assert_eq!(error.suggestion(), None);
+    }
+
+    #[test]
+    fn piped_mapping_runs_through_the_configured_shell() {
+        let config = testutil::config("[commands]\nshow-log = \"journalctl -f | grep ERROR\"");
+        let steps = resolve_steps(&config, "bash", &words("show-log --since today"), false, false).unwrap();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].1, ["bash", "-c", "journalctl -f | grep ERROR --since today"]);
+    }
+
+    #[test]
+    fn list_json_parses_back_into_commands_and_paths() {
+        let config = testutil::config(
+            "[commands]\ninstall = \"sudo pacman -S\"\nrefresh = [\"update\", \"clean\"]\n\
+             [paths]\n\"/apps\" = \"/usr/bin\"",
+        );
+        let commands = config.commands_for("bash");
+
+        let text = serde_json::to_string(&list_json(&config, &commands, "bash", true, true)).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(parsed["commands"]["install"], "sudo pacman -S");
+        assert_eq!(parsed["commands"]["refresh"], serde_json::json!(["update", "clean"]));
+        assert_eq!(parsed["paths"], serde_json::json!({ "/apps": "/usr/bin" }));
+
+        let paths_only = list_json(&config, &commands, "bash", false, true);
+        assert!(paths_only.get("commands").is_none());
+    }
+
+    #[test]
+    fn which_json_names_the_map_and_the_expansion() {
+        let config =
+            testutil::config("[commands]\ninstall = \"sudo pacman -S\"\n[paths]\n\"/apps\" = \"/usr/bin\"");
+
+        let text = serde_json::to_string(&which_json(&config, "install").unwrap()).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(parsed["name"], "install");
+        assert_eq!(parsed["map"], "commands");
+        assert_eq!(parsed["expansion"], "sudo pacman -S");
+
+        let path = which_json(&config, "/apps").unwrap();
+        let expected = serde_json::json!({ "name": "/apps", "map": "paths", "expansion": "/usr/bin" });
+        assert_eq!(path, expected);
+        assert_eq!(which_json(&config, "nothing"), None);
+    }
+
+    #[test]
+    fn batch_runs_every_line() {
+        let sandbox = Sandbox::new();
+        sandbox.write_config("[commands]\nfirst = \"touch one\"\nsecond = \"touch two\"");
+        let config = config::SemanticConfig::load().unwrap();
+
+        let input = "first\n\n# a comment\nsecond\n";
+        assert_eq!(run_batch(&config, "bash", input.as_bytes(), &run_options(), false, 1), 0);
+        assert!(sandbox.path("home/one").exists());
+        assert!(sandbox.path("home/two").exists());
+    }
+
+    #[test]
+    fn batch_stops_at_the_first_failure_unless_told_to_keep_going() {
+        let sandbox = Sandbox::new();
+        sandbox.write_config("[commands]\nfail = \"false\"\nmark = \"touch marked\"");
+        let config = config::SemanticConfig::load().unwrap();
+
+        assert_eq!(run_batch(&config, "bash", "fail\nmark\n".as_bytes(), &run_options(), false, 1), 1);
+        assert!(!sandbox.path("home/marked").exists());
+
+        assert_eq!(run_batch(&config, "bash", "fail\nmark\n".as_bytes(), &run_options(), true, 1), 1);
+        assert!(sandbox.path("home/marked").exists());
+    }
+
+    #[test]
+    fn profile_save_then_load_round_trips() {
+        let sandbox = Sandbox::new();
+        sandbox.write_config("[commands]\ninstall = \"sudo pacman -S\"");
+        cmd_profile(&words("save work"));
+        assert!(sandbox.path("config/profiles/work.toml").is_file());
+
+        sandbox.write_config("[commands]\ninstall = \"sudo apt install\"");
+        cmd_profile(&words("load work"));
+
+        let config = config::SemanticConfig::load().unwrap();
+        assert_eq!(config.commands["install"], "sudo pacman -S");
+        let backup = std::fs::read_to_string(sandbox.path("config/config.toml.bak")).unwrap();
+        assert!(backup.contains("sudo apt install"), "{backup}");
+    }
+
+    fn strings(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn one_quoted_argument_is_a_chain() {
+        let (commands, operators) = chain_of(&strings(&["update && install firefox"])).unwrap().unwrap();
+        assert_eq!(commands, [words("update"), words("install firefox")]);
+        assert_eq!(operators, ["&&"]);
+
+        let (commands, operators) = chain_of(&strings(&["show | grep foo"])).unwrap().unwrap();
+        assert_eq!(commands, [words("show"), words("grep foo")]);
+        assert_eq!(operators, ["|"]);
+
+        assert_eq!(chain_of(&strings(&["update"])), None);
+        assert!(chain_of(&strings(&["update &&"])).unwrap().is_err());
+    }
+
+    #[test]
+    fn operators_in_quotes_or_separate_arguments_are_plain_arguments() {
+        let (commands, operators) = chain_of(&strings(&["search '|' file.txt"])).unwrap().unwrap();
+        assert_eq!(commands, [strings(&["search", "|", "file.txt"])]);
+        assert!(operators.is_empty());
+
+        // as the shell hands over `search '|' file.txt` or `install a && b` in "$@"
+        let args = strings(&["search", "|", "file.txt"]);
+        assert_eq!(chain_of(&args), None);
+        assert_eq!(chain_of(&strings(&["install", "a", "&&", "b"])), None);
+
+        let config = testutil::config("[commands]\nsearch = \"grep -rn\"");
+        let steps = resolve_steps(&config, "bash", &args, false, false).unwrap();
+        assert_eq!(steps[0].1, ["grep", "-rn", "|", "file.txt"]);
+    }
+
+    #[test]
+    fn an_and_chain_runs_both_commands() {
+        let sandbox = Sandbox::new();
+        let config = testutil::config("[commands]\nfirst = \"touch one\"\nsecond = \"touch two\"");
+        let (commands, operators) = chain_of(&strings(&["first && second"])).unwrap().unwrap();
+
+        assert_eq!(translate_chain(&config, "bash", &commands, &operators, &run_options()), 0);
+        assert!(sandbox.path("home/one").exists());
+        assert!(sandbox.path("home/two").exists());
+    }
+
+    #[test]
+    fn a_pipe_feeds_one_command_into_the_next() {
+        let sandbox = Sandbox::new();
+        let config = testutil::config("[commands]\nemit = \"echo hello\"\nkeep = \"sort -o piped.txt\"");
+        let (commands, operators) = chain_of(&strings(&["emit | keep"])).unwrap().unwrap();
+
+        assert_eq!(translate_chain(&config, "bash", &commands, &operators, &run_options()), 0);
+        assert_eq!(std::fs::read_to_string(sandbox.path("home/piped.txt")).unwrap(), "hello\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_signal_exit_becomes_128_plus_the_signal() {
+        let status = |script: &str| Command::new("sh").args(["-c", script]).status().unwrap();
+
+        assert_eq!(exit_code(status("kill -TERM $$")), 128 + 15);
+        assert_eq!(exit_code(status("kill -KILL $$")), 128 + 9);
+        assert!(killed_by_sigpipe(status("kill -PIPE $$")));
+        assert!(!killed_by_sigpipe(status("kill -TERM $$")));
+        assert_eq!(exit_code(status("exit 3")), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn translate_passes_on_a_signal_exit() {
+        let _sandbox = Sandbox::new();
+        let config = testutil::config("[commands]\nscript = \"sh -c {1}\"");
+        let args = strings(&["script", "kill -TERM $$"]);
+        assert_eq!(translate(&config, "bash", &args, &run_options()), 143);
+    }
+
+    #[test]
+    fn emoji_commands_translate() {
+        let mut config = testutil::config("");
+        config.commands = config::style_commands("emoji", "en");
+
+        let steps = resolve_steps(&config, "bash", &strings(&["📋", "/tmp"]), false, false).unwrap();
+        assert_eq!(steps[0].1, ["ls", "-la", "/tmp"]);
+
+        let error = resolve_steps(&config, "bash", &strings(&["📋📋"]), false, false).unwrap_err();
+        assert!(matches!(error, SemanticError::UnknownCommand { .. }));
+    }
+
+    #[test]
+    fn wide_names_are_padded_by_columns() {
+        assert_eq!(display_width("📋"), 2);
+        assert_eq!(pad("📋", 4), "📋  ");
+        assert_eq!(pad("ls", 4), "ls  ");
+        assert_eq!(pad("too-long", 4), "too-long");
+    }
+
+    #[test]
+    fn list_format_lays_out_each_mapping() {
+        let config = testutil::config(
+            "[commands]\ninstall = \"sudo pacman -S\"\nship = [\"make\", \"make deploy\"]\n\
+             [paths]\n\"/apps\" = \"/usr/bin\"",
+        );
+        let format = parse_list_format("{name}\\t{command}").unwrap();
+        assert_eq!(
+            list_formatted(&config, &config.commands_for("bash"), &format),
+            "install\tsudo pacman -S\nship\tmake ; make deploy\n/apps\t/usr/bin\n"
+        );
+
+        let format = parse_list_format("{kind}: {{{name}}}\\n").unwrap();
+        let out = list_formatted(&config, &config.commands_for("bash"), &format);
+        assert!(out.starts_with("command: {install}\n\n"), "{out}");
+    }
+
+    #[test]
+    fn list_format_rejects_unknown_placeholders_and_escapes() {
+        let error = parse_list_format("{name}\\t{real}").unwrap_err();
+        assert!(error.starts_with("unknown placeholder `{real}`"), "{error}");
+        assert_eq!(parse_list_format("{name").unwrap_err(), "unclosed `{name`");
+        assert_eq!(parse_list_format("{name}\\x").unwrap_err(), "unknown escape `\\x`");
+    }
+
+    #[test]
+    fn stats_since_takes_a_duration_or_a_date() {
+        let now = Duration::from_secs(1_772_323_200 + 3 * 3_600);
+        assert_eq!(since_cutoff("2h", now), Some(1_772_323_200 + 3_600));
+        assert_eq!(since_cutoff("1w", now), Some(1_772_323_200 + 3 * 3_600 - 604_800));
+        assert_eq!(since_cutoff("2026-03-01", now), Some(1_772_323_200));
+        assert_eq!(since_cutoff("2024-02-29", now), Some(1_709_164_800));
+        // further back than the epoch is everything
+        assert_eq!(since_cutoff("99999w", now), Some(0));
+
+        for bad in ["", "30", "30y", "h", "2026-03", "2025-02-29", "2026-13-01", "yesterday"] {
+            assert_eq!(since_cutoff(bad, now), None, "{bad}");
+        }
+    }
+
+    /// A history log with a line that doesn't parse, which is skipped.
+    const HISTORY: &str = r#"
+{"timestamp":1772323200,"name":"ship","resolved":["make"],"exit_code":0,"duration_ms":5}
+{"timestamp":1772323210,"name":"ls","resolved":["ls"],"exit_code":0,"duration_ms":1}
+{"timestamp":1772323230,"name":"install","resolved":["pacman"],"exit_code":1,"duration_ms":9}
+not json
+{"timestamp":1772323260,"name":"ship","resolved":["make"],"exit_code":2,"duration_ms":5}
+{"timestamp":1772323320,"name":"ship","resolved":["make"],"exit_code":0,"duration_ms":5}
+"#;
+
+    /// `HISTORY` written to the sandbox's log and read back.
+    fn history_fixture() -> Vec<history::Entry> {
+        let path = history::history_path();
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, HISTORY.trim_start()).unwrap();
+        history::read().unwrap()
+    }
+
+    #[test]
+    fn stats_count_runs_and_failures_over_a_history() {
+        let _sandbox = Sandbox::new();
+        let entries = history_fixture();
+
+        let report = stats_report(&entries, None);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "COMMAND   RUNS  SUCCESS  LAST USED");
+        assert_eq!(lines[1], "ship         3      66%  2026-03-01 00:02:00");
+        assert_eq!(lines[2], "install      1       0%  2026-03-01 00:00:30");
+        assert_eq!(lines[3], "ls           1     100%  2026-03-01 00:00:10");
+        assert_eq!(lines[4..], ["", "Total runs: 5, failed: 2 (40%)"]);
+    }
+
+    #[test]
+    fn stats_top_keeps_the_most_used_and_counts_them_all() {
+        let _sandbox = Sandbox::new();
+        let entries = history_fixture();
+
+        let report = stats_report(&entries, Some(2));
+        let names: Vec<&str> = report.lines().skip(1).map_while(|line| line.split(' ').next()).collect();
+        assert_eq!(names, ["ship", "install", "", "Total"]);
+        assert!(report.ends_with("Total runs: 5, failed: 2 (40%)\n"), "{report}");
+        // more than there are is all of them
+        assert_eq!(stats_report(&entries, Some(10)), stats_report(&entries, None));
+    }
+
+    /// What `semantic list` would show of a small config in bash, given its
+    /// section flags (`--commands`, `--paths`, `--all`) and `--filter`.
+    fn listed(
+        flags: &[&str],
+        filter: Option<&str>,
+    ) -> (config::SemanticConfig, std::collections::HashMap<String, String>) {
+        let mut config = testutil::config(
+            "[commands]\ninstall = \"sudo pacman -S\"\ngoto = \"cd\"\n\
+             ship = [\"make\", \"make deploy\"]\n\
+             [paths]\n\"/apps\" = \"/usr/bin\"\n\"/logs\" = \"/var/log\"",
+        );
+        let flags: Vec<String> = flags.iter().map(|f| f.to_string()).collect();
+        let (show_commands, show_paths) = list_sections(&flags);
+        let filter = filter.map(|pattern| glob::Pattern::new(pattern).unwrap());
+        let commands = list_filtered(&mut config, "bash", show_commands, show_paths, filter.as_ref());
+        (config, commands)
+    }
+
+    /// `semantic list`'s plain text for `listed(flags, filter)`: one name a line.
+    fn listed_text(flags: &[&str], filter: Option<&str>) -> String {
+        let (config, commands) = listed(flags, filter);
+        list_formatted(&config, &commands, &parse_list_format("{name}").unwrap())
+    }
+
+    #[test]
+    fn list_shows_only_command_names_by_default() {
+        assert_eq!(listed_text(&[], None), "goto\ninstall\nship\n");
+        assert_eq!(listed_text(&["--commands"], None), "goto\ninstall\nship\n");
+        assert_eq!(list_sections(&[]), (true, false));
+    }
+
+    #[test]
+    fn list_paths_alone_or_all_of_it() {
+        let (config, commands) = listed(&["--paths"], None);
+        assert!(commands.is_empty() && config.composite_commands.is_empty());
+        assert_eq!(listed_text(&["--paths"], None), "/apps\n/logs\n");
+
+        let all = "goto\ninstall\nship\n/apps\n/logs\n";
+        assert_eq!(listed_text(&["--all"], None), all);
+        assert_eq!(listed_text(&["--commands", "--paths"], None), all);
+        assert_eq!(listed_text(&["--paths", "--all"], None), all);
+    }
+
+    #[test]
+    fn list_filter_narrows_every_section_and_format() {
+        assert_eq!(listed_text(&["--all"], Some("*l*")), "install\n/logs\n");
+        assert_eq!(listed_text(&[], Some("*l*")), "install\n");
+        let (config, commands) = listed(&["--all"], Some("*l*"));
+        let tsv = parse_list_format("{name}\\t{command}").unwrap();
+        let lines = list_formatted(&config, &commands, &tsv);
+        assert_eq!(lines, "install\tsudo pacman -S\n/logs\t/var/log\n");
+
+        // a filter that only matches paths leaves nothing by default
+        assert_eq!(listed_text(&[], Some("/a*")), "");
+        assert_eq!(listed_text(&["--all"], Some("/a*")), "/apps\n");
+
+        let (config, commands) = listed(&["--paths"], Some("/a*"));
+        let json = list_json(&config, &commands, "bash", false, true);
+        assert_eq!(json["paths"], serde_json::json!({ "/apps": "/usr/bin" }));
+        assert!(json.get("commands").is_none(), "{json}");
+    }
+
+    /// A program's output as it arrives, one read per piece.
+    struct Pieces(Vec<&'static [u8]>);
+
+    impl io::Read for Pieces {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Ok(0);
+            }
+            let piece = self.0.remove(0);
+            buffer[..piece.len()].copy_from_slice(piece);
+            Ok(piece.len())
+        }
+    }
+
+    fn rewritten(pieces: Vec<&'static [u8]>) -> Vec<u8> {
+        let config = testutil::config("[paths]\n\"/data\" = \"/srv/data\"");
+        let mut out = Vec::new();
+        copy_rewritten(Pieces(pieces), &mut out, &config::PathRewriter::new(&config));
+        out
+    }
+
+    #[test]
+    fn rewritten_output_keeps_a_path_split_between_reads_whole() {
+        let out = rewritten(vec![b"/srv/data/one\n/srv/da", b"ta/two\n/srv", b"/data"]);
+        assert_eq!(String::from_utf8(out).unwrap(), "/data/one\n/data/two\n/data");
+    }
+
+    #[test]
+    fn rewritten_output_keeps_invalid_bytes_and_rewrites_around_them() {
+        let out = rewritten(vec![b"/srv/data/a \xff /srv/data/b\n"]);
+        assert_eq!(out, b"/data/a \xff /data/b\n");
+    }
+}
@@ -0,0 +1,71 @@
+// script/mod.rs
+// Semantic scripts: files of semantic commands, one per line, run in order by
+// `semantic run`. Written with semantic names (`install firefox`) rather than
+// real ones, a script works on any machine whose config maps those names.
+//
+//   #!/usr/bin/env -S semantic run
+//   # set up a new machine
+//   update
+//   install git "visual studio code"
+//
+// Lines starting with `#` are comments (the shebang line included); words are
+// split like a shell would, quotes and backslashes and all. Chaining commands
+// with `&&`, `||` or `|` isn't supported — each line is one command.
+
+use std::fmt;
+
+use crate::shell;
+
+/// One line of a script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptLine {
+    /// A semantic command and its arguments, unquoted.
+    Command { name: String, args: Vec<String> },
+    /// A comment or a blank line.
+    Comment,
+}
+
+/// A line that can't be read as a command, by its (1-based) line number.
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Reads semantic scripts.
+pub struct SemanticScript;
+
+impl SemanticScript {
+    /// Split `source` into one `ScriptLine` per line, so the nth entry is line n.
+    /// Fails at the first line with an unclosed quote or a chain of commands.
+    pub fn parse(source: &str) -> Result<Vec<ScriptLine>, ParseError> {
+        source
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    return Ok(ScriptLine::Comment);
+                }
+                let error = |message: String| ParseError { line: i + 1, message };
+
+                let (mut commands, operators) =
+                    shell::split_chain(line).map_err(|e| error(format!("`{line}` {e}")))?;
+                if let Some(op) = operators.first() {
+                    return Err(error(format!("`{op}` joins two commands; put each on its own line")));
+                }
+                let mut words = commands.pop().unwrap_or_default().into_iter();
+                let name = words.next().ok_or_else(|| error("no command".to_string()))?;
+                Ok(ScriptLine::Command { name, args: words.collect() })
+            })
+            .collect()
+    }
+}
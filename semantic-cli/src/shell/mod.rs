@@ -6,30 +6,939 @@
 //   Fish:  semantic init | source
 //   Bash:  eval "$(semantic init)"
 //   Zsh:   eval "$(semantic init)"
+//   Nu:    semantic init nu | save -f ~/.config/semantic/init.nu
+//          then add `source ~/.config/semantic/init.nu` to the file at $nu.config-path
+//          (nushell can't eval generated code at startup, so it has to be saved first)
+//   PowerShell: semantic init pwsh | Out-String | Invoke-Expression
+//          in $PROFILE.CurrentUserAllHosts (or $PROFILE for just the current host)
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 
-/// Detect the current shell from $SHELL env var.
-/// Returns just the shell name (e.g. "fish", "bash", "zsh").
-pub fn detect_shell() -> String {
-    env::var("SHELL")
-        .ok()
-        .and_then(|s| s.rsplit('/').next().map(String::from))
-        .unwrap_or_else(|| "bash".to_string())
+use crate::doctor::INIT_MARKER;
+use crate::error::SemanticError;
+
+/// Commands that are built into the shell rather than living in $PATH.
+/// These can't be looked up on disk, so PATH checks treat them as always present.
+pub const SHELL_BUILTINS: &[&str] = &[
+    "cd", "source", ".", "export", "alias", "unalias", "set", "unset", "exit", "exec",
+    "history", "pushd", "popd", "dirs", "jobs", "fg", "bg", "type", "echo", "test",
+];
+
+/// Names an alias or function would hide from the shell besides `SHELL_BUILTINS`:
+/// more builtins, and keywords scripts rely on, like `time`.
+const SHADOWABLE_WORDS: &[&str] = &[
+    "builtin", "command", "eval", "false", "hash", "kill", "let", "local", "printf", "read",
+    "return", "shift", "time", "trap", "true", "ulimit", "umask", "wait", "function", "if",
+    "then", "else", "fi", "for", "while", "until", "do", "done", "case", "esac", "select",
+];
+
+/// Builtins that act on the shell that runs them (its directory, its variables,
+/// what it has sourced), so a mapping to one is useless via `semantic translate`,
+/// which runs in a child process. bash, zsh and fish get a function that runs
+/// the builtin itself instead.
+pub const CURRENT_SHELL_BUILTINS: &[&str] =
+    &["cd", "pushd", "popd", "source", ".", "export", "unset"];
+
+/// Shell syntax that only means something to a shell (pipes, chaining,
+/// redirection, command substitution). Mappings containing any of these
+/// are run through `<shell> -c` instead of being split into argv.
+const SHELL_OPERATORS: &[&str] = &["|", "&&", "||", ";", ">", "<", "`"];
+
+/// Shells that can run a POSIX-ish `-c` script with the operators above.
+/// nushell's syntax differs too much, so it falls back to sh like an unknown shell.
+const SCRIPT_SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+/// Shells semantic generates init code for, and so the ones worth detecting.
+pub const KNOWN_SHELLS: &[&str] = &["fish", "bash", "zsh", "nu", "pwsh", "powershell"];
+
+/// The shells the setup wizard offers on this platform, most likely first.
+#[cfg(not(windows))]
+pub const PLATFORM_SHELLS: &[&str] = KNOWN_SHELLS;
+#[cfg(windows)]
+pub const PLATFORM_SHELLS: &[&str] = &["pwsh", "powershell", "nu", "bash"];
+
+/// How `detect_shell_with_source` found the shell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellSource {
+    /// $SEMANTIC_SHELL, set by the generated aliases (or by hand, e.g. in CI).
+    Override,
+    /// The nearest known shell among our parent processes.
+    ParentProcess,
+    /// $SHELL — the login shell, which isn't necessarily the one in use.
+    LoginShell,
+    /// Nothing said, so the shell the platform always has (PowerShell on Windows).
+    PlatformDefault,
+}
+
+impl ShellSource {
+    pub fn describe(self) -> &'static str {
+        match self {
+            ShellSource::Override => "from $SEMANTIC_SHELL",
+            ShellSource::ParentProcess => "from the parent process",
+            ShellSource::LoginShell => "from $SHELL",
+            ShellSource::PlatformDefault => "as the platform's default",
+        }
+    }
+}
+
+/// Detect the shell semantic was invoked from (e.g. "fish", "bash", "zsh").
+/// None if nothing says — callers fall back to the config's default shell.
+pub fn detect_shell() -> Option<String> {
+    detect_shell_with_source().map(|(shell, _)| shell)
+}
+
+/// `detect_shell`, along with where the answer came from: $SEMANTIC_SHELL,
+/// then the parent process chain, then $SHELL, then (on Windows, which has no
+/// $SHELL) PowerShell. Each is normalized, and one that isn't in `KNOWN_SHELLS`
+/// (sh, tcsh, a terminal multiplexer...) is passed over for the next.
+pub fn detect_shell_with_source() -> Option<(String, ShellSource)> {
+    if let Some(shell) = env::var("SEMANTIC_SHELL").ok().and_then(|s| known_shell(&s)) {
+        return Some((shell, ShellSource::Override));
+    }
+    if let Some(shell) = parent_shell() {
+        return Some((shell, ShellSource::ParentProcess));
+    }
+    if let Some(shell) = env::var("SHELL").ok().and_then(|s| known_shell(&s)) {
+        return Some((shell, ShellSource::LoginShell));
+    }
+    platform_shell().map(|s| (s, ShellSource::PlatformDefault))
+}
+
+/// The shell to assume when nothing else says: pwsh on Windows if it's
+/// installed, else the Windows PowerShell every install has. None elsewhere.
+fn platform_shell() -> Option<String> {
+    if !cfg!(windows) {
+        return None;
+    }
+    let shell = if find_in_path("pwsh").is_some() { "pwsh" } else { "powershell" };
+    Some(shell.to_string())
+}
+
+/// Strip what process listings add around a shell's name: its directory, the
+/// `-` of a login shell, a version suffix and an .exe, and lowercase it.
+/// e.g. "-bash" -> "bash", "/usr/bin/zsh-5.9" -> "zsh",
+/// `C:\Program Files\PowerShell\7\pwsh.exe` -> "pwsh".
+pub fn normalize_shell_name(name: &str) -> String {
+    let name = name.trim();
+    let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    name.trim_start_matches('-')
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// The normalized name, if it's one of `KNOWN_SHELLS`.
+fn known_shell(name: &str) -> Option<String> {
+    let name = normalize_shell_name(name);
+    KNOWN_SHELLS.contains(&name.as_str()).then_some(name)
+}
+
+/// Walk up the process tree from our parent to the first known shell, so
+/// wrappers like `sudo` or `env` in between don't hide it. A shell that's only
+/// running a script (a `#!/bin/bash` wrapper, `bash -c ...`) is passed over for
+/// the interactive one further up, and used only if there's none.
+#[cfg(unix)]
+fn parent_shell() -> Option<String> {
+    let mut pid = std::os::unix::process::parent_id();
+    let mut scripted = None;
+    // a shell is normally within a couple of levels; don't wander up to init
+    for _ in 0..8 {
+        if pid <= 1 {
+            break;
+        }
+        let Some((name, parent)) = process_info(pid) else { break };
+        if let Some(shell) = known_shell(&name) {
+            if !runs_script(pid) {
+                return Some(shell);
+            }
+            scripted.get_or_insert(shell);
+        }
+        pid = parent;
+    }
+    scripted
+}
+
+/// Options of the known shells that take the next argument as their value,
+/// so it isn't mistaken for a script to run.
+#[cfg(unix)]
+const VALUE_OPTIONS: &[&str] =
+    &["--rcfile", "--init-file", "-o", "-O", "-C", "--config", "--env-config"];
+
+/// Whether a shell process was started to run a script (a file or `-c`) rather
+/// than interactively, judging by its command line. Linux only (it needs
+/// /proc/<pid>/cmdline); elsewhere every shell counts as interactive.
+#[cfg(unix)]
+fn runs_script(pid: u32) -> bool {
+    let Ok(cmdline) = std::fs::read(format!("/proc/{pid}/cmdline")) else {
+        return false;
+    };
+    let cmdline = String::from_utf8_lossy(&cmdline);
+    let mut args = cmdline.split('\0').filter(|arg| !arg.is_empty()).skip(1);
+    while let Some(arg) = args.next() {
+        match arg {
+            "-i" | "--interactive" => return false,
+            "-c" | "--command" => return true,
+            // PowerShell's are case-insensitive
+            _ if ["-command", "-file"].contains(&arg.to_ascii_lowercase().as_str()) => return true,
+            option if VALUE_OPTIONS.contains(&option) => {
+                args.next();
+            }
+            option if option.starts_with('-') => {}
+            // the first operand is the script file
+            _ => return true,
+        }
+    }
+    false
+}
+
+#[cfg(not(unix))]
+fn parent_shell() -> Option<String> {
+    None
+}
+
+/// A process's command name and parent pid: from /proc on Linux, otherwise `ps`.
+#[cfg(unix)]
+fn process_info(pid: u32) -> Option<(String, u32)> {
+    if let Ok(stat) = std::fs::read_to_string(format!("/proc/{pid}/stat")) {
+        // "<pid> (<comm>) <state> <ppid> ..." — comm may contain spaces or parens itself
+        let open = stat.find('(')?;
+        let close = stat.rfind(')')?;
+        let ppid = stat[close + 1..].split_whitespace().nth(1)?.parse().ok()?;
+        return Some((stat[open + 1..close].to_string(), ppid));
+    }
+
+    let output = std::process::Command::new("ps")
+        .args(["-o", "ppid=,comm=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let text = String::from_utf8(output.stdout).ok()?;
+    let (ppid, name) = text.trim().split_once(char::is_whitespace)?;
+    Some((name.trim().to_string(), ppid.parse().ok()?))
+}
+
+/// Where a shell's rc file lives and the line that loads semantic from it
+/// (matching the usage notes at the top of this file). None for unknown shells.
+pub fn rc_setup(shell: &str) -> Option<(&'static str, &'static str)> {
+    match shell {
+        "fish" => Some(("~/.config/fish/conf.d/semantic.fish", "semantic init | source")),
+        "bash" => Some(("~/.bashrc", "eval \"$(semantic init)\"")),
+        "zsh" => Some(("~/.zshrc", "eval \"$(semantic init)\"")),
+        "nu" => Some(("$nu.config-path", "source ~/.config/semantic/init.nu")),
+        "pwsh" | "powershell" => Some((
+            "$PROFILE.CurrentUserAllHosts",
+            "semantic init pwsh | Out-String | Invoke-Expression",
+        )),
+        _ => None,
+    }
+}
+
+/// The shell's own rc file as a real path, for reading and editing it.
+/// None for unknown shells or when there's no home directory.
+/// fish's is config.fish, though semantic installs itself beside it (see `install_path`).
+pub fn rc_path(shell: &str) -> Option<PathBuf> {
+    let home = dirs::home_dir()?;
+    match shell {
+        "fish" => Some(xdg_config_home()?.join("fish").join("config.fish")),
+        "bash" => Some(home.join(".bashrc")),
+        "zsh" => Some(
+            env::var_os("ZDOTDIR")
+                .filter(|dir| !dir.is_empty())
+                .map_or(home, PathBuf::from)
+                .join(".zshrc"),
+        ),
+        "nu" => Some(dirs::config_dir()?.join("nushell").join("config.nu")),
+        // $PROFILE.CurrentUserAllHosts; Windows PowerShell 5 keeps its own
+        "powershell" if cfg!(windows) => {
+            Some(dirs::document_dir()?.join("WindowsPowerShell").join("profile.ps1"))
+        }
+        "pwsh" | "powershell" if cfg!(windows) => {
+            Some(dirs::document_dir()?.join("PowerShell").join("profile.ps1"))
+        }
+        "pwsh" | "powershell" => Some(dirs::config_dir()?.join("powershell").join("profile.ps1")),
+        _ => None,
+    }
+}
+
+/// The rc file `shell` actually reads, for pointing at it: nu's $nu.config-path
+/// and PowerShell's $PROFILE.CurrentUserAllHosts are asked of the shell itself
+/// (run once, without its own config), and bash on macOS, where terminals start
+/// login shells, gets ~/.bash_profile when there's no ~/.bashrc. Otherwise, or
+/// if the shell can't be asked, the same as `rc_path`. Never touches the file.
+/// None for unknown shells.
+pub fn rc_file_for(shell: &str) -> Option<PathBuf> {
+    let reported = match shell {
+        "nu" => ask_shell("nu", &["-n", "-c", "$nu.config-path"]),
+        "pwsh" | "powershell" => {
+            ask_shell(shell, &["-NoProfile", "-Command", "$PROFILE.CurrentUserAllHosts"])
+        }
+        "bash" if cfg!(target_os = "macos") => {
+            let home = dirs::home_dir()?;
+            let profile = home.join(".bash_profile");
+            (!home.join(".bashrc").exists() && profile.exists()).then_some(profile)
+        }
+        _ => None,
+    };
+    reported.or_else(|| rc_path(shell))
+}
+
+/// The path `program args` prints, if it runs, succeeds and prints an absolute one.
+fn ask_shell(program: &str, args: &[&str]) -> Option<PathBuf> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let path = PathBuf::from(String::from_utf8(output.stdout).ok()?.trim());
+    path.is_absolute().then_some(path)
+}
+
+/// Whether rc file `content` loads semantic in `shell`: it has a semantic block
+/// (see `strip_rc_block`), the shell's line from `rc_setup`, or some other line
+/// that isn't a comment and runs `semantic init`.
+pub fn loads_semantic(shell: &str, content: &str) -> bool {
+    strip_rc_block(content).is_some()
+        || rc_setup(shell).is_some_and(|(_, setup_line)| content.contains(setup_line))
+        || content.lines().any(|line| {
+            let line = line.trim_start();
+            !line.starts_with('#') && line.contains("semantic init")
+        })
+}
+
+/// Where `semantic init --install` adds the block that loads semantic: the file
+/// from `rc_setup`. That's the rc file itself, except for fish, which reads
+/// every file in conf.d and so gets one of its own.
+pub fn install_path(shell: &str) -> Option<PathBuf> {
+    match shell {
+        "fish" => Some(xdg_config_home()?.join("fish").join("conf.d").join("semantic.fish")),
+        _ => rc_path(shell),
+    }
+}
+
+/// $XDG_CONFIG_HOME, or ~/.config, as fish and other XDG programs look for it
+/// on every platform (unlike `dirs::config_dir`, which is elsewhere on macOS).
+fn xdg_config_home() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| Some(dirs::home_dir()?.join(".config")))
+}
+
+/// Add a semantic block that loads it in `shell` to `install_path`, creating
+/// the file and its directories as needed. Returns the file, or None if it (or
+/// the shell's rc file) already loads semantic, so the block is never doubled.
+/// With `dry_run`, only says which file it would write.
+pub fn install_rc_block(shell: &str, dry_run: bool) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let (_, setup_line) = rc_setup(shell).ok_or("semantic doesn't support it")?;
+    let path = install_path(shell).ok_or("no home directory to find its rc file in")?;
+    let read = |path: &Path| match fs::read_to_string(path) {
+        Ok(content) => Ok(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(format!("{}: {e}", path.display())),
+    };
+    let loads_semantic = |content: &str| loads_semantic(shell, content);
+
+    let content = read(&path)?;
+    if loads_semantic(&content) {
+        return Ok(None);
+    }
+    if let Some(rc) = rc_path(shell).filter(|rc| *rc != path)
+        && loads_semantic(&read(&rc)?)
+    {
+        return Ok(None);
+    }
+    if dry_run {
+        return Ok(Some(path));
+    }
+
+    // nushell can't eval init code, so it sources a generated copy instead
+    let line = if shell == "nu" {
+        let init = crate::config::config_dir().join("init.nu");
+        write_nu_init(&init)?;
+        format!("source {}", init.display())
+    } else {
+        setup_line.to_string()
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, append_rc_block(&content, &line)).map_err(|e| format!("{}: {e}", path.display()))?;
+    Ok(Some(path))
+}
+
+/// Write nushell init code for the current config to `path`.
+fn write_nu_init(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = crate::config::SemanticConfig::load()?;
+    config.expand_env_vars();
+    let bin_dir = config.bin_dir();
+    let code = generate_init(
+        &config.commands_for("nu"),
+        &config.composite_commands,
+        &config.paths,
+        "nu",
+        &InitOptions {
+            guard: false,
+            mode: config.integration_mode(),
+            shell_mode: &config.shell_mode_commands,
+            bin_dir: bin_dir.as_deref(),
+        },
+    )?;
+    fs::write(path, code).map_err(|e| format!("{}: {e}", path.display()).into())
+}
+
+/// Comment lines around the block semantic owns in a shell rc file, which
+/// `install_rc_block` writes and `semantic uninstall` removes.
+/// Only text between these is ever removed; anything outside is the user's.
+pub const RC_BEGIN_MARKER: &str = "# >>> semantic >>>";
+pub const RC_END_MARKER: &str = "# <<< semantic <<<";
+
+/// The same for a block holding the init code itself rather than a line that
+/// loads it, as `install_init_block` writes.
+pub const INIT_BEGIN_MARKER: &str = "# >>> semantic init >>>";
+pub const INIT_END_MARKER: &str = "# <<< semantic init <<<";
+
+/// `content` with every semantic block of either kind (markers included) cut
+/// out, or None if it has no complete block.
+pub fn strip_rc_block(content: &str) -> Option<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut kept = Vec::with_capacity(lines.len());
+    let mut found = false;
+
+    let mut i = 0;
+    while i < lines.len() {
+        let end = [(RC_BEGIN_MARKER, RC_END_MARKER), (INIT_BEGIN_MARKER, INIT_END_MARKER)]
+            .into_iter()
+            .find(|(begin, _)| lines[i].trim() == *begin)
+            .and_then(|(_, end)| lines[i..].iter().position(|line| line.trim() == end));
+        match end {
+            Some(end) => {
+                found = true;
+                i += end + 1;
+            }
+            None => {
+                kept.push(lines[i]);
+                i += 1;
+            }
+        }
+    }
+
+    if !found {
+        return None;
+    }
+    let mut out = kept.join("\n");
+    if content.ends_with('\n') && !out.is_empty() {
+        out.push('\n');
+    }
+    Some(out)
+}
+
+/// `content` with a semantic block that runs `line` appended, in the form
+/// `strip_rc_block` (and so `semantic uninstall`) takes out again.
+pub fn append_rc_block(content: &str, line: &str) -> String {
+    append_block(content, RC_BEGIN_MARKER, line, RC_END_MARKER)
+}
+
+/// `content` with `body` appended between `begin` and `end`, a blank line apart.
+fn append_block(content: &str, begin: &str, body: &str, end: &str) -> String {
+    let mut out = content.to_string();
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(&format!("{begin}\n{}\n{end}\n", body.trim_end()));
+    out
+}
+
+/// Write `code` (init code for `shell`) itself into `install_path` between the
+/// `INIT_BEGIN_MARKER` lines, for `semantic init --install --inline`. Any
+/// semantic block already there is replaced rather than added to, so running
+/// it again after editing the config leaves just the new code, and a command
+/// that's gone from the config is gone from the file too.
+/// Returns the file, or None if it already held exactly this code.
+/// With `dry_run`, only says which file it would write.
+pub fn install_init_block(
+    shell: &str,
+    code: &str,
+    dry_run: bool,
+) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let path = install_path(shell).ok_or("no home directory to find its rc file in")?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("{}: {e}", path.display()).into()),
+    };
+    let rest = strip_rc_block(&content).unwrap_or_else(|| content.clone());
+    // keep a block at the end of the file from drifting down a blank line per run
+    let rest = if rest.trim().is_empty() { String::new() } else { rest.trim_end().to_string() + "\n" };
+    let updated = append_block(&rest, INIT_BEGIN_MARKER, code, INIT_END_MARKER);
+    if updated == content {
+        return Ok(None);
+    }
+    if dry_run {
+        return Ok(Some(path));
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, updated).map_err(|e| format!("{}: {e}", path.display()))?;
+    Ok(Some(path))
+}
+
+/// Look up a program in the directories listed in $PATH.
+/// Returns the first matching file, or None if it isn't found anywhere.
+pub fn find_in_path(program: &str) -> Option<PathBuf> {
+    // programs given with a path (e.g. ./run.sh, /usr/bin/ls) are checked directly
+    if program.contains('/') || (cfg!(windows) && program.contains('\\')) {
+        let path = PathBuf::from(program);
+        return path.is_file().then_some(path);
+    }
+
+    let path_var = env::var_os("PATH")?;
+    let names = executable_names(program);
+    env::split_paths(&path_var)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| candidate.is_file())
+}
+
+/// The file names `program` may have on disk. On Windows a name without an
+/// extension is tried with each of $PATHEXT's (.exe, .cmd, ...), as cmd.exe
+/// does; elsewhere it's only itself.
+fn executable_names(program: &str) -> Vec<String> {
+    #[cfg(windows)]
+    if std::path::Path::new(program).extension().is_none() {
+        let pathext = env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+        return pathext
+            .split(';')
+            .filter(|ext| !ext.is_empty())
+            .map(|ext| format!("{program}{}", ext.to_ascii_lowercase()))
+            .collect();
+    }
+    vec![program.to_string()]
+}
+
+/// Mappings whose program can't be found in $PATH, as (name, program) pairs sorted by name.
+/// Shell builtins are skipped, since they never live on disk.
+pub fn missing_programs(commands: &HashMap<String, String>) -> Vec<(&str, &str)> {
+    let mut missing: Vec<_> = commands
+        .iter()
+        .filter_map(|(name, real_cmd)| {
+            let program = crate::config::real_program(real_cmd)?;
+            let found = SHELL_BUILTINS.contains(&program) || find_in_path(program).is_some();
+            (!found).then_some((name.as_str(), program))
+        })
+        .collect();
+    missing.sort();
+    missing
+}
+
+/// The (name, real command) mappings whose name hides something else of that
+/// name: a shell builtin or keyword, or a program in $PATH (the natural style's
+/// `install` hides coreutils' install, say). Pasted scripts then run the
+/// mapping instead. A name that maps to the very program it hides is fine.
+/// Returns (name, what it hides), sorted by name.
+pub fn shadowed<'a>(mappings: impl IntoIterator<Item = (&'a str, &'a str)>) -> Vec<(String, String)> {
+    let mut shadowed: Vec<_> = mappings
+        .into_iter()
+        .filter(|(name, real_cmd)| crate::config::real_program(real_cmd) != Some(*name))
+        .filter_map(|(name, _)| {
+            let hidden = if SHELL_BUILTINS.contains(&name) || SHADOWABLE_WORDS.contains(&name) {
+                format!("the shell builtin `{name}`")
+            } else {
+                find_in_path(name)?.display().to_string()
+            };
+            Some((name.to_string(), hidden))
+        })
+        .collect();
+    shadowed.sort();
+    shadowed
+}
+
+/// Whether a mapped command uses shell operators and so has to run through a shell.
+pub fn needs_shell(real_cmd: &str) -> bool {
+    SHELL_OPERATORS.iter().any(|op| real_cmd.contains(op))
+}
+
+/// Stands for the user's extra arguments inside a mapping,
+/// e.g. `"cargo test {args} -- --nocapture"`. Without it they're appended at the end.
+/// Alongside `{N}` placeholders it stands for whatever `{N}` didn't use.
+pub const ARGS_PLACEHOLDER: &str = "{args}";
+
+/// Ends a mapping that takes no extra arguments: with `update = "sudo pacman -Syu!"`,
+/// `update kernel` is an error rather than `pacman -Syu kernel`.
+pub const NO_ARGS_MARK: char = '!';
+
+/// Ends a mapping whose extra arguments go after a `--`, so the program takes
+/// them as operands even when they start with a dash: `remove = "rm --"` runs
+/// `rm -- -x` for `remove -x`, and plain `rm` when given nothing.
+pub const SEPARATOR_MARK: &str = "--";
+
+/// What a mapping's mark (see `extra_args_mark`) says about extra arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtraArgs {
+    /// No mark: they're added as usual.
+    Allowed,
+    /// `!`: there mustn't be any.
+    Rejected,
+    /// `--`: they're added after a `--`.
+    AfterSeparator,
+}
+
+/// `real_cmd` without the mark it ends with, if any, and what the mark asks for.
+/// `!` is checked first; `!!` (bash's "the last command") isn't a mark, and
+/// `--` only is as a word of its own.
+pub fn extra_args_mark(real_cmd: &str) -> (&str, ExtraArgs) {
+    let trimmed = real_cmd.trim_end();
+    if let Some(rest) = trimmed.strip_suffix(NO_ARGS_MARK)
+        && !rest.ends_with(NO_ARGS_MARK)
+        && !rest.trim().is_empty()
+    {
+        return (rest.trim_end(), ExtraArgs::Rejected);
+    }
+    if let Some(rest) = trimmed.strip_suffix(SEPARATOR_MARK)
+        && rest.ends_with(char::is_whitespace)
+        && !rest.trim().is_empty()
+    {
+        return (rest.trim_end(), ExtraArgs::AfterSeparator);
+    }
+    (real_cmd, ExtraArgs::Allowed)
+}
+
+/// Whether a mapping places the extra arguments itself, with `{args}` or `{N}`.
+pub fn takes_args(real_cmd: &str) -> bool {
+    real_cmd.contains(ARGS_PLACEHOLDER) || next_positional(real_cmd).is_some()
+}
+
+/// The first `{N}` (N ≥ 1) in `text`, as (byte range, N).
+fn next_positional(text: &str) -> Option<(std::ops::Range<usize>, usize)> {
+    let mut search_from = 0;
+    while let Some(open) = text[search_from..].find('{').map(|i| i + search_from) {
+        let rest = &text[open + 1..];
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits > 0
+            && rest[digits..].starts_with('}')
+            && let Ok(n) = rest[..digits].parse::<usize>()
+            && n > 0
+        {
+            return Some((open..open + digits + 2, n));
+        }
+        search_from = open + 1;
+    }
+    None
+}
+
+/// Replace every `{N}` in `text` with the Nth extra argument (1-based), noting
+/// which arguments were used. `quote` shell-escapes what's substituted.
+/// Errors if a placeholder refers past the end of `extra_args`.
+fn substitute_positional(
+    text: &str,
+    extra_args: &[String],
+    used: &mut [bool],
+    quote: bool,
+) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = text;
+
+    while let Some((range, n)) = next_positional(rest) {
+        let Some(arg) = extra_args.get(n - 1) else {
+            return Err(format!(
+                "needs argument {{{n}}}, but only {} given",
+                extra_args.len()
+            ));
+        };
+        used[n - 1] = true;
+
+        out.push_str(&rest[..range.start]);
+        if quote {
+            out.push_str(&shell_escape::unix::escape(arg.into()));
+        } else {
+            out.push_str(arg);
+        }
+        rest = &rest[range.end..];
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// The argv that runs one mapped command with the user's `extra_args`.
+/// `{N}` placeholders take the Nth argument; the rest go where `{args}` is, or
+/// at the end, after a `--` if the mapping ends in one (see `extra_args_mark`).
+/// Commands using shell operators go through `script_shell` (see
+/// `script_argv`); everything else is split on whitespace and run directly.
+/// Errors if the mapping ends in `!` and there are extra arguments.
+pub fn command_argv(
+    script_shell: &str,
+    real_cmd: &str,
+    extra_args: &[String],
+) -> Result<Vec<String>, String> {
+    let (real_cmd, mark) = extra_args_mark(real_cmd);
+    if mark == ExtraArgs::Rejected && !extra_args.is_empty() {
+        return Err(format!(
+            "takes no arguments (its mapping ends in `{NO_ARGS_MARK}`), but was given `{}`",
+            extra_args.join(" ")
+        ));
+    }
+    let separator = mark == ExtraArgs::AfterSeparator;
+    if needs_shell(real_cmd) {
+        return script_argv(script_shell, real_cmd, extra_args, separator);
+    }
+
+    let mut used = vec![false; extra_args.len()];
+    let mut argv = Vec::new();
+    let mut args_at = None;
+    for token in real_cmd.split_whitespace() {
+        if token == ARGS_PLACEHOLDER {
+            args_at.get_or_insert(argv.len());
+        } else {
+            argv.push(substitute_positional(token, extra_args, &mut used, false)?);
+        }
+    }
+
+    let mut leftover: Vec<String> = extra_args
+        .iter()
+        .zip(&used)
+        .filter(|(_, used)| !**used)
+        .map(|(arg, _)| arg.clone())
+        .collect();
+    // the user may have typed one already (see `semantic translate`'s `--`)
+    if separator && leftover.first().is_some_and(|arg| arg != SEPARATOR_MARK) {
+        leftover.insert(0, SEPARATOR_MARK.to_string());
+    }
+    let at = args_at.unwrap_or(argv.len());
+    argv.splice(at..at, leftover);
+    Ok(argv)
+}
+
+/// The argv that runs `real_cmd` as a script in `shell`, with `extra_args`
+/// quoted and substituted for `{N}`, the rest put in place of `{args}` (or
+/// appended), after a `--` with `separator`. Unknown or unset shells fall back to `sh`.
+pub fn script_argv(
+    shell: &str,
+    real_cmd: &str,
+    extra_args: &[String],
+    separator: bool,
+) -> Result<Vec<String>, String> {
+    let shell = if SCRIPT_SHELLS.contains(&shell) { shell } else { "sh" };
+
+    let mut used = vec![false; extra_args.len()];
+    let script = substitute_positional(real_cmd, extra_args, &mut used, true)?;
+
+    let mut quoted: Vec<String> = extra_args
+        .iter()
+        .zip(&used)
+        .filter(|(_, used)| !**used)
+        .map(|(arg, _)| shell_escape::unix::escape(arg.into()).into_owned())
+        .collect();
+    if separator && quoted.first().is_some_and(|arg| arg != SEPARATOR_MARK) {
+        quoted.insert(0, SEPARATOR_MARK.to_string());
+    }
+    let quoted = quoted.join(" ");
+
+    let script = if script.contains(ARGS_PLACEHOLDER) {
+        script.replace(ARGS_PLACEHOLDER, &quoted)
+    } else if quoted.is_empty() {
+        script
+    } else {
+        format!("{script} {quoted}")
+    };
+
+    Ok(vec![shell.to_string(), "-c".to_string(), script])
+}
+
+/// The argv that runs a shell-mode mapping: `script` goes to `shell -c` untouched
+/// and `extra_args` become its positional parameters ("$@", or $argv in fish),
+/// so they never need quoting. `name` fills $0 in POSIX shells.
+/// Unknown or unset shells fall back to `sh`.
+pub fn shell_mode_argv(shell: &str, script: &str, name: &str, extra_args: &[String]) -> Vec<String> {
+    let shell = if SCRIPT_SHELLS.contains(&shell) { shell } else { "sh" };
+
+    let mut argv = vec![shell.to_string(), "-c".to_string(), script.to_string()];
+    // fish hands everything after the script to $argv; the others start at $0
+    if shell != "fish" {
+        argv.push(name.to_string());
+    }
+    argv.extend(extra_args.iter().cloned());
+    argv
+}
+
+/// The operators `semantic translate` can join semantic commands with.
+/// Longest first, so `||` isn't read as two pipes.
+pub const CHAIN_OPERATORS: &[&str] = &["&&", "||", "|"];
+
+/// Commands (each a list of words) and the operators between them.
+pub type Chain = (Vec<Vec<String>>, Vec<&'static str>);
+
+/// A line of commands joined by `CHAIN_OPERATORS`, split into the commands
+/// (each a list of words, with quotes and backslashes removed as a shell would)
+/// and the operators between them: `update && install "a b"` gives
+/// `[[update], [install, a b]]` and `[&&]`. Operators inside quotes or after a
+/// backslash are ordinary text. Errors on an unclosed quote or an operator
+/// without a command on each side.
+pub fn split_chain(line: &str) -> Result<Chain, String> {
+    let mut commands = Vec::new();
+    let mut operators = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut word: Option<String> = None; // None between words
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((at, c)) = chars.next() {
+        match c {
+            '\\' => {
+                let escaped = chars.next().map(|(_, c)| c).ok_or("ends in a lone backslash")?;
+                word.get_or_insert_default().push(escaped);
+            }
+            '\'' | '"' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some((_, q)) if q == c => break,
+                        // inside double quotes a backslash still escapes " and itself
+                        Some((_, '\\')) if c == '"' && matches!(chars.peek(), Some((_, '"' | '\\'))) => {
+                            word.extend(chars.next().map(|(_, c)| c));
+                        }
+                        Some((_, ch)) => word.push(ch),
+                        None => return Err(format!("has an unclosed {c}")),
+                    }
+                }
+            }
+            c if c.is_whitespace() => words.extend(word.take()),
+            _ => {
+                let Some(op) = CHAIN_OPERATORS.iter().find(|op| line[at..].starts_with(**op)) else {
+                    word.get_or_insert_default().push(c);
+                    continue;
+                };
+                for _ in 1..op.len() {
+                    chars.next();
+                }
+                words.extend(word.take());
+                if words.is_empty() {
+                    return Err(format!("has `{op}` without a command before it"));
+                }
+                commands.push(std::mem::take(&mut words));
+                operators.push(*op);
+            }
+        }
+    }
+
+    words.extend(word.take());
+    if words.is_empty() {
+        if let Some(op) = operators.last() {
+            return Err(format!("has `{op}` without a command after it"));
+        }
+    } else {
+        commands.push(words);
+    }
+    Ok((commands, operators))
+}
+
+/// Set (exported) by the init code to the pid of the shell that ran it, so
+/// running it again in that shell is a no-op. A child shell has a different
+/// pid and sets itself up again, since functions and aliases aren't inherited.
+pub const INIT_GUARD: &str = "SEMANTIC_INIT_DONE";
+
+/// How `generate_init` defines commands, from `[shells] integration_mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrationMode {
+    /// Bake each mapping into a static alias (or the shell's nearest equivalent).
+    Alias,
+    /// A function per command that calls `semantic translate`, so mappings are
+    /// looked up at call time and config edits apply without re-sourcing.
+    Function,
+}
+
+impl IntegrationMode {
+    pub const NAMES: &[&str] = &["alias", "function"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "alias" => Some(IntegrationMode::Alias),
+            "function" => Some(IntegrationMode::Function),
+            _ => None,
+        }
+    }
+}
+
+/// What `generate_init` needs besides the mappings themselves.
+pub struct InitOptions<'a> {
+    /// Do nothing in a shell that already ran the code (see `INIT_GUARD`).
+    pub guard: bool,
+    /// None keeps each shell's usual style: translate functions for bash, zsh and
+    /// fish, baked aliases for nushell and PowerShell.
+    pub mode: Option<IntegrationMode>,
+    /// Shell-mode commands. They're scripts, so they always go through translate.
+    pub shell_mode: &'a HashSet<String>,
+    /// Put at the front of $PATH before anything else (see `SemanticConfig::bin_dir`).
+    pub bin_dir: Option<&'a str>,
+}
+
+impl InitOptions<'_> {
+    /// Whether `name` has to be resolved by `semantic translate` when it runs,
+    /// because a baked alias can't do what its mapping asks for: placing
+    /// arguments, or checking them for a `!` or `--` mark.
+    fn needs_translate(&self, name: &str, real_cmd: &str) -> bool {
+        self.shell_mode.contains(name)
+            || takes_args(real_cmd)
+            || extra_args_mark(real_cmd).1 != ExtraArgs::Allowed
+    }
 }
 
 /// Generate shell init code that creates aliases/functions for all semantic commands.
 /// Also handles path translation for commands that take path arguments.
+/// With `options.guard`, the code does nothing in a shell that already ran it;
+/// nushell defines commands at parse time, so its code is never guarded.
+/// PowerShell ("pwsh" or "powershell") has its own generator too.
 ///
-/// For most commands, we generate simple aliases.
-/// For commands that map to "cd" we generate shell functions instead,
-/// since cd is a shell builtin that can't work through a subprocess.
+/// For most commands, we generate a function that calls `semantic translate`,
+/// or in alias mode a static alias of the real command.
+/// Commands that map to one of `CURRENT_SHELL_BUILTINS` get a shell function
+/// that runs the builtin itself, since it can't work through a subprocess;
+/// their path arguments are translated with `semantic translate --resolve-path`.
+/// Composite commands get a single function each; `semantic translate` runs the steps.
+/// bash, zsh and fish also get completions that complete each plain mapping
+/// like the command it runs (see `generate_completions`).
+/// Fails with `UnknownShell` for a shell not in `KNOWN_SHELLS`.
 pub fn generate_init(
     commands: &HashMap<String, String>,
+    composites: &HashMap<String, Vec<String>>,
     paths: &HashMap<String, String>,
     shell: &str,
-) -> String {
+    options: &InitOptions,
+) -> Result<String, SemanticError> {
+    if !KNOWN_SHELLS.contains(&shell) {
+        return Err(SemanticError::UnknownShell { name: shell.to_string() });
+    }
+    log::debug!(
+        "init for {shell}: {} commands, {} composite commands, {} paths, mode {}",
+        commands.len(),
+        composites.len(),
+        paths.len(),
+        match options.mode {
+            Some(IntegrationMode::Alias) => "alias",
+            Some(IntegrationMode::Function) => "function",
+            None => "the shell's default",
+        }
+    );
+    // nushell's syntax is different enough to need its own generator
+    if shell == "nu" {
+        return Ok(generate_init_nu(commands, composites, paths, options));
+    }
+    if shell == "pwsh" || shell == "powershell" {
+        return Ok(generate_init_powershell(commands, composites, paths, shell, options));
+    }
+    let guard = options.guard;
+
     let mut output = String::new();
 
     // header comment
@@ -37,106 +946,1016 @@ pub fn generate_init(
         "# SemanticOS shell init ({shell})\n# Auto-generated by `semantic init`\n\n"
     ));
 
+    // wrap everything in a check for this shell's pid rather than returning early —
+    // `return` would also end the rc file that evals this
+    if guard {
+        output.push_str(&match shell {
+            "fish" => format!("if test \"${INIT_GUARD}\" != \"$fish_pid\"\n\n"),
+            _ => format!("if [ \"${{{INIT_GUARD}:-}}\" != \"$$\" ]; then\n\n"),
+        });
+    }
+
+    // lets `semantic doctor` tell that this has been sourced
+    output.push_str(&match shell {
+        "fish" => format!("set -gx {INIT_MARKER} {shell}\n\n"),
+        _ => format!("export {INIT_MARKER}={shell}\n\n"),
+    });
+    if let Some(dir) = options.bin_dir {
+        output.push_str(&generate_path_prepend(dir, shell));
+    }
+
     // find the semantic binary path so aliases can call it
     let semantic_bin = env::current_exe()
         .map(|p| p.display().to_string())
         .unwrap_or_else(|_| "semantic".to_string());
 
-    for (alias, real_cmd) in commands {
+    // sort so the output is stable between runs
+    let mut sorted: Vec<_> = commands.iter().collect();
+    sorted.sort();
+
+    for (alias, real_cmd) in sorted {
         // skip identity mappings (traditional style)
         if alias == real_cmd {
             continue;
         }
 
-        // cd needs to be a shell function, not an alias,
-        // because cd only works in the current shell process
-        if real_cmd == "cd" || real_cmd.starts_with("cd ") {
-            output.push_str(&generate_cd_function(alias, real_cmd, paths, shell));
+        // cd and friends only work in the current shell process
+        if runs_in_current_shell(real_cmd) && !options.shell_mode.contains(alias) {
+            output.push_str(&generate_builtin_function(alias, real_cmd, &semantic_bin, shell));
+        } else if options.mode == Some(IntegrationMode::Alias)
+            && !options.needs_translate(alias, real_cmd)
+        {
+            output.push_str(&generate_static_alias(alias, real_cmd, shell));
         } else {
             output.push_str(&generate_alias(alias, real_cmd, &semantic_bin, shell));
         }
     }
 
+    for name in composite_names(commands, composites) {
+        output.push_str(&generate_alias(name, "", &semantic_bin, shell));
+    }
+
+    output.push_str(&generate_completions(&completion_targets(commands, options), shell));
+
+    if guard {
+        output.push_str(&match shell {
+            "fish" => format!("set -gx {INIT_GUARD} $fish_pid\nend\n"),
+            _ => format!("export {INIT_GUARD}=$$\nfi\n"),
+        });
+    }
+
+    Ok(output)
+}
+
+/// Shells `generate_init_polyglot` can combine, with the test that picks each
+/// one. Every test is valid in all three (and in plain sh, which runs none).
+const POLYGLOT_SHELLS: &[(&str, &str)] = &[
+    ("fish", "[ -n \"$FISH_VERSION\" ]"),
+    ("zsh", "[ -n \"$ZSH_VERSION\" ]"),
+    ("bash", "[ -n \"$BASH_VERSION\" ]"),
+];
+
+/// Init code for several shells at once, for `semantic init --all`: each enabled
+/// shell's code (see `generate_init`) inside a double-quoted `eval` behind a
+/// test for that shell, so one rc snippet can be shared between fish, zsh and bash.
+/// The quoting is the part fish and POSIX shells read alike (`\"`, `\$`, `\\`),
+/// so every shell parses the whole snippet and runs only its own part.
+/// `commands` holds each shell's commands (see `SemanticConfig::commands_for`).
+/// Other enabled shells (nushell, PowerShell) can't parse it and are left out.
+pub fn generate_init_polyglot(
+    commands: &HashMap<String, HashMap<String, String>>,
+    composites: &HashMap<String, Vec<String>>,
+    paths: &HashMap<String, String>,
+    enabled_shells: &[String],
+    options: &InitOptions,
+) -> String {
+    let mut output = String::from(
+        "# SemanticOS shell init (polyglot)\n# Auto-generated by `semantic init --all`\n\n",
+    );
+    for (shell, test) in POLYGLOT_SHELLS {
+        if !enabled_shells.iter().any(|enabled| enabled == shell) {
+            continue;
+        }
+        let empty = HashMap::new();
+        let code = generate_init(commands.get(*shell).unwrap_or(&empty), composites, paths, shell, options)
+            .expect("polyglot shells are known shells");
+        // POSIX shells would read an unescaped backtick even in the branch they skip
+        let quoted = code
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('$', "\\$")
+            .replace('`', "\\`");
+        output.push_str(&format!("{test} && eval \"{quoted}\"\n\n"));
+    }
+    output
+}
+
+/// Names of composite commands, sorted, minus any a plain mapping already covers
+/// (a per-shell override can replace a composite command with a plain one).
+fn composite_names<'a>(
+    commands: &HashMap<String, String>,
+    composites: &'a HashMap<String, Vec<String>>,
+) -> Vec<&'a String> {
+    let mut names: Vec<_> = composites
+        .keys()
+        .filter(|name| !commands.contains_key(*name))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Nushell init code.
+/// Single-token mappings become `alias`; multi-token ones need a `def` custom command,
+/// since nushell aliases can't carry extra arguments into the middle of a pipeline.
+/// cd-like commands become `def --env` so the directory change reaches the caller,
+/// with a `match` translating virtual paths the same way the other shells do.
+/// Everything else is prefixed with `^` so nushell runs the real binary rather
+/// than its own builtin of the same name (its `ls`/`rm` take different flags).
+/// Composite commands call back into `semantic translate`, which runs the steps,
+/// and so does everything else in function mode.
+fn generate_init_nu(
+    commands: &HashMap<String, String>,
+    composites: &HashMap<String, Vec<String>>,
+    paths: &HashMap<String, String>,
+    options: &InitOptions,
+) -> String {
+    let mut output = String::from(
+        "# SemanticOS shell init (nu)\n\
+         # Auto-generated by `semantic init`\n\
+         # Save this to a file and `source` it from your config ($nu.config-path).\n\n",
+    );
+    output.push_str(&format!("$env.{INIT_MARKER} = \"nu\"\n\n"));
+    if let Some(dir) = options.bin_dir {
+        output.push_str(&generate_path_prepend(dir, "nu"));
+    }
+
+    let semantic_bin = env::current_exe()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| "semantic".to_string());
+    let translate_def = |name: &str| {
+        format!("def --wrapped {name} [...args] {{\n    ^\"{semantic_bin}\" translate {name} ...$args\n}}\n\n")
+    };
+
+    let mut sorted: Vec<_> = commands.iter().collect();
+    sorted.sort();
+
+    for (alias, real_cmd) in sorted {
+        // skip identity mappings (traditional style)
+        if alias == real_cmd {
+            continue;
+        }
+
+        if real_cmd == "cd" {
+            // translate virtual paths before changing directory
+            let mut func = format!(
+                "def --env {alias} [target?: string] {{\n    let real = match $target {{\n"
+            );
+            for (virtual_path, real_path) in sorted_paths(paths) {
+                func.push_str(&format!("        \"{virtual_path}\" => \"{real_path}\",\n"));
+            }
+            func.push_str("        null => \"~\",\n        _ => $target\n    }\n    cd $real\n}\n\n");
+            output.push_str(&func);
+        } else if real_cmd.starts_with("cd ") {
+            // takes no arguments anyway, so a mark means nothing more here
+            let real_cmd = extra_args_mark(real_cmd).0;
+            output.push_str(&format!("def --env {alias} [] {{\n    {real_cmd}\n}}\n\n"));
+        } else if options.mode == Some(IntegrationMode::Function)
+            || options.needs_translate(alias, real_cmd)
+        {
+            output.push_str(&translate_def(alias));
+        } else if real_cmd.contains(' ') {
+            // --wrapped passes flags like -y through untouched instead of parsing them
+            output.push_str(&format!(
+                "def --wrapped {alias} [...args] {{\n    ^{real_cmd} ...$args\n}}\n\n"
+            ));
+        } else {
+            output.push_str(&format!("alias {alias} = ^{real_cmd}\n\n"));
+        }
+    }
+
+    for name in composite_names(commands, composites) {
+        output.push_str(&translate_def(name));
+    }
+
     output
 }
 
-/// Generate a shell function for cd-like commands.
-/// These need path translation (e.g. /apps -> /usr/bin) built in.
-fn generate_cd_function(
-    alias: &str,
-    real_cmd: &str,
+/// PowerShell init code.
+/// Single-token mappings become `Set-Alias`, which can't carry arguments, so
+/// multi-token ones need a `function` that splats `@args` onto the real command.
+/// Aliases win over functions in PowerShell, so a built-in alias of the same
+/// name (`dir`, `cls`, ...) is removed before defining a function.
+/// cd-like commands translate virtual paths with a `switch`, and single-component
+/// virtual paths also get a PSDrive, so `/apps` is reachable as `apps:` too.
+/// Composite commands call back into `semantic translate`, which runs the steps,
+/// and so does everything else in function mode.
+fn generate_init_powershell(
+    commands: &HashMap<String, String>,
+    composites: &HashMap<String, Vec<String>>,
     paths: &HashMap<String, String>,
     shell: &str,
+    options: &InitOptions,
 ) -> String {
+    let guard = options.guard;
+    let mut output = format!(
+        "# SemanticOS shell init ({shell})\n\
+         # Auto-generated by `semantic init`\n\
+         # Add this line to $PROFILE.CurrentUserAllHosts (or $PROFILE for just this host):\n\
+         #   semantic init {shell} | Out-String | Invoke-Expression\n\n"
+    );
+
+    // same pid check as the POSIX shells; an if block doesn't open a new scope
+    if guard {
+        output.push_str(&format!("if ($env:{INIT_GUARD} -ne \"$PID\") {{\n\n"));
+    }
+    output.push_str(&format!("$env:{INIT_MARKER} = \"{shell}\"\n\n"));
+    if let Some(dir) = options.bin_dir {
+        output.push_str(&generate_path_prepend(dir, shell));
+    }
+
+    for (virtual_path, real_path) in sorted_paths(paths) {
+        let name = virtual_path.trim_matches('/');
+        if !name.is_empty() && !name.contains('/') {
+            output.push_str(&format!(
+                "New-PSDrive -Name {name} -PSProvider FileSystem -Root \"{real_path}\" \
+                 -Scope Global -ErrorAction SilentlyContinue | Out-Null\n"
+            ));
+        }
+    }
+    if !paths.is_empty() {
+        output.push('\n');
+    }
+
+    let semantic_bin = env::current_exe()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| "semantic".to_string());
+    let translate_function = |name: &str| {
+        format!(
+            "Remove-Item Alias:{name} -Force -ErrorAction SilentlyContinue\n\
+             function global:{name} {{\n    & \"{semantic_bin}\" translate {name} @args\n}}\n\n"
+        )
+    };
+
+    let mut sorted: Vec<_> = commands.iter().collect();
+    sorted.sort();
+
+    for (alias, real_cmd) in sorted {
+        // skip identity mappings (traditional style)
+        if alias == real_cmd {
+            continue;
+        }
+
+        if real_cmd == "cd" {
+            // translate virtual paths before changing directory
+            let mut func = format!(
+                "Remove-Item Alias:{alias} -Force -ErrorAction SilentlyContinue\n\
+                 function global:{alias} {{\n    param([string]$target)\n    switch ($target) {{\n"
+            );
+            for (virtual_path, real_path) in sorted_paths(paths) {
+                func.push_str(&format!(
+                    "        \"{virtual_path}\" {{ $target = \"{real_path}\" }}\n"
+                ));
+            }
+            func.push_str(
+                "        \"\" { $target = \"~\" }\n    }\n    Set-Location $target\n}\n\n",
+            );
+            output.push_str(&func);
+        } else if real_cmd.starts_with("cd ") {
+            let real_cmd = extra_args_mark(real_cmd).0;
+            output.push_str(&format!(
+                "Remove-Item Alias:{alias} -Force -ErrorAction SilentlyContinue\n\
+                 function global:{alias} {{\n    {real_cmd}\n}}\n\n"
+            ));
+        } else if options.mode == Some(IntegrationMode::Function)
+            || options.needs_translate(alias, real_cmd)
+        {
+            output.push_str(&translate_function(alias));
+        } else if real_cmd.contains(' ') {
+            output.push_str(&format!(
+                "Remove-Item Alias:{alias} -Force -ErrorAction SilentlyContinue\n\
+                 function global:{alias} {{\n    {real_cmd} @args\n}}\n\n"
+            ));
+        } else {
+            output.push_str(&format!(
+                "Set-Alias -Name {alias} -Value {real_cmd} -Scope Global -Force\n\n"
+            ));
+        }
+    }
+
+    for name in composite_names(commands, composites) {
+        output.push_str(&translate_function(name));
+    }
+
+    if guard {
+        output.push_str(&format!("$env:{INIT_GUARD} = \"$PID\"\n}}\n"));
+    }
+
+    output
+}
+
+/// Whether a mapping's program is one of `CURRENT_SHELL_BUILTINS`.
+pub fn runs_in_current_shell(real_cmd: &str) -> bool {
+    real_cmd
+        .split_whitespace()
+        .next()
+        .is_some_and(|program| CURRENT_SHELL_BUILTINS.contains(&program))
+}
+
+/// Generate a function that runs a builtin like cd in the calling shell.
+/// Each argument goes through `semantic translate --resolve-path` first (falling
+/// back to the argument as typed), so `goto /apps/x` lands in /usr/bin/x.
+/// A mapping that already has its own arguments (`back` → `cd ..`) runs as
+/// written, plus any given; one with shell syntax or placeholders runs verbatim.
+/// A `!` mark (see `extra_args_mark`) makes the function refuse arguments, and a
+/// `--` mark puts one before them.
+fn generate_builtin_function(alias: &str, real_cmd: &str, semantic_bin: &str, shell: &str) -> String {
+    let (real_cmd, mark) = extra_args_mark(real_cmd);
+    let verbatim = needs_shell(real_cmd) || takes_args(real_cmd);
+    // `cd = "cd -P"` would otherwise call itself
+    let real_cmd = if real_cmd.split_whitespace().next() == Some(alias) {
+        format!("builtin {real_cmd}")
+    } else {
+        real_cmd.to_string()
+    };
+    if mark == ExtraArgs::Rejected {
+        return match shell {
+            "fish" => format!(
+                "function {alias}\n    if set -q argv[1]\n        \
+                 echo \"{alias}: takes no arguments\" >&2\n        return 2\n    end\n    \
+                 {real_cmd}\nend\n\n"
+            ),
+            _ => format!(
+                "{alias}() {{\n    if [ $# -gt 0 ]; then\n        \
+                 echo \"{alias}: takes no arguments\" >&2\n        return 2\n    fi\n    \
+                 {real_cmd}\n}}\n\n"
+            ),
+        };
+    }
+    let real_cmd = if mark == ExtraArgs::AfterSeparator && !verbatim {
+        format!("{real_cmd} {SEPARATOR_MARK}")
+    } else {
+        real_cmd
+    };
     match shell {
-        "fish" => generate_cd_function_fish(alias, real_cmd, paths),
-        _ => generate_cd_function_posix(alias, real_cmd, paths, shell),
+        "fish" if verbatim => format!("function {alias}\n    {real_cmd}\nend\n\n"),
+        "fish" => format!(
+            "function {alias}\n    set -l args\n    for arg in $argv\n        \
+             set -a args (\"{semantic_bin}\" translate --resolve-path $arg 2>/dev/null; or printf '%s\\n' $arg)\n    \
+             end\n    {real_cmd} $args\nend\n\n"
+        ),
+        _ if verbatim => format!("{alias}() {{\n    {real_cmd}\n}}\n\n"),
+        _ => format!(
+            "{alias}() {{\n    local arg\n    local -a args=()\n    for arg in \"$@\"; do\n        \
+             args+=(\"$(\"{semantic_bin}\" translate --resolve-path \"$arg\" 2>/dev/null || printf '%s' \"$arg\")\")\n    \
+             done\n    {real_cmd} \"${{args[@]}}\"\n}}\n\n"
+        ),
     }
 }
 
-/// Fish shell cd function with path translation.
-fn generate_cd_function_fish(
-    alias: &str,
-    real_cmd: &str,
-    paths: &HashMap<String, String>,
-) -> String {
-    // if the real command already has args (like "cd .."), make it a simple function
-    if real_cmd.contains(' ') {
-        return format!(
-            "function {alias}\n    {real_cmd}\nend\n\n"
-        );
+/// Commands whose completion can be borrowed from the real command's, with the
+/// words to complete as instead: `install` → ["pacman", "-S"]. A leading sudo is
+/// dropped, since it's the program after it whose arguments matter.
+/// Shell snippets, placeholders and shell-mode commands are left out (there's no
+/// one command line to complete as), and so is a mapping to its own name, whose
+/// completion would end up calling itself.
+fn completion_targets<'a>(
+    commands: &'a HashMap<String, String>,
+    options: &InitOptions,
+) -> Vec<(&'a str, Vec<&'a str>)> {
+    let mut targets: Vec<_> = commands
+        .iter()
+        .filter(|(alias, real_cmd)| {
+            !needs_shell(real_cmd) && !takes_args(real_cmd) && !options.shell_mode.contains(*alias)
+        })
+        .filter_map(|(alias, real_cmd)| {
+            // nothing to complete for a `!` mapping, which takes no arguments
+            let (real_cmd, mark) = extra_args_mark(real_cmd);
+            if mark == ExtraArgs::Rejected {
+                return None;
+            }
+            let words: Vec<&str> =
+                real_cmd.split_whitespace().skip_while(|word| *word == "sudo").collect();
+            let program = words.first()?;
+            (!program.starts_with('-') && program != alias).then_some((alias.as_str(), words))
+        })
+        .collect();
+    targets.sort();
+    targets
+}
+
+/// Completion definitions that hand each target's arguments to the real
+/// command's completion. fish does this itself with `--wraps`; bash and zsh get
+/// a function that rewrites the words being completed and calls the real
+/// command's completer. zsh can only register them once compinit has run.
+fn generate_completions(targets: &[(&str, Vec<&str>)], shell: &str) -> String {
+    if targets.is_empty() || !matches!(shell, "bash" | "zsh" | "fish") {
+        return String::new();
     }
+    let quote = |word: &str| shell_escape::unix::escape(word.into()).into_owned();
 
-    // otherwise, build a function that translates paths before cd'ing
-    let mut func = format!("function {alias}\n    set -l target $argv[1]\n");
+    let mut output = String::from("# complete each command like the one it runs\n");
+    match shell {
+        "fish" => {
+            for (alias, words) in targets {
+                output.push_str(&format!("complete -c {alias} --wraps {}\n", quote(&words.join(" "))));
+            }
+        }
+        "zsh" => {
+            output.push_str(
+                "if (( $+functions[compdef] )); then\n\
+                 _semantic_complete_as() {\n    \
+                 words=(\"$@\" \"${(@)words[2,-1]}\")\n    \
+                 (( CURRENT += $# - 1 ))\n    \
+                 _normal\n\
+                 }\n",
+            );
+            for (alias, words) in targets {
+                let words: Vec<_> = words.iter().map(|w| quote(w)).collect();
+                output.push_str(&format!(
+                    "_semantic_complete_{alias}() {{ _semantic_complete_as {}; }}\n\
+                     compdef _semantic_complete_{alias} {alias}\n",
+                    words.join(" ")
+                ));
+            }
+            output.push_str("fi\n");
+        }
+        _ => {
+            // the real program's completer, loading it the way bash-completion
+            // does on first use; _longopt (completion from --help) otherwise
+            output.push_str(
+                "_semantic_complete_as() {\n    \
+                 local spec func alias=${COMP_WORDS[0]}\n    \
+                 spec=$(complete -p \"$1\" 2>/dev/null)\n    \
+                 if [ -z \"$spec\" ] && declare -F _completion_loader >/dev/null; then\n        \
+                 _completion_loader \"$1\"\n        \
+                 spec=$(complete -p \"$1\" 2>/dev/null)\n    \
+                 fi\n    \
+                 if [[ $spec =~ -F\\ ([^ ]+) ]]; then\n        \
+                 func=${BASH_REMATCH[1]}\n    \
+                 elif declare -F _longopt >/dev/null; then\n        \
+                 func=_longopt\n    \
+                 else\n        \
+                 return\n    \
+                 fi\n    \
+                 local prefix=\"$*\"\n    \
+                 COMP_LINE=\"$prefix${COMP_LINE#\"$alias\"}\"\n    \
+                 COMP_POINT=$((COMP_POINT + ${#prefix} - ${#alias}))\n    \
+                 COMP_WORDS=(\"$@\" \"${COMP_WORDS[@]:1}\")\n    \
+                 COMP_CWORD=$((COMP_CWORD + $# - 1))\n    \
+                 \"$func\" \"$1\" \"${COMP_WORDS[COMP_CWORD]}\" \"${COMP_WORDS[COMP_CWORD-1]}\"\n\
+                 }\n",
+            );
+            for (alias, words) in targets {
+                let words: Vec<_> = words.iter().map(|w| quote(w)).collect();
+                output.push_str(&format!(
+                    "_semantic_complete_{alias}() {{ _semantic_complete_as {}; }}\n\
+                     complete -o default -F _semantic_complete_{alias} {alias}\n",
+                    words.join(" ")
+                ));
+            }
+        }
+    }
+    output.push('\n');
+    output
+}
 
-    // add path translation cases
-    for (virtual_path, real_path) in paths {
-        func.push_str(&format!(
-            "    if test \"$target\" = \"{virtual_path}\"\n        set target \"{real_path}\"\n    end\n"
-        ));
+/// Shells `generate_cli_completions` writes for.
+pub const CLI_COMPLETION_SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+/// Subcommands whose first argument is a semantic command name.
+const NAME_SUBCOMMANDS: &[&str] = &["translate", "which", "help"];
+
+/// A completion script for `semantic` itself, for `semantic completions`:
+/// `subcommands` as its first word, and each semantic command in `commands`
+/// after `translate`, `which` or `help`. fish and zsh show each one's
+/// description (its real command) next to it; bash can only list the names.
+/// Only the shells in `CLI_COMPLETION_SHELLS`.
+pub fn generate_cli_completions(subcommands: &[&str], commands: &[(String, String)], shell: &str) -> String {
+    let quote = |word: &str| shell_escape::unix::escape(word.into()).into_owned();
+    // a description is one line of plain text, however the command was written
+    let flatten = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    let mut output = format!(
+        "# semantic completions ({shell})\n\
+         # Auto-generated by `semantic completions`; run it again after changing your mappings\n\n"
+    );
+    match shell {
+        "fish" => {
+            let name_subcommands = NAME_SUBCOMMANDS.join(" ");
+            output.push_str("complete -c semantic -f\n");
+            output.push_str(&format!(
+                "complete -c semantic -n __fish_use_subcommand -a {}\n",
+                quote(&subcommands.join(" "))
+            ));
+            for (name, real_cmd) in commands {
+                output.push_str(&format!(
+                    "complete -c semantic -n '__fish_seen_subcommand_from {name_subcommands}' -a {} -d {}\n",
+                    quote(name),
+                    quote(&flatten(real_cmd))
+                ));
+            }
+        }
+        "zsh" => {
+            // _describe splits each entry at its first unescaped colon
+            let entries: Vec<String> = commands
+                .iter()
+                .map(|(name, real_cmd)| {
+                    quote(&format!("{}:{}", name.replace('\\', "\\\\").replace(':', "\\:"), flatten(real_cmd)))
+                })
+                .collect();
+            output.push_str(&format!(
+                "_semantic() {{\n    \
+                 local -a subcommands commands\n    \
+                 subcommands=({})\n    \
+                 commands=(\n        {}\n    )\n    \
+                 if (( CURRENT == 2 )); then\n        \
+                 compadd -a subcommands\n    \
+                 elif (( CURRENT == 3 )) && [[ ${{words[2]}} == ({}) ]]; then\n        \
+                 _describe 'semantic command' commands\n    \
+                 fi\n\
+                 }}\n\
+                 if (( $+functions[compdef] )); then\n    \
+                 compdef _semantic semantic\n\
+                 fi\n",
+                subcommands.join(" "),
+                entries.join("\n        "),
+                NAME_SUBCOMMANDS.join("|")
+            ));
+        }
+        _ => {
+            let names: Vec<&str> = commands.iter().map(|(name, _)| name.as_str()).collect();
+            output.push_str(&format!(
+                "_semantic() {{\n    \
+                 local cur=${{COMP_WORDS[COMP_CWORD]}}\n    \
+                 if [ \"$COMP_CWORD\" -eq 1 ]; then\n        \
+                 COMPREPLY=($(compgen -W {} -- \"$cur\"))\n    \
+                 elif [ \"$COMP_CWORD\" -eq 2 ]; then\n        \
+                 case ${{COMP_WORDS[1]}} in\n            \
+                 {}) COMPREPLY=($(compgen -W {} -- \"$cur\")) ;;\n        \
+                 esac\n    \
+                 fi\n\
+                 }}\n\
+                 complete -o default -F _semantic semantic\n",
+                quote(&subcommands.join(" ")),
+                NAME_SUBCOMMANDS.join("|"),
+                quote(&names.join(" "))
+            ));
+        }
     }
+    output
+}
 
-    func.push_str("    cd $target\nend\n\n");
-    func
+/// Path mappings sorted by virtual path, so generated code is deterministic.
+fn sorted_paths(paths: &HashMap<String, String>) -> Vec<(&String, &String)> {
+    let mut sorted: Vec<_> = paths.iter().collect();
+    sorted.sort();
+    sorted
 }
 
-/// Bash/Zsh cd function with path translation.
-fn generate_cd_function_posix(
-    alias: &str,
-    real_cmd: &str,
+/// Generate a static alias for alias mode: the real command baked in, with any
+/// arguments appended by the shell. Fish's `alias` writes a wrapping function.
+fn generate_static_alias(alias: &str, real_cmd: &str, shell: &str) -> String {
+    // fish reads '…'\''…' the same way POSIX shells do
+    let quoted = shell_escape::unix::escape(real_cmd.into());
+    match shell {
+        "fish" => format!("alias {alias} {quoted}\n\n"),
+        _ => format!("alias {alias}={quoted}\n\n"),
+    }
+}
+
+/// A line that sets (and exports) an environment variable in `shell`.
+pub fn generate_env_export(shell: &str, name: &str, value: &str) -> String {
+    let quoted = shell_escape::unix::escape(value.into());
+    match shell {
+        "fish" => format!("set -gx {name} {quoted}\n"),
+        "nu" => format!("$env.{name} = \"{value}\"\n"),
+        "pwsh" | "powershell" => format!("$env:{name} = \"{value}\"\n"),
+        _ => format!("export {name}={quoted}\n"),
+    }
+}
+
+/// Code that puts `dir` at the front of $PATH in `shell`, unless it's already
+/// in it (nushell moves it to the front instead), for `[general] semantic_bin_dir`.
+fn generate_path_prepend(dir: &str, shell: &str) -> String {
+    let quoted = shell_escape::unix::escape(dir.into());
+    match shell {
+        "fish" => format!("contains -- {quoted} $PATH; or set -gx PATH {quoted} $PATH\n\n"),
+        // single-quoted strings are raw in nushell and PowerShell, so Windows
+        // paths keep their backslashes
+        "nu" => {
+            format!("$env.PATH = ($env.PATH | where {{|dir| $dir != '{dir}'}} | prepend '{dir}')\n\n")
+        }
+        "pwsh" | "powershell" => {
+            let dir = dir.replace('\'', "''");
+            format!(
+                "if (($env:PATH -split [IO.Path]::PathSeparator) -notcontains '{dir}') {{\n    \
+                 $env:PATH = '{dir}' + [IO.Path]::PathSeparator + $env:PATH\n}}\n\n"
+            )
+        }
+        _ => format!(
+            "case \":$PATH:\" in\n    *:{quoted}:*) ;;\n    *) export PATH={quoted}:\"$PATH\" ;;\nesac\n\n"
+        ),
+    }
+}
+
+/// A definition of one mapping for `semantic alias`, with the real command
+/// baked in so nothing reads the config when it runs. A single word becomes an
+/// alias; anything longer a function that appends its arguments, so
+/// `deploy -v` runs `ssh user@prod -v`.
+pub fn generate_session_alias(name: &str, real_cmd: &str, shell: &str) -> String {
+    let single = !real_cmd.trim().contains(char::is_whitespace);
+    match shell {
+        // nushell's own cd has no binary behind it, and needs --env to reach the caller
+        "nu" if runs_in_current_shell(real_cmd) => {
+            format!("def --env --wrapped {name} [...args] {{\n    {real_cmd} ...$args\n}}\n")
+        }
+        "nu" if single => format!("alias {name} = ^{real_cmd}\n"),
+        "nu" => format!("def --wrapped {name} [...args] {{\n    ^{real_cmd} ...$args\n}}\n"),
+        "pwsh" | "powershell" if single => {
+            format!("Set-Alias -Name {name} -Value {real_cmd} -Scope Global -Force\n")
+        }
+        "pwsh" | "powershell" => format!(
+            "Remove-Item Alias:{name} -Force -ErrorAction SilentlyContinue\n\
+             function global:{name} {{\n    {real_cmd} @args\n}}\n"
+        ),
+        _ if single => generate_static_alias(name, real_cmd, shell).trim_end().to_string() + "\n",
+        "fish" => format!("function {name}\n    {real_cmd} $argv\nend\n"),
+        _ => format!("{name}() {{\n    {real_cmd} \"$@\"\n}}\n"),
+    }
+}
+
+/// Shells `generate_alias_file` writes for: POSIX sh and the shells that read it, and fish.
+pub const ALIAS_FILE_SHELLS: &[&str] = &["sh", "bash", "zsh", "fish"];
+
+/// A standalone script of definitions for every mapping, for `semantic alias-file`:
+/// `header` (comment lines) under a shebang, then each command with its real
+/// command baked in, so nothing calls semantic when it runs. Single words become
+/// aliases and everything else a function; composite commands run their steps
+/// with `&&`, and `{args}`/`{N}` placeholders become the shell's own arguments.
+/// A `_semantic_path` function maps virtual paths to real ones (longest
+/// match first) for completion scripts and the cd-like functions, which
+/// translate their arguments with it the way `semantic translate` would.
+/// Only the shells in `ALIAS_FILE_SHELLS`.
+pub fn generate_alias_file(
+    commands: &HashMap<String, String>,
+    composites: &HashMap<String, Vec<String>>,
     paths: &HashMap<String, String>,
-    _shell: &str,
+    shell_mode: &HashSet<String>,
+    shell: &str,
+    header: &str,
 ) -> String {
-    // if the real command already has args (like "cd .."), make it a simple function
-    if real_cmd.contains(' ') {
-        return format!(
-            "{alias}() {{\n    {real_cmd}\n}}\n\n"
-        );
+    let fish = shell == "fish";
+    let mut output = format!("#!/usr/bin/env {shell}\n{header}\n");
+
+    // longest first, so /apps/games wins over /apps
+    let mut sorted_paths = sorted_paths(paths);
+    sorted_paths.sort_by_key(|(virtual_path, _)| std::cmp::Reverse(virtual_path.len()));
+    output.push_str("# the real path for a virtual one, or the argument unchanged\n");
+    if fish {
+        output.push_str("function _semantic_path\n    switch $argv[1]\n");
+        for (virtual_path, real_path) in &sorted_paths {
+            let virtual_path = virtual_path.trim_end_matches('/');
+            output.push_str(&format!(
+                "        case '{virtual_path}' '{virtual_path}/*'\n            \
+                 printf '%s\\n' '{real_path}'(string sub -s {} -- $argv[1])\n",
+                virtual_path.len() + 1
+            ));
+        }
+        output.push_str("        case '*'\n            printf '%s\\n' $argv[1]\n    end\nend\n\n");
+    } else {
+        output.push_str("_semantic_path() {\n    case \"$1\" in\n");
+        for (virtual_path, real_path) in &sorted_paths {
+            let virtual_path = virtual_path.trim_end_matches('/');
+            output.push_str(&format!(
+                "        '{virtual_path}' | '{virtual_path}'/*) printf '%s\\n' '{real_path}'\"${{1#'{virtual_path}'}}\" ;;\n"
+            ));
+        }
+        output.push_str("        *) printf '%s\\n' \"$1\" ;;\n    esac\n}\n\n");
     }
 
-    // build a function with path translation via case statement
-    let mut func = format!("{alias}() {{\n    local target=\"$1\"\n    case \"$target\" in\n");
+    let all_args = if fish { "$argv" } else { "\"$@\"" };
+    let function = |name: &str, body: &str| {
+        if fish {
+            format!("function {name}\n    {body}\nend\n\n")
+        } else {
+            format!("{name}() {{\n    {body}\n}}\n\n")
+        }
+    };
+    let placeholders = |real_cmd: &str| {
+        let mut body = real_cmd.replace(ARGS_PLACEHOLDER, all_args);
+        for n in 1..=9 {
+            let arg = if fish { format!("$argv[{n}]") } else { format!("\"${{{n}}}\"") };
+            body = body.replace(&format!("{{{n}}}"), &arg);
+        }
+        body
+    };
 
-    for (virtual_path, real_path) in paths {
-        func.push_str(&format!(
-            "        \"{virtual_path}\") target=\"{real_path}\" ;;\n"
-        ));
+    let mut sorted: Vec<_> = commands.iter().collect();
+    sorted.sort();
+    for (name, real_cmd) in sorted {
+        // skip identity mappings (traditional style)
+        if name == real_cmd {
+            continue;
+        }
+        let (bare, mark) = extra_args_mark(real_cmd);
+        let definition = if shell_mode.contains(name) {
+            // a script already says where its arguments go
+            function(name, real_cmd)
+        } else if takes_args(real_cmd) {
+            function(name, &placeholders(real_cmd))
+        } else if mark == ExtraArgs::Rejected {
+            function(name, bare)
+        } else if runs_in_current_shell(real_cmd) && !needs_shell(real_cmd) {
+            let separator = if mark == ExtraArgs::AfterSeparator { " --" } else { "" };
+            let bare = if bare.split_whitespace().next() == Some(name.as_str()) {
+                format!("builtin {bare}")
+            } else {
+                bare.to_string()
+            };
+            if fish {
+                format!(
+                    "function {name}\n    set -l args\n    for arg in $argv\n        \
+                     set -a args (_semantic_path $arg)\n    end\n    {bare}{separator} $args\nend\n\n"
+                )
+            } else {
+                // no arrays in plain sh, so rebuild "$@" in place
+                format!(
+                    "{name}() {{\n    for arg in \"$@\"; do\n        shift\n        \
+                     set -- \"$@\" \"$(_semantic_path \"$arg\")\"\n    done\n    \
+                     {bare}{separator} \"$@\"\n}}\n\n"
+                )
+            }
+        } else if mark == ExtraArgs::AfterSeparator {
+            function(name, &format!("{bare} -- {all_args}"))
+        } else {
+            generate_session_alias(name, real_cmd, shell) + "\n"
+        };
+        output.push_str(&definition);
     }
 
-    func.push_str("    esac\n    cd \"$target\"\n}\n\n");
-    func
+    for name in composite_names(commands, composites) {
+        let steps: Vec<String> = composites[name].iter().map(|step| placeholders(step)).collect();
+        let joiner = if fish { "; and " } else { " && " };
+        output.push_str(&function(name, &steps.join(joiner)));
+    }
+
+    output
 }
 
 /// Generate an alias that delegates to `semantic translate`.
 /// The semantic binary handles looking up the command and running it.
+/// SEMANTIC_SHELL tells it which shell's `[commands.<shell>]` overrides apply.
 fn generate_alias(alias: &str, _real_cmd: &str, semantic_bin: &str, shell: &str) -> String {
     match shell {
         "fish" => format!(
-            "function {alias}\n    {semantic_bin} translate {alias} $argv\nend\n\n"
+            "function {alias}\n    SEMANTIC_SHELL=fish {semantic_bin} translate {alias} $argv\nend\n\n"
         ),
         _ => format!(
-            "{alias}() {{\n    \"{semantic_bin}\" translate {alias} \"$@\"\n}}\n\n"
+            "{alias}() {{\n    SEMANTIC_SHELL={shell} \"{semantic_bin}\" translate {alias} \"$@\"\n}}\n\n"
         ),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Sandbox;
+
+    fn strings(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn operators_send_a_mapping_through_the_shell() {
+        assert!(needs_shell("journalctl -f | grep ERROR"));
+        assert!(needs_shell("make && make install"));
+        assert!(!needs_shell("ls -la"));
+
+        let argv = command_argv("zsh", "journalctl -f | grep ERROR", &strings(&["a b", "it's"])).unwrap();
+        assert_eq!(argv, ["zsh", "-c", r#"journalctl -f | grep ERROR 'a b' 'it'\''s'"#]);
+    }
+
+    #[test]
+    fn unknown_or_unset_shells_run_scripts_with_sh() {
+        for shell in ["nu", "pwsh", ""] {
+            let argv = command_argv(shell, "echo hi > /dev/null", &[]).unwrap();
+            assert_eq!(argv, ["sh", "-c", "echo hi > /dev/null"]);
+        }
+    }
+
+    #[test]
+    fn plain_mappings_are_split_and_run_directly() {
+        let argv = command_argv("bash", "ls -la", &strings(&["a b"])).unwrap();
+        assert_eq!(argv, ["ls", "-la", "a b"]);
+    }
+
+    #[test]
+    fn positional_placeholders_take_their_arguments() {
+        let argv = command_argv("bash", "grep -rn {1} {2}", &strings(&["TODO", "src", "-i"])).unwrap();
+        assert_eq!(argv, ["grep", "-rn", "TODO", "src", "-i"]);
+
+        let argv = command_argv("bash", "cp {2} {1}", &strings(&["to", "from"])).unwrap();
+        assert_eq!(argv, ["cp", "from", "to"]);
+    }
+
+    #[test]
+    fn mappings_without_placeholders_append_the_arguments() {
+        let argv = command_argv("bash", "grep -rn", &strings(&["TODO", "src"])).unwrap();
+        assert_eq!(argv, ["grep", "-rn", "TODO", "src"]);
+    }
+
+    #[test]
+    fn a_missing_positional_argument_is_an_error() {
+        let error = command_argv("bash", "grep -rn {1} {2}", &strings(&["TODO"])).unwrap_err();
+        assert_eq!(error, "needs argument {2}, but only 1 given");
+    }
+
+    #[test]
+    fn shell_names_are_normalized() {
+        assert_eq!(normalize_shell_name("-bash"), "bash");
+        assert_eq!(normalize_shell_name("fish"), "fish");
+        assert_eq!(normalize_shell_name("zsh-5.9"), "zsh");
+        assert_eq!(normalize_shell_name("/usr/bin/zsh-5.9"), "zsh");
+        assert_eq!(normalize_shell_name(r"C:\Program Files\PowerShell\7\pwsh.exe"), "pwsh");
+        assert_eq!(normalize_shell_name(" Bash\n"), "bash");
+    }
+
+    #[test]
+    fn semantic_shell_overrides_detection() {
+        let mut sandbox = Sandbox::new();
+        sandbox.set("SEMANTIC_SHELL", "/usr/local/bin/fish");
+        assert_eq!(detect_shell_with_source(), Some(("fish".to_string(), ShellSource::Override)));
+
+        // one semantic doesn't know is passed over
+        sandbox.set("SEMANTIC_SHELL", "tcsh");
+        assert_ne!(detect_shell_with_source().map(|(_, source)| source), Some(ShellSource::Override));
+    }
+
+    /// `generate_init` for one hyphenated command, and the binary it calls back into.
+    fn init_for(shell: &str, mode: Option<IntegrationMode>) -> (String, String) {
+        let commands = HashMap::from([("pkg-add".to_string(), "pacman -S".to_string())]);
+        let options = InitOptions { guard: false, mode, shell_mode: &HashSet::new(), bin_dir: None };
+        let init = generate_init(&commands, &HashMap::new(), &HashMap::new(), shell, &options).unwrap();
+        (init, env::current_exe().unwrap().display().to_string())
+    }
+
+    #[test]
+    fn function_mode_calls_translate_in_every_shell() {
+        for shell in ["bash", "zsh"] {
+            let (init, bin) = init_for(shell, Some(IntegrationMode::Function));
+            let expected = format!(
+                "pkg-add() {{\n    SEMANTIC_SHELL={shell} \"{bin}\" translate pkg-add \"$@\"\n}}\n"
+            );
+            assert!(init.contains(&expected), "{shell}:\n{init}");
+        }
+
+        let (init, bin) = init_for("fish", Some(IntegrationMode::Function));
+        let expected =
+            format!("function pkg-add\n    SEMANTIC_SHELL=fish {bin} translate pkg-add $argv\nend\n");
+        assert!(init.contains(&expected), "{init}");
+
+        let (init, bin) = init_for("nu", Some(IntegrationMode::Function));
+        let expected = format!(
+            "def --wrapped pkg-add [...args] {{\n    ^\"{bin}\" translate pkg-add ...$args\n}}\n"
+        );
+        assert!(init.contains(&expected), "{init}");
+
+        let (init, bin) = init_for("pwsh", Some(IntegrationMode::Function));
+        let expected =
+            format!("function global:pkg-add {{\n    & \"{bin}\" translate pkg-add @args\n}}\n");
+        assert!(init.contains(&expected), "{init}");
+    }
+
+    #[test]
+    fn alias_mode_bakes_the_mapping_in() {
+        for shell in ["bash", "zsh"] {
+            let (init, _) = init_for(shell, Some(IntegrationMode::Alias));
+            assert!(init.contains("alias pkg-add='pacman -S'\n"), "{shell}:\n{init}");
+            assert!(!init.contains("translate pkg-add"), "{shell}:\n{init}");
+        }
+
+        let (init, _) = init_for("fish", Some(IntegrationMode::Alias));
+        assert!(init.contains("alias pkg-add 'pacman -S'\n"), "{init}");
+
+        let (init, _) = init_for("nu", Some(IntegrationMode::Alias));
+        assert!(init.contains("def --wrapped pkg-add [...args] {\n    ^pacman -S ...$args\n}\n"));
+
+        let (init, _) = init_for("pwsh", Some(IntegrationMode::Alias));
+        assert!(init.contains("function global:pkg-add {\n    pacman -S @args\n}\n"), "{init}");
+    }
+
+    #[test]
+    fn each_shell_keeps_its_usual_mode_by_default() {
+        for shell in ["bash", "zsh", "fish"] {
+            let (init, _) = init_for(shell, None);
+            assert!(init.contains("translate pkg-add"), "{shell}:\n{init}");
+        }
+        for shell in ["nu", "pwsh"] {
+            let (init, _) = init_for(shell, None);
+            assert!(!init.contains("translate pkg-add"), "{shell}:\n{init}");
+        }
+    }
+
+    #[test]
+    fn raw_shell_paths_map_onto_the_known_shells() {
+        assert_eq!(known_shell("/usr/bin/zsh").as_deref(), Some("zsh"));
+        assert_eq!(known_shell("/usr/local/bin/fish").as_deref(), Some("fish"));
+        assert_eq!(known_shell("-bash").as_deref(), Some("bash"));
+        assert_eq!(known_shell("/bin/tcsh"), None);
+        assert_eq!(known_shell("tmux: server"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn process_info_reads_our_own_parent() {
+        let (name, parent) = process_info(std::process::id()).unwrap();
+        assert!(!name.is_empty());
+        assert_eq!(parent, std::os::unix::process::parent_id());
+    }
+
+    #[test]
+    fn reinstalling_the_init_block_replaces_it() {
+        let sandbox = Sandbox::new();
+        let block = format!("{INIT_BEGIN_MARKER}\nalias gone='true'\n{INIT_END_MARKER}\n");
+        let rc = sandbox.write("home/.bashrc", &format!("alias ll='ls -l'\n\n{block}"));
+        let code = "alias install='sudo pacman -S'\n";
+
+        assert_eq!(install_init_block("bash", code, false).unwrap(), Some(rc.clone()));
+        let first = fs::read_to_string(&rc).unwrap();
+        assert_eq!(first.matches(INIT_BEGIN_MARKER).count(), 1);
+        assert_eq!(first.matches(INIT_END_MARKER).count(), 1);
+        assert!(first.starts_with("alias ll='ls -l'\n"));
+        assert!(first.contains("alias install='sudo pacman -S'"));
+        assert!(!first.contains("alias gone"));
+
+        // the same code again leaves the file as it is
+        assert_eq!(install_init_block("bash", code, false).unwrap(), None);
+        assert_eq!(fs::read_to_string(&rc).unwrap(), first);
+    }
+
+    #[test]
+    fn strip_rc_block_keeps_what_is_outside_the_markers() {
+        let content = format!("export A=1\n{RC_BEGIN_MARKER}\neval x\n{RC_END_MARKER}\nexport B=2\n");
+        assert_eq!(strip_rc_block(&content).unwrap(), "export A=1\nexport B=2\n");
+        assert_eq!(strip_rc_block("export A=1\n"), None);
+    }
+
+    #[test]
+    fn fish_completions_describe_each_command_with_its_real_one() {
+        let commands = vec![("install".to_string(), "sudo pacman -S".to_string())];
+        let script = generate_cli_completions(&["translate", "which"], &commands, "fish");
+        let line = script.lines().find(|line| line.contains("-a install")).expect("install completes");
+        assert!(line.ends_with("-d 'sudo pacman -S'"), "{line}");
+    }
+
+    #[test]
+    fn zsh_completions_escape_colons_in_names() {
+        let commands = vec![("db:up".to_string(), "docker compose\n  up -d".to_string())];
+        let script = generate_cli_completions(&["translate"], &commands, "zsh");
+        assert!(script.contains(r"'db\:up:docker compose up -d'"), "{script}");
+    }
+
+    /// `generate_init` for one command, with `semantic_bin_dir` set to `bin_dir`.
+    fn init_with_bin_dir(shell: &str, bin_dir: Option<&str>) -> String {
+        let commands = HashMap::from([("pkg-add".to_string(), "pacman -S".to_string())]);
+        let options = InitOptions { guard: true, mode: None, shell_mode: &HashSet::new(), bin_dir };
+        generate_init(&commands, &HashMap::new(), &HashMap::new(), shell, &options).unwrap()
+    }
+
+    #[test]
+    fn bin_dir_goes_on_the_front_of_path_in_every_shell() {
+        let prepends = [
+            ("bash", "case \":$PATH:\" in\n    *:'/opt/my bin':*) ;;\n    \
+                      *) export PATH='/opt/my bin':\"$PATH\" ;;\nesac\n"),
+            ("zsh", "*) export PATH='/opt/my bin':\"$PATH\" ;;\n"),
+            ("fish", "contains -- '/opt/my bin' $PATH; or set -gx PATH '/opt/my bin' $PATH\n"),
+            ("nu", "$env.PATH = ($env.PATH | where {|dir| $dir != '/opt/my bin'} \
+                    | prepend '/opt/my bin')\n"),
+            ("pwsh", "-notcontains '/opt/my bin') {\n    \
+                      $env:PATH = '/opt/my bin' + [IO.Path]::PathSeparator + $env:PATH\n}\n"),
+            ("powershell", "$env:PATH = '/opt/my bin' + [IO.Path]::PathSeparator + $env:PATH\n"),
+        ];
+        assert_eq!(prepends.len(), KNOWN_SHELLS.len());
+        for (shell, prepend) in prepends {
+            let init = init_with_bin_dir(shell, Some("/opt/my bin"));
+            let at = init.find(prepend).unwrap_or_else(|| panic!("{shell}:\n{init}"));
+            // before any command, so the commands find semantic through it
+            assert!(at < init.find("pkg-add").unwrap(), "{shell}:\n{init}");
+            assert_eq!(init.matches("/opt/my bin").count(), 2, "{shell}:\n{init}");
+        }
+    }
+
+    #[test]
+    fn an_empty_bin_dir_leaves_path_alone() {
+        for setting in ["", "semantic_bin_dir = \"\"\n", "semantic_bin_dir = \"  \"\n"] {
+            let general = format!("[general]\n{setting}");
+            let toml = crate::testutil::BASE_CONFIG.replacen("[general]\n", &general, 1);
+            let config: crate::config::SemanticConfig = toml::from_str(&toml).unwrap();
+            assert_eq!(config.bin_dir(), None, "{setting}");
+        }
+        for shell in KNOWN_SHELLS {
+            let init = init_with_bin_dir(shell, None);
+            assert!(!init.contains("PATH"), "{shell}:\n{init}");
+        }
+    }
+}
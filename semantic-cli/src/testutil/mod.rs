@@ -0,0 +1,169 @@
+// testutil/mod.rs
+// Shared setup for the unit tests. semantic finds its files through environment
+// variables ($SEMANTIC_CONFIG_DIR, $XDG_STATE_HOME, ...) and the current
+// directory, which every test thread shares. A test that reads or sets either
+// takes a `Sandbox`: it holds a lock for the rest of the test and points all of
+// them at a fresh temporary directory, putting everything back when dropped.
+
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::config::SemanticConfig;
+
+/// Held by whichever test is using the environment.
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+/// A config with just the required settings, for tests to add mappings to.
+pub const BASE_CONFIG: &str = "\
+[general]
+command_style = \"natural\"
+folder_style = \"natural\"
+
+[shells]
+default = \"bash\"
+enabled = [\"bash\"]
+on_new_shell = \"ignore\"
+";
+
+/// templates/verbose.toml drifted from the built-in verbose style: `list-files`
+/// changed, `update-system` missing and `search-package` extra, and no [paths].
+pub const DRIFTED_VERBOSE_TEMPLATE: &str = "\
+[commands]
+go-to = \"cd\"
+go-back = \"cd ..\"
+list-files = \"ls -al\"
+delete-file = \"rm -rf\"
+copy-file = \"cp -r\"
+move-file = \"mv\"
+install-package = \"sudo pacman -S\"
+remove-package = \"sudo pacman -R\"
+search-package = \"pacman -Ss\"
+";
+
+/// Variables that change where semantic looks or how it behaves, cleared for
+/// every test unless the sandbox sets them.
+const VARS: &[&str] = &[
+    "HOME",
+    "XDG_CONFIG_HOME",
+    "XDG_CACHE_HOME",
+    "XDG_STATE_HOME",
+    "SEMANTIC_CONFIG",
+    "SEMANTIC_CONFIG_DIR",
+    "SEMANTIC_PROFILE",
+    "SEMANTIC_SHELL",
+    "SEMANTIC_NO_LOCAL",
+    "SEMANTIC_NO_CACHE",
+    "SEMANTIC_LOCK_TIMEOUT_MS",
+    "SEMANTIC_TRANSLATE_DEPTH",
+    "SEMANTIC_LOG",
+];
+
+/// `BASE_CONFIG` plus `extra` (more tables, usually `[commands]` and `[paths]`), parsed.
+pub fn config(extra: &str) -> SemanticConfig {
+    toml::from_str(&format!("{BASE_CONFIG}\n{extra}")).expect("test config parses")
+}
+
+/// A temporary home, config dir, cache and state dir, and current directory,
+/// for one test at a time.
+pub struct Sandbox {
+    root: tempfile::TempDir,
+    saved: Vec<(&'static str, Option<OsString>)>,
+    cwd: Option<PathBuf>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Sandbox {
+    /// Take the environment and point it at a new temporary directory:
+    /// config/ is $SEMANTIC_CONFIG_DIR (created empty), home/ is $HOME and the
+    /// current directory, and cache/ and state/ are the XDG dirs.
+    /// Project configs are off ($SEMANTIC_NO_LOCAL) until a test unsets it.
+    pub fn new() -> Self {
+        let lock = ENV_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let root = tempfile::tempdir().expect("temporary directory");
+        let mut sandbox = Sandbox { root, saved: Vec::new(), cwd: env::current_dir().ok(), _lock: lock };
+
+        for var in VARS {
+            sandbox.unset(var);
+        }
+        for dir in ["config", "home"] {
+            fs::create_dir_all(sandbox.path(dir)).expect("sandbox directory");
+        }
+        sandbox.set("SEMANTIC_CONFIG_DIR", sandbox.path("config"));
+        sandbox.set("HOME", sandbox.path("home"));
+        sandbox.set("XDG_CACHE_HOME", sandbox.path("cache"));
+        sandbox.set("XDG_STATE_HOME", sandbox.path("state"));
+        sandbox.set("SEMANTIC_NO_LOCAL", "1");
+        sandbox.chdir("home");
+
+        SemanticConfig::forget_cached();
+        sandbox
+    }
+
+    /// `relative` inside the sandbox.
+    pub fn path(&self, relative: &str) -> PathBuf {
+        self.root.path().join(relative)
+    }
+
+    /// Write `content` to `relative` inside the sandbox, creating directories as needed.
+    pub fn write(&self, relative: &str, content: &str) -> PathBuf {
+        let path = self.path(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("parent directory");
+        }
+        fs::write(&path, content).expect("write test file");
+        path
+    }
+
+    /// Write `BASE_CONFIG` plus `extra` as the config file.
+    pub fn write_config(&self, extra: &str) -> PathBuf {
+        self.write("config/config.toml", &format!("{BASE_CONFIG}\n{extra}"))
+    }
+
+    /// Set a variable until the sandbox is dropped.
+    pub fn set(&mut self, var: &'static str, value: impl AsRef<OsStr>) {
+        self.save(var);
+        // SAFETY: every test that reads or writes the environment holds ENV_LOCK
+        unsafe { env::set_var(var, value) };
+    }
+
+    /// Unset a variable until the sandbox is dropped.
+    pub fn unset(&mut self, var: &'static str) {
+        self.save(var);
+        // SAFETY: as in `set`
+        unsafe { env::remove_var(var) };
+    }
+
+    /// Make `relative` (created if needed) the current directory until the
+    /// sandbox is dropped.
+    pub fn chdir(&self, relative: &str) -> PathBuf {
+        let dir = self.path(relative);
+        fs::create_dir_all(&dir).expect("directory to enter");
+        env::set_current_dir(&dir).expect("enter directory");
+        dir
+    }
+
+    fn save(&mut self, var: &'static str) {
+        if !self.saved.iter().any(|(saved, _)| *saved == var) {
+            self.saved.push((var, env::var_os(var)));
+        }
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        for (var, value) in self.saved.drain(..).rev() {
+            // SAFETY: the lock is still held; it's released after this returns
+            match value {
+                Some(value) => unsafe { env::set_var(var, value) },
+                None => unsafe { env::remove_var(var) },
+            }
+        }
+        if let Some(cwd) = &self.cwd {
+            let _ = env::set_current_dir(cwd);
+        }
+        SemanticConfig::forget_cached();
+    }
+}
@@ -1,25 +1,43 @@
 // tui/mod.rs
 // The TUI installer wizard for SemanticOS.
-// Walks the user through setup: shell, command style, folder style, new shell behavior.
-// Writes the result to ~/.config/semantic/config.toml.
+// Walks the user through setup: color theme, shell, the other shells to enable,
+// language, command style, folder style, custom mappings, new shell behavior.
+// Writes the result to ~/.config/semantic/config.toml (%APPDATA%\semantic on Windows),
+// or to the file given to `run_with_path`.
+// Over an existing config, the summary shows the lines saving would change,
+// and an unchanged config isn't rewritten.
 // Does NOT modify the system — config only.
+// Follows NO_COLOR (and --no-color): no colors, and the selected row in reverse video.
+// Lists wrap around at either end unless `[general] wrap_navigation = false`.
+// If the config can't be written, a recovery step offers to retry, to save it
+// in another directory instead, or to quit without saving.
+// The summary shows the directory semantic runs from, which the init code adds
+// to $PATH (`[general] semantic_bin_dir`); `b` there corrects it.
 
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, MouseButton,
+        MouseEvent, MouseEventKind,
+    },
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
 use ratatui::{
-    backend::CrosstermBackend,
-    layout::{Alignment, Constraint, Layout, Rect},
+    backend::{Backend, CrosstermBackend},
+    layout::{Alignment, Constraint, Layout, Position, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
     Frame, Terminal,
 };
-use std::io::{self, stdout};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, IsTerminal, Write, stdout};
+use std::path::{Path, PathBuf};
 
-use crate::config::SemanticConfig;
+use crate::config::{self, ConfigFile, ConfigFormat, LineDiff, SemanticConfig};
+use crate::doctor::INIT_MARKER;
+use crate::logging;
+use crate::shell;
 
 // -- installer steps --
 // The wizard progresses linearly through these steps.
@@ -28,11 +46,19 @@ use crate::config::SemanticConfig;
 #[derive(Clone, Copy, PartialEq)]
 enum Step {
     Welcome,
+    ColorTheme,
     Shell,
+    // the other shells to set up besides the default; a second page of the
+    // shell question, so it shares its progress dot
+    EnabledShells,
+    Language,
     CommandStyle,
     FolderStyle,
+    CustomMappings,
     NewShellBehavior,
     Summary,
+    // only reached when saving on the summary fails; not one of the numbered steps
+    WriteError,
     Done,
 }
 
@@ -40,12 +66,16 @@ impl Step {
     /// Move to the next step in the wizard.
     fn next(self) -> Self {
         match self {
-            Step::Welcome => Step::Shell,
-            Step::Shell => Step::CommandStyle,
+            Step::Welcome => Step::ColorTheme,
+            Step::ColorTheme => Step::Shell,
+            Step::Shell => Step::EnabledShells,
+            Step::EnabledShells => Step::Language,
+            Step::Language => Step::CommandStyle,
             Step::CommandStyle => Step::FolderStyle,
-            Step::FolderStyle => Step::NewShellBehavior,
+            Step::FolderStyle => Step::CustomMappings,
+            Step::CustomMappings => Step::NewShellBehavior,
             Step::NewShellBehavior => Step::Summary,
-            Step::Summary => Step::Done,
+            Step::Summary | Step::WriteError => Step::Done,
             Step::Done => Step::Done,
         }
     }
@@ -54,11 +84,16 @@ impl Step {
     fn prev(self) -> Self {
         match self {
             Step::Welcome => Step::Welcome,
-            Step::Shell => Step::Welcome,
-            Step::CommandStyle => Step::Shell,
+            Step::ColorTheme => Step::Welcome,
+            Step::Shell => Step::ColorTheme,
+            Step::EnabledShells => Step::Shell,
+            Step::Language => Step::EnabledShells,
+            Step::CommandStyle => Step::Language,
             Step::FolderStyle => Step::CommandStyle,
-            Step::NewShellBehavior => Step::FolderStyle,
+            Step::CustomMappings => Step::FolderStyle,
+            Step::NewShellBehavior => Step::CustomMappings,
             Step::Summary => Step::NewShellBehavior,
+            Step::WriteError => Step::Summary,
             Step::Done => Step::Done,
         }
     }
@@ -67,86 +102,502 @@ impl Step {
     fn index(self) -> usize {
         match self {
             Step::Welcome => 0,
-            Step::Shell => 1,
-            Step::CommandStyle => 2,
-            Step::FolderStyle => 3,
-            Step::NewShellBehavior => 4,
-            Step::Summary => 5,
-            Step::Done => 6,
+            Step::ColorTheme => 1,
+            Step::Shell | Step::EnabledShells => 2,
+            Step::Language => 3,
+            Step::CommandStyle => 4,
+            Step::FolderStyle => 5,
+            Step::CustomMappings => 6,
+            Step::NewShellBehavior => 7,
+            Step::Summary | Step::WriteError => 8,
+            Step::Done => 9,
+        }
+    }
+
+    /// Inverse of `index`, for the visible steps only (Done isn't reachable this way).
+    fn from_index(i: usize) -> Option<Self> {
+        match i {
+            0 => Some(Step::Welcome),
+            1 => Some(Step::ColorTheme),
+            2 => Some(Step::Shell),
+            3 => Some(Step::Language),
+            4 => Some(Step::CommandStyle),
+            5 => Some(Step::FolderStyle),
+            6 => Some(Step::CustomMappings),
+            7 => Some(Step::NewShellBehavior),
+            8 => Some(Step::Summary),
+            _ => None,
+        }
+    }
+
+    /// The step a letter jumps to from Welcome or Summary: the initial of what it sets.
+    fn from_key(c: char) -> Option<Self> {
+        match c {
+            's' => Some(Step::Shell),
+            'c' => Some(Step::CommandStyle),
+            'f' => Some(Step::FolderStyle),
+            'm' => Some(Step::CustomMappings),
+            'n' => Some(Step::NewShellBehavior),
+            _ => None,
         }
     }
 }
 
 /// Total number of visible steps (Welcome through Summary).
-const TOTAL_STEPS: usize = 6;
+const TOTAL_STEPS: usize = 9;
+
+/// Minimum terminal width for showing the alias preview next to a style list.
+/// Narrower terminals fall back to the single-column layout.
+const PREVIEW_MIN_WIDTH: u16 = 80;
+
+/// Maximum number of generated lines shown in the alias preview.
+const PREVIEW_LINES: usize = 8;
+
+/// Smallest terminal the wizard will lay itself out in.
+/// Anything smaller gets a "too small" message instead of clipped widgets.
+const MIN_WIDTH: u16 = 60;
+const MIN_HEIGHT: u16 = 16;
+
+/// What the write error step offers, as (option, description).
+const WRITE_ERROR_OPTIONS: &[(&str, &str)] = &[
+    ("Retry", "try saving again, in case it was a passing problem"),
+    ("Change directory", "save the config somewhere else"),
+    ("Quit without saving", ""),
+];
+
+/// Which text field has focus on the custom mappings step.
+#[derive(Clone, Copy, PartialEq)]
+enum CustomField {
+    Name,
+    Command,
+}
+
+/// The built-in palettes offered on the theme step, as (name, description).
+/// The chosen one is saved as `[general] theme`.
+const THEMES: &[(&str, &str)] = &[
+    ("default", "cyan highlights"),
+    ("light", "for light terminal backgrounds"),
+    ("gruvbox", "warm orange and brown"),
+    ("solarized", "blue and yellow"),
+    ("nord", "arctic blue"),
+    ("monochrome", "white on dark"),
+];
+
+/// The wizard's palette: one of `THEMES`, with any color overridden in the
+/// config's `[theme]`.
+#[derive(Clone, Copy)]
+struct Theme {
+    accent: Color,   // highlights, the selected row, the current step
+    success: Color,  // completed steps, added entries
+    muted: Color,    // hints, borders, descriptions
+    warning: Color,  // filters, dialogs, changed entries
+    error: Color,    // write errors, removed entries
+    text: Color,     // prompts, unselected options
+    selected: Color, // text on the selected row
+    plain: bool,     // no colors at all (NO_COLOR); the selected row is reversed
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            accent: Color::Cyan,
+            success: Color::Green,
+            muted: Color::DarkGray,
+            warning: Color::Yellow,
+            error: Color::Red,
+            text: Color::White,
+            selected: Color::Black,
+            plain: false,
+        }
+    }
+}
+
+impl Theme {
+    /// A built-in palette by name (see `THEMES`).
+    fn preset(name: &str) -> Option<Self> {
+        let theme = match name {
+            "default" => Theme::default(),
+            // dark text throughout, and white on the (darker) selected row
+            "light" => Theme {
+                accent: Color::Rgb(0, 95, 175),
+                success: Color::Rgb(0, 125, 0),
+                muted: Color::Rgb(110, 110, 110),
+                warning: Color::Rgb(175, 95, 0),
+                error: Color::Rgb(190, 0, 0),
+                text: Color::Black,
+                selected: Color::White,
+                ..Theme::default()
+            },
+            "gruvbox" => Theme {
+                accent: Color::Rgb(254, 128, 25),
+                success: Color::Rgb(184, 187, 38),
+                muted: Color::Rgb(146, 131, 116),
+                warning: Color::Rgb(250, 189, 47),
+                error: Color::Rgb(251, 73, 52),
+                text: Color::Rgb(235, 219, 178),
+                selected: Color::Rgb(40, 40, 40),
+                ..Theme::default()
+            },
+            "solarized" => Theme {
+                accent: Color::Rgb(38, 139, 210),
+                success: Color::Rgb(133, 153, 0),
+                muted: Color::Rgb(88, 110, 117),
+                warning: Color::Rgb(181, 137, 0),
+                error: Color::Rgb(220, 50, 47),
+                text: Color::Rgb(238, 232, 213),
+                selected: Color::Rgb(0, 43, 54),
+                ..Theme::default()
+            },
+            "nord" => Theme {
+                accent: Color::Rgb(136, 192, 208),
+                success: Color::Rgb(163, 190, 140),
+                muted: Color::Rgb(97, 110, 136),
+                warning: Color::Rgb(235, 203, 139),
+                error: Color::Rgb(191, 97, 106),
+                text: Color::Rgb(236, 239, 244),
+                selected: Color::Rgb(46, 52, 64),
+                ..Theme::default()
+            },
+            "monochrome" => Theme {
+                accent: Color::White,
+                success: Color::Gray,
+                muted: Color::DarkGray,
+                warning: Color::White,
+                error: Color::White,
+                ..Theme::default()
+            },
+            _ => return None,
+        };
+        Some(theme)
+    }
+
+    /// Build the palette from the `preset` name and `[theme]`. An unknown preset
+    /// means the default one, and a color that doesn't parse keeps the preset's;
+    /// each comes back as a warning for the caller to print (or not).
+    fn from_config(preset: Option<&str>, config: &config::ThemeConfig) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        let preset = preset.unwrap_or("default");
+        let mut theme = Theme::preset(preset).unwrap_or_else(|| {
+            let known: Vec<_> = THEMES.iter().map(|(name, _)| *name).collect();
+            warnings.push(format!(
+                "semantic: warning: theme \"{preset}\" doesn't exist (known: {}), using the default",
+                known.join(", ")
+            ));
+            Theme::default()
+        });
+        let fields = [
+            ("accent", &config.accent, &mut theme.accent),
+            ("success", &config.success, &mut theme.success),
+            ("muted", &config.muted, &mut theme.muted),
+            ("warning", &config.warning, &mut theme.warning),
+            ("error", &config.error, &mut theme.error),
+            ("text", &config.text, &mut theme.text),
+            ("selected", &config.selected, &mut theme.selected),
+        ];
+        for (name, value, color) in fields {
+            let Some(value) = value else { continue };
+            match value.parse() {
+                Ok(parsed) => *color = parsed,
+                Err(_) => warnings.push(format!(
+                    "semantic: warning: theme.{name} = \"{value}\" isn't a color, using the theme's"
+                )),
+            }
+        }
+        (theme, warnings)
+    }
+
+    /// The terminal's own colors and nothing else, for NO_COLOR and --no-color.
+    fn plain() -> Self {
+        Theme {
+            accent: Color::Reset,
+            success: Color::Reset,
+            muted: Color::Reset,
+            warning: Color::Reset,
+            error: Color::Reset,
+            text: Color::Reset,
+            selected: Color::Reset,
+            plain: true,
+        }
+    }
+
+    /// The background of the selected row: the accent color, or reversed video
+    /// when there are no colors to tell it apart with.
+    fn selected_row(&self) -> Style {
+        if self.plain {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default().bg(self.accent)
+        }
+    }
+}
 
 // -- app state --
 // Holds all the state for the TUI: current step, list selections, and options.
 
 struct App {
     step: Step,
+    furthest_step: Step, // the latest step reached, so jumps can't skip ahead
 
-    // list selection state for each step (tracks which item is highlighted)
+    // list selection state for each step (tracks which item is highlighted);
+    // the shell step's selection is the default shell
     shell_state: ListState,
+
+    // the enabled shells step is multi-select: one flag per shell in `shells`,
+    // with its own highlighted row
+    enabled_shells: Vec<bool>,
+    enabled_shells_state: ListState,
+
+    theme_state: ListState,
+    language_state: ListState,
     command_style_state: ListState,
     folder_style_state: ListState,
     new_shell_state: ListState,
 
     // available options for each step
+    themes: Vec<(&'static str, &'static str)>, // (name, description), as in THEMES
     shells: Vec<&'static str>,
-    command_styles: Vec<&'static str>,
-    folder_styles: Vec<&'static str>,
+    languages: Vec<(&'static str, &'static str)>, // (name, code), as in config::LANGUAGES
+    command_styles: Vec<(&'static str, &'static str)>, // (value, example commands)
+    folder_styles: Vec<(&'static str, &'static str)>,  // (value, example paths)
     new_shell_options: Vec<(&'static str, &'static str)>, // (value, description)
 
+    // typed text narrowing the current list; cleared when the step changes
+    filter: String,
+
+    // custom mappings step: the two input fields and the pairs added so far
+    custom_name: String,
+    custom_command: String,
+    custom_focus: CustomField,
+    custom_commands: Vec<(String, String)>, // (semantic name, real command)
+
+    // render state for the visible list: highlighted row and scroll offset.
+    // Kept across frames so long lists scroll instead of jumping.
+    list_view: ListState,
+    // where that list was last drawn, so clicks can be mapped to rows
+    list_area: Option<Rect>,
+
+    // colors to draw with, and the `[theme]` they came from (kept when saving)
+    theme: Theme,
+    theme_config: config::ThemeConfig,
+    no_color: bool, // NO_COLOR or --no-color: draw with `Theme::plain` whatever is picked
+    // `[general] wrap_navigation`: None wraps, like an unset key
+    wrap_navigation: Option<bool>,
+    // `[styles]`, `[shells] integration_mode` and `include`, which the wizard
+    // doesn't ask about either
+    styles: config::StyleOverrides,
+    integration_mode: Option<String>,
+    include: Vec<String>,
+    // the existing config's own mappings (and deleted stock ones), kept
+    // whatever styles are picked
+    carried: config::Customizations,
+    // the config on disk when the wizard started, which the summary compares against
+    existing: Option<SemanticConfig>,
+    // Enter on a summary with no changes: Done without writing anything
+    unchanged: bool,
+
     should_quit: bool,
-    write_error: Option<String>, // set if config write fails on summary
+    confirm_quit: bool, // "quit without saving?" dialog is open
+    write_error: Option<String>, // why saving failed, shown on the write error step
+    write_error_state: ListState,
+    // the write error step's directory field while it's open, and what's wrong
+    // with what was entered
+    dir_input: Option<String>,
+    dir_input_error: Option<String>,
+    // where the user chose to save instead of the config dir
+    override_config_dir: Option<PathBuf>,
+    // the config file the wizard reads the existing config from and saves to,
+    // and where saving actually wrote it (the config dir's config.toml may
+    // replace a config.json, see `ConfigFile::save`)
+    config_path: PathBuf,
+    written_to: Option<PathBuf>,
+    // `[general] semantic_bin_dir` (empty for none), and the summary's field
+    // for it while it's open
+    bin_dir: String,
+    bin_dir_input: Option<String>,
+    // where the saved styles' built-in mappings differ from their templates
+    // (see `config::template_drift`), for after the wizard closes
+    template_drift: Vec<String>,
 }
 
 impl App {
-    fn new() -> Self {
+    fn new(config_path: PathBuf) -> Self {
         // initialize all list states with the first item selected
+        let mut theme_state = ListState::default();
+        theme_state.select(Some(0));
         let mut shell_state = ListState::default();
         shell_state.select(Some(0));
+        let mut enabled_shells_state = ListState::default();
+        enabled_shells_state.select(Some(0));
+        let shells = shell::PLATFORM_SHELLS.to_vec();
+        // start on the language the locale asks for, if semantic has it
+        let mut language_state = ListState::default();
+        let locale = config::language_from_env()
+            .and_then(|lang| config::LANGUAGES.iter().position(|l| l.code == lang.code));
+        language_state.select(Some(locale.unwrap_or(0)));
         let mut command_style_state = ListState::default();
         command_style_state.select(Some(0));
         let mut folder_style_state = ListState::default();
         folder_style_state.select(Some(0));
         let mut new_shell_state = ListState::default();
         new_shell_state.select(Some(0));
+        let mut write_error_state = ListState::default();
+        write_error_state.select(Some(0));
 
         App {
             step: Step::Welcome,
+            furthest_step: Step::Welcome,
+            theme_state,
             shell_state,
+            enabled_shells: vec![false; shells.len()],
+            enabled_shells_state,
+            language_state,
             command_style_state,
             folder_style_state,
             new_shell_state,
 
-            shells: vec!["fish", "bash", "zsh"],
-            command_styles: vec!["natural", "traditional", "verbose"],
-            folder_styles: vec!["natural", "traditional", "verbose"],
+            themes: THEMES.to_vec(),
+            shells,
+            languages: config::LANGUAGES.iter().map(|l| (l.name, l.code)).collect(),
+            command_styles: vec![
+                ("natural", "goto, list, install, delete"),
+                ("traditional", "cd, ls, pacman, rm"),
+                ("verbose", "go-to, list-files, install-package"),
+                ("emoji", "📂 📋 📦 🚮"),
+            ],
+            folder_styles: vec![
+                ("natural", "/apps, /settings, /logs"),
+                ("traditional", "/usr/bin, /etc, /var/log"),
+                ("verbose", "/user/applications, /configuration"),
+            ],
+            filter: String::new(),
+            custom_name: String::new(),
+            custom_command: String::new(),
+            custom_focus: CustomField::Name,
+            custom_commands: Vec::new(),
+            list_view: ListState::default(),
+            list_area: None,
             new_shell_options: vec![
                 ("auto-setup", "Automatically configure new shells"),
                 ("notify", "Notify when a new shell is detected"),
                 ("ignore", "Do nothing"),
             ],
 
+            theme: Theme::default(),
+            theme_config: config::ThemeConfig::default(),
+            no_color: false,
+            wrap_navigation: None,
+            styles: config::StyleOverrides::default(),
+            integration_mode: None,
+            include: Vec::new(),
+            carried: config::Customizations::default(),
+            existing: None,
+            unchanged: false,
+
             should_quit: false,
+            confirm_quit: false,
             write_error: None,
+            write_error_state,
+            dir_input: None,
+            dir_input_error: None,
+            override_config_dir: None,
+            config_path,
+            written_to: None,
+            bin_dir: config::detect_bin_dir().unwrap_or_default(),
+            bin_dir_input: None,
+            template_drift: Vec::new(),
         }
     }
 
     // -- accessors for the currently selected value in each step --
 
+    /// The default shell — the one `semantic init` targets unless told otherwise.
     fn selected_shell(&self) -> &str {
-        self.shells[self.shell_state.selected().unwrap_or(0)]
+        self.shells[self.default_shell()]
+    }
+
+    /// The default shell's position in `shells`.
+    fn default_shell(&self) -> usize {
+        self.shell_state.selected().unwrap_or(0)
+    }
+
+    /// All enabled shells, in list order. The default always is, even if the
+    /// enabled shells step was never reached to toggle it on.
+    fn selected_enabled_shells(&self) -> Vec<&'static str> {
+        let default = self.default_shell();
+        self.shells
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| self.enabled_shells[i] || i == default)
+            .map(|(_, s)| *s)
+            .collect()
+    }
+
+    /// The name of the highlighted theme.
+    fn selected_theme(&self) -> &'static str {
+        self.themes[self.theme_state.selected().unwrap_or(0)].0
+    }
+
+    /// Select the theme with this name, if it's one of ours.
+    fn select_theme(&mut self, name: &str) {
+        if let Some(i) = self.themes.iter().position(|(n, _)| *n == name) {
+            self.theme_state.select(Some(i));
+        }
+    }
+
+    /// Redraw in the highlighted theme, so the theme step previews each one.
+    /// Bad `[theme]` colors were already reported before the wizard started.
+    fn refresh_theme(&mut self) {
+        self.theme = if self.no_color {
+            Theme::plain()
+        } else {
+            Theme::from_config(Some(self.selected_theme()), &self.theme_config).0
+        };
+    }
+
+    /// The `[general] language` code of the highlighted language.
+    fn selected_language(&self) -> &'static str {
+        self.languages[self.language_state.selected().unwrap_or(0)].1
+    }
+
+    /// Select the language with this code, if it's one of ours.
+    fn select_language(&mut self, code: &str) {
+        if let Some(i) = self.languages.iter().position(|(_, c)| *c == code) {
+            self.language_state.select(Some(i));
+        }
+    }
+
+    /// The command styles, with the natural example in the chosen language and
+    /// the names the user's `[styles.commands.<style>]` sets.
+    fn command_style_options(&self) -> Vec<(&'static str, String)> {
+        let example = config::language(self.selected_language()).command_example;
+        self.command_styles
+            .iter()
+            .map(|&(style, desc)| {
+                let desc = if style == "natural" { example } else { desc };
+                (style, with_own(desc, self.styles.own_commands(style)))
+            })
+            .collect()
+    }
+
+    /// The folder styles, with the natural example in the chosen language and
+    /// the paths the user's `[styles.paths.<style>]` sets.
+    fn folder_style_options(&self) -> Vec<(&'static str, String)> {
+        let example = config::language(self.selected_language()).path_example;
+        self.folder_styles
+            .iter()
+            .map(|&(style, desc)| {
+                let desc = if style == "natural" { example } else { desc };
+                (style, with_own(desc, self.styles.own_paths(style)))
+            })
+            .collect()
     }
 
     fn selected_command_style(&self) -> &str {
-        self.command_styles[self.command_style_state.selected().unwrap_or(0)]
+        self.command_styles[self.command_style_state.selected().unwrap_or(0)].0
     }
 
     fn selected_folder_style(&self) -> &str {
-        self.folder_styles[self.folder_style_state.selected().unwrap_or(0)]
+        self.folder_styles[self.folder_style_state.selected().unwrap_or(0)].0
     }
 
     fn selected_new_shell(&self) -> &str {
@@ -157,7 +608,10 @@ impl App {
     /// None if the current step doesn't have a list (Welcome, Summary, Done).
     fn current_list_state(&mut self) -> Option<(&mut ListState, usize)> {
         match self.step {
+            Step::ColorTheme => Some((&mut self.theme_state, self.themes.len())),
             Step::Shell => Some((&mut self.shell_state, self.shells.len())),
+            Step::EnabledShells => Some((&mut self.enabled_shells_state, self.shells.len())),
+            Step::Language => Some((&mut self.language_state, self.languages.len())),
             Step::CommandStyle => {
                 Some((&mut self.command_style_state, self.command_styles.len()))
             }
@@ -167,94 +621,587 @@ impl App {
             Step::NewShellBehavior => {
                 Some((&mut self.new_shell_state, self.new_shell_options.len()))
             }
+            // while the directory field is open, typing goes there instead
+            Step::WriteError if self.dir_input.is_none() => {
+                Some((&mut self.write_error_state, WRITE_ERROR_OPTIONS.len()))
+            }
             _ => None,
         }
     }
 
+    /// Option values for the current step's list (empty for steps without one),
+    /// each with the description shown beside it.
+    fn option_texts(&self) -> Vec<(&'static str, String)> {
+        let plain = |options: &[(&'static str, &'static str)]| {
+            options.iter().map(|&(name, desc)| (name, desc.to_string())).collect()
+        };
+        match self.step {
+            Step::ColorTheme => plain(&self.themes),
+            Step::Shell | Step::EnabledShells => {
+                self.shells.iter().map(|&name| (name, String::new())).collect()
+            }
+            Step::Language => plain(&self.languages),
+            Step::CommandStyle => self.command_style_options(),
+            Step::FolderStyle => self.folder_style_options(),
+            Step::NewShellBehavior => plain(&self.new_shell_options),
+            Step::WriteError => plain(WRITE_ERROR_OPTIONS),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Indices of the current step's options whose name or description matches
+    /// the filter (see `fuzzy_match`).
+    fn visible_options(&self) -> Vec<usize> {
+        self.option_texts()
+            .iter()
+            .enumerate()
+            .filter(|(_, (name, desc))| fuzzy_match(&self.filter, name) || fuzzy_match(&self.filter, desc))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // -- filtering --
+
+    fn push_filter(&mut self, c: char) {
+        self.filter.push(c);
+        self.clamp_selection();
+    }
+
+    fn pop_filter(&mut self) {
+        self.filter.pop();
+        self.clamp_selection();
+    }
+
+    fn clear_filter(&mut self) {
+        self.filter.clear();
+        *self.list_view.offset_mut() = 0;
+    }
+
+    /// Keep the selection inside the filtered subset by jumping to its first match.
+    fn clamp_selection(&mut self) {
+        let visible = self.visible_options();
+        if let Some((state, _)) = self.current_list_state()
+            && let Some(&first) = visible.first()
+            && !visible.contains(&state.selected().unwrap_or(0))
+        {
+            state.select(Some(first));
+        }
+    }
+
+    /// Build the config that the current selections would produce.
+    /// The existing `[theme]`, `[styles]`, `[safety]`, `[overrides]`, wrap_navigation, integration
+    /// mode and includes are carried over, since the wizard doesn't ask about them, and so
+    /// are the mappings the user added, changed or deleted by hand. Custom mappings
+    /// from this run win.
+    fn build_config(&self) -> SemanticConfig {
+        let mut config = SemanticConfig::from_selections(
+            self.selected_shell(),
+            &self.selected_enabled_shells(),
+            self.selected_command_style(),
+            self.selected_folder_style(),
+            self.selected_language(),
+            self.selected_new_shell(),
+            &self.custom_commands,
+            &self.styles,
+        );
+        self.carried.apply_to(&mut config);
+        for (name, command) in &self.custom_commands {
+            config.composite_commands.remove(name);
+            config.shell_mode_commands.remove(name);
+            config.untranslated_commands.remove(name);
+            config.commands.insert(name.clone(), command.clone());
+        }
+        config.general.theme = Some(self.selected_theme().to_string());
+        config.theme = self.theme_config.clone();
+        config.general.wrap_navigation = self.wrap_navigation;
+        let bin_dir = self.bin_dir.trim();
+        config.general.semantic_bin_dir = (!bin_dir.is_empty()).then(|| bin_dir.to_string());
+        config.shells.integration_mode = self.integration_mode.clone();
+        config.include = self.include.clone();
+        if let Some(existing) = &self.existing {
+            config.safety = existing.safety.clone();
+            config.overrides = existing.overrides.clone();
+        }
+        config
+    }
+
+    // -- custom mappings --
+
+    /// The input field that currently has focus.
+    fn custom_field_mut(&mut self) -> &mut String {
+        match self.custom_focus {
+            CustomField::Name => &mut self.custom_name,
+            CustomField::Command => &mut self.custom_command,
+        }
+    }
+
+    fn toggle_custom_focus(&mut self) {
+        self.custom_focus = match self.custom_focus {
+            CustomField::Name => CustomField::Command,
+            CustomField::Command => CustomField::Name,
+        };
+    }
+
+    /// Enter on the custom step: add the pair if both fields are filled,
+    /// advance if both are empty, otherwise wait for the missing half.
+    fn submit_custom(&mut self) {
+        let name = self.custom_name.trim().to_string();
+        let command = self.custom_command.trim().to_string();
+
+        match (name.is_empty(), command.is_empty()) {
+            (true, true) => self.advance(),
+            (false, false) => {
+                // re-adding a name replaces the earlier entry
+                self.custom_commands.retain(|(n, _)| *n != name);
+                self.custom_commands.push((name, command));
+                self.custom_name.clear();
+                self.custom_command.clear();
+                self.custom_focus = CustomField::Name;
+            }
+            _ => {}
+        }
+    }
+
+    /// Backspace on the custom step: delete a character, or step out of an empty field.
+    /// An empty name field goes back to the previous step.
+    fn backspace_custom(&mut self) {
+        if self.custom_field_mut().pop().is_some() {
+            return;
+        }
+        match self.custom_focus {
+            CustomField::Command => self.custom_focus = CustomField::Name,
+            CustomField::Name => self.go_back(),
+        }
+    }
+
+    // -- enabled shells multi-select --
+
+    /// Space on the enabled shells step: toggle the highlighted shell on/off.
+    /// The default shell stays on.
+    fn toggle_shell(&mut self) {
+        let i = self.enabled_shells_state.selected().unwrap_or(0);
+        if i != self.default_shell() {
+            self.enabled_shells[i] = !self.enabled_shells[i];
+        }
+    }
+
+    /// Start from an existing config's `[shells]`: its default selected on the
+    /// shell step and its enabled shells toggled on. Shells this platform's list
+    /// doesn't have are left out; if that's all of them, the selection stays.
+    fn select_shells(&mut self, default: &str, enabled: &[String]) {
+        let known: HashSet<usize> = enabled
+            .iter()
+            .chain(std::iter::once(&default.to_string()))
+            .filter_map(|name| self.shells.iter().position(|s| s == name))
+            .collect();
+        let Some(&first) = known.iter().min() else { return };
+        for (i, on) in self.enabled_shells.iter_mut().enumerate() {
+            *on = known.contains(&i);
+        }
+        let default = self.shells.iter().position(|s| *s == default).unwrap_or(first);
+        self.shell_state.select(Some(default));
+    }
+
     // -- navigation --
 
+    // movement happens within the filtered options, so hidden items are skipped
+
     fn move_up(&mut self) {
-        if let Some((state, len)) = self.current_list_state() {
-            let i = state.selected().unwrap_or(0);
-            // wrap around to the bottom if at the top
-            state.select(Some(if i == 0 { len - 1 } else { i - 1 }));
+        let wrap = self.wrap_navigation.unwrap_or(true);
+        let visible = self.visible_options();
+        if let Some((state, _)) = self.current_list_state() {
+            let len = visible.len();
+            if len == 0 {
+                return;
+            }
+            let pos = visible
+                .iter()
+                .position(|&i| Some(i) == state.selected())
+                .unwrap_or(0);
+            // wrap around to the bottom if at the top, unless told to stop there
+            let pos = match pos {
+                0 if wrap => len - 1,
+                0 => 0,
+                _ => pos - 1,
+            };
+            state.select(Some(visible[pos]));
         }
     }
 
     fn move_down(&mut self) {
-        if let Some((state, len)) = self.current_list_state() {
-            let i = state.selected().unwrap_or(0);
-            // wrap around to the top if at the bottom
-            state.select(Some((i + 1) % len));
+        let wrap = self.wrap_navigation.unwrap_or(true);
+        let visible = self.visible_options();
+        if let Some((state, _)) = self.current_list_state() {
+            let len = visible.len();
+            if len == 0 {
+                return;
+            }
+            let pos = visible
+                .iter()
+                .position(|&i| Some(i) == state.selected())
+                .unwrap_or(0);
+            // wrap around to the top if at the bottom, unless told to stop there
+            let pos = if pos + 1 < len { pos + 1 } else if wrap { 0 } else { pos };
+            state.select(Some(visible[pos]));
+        }
+    }
+
+    /// Select a list item by its position in the full option list, dropping any filter.
+    /// Picking the item that's already selected confirms it, like pressing Enter.
+    fn select_number(&mut self, index: usize) {
+        self.clear_filter();
+        let Some((state, len)) = self.current_list_state() else {
+            return;
+        };
+        if index >= len {
+            return;
+        }
+        if state.selected() == Some(index) {
+            self.advance();
+        } else {
+            state.select(Some(index));
         }
     }
 
-    /// Move forward. On the summary step, this writes the config file.
+    /// A left click at (column, row) selects the list row under it. Clicking the
+    /// row that's already selected confirms it, like its number key.
+    fn click(&mut self, column: u16, row: u16) {
+        let Some(area) = self.list_area else {
+            return;
+        };
+        if !area.contains(Position::new(column, row)) {
+            return;
+        }
+        // the list scrolls, so the row on screen is relative to its offset
+        let position = self.list_view.offset() + usize::from(row - area.y);
+        let Some(&index) = self.visible_options().get(position) else {
+            return;
+        };
+        let Some((state, _)) = self.current_list_state() else {
+            return;
+        };
+        if state.selected() == Some(index) {
+            self.advance();
+        } else {
+            state.select(Some(index));
+        }
+    }
+
+    /// Move forward. On the summary step, this writes the config file; on the
+    /// write error step, it does what the highlighted option says.
+    /// Does nothing on a list step whose filter matches nothing.
     fn advance(&mut self) {
-        if self.step == Step::Summary {
-            // build config from all the selections and write it
-            let config = SemanticConfig::from_selections(
-                self.selected_shell(),
-                self.selected_command_style(),
-                self.selected_folder_style(),
-                self.selected_new_shell(),
-            );
-            match config.save() {
-                Ok(()) => {
-                    self.write_error = None;
-                    self.step = Step::Done;
-                }
-                Err(e) => {
-                    self.write_error = Some(format!("Failed to write config: {e}"));
+        if self.current_list_state().is_some() && self.visible_options().is_empty() {
+            return;
+        }
+
+        self.clear_filter();
+        if self.step == Step::Summary
+            && self
+                .pending_changes()
+                .is_some_and(|diff| diff.iter().all(|line| matches!(line, LineDiff::Same(_))))
+        {
+            // rewriting an identical config would only churn its mtime
+            self.unchanged = true;
+            self.step = Step::Done;
+        } else if self.step == Step::Summary {
+            self.save();
+        } else if self.step == Step::WriteError {
+            match WRITE_ERROR_OPTIONS[self.write_error_state.selected().unwrap_or(0)].0 {
+                "Retry" => self.save(),
+                "Change directory" => {
+                    let current = self.override_config_dir.as_ref().map(|dir| dir.display().to_string());
+                    self.dir_input = Some(current.unwrap_or_default());
+                    self.dir_input_error = None;
                 }
+                _ => self.should_quit = true,
             }
         } else {
+            // the default shell starts toggled on among the enabled ones
+            if self.step == Step::Shell {
+                let default = self.default_shell();
+                self.enabled_shells[default] = true;
+            }
             self.step = self.step.next();
+            if self.step.index() > self.furthest_step.index() {
+                self.furthest_step = self.step;
+            }
+        }
+    }
+
+    /// Build the config from all the selections and write it to `config_path`,
+    /// or to the directory picked on the write error step. The user config is
+    /// always written as TOML; another file in the format its extension says.
+    /// Done if that worked, otherwise on to the write error step with why.
+    fn save(&mut self) {
+        let file = ConfigFile {
+            format: ConfigFormat::Toml,
+            config: self.build_config(),
+        };
+        let result = match &self.override_config_dir {
+            Some(dir) => {
+                let path = dir.join(file.format.file_name());
+                file.config.save_to(&path).map(|()| path)
+            }
+            None if self.config_path == SemanticConfig::config_path() => file.save(),
+            None => file.config.save_to(&self.config_path).map(|()| self.config_path.clone()),
+        };
+        match result {
+            Ok(path) => {
+                self.write_error = None;
+                self.written_to = Some(path);
+                self.template_drift = config::template_drift(
+                    &config::templates_dir(),
+                    self.selected_command_style(),
+                    self.selected_folder_style(),
+                    self.selected_language(),
+                );
+                self.step = Step::Done;
+            }
+            Err(e) => {
+                self.write_error = Some(format!("Failed to write config: {e}"));
+                self.write_error_state.select(Some(0));
+                self.step = Step::WriteError;
+            }
+        }
+    }
+
+    /// Enter in the write error step's directory field: save there if it's an
+    /// absolute path, otherwise say so and keep the field open.
+    fn submit_dir_input(&mut self) {
+        let Some(input) = &self.dir_input else { return };
+        let dir = PathBuf::from(input.trim());
+        if !dir.is_absolute() {
+            self.dir_input_error = Some("Enter an absolute path, e.g. /tmp/semantic".to_string());
+            return;
+        }
+        self.dir_input = None;
+        self.dir_input_error = None;
+        self.override_config_dir = Some(dir);
+        self.save();
+    }
+
+    /// `b` on the summary: open the field for semantic's directory, holding the
+    /// current one.
+    fn open_bin_dir_input(&mut self) {
+        self.bin_dir_input = Some(self.bin_dir.clone());
+    }
+
+    /// Enter in that field: keep what's there (empty for no $PATH change).
+    fn submit_bin_dir_input(&mut self) {
+        if let Some(input) = self.bin_dir_input.take() {
+            self.bin_dir = input.trim().to_string();
+        }
+    }
+
+    /// Backspace in that field: delete a character, or close it unchanged when
+    /// it's empty.
+    fn backspace_bin_dir_input(&mut self) {
+        if let Some(input) = &mut self.bin_dir_input
+            && input.pop().is_none()
+        {
+            self.bin_dir_input = None;
+        }
+    }
+
+    /// Backspace in the directory field: delete a character, or close the field
+    /// when it's empty.
+    fn backspace_dir_input(&mut self) {
+        if let Some(input) = &mut self.dir_input
+            && input.pop().is_none()
+        {
+            self.dir_input = None;
+        }
+        self.dir_input_error = None;
+    }
+
+    /// How saving would change the existing config, line by line, or None if
+    /// there's no config yet (or one of the two can't be rendered).
+    fn pending_changes(&self) -> Option<Vec<LineDiff>> {
+        let old = config::sorted_toml(self.existing.as_ref()?).ok()?;
+        let new = config::sorted_toml(&self.build_config()).ok()?;
+        Some(config::line_diff(&old, &new))
+    }
+
+    /// Jump straight to a step, keeping every selection (only the filter is
+    /// cleared). Only steps already reached are allowed on a first run, so nothing
+    /// can be skipped before the user has seen it; over an existing config every
+    /// step already has an answer, so any of them is. Never past the summary:
+    /// saving always takes an Enter there.
+    fn jump_to(&mut self, step: Step) {
+        if step == Step::Done
+            || (self.existing.is_none() && step.index() > self.furthest_step.index())
+        {
+            return;
+        }
+        self.clear_filter();
+        self.write_error = None;
+        self.step = step;
+    }
+
+    /// Quit right away on Welcome (nothing chosen yet), otherwise ask first.
+    fn request_quit(&mut self) {
+        if matches!(self.step, Step::Welcome | Step::Done) {
+            self.should_quit = true;
+        } else {
+            self.confirm_quit = true;
         }
     }
 
     fn go_back(&mut self) {
+        self.clear_filter();
         self.write_error = None;
+        self.dir_input = None;
         self.step = self.step.prev();
     }
+
+    /// The wizard for the config file at `path`, starting from what's there, if
+    /// anything, with warnings about its `[theme]` for the caller to print.
+    /// Nothing here touches the terminal: with `handle_key` for input, this is
+    /// the whole wizard, which `run_with_path` only draws.
+    fn open(path: &Path, no_color: bool) -> (Self, Vec<String>) {
+        let mut app = App::new(path.to_path_buf());
+        app.no_color = no_color;
+        let existing = if path == SemanticConfig::config_path() {
+            SemanticConfig::load_user().ok()
+        } else {
+            SemanticConfig::load_from(path).ok()
+        };
+        let mut warnings = Vec::new();
+        if let Some(existing) = existing {
+            let (theme, theme_warnings) =
+                Theme::from_config(existing.general.theme.as_deref(), &existing.theme);
+            warnings = theme_warnings;
+            app.theme = theme;
+            app.carried = config::Customizations::of(&existing);
+            if let Some(name) = &existing.general.theme {
+                app.select_theme(name);
+            }
+            app.theme_config = existing.theme.clone();
+            app.styles = existing.styles.clone();
+            app.integration_mode = existing.shells.integration_mode.clone();
+            app.wrap_navigation = existing.general.wrap_navigation;
+            if let Some(dir) = &existing.general.semantic_bin_dir {
+                app.bin_dir = dir.clone();
+            }
+            app.include = existing.include.clone();
+            app.select_shells(&existing.shells.default, &existing.shells.enabled);
+            if let Some(language) = &existing.general.language {
+                app.select_language(language);
+            }
+            app.existing = Some(existing);
+        }
+        if no_color {
+            app.theme = Theme::plain();
+        }
+        (app, warnings)
+    }
 }
 
 // -- public entry point --
 
-pub fn run() {
-    if let Err(e) = run_inner() {
+/// Run the setup wizard on the user config; with `no_color`, in the terminal's
+/// own colors only.
+pub fn run(no_color: bool) {
+    if let Err(e) = run_with_path(&SemanticConfig::config_path(), no_color) {
         eprintln!("Error: {e}");
         std::process::exit(1);
     }
 }
 
-fn run_inner() -> Result<(), Box<dyn std::error::Error>> {
-    // set up terminal for TUI rendering
-    enable_raw_mode()?;
-    stdout().execute(EnterAlternateScreen)?;
+/// Run the setup wizard on the config file at `path` instead: it starts from
+/// what's there, if anything, and saves there.
+pub fn run_with_path(path: &Path, no_color: bool) -> Result<(), Box<dyn std::error::Error>> {
+    // colors come from an existing config; read them first so any warning
+    // lands on the normal screen rather than inside the wizard
+    let (mut app, warnings) = App::open(path, no_color);
+    for warning in warnings {
+        eprintln!("{warning}");
+    }
 
-    let backend = CrosstermBackend::new(stdout());
-    let mut terminal = Terminal::new(backend)?;
+    // log lines on stderr would be drawn over by the wizard
+    let log_file = config::config_dir().join("tui.log");
+    if let Err(e) = logging::to_file(&log_file) {
+        eprintln!("semantic: can't log to {}: {e}", log_file.display());
+    }
 
-    let mut app = App::new();
+    // set up terminal for TUI rendering
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?.execute(EnableMouseCapture)?;
 
-    // main loop: draw -> wait for input -> repeat
-    while !app.should_quit && app.step != Step::Done {
-        terminal.draw(|f| draw(f, &mut app))?;
-        handle_event(&mut app)?;
-    }
+    let result = Terminal::new(CrosstermBackend::new(stdout()))
+        .map_err(Into::into)
+        .and_then(|mut terminal| event_loop(&mut app, &mut terminal, event::read));
 
-    // restore terminal to normal state
+    // restore terminal to normal state, even if the loop failed
+    let _ = stdout().execute(DisableMouseCapture);
     disable_raw_mode()?;
     stdout().execute(LeaveAlternateScreen)?;
+    logging::to_stderr();
+    log::info!("the wizard's log is in {}", log_file.display());
+    result?;
 
     // print confirmation after exiting the TUI
-    if app.step == Step::Done {
-        let path = SemanticConfig::config_path();
-        println!("Config written to {}", path.display());
-        println!("Run `semantic init` to generate shell aliases.");
+    for drift in &app.template_drift {
+        eprintln!("semantic: warning: {drift}");
+    }
+    let written_to = app.written_to.as_deref().unwrap_or(&app.config_path);
+    if app.step == Step::Done && app.unchanged {
+        println!("No changes; {} was left as it is.", app.config_path.display());
+    } else if let (Step::Done, Some(dir)) = (app.step, &app.override_config_dir) {
+        println!("Config written to {}", written_to.display());
+        println!("semantic reads it from there with SEMANTIC_CONFIG_DIR={} set.", dir.display());
+    } else if app.step == Step::Done {
+        println!("Config written to {}", written_to.display());
+        offer_install(&app.selected_enabled_shells());
+    }
+
+    Ok(())
+}
+
+/// Ask, back on the normal screen, whether to add semantic to the enabled
+/// shells' rc files as `semantic init --install` does, and do it on a yes.
+fn offer_install(shells: &[&str]) {
+    if !io::stdin().is_terminal() {
+        println!("Run `semantic init --install` to load semantic in your shells.");
+        return;
+    }
+    print!("Add semantic to your shells' rc files now? [y/N] ");
+    let _ = stdout().flush();
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() || !answer.trim().eq_ignore_ascii_case("y") {
+        println!("Run `semantic init --install` to do it later.");
+        return;
     }
 
+    for shell in shells {
+        match shell::install_rc_block(shell, false) {
+            Ok(Some(rc)) => println!("Added semantic to {}", rc.display()),
+            Ok(None) => println!("{shell}: its rc file already loads semantic"),
+            Err(e) => eprintln!("Couldn't set up {shell}: {e}"),
+        }
+    }
+    println!("Open a new shell to start using semantic.");
+}
+
+/// Main loop: draw on `terminal` -> wait for `next_event` -> repeat, until the
+/// wizard is done or quit. `run_with_path` gives it the real terminal and its
+/// input; any other backend and source of events (a `TestBackend` and a list
+/// of keys) works as well.
+fn event_loop<B: Backend>(
+    app: &mut App,
+    terminal: &mut Terminal<B>,
+    mut next_event: impl FnMut() -> io::Result<Event>,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    B::Error: 'static,
+{
+    while !app.should_quit && app.step != Step::Done {
+        terminal.draw(|f| draw(f, app))?;
+        handle_event(app, next_event()?);
+        // follows the highlight on the theme step
+        app.refresh_theme();
+    }
     Ok(())
 }
 
@@ -268,6 +1215,13 @@ fn run_inner() -> Result<(), Box<dyn std::error::Error>> {
 
 fn draw(f: &mut Frame, app: &mut App) {
     let area = f.area();
+    // set again by draw_selection if this frame has a list
+    app.list_area = None;
+
+    if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+        draw_too_small(f, area, &app.theme);
+        return;
+    }
 
     let layout = Layout::vertical([
         Constraint::Length(3), // progress dots
@@ -279,20 +1233,73 @@ fn draw(f: &mut Frame, app: &mut App) {
     draw_progress(f, layout[0], app);
     draw_content(f, layout[1], app);
     draw_help(f, layout[2], app);
+
+    if app.confirm_quit {
+        draw_confirm_quit(f, area, &app.theme);
+    }
+}
+
+/// Draws the "quit without saving?" dialog as a small box over the current step.
+fn draw_confirm_quit(f: &mut Frame, area: Rect, theme: &Theme) {
+    let width = 36.min(area.width);
+    let height = 5.min(area.height);
+    let dialog = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let text = vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            "Quit without saving? (y/n)",
+            Style::default().fg(theme.text).bold(),
+        )),
+    ];
+    let paragraph = Paragraph::new(text).alignment(Alignment::Center).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.warning)),
+    );
+
+    // clear what's underneath so the dialog reads as a separate layer
+    f.render_widget(Clear, dialog);
+    f.render_widget(paragraph, dialog);
+}
+
+/// Shown instead of the wizard when the terminal is below the minimum size.
+fn draw_too_small(f: &mut Frame, area: Rect, theme: &Theme) {
+    let message = Paragraph::new(format!(
+        "Terminal too small — resize to at least {MIN_WIDTH}x{MIN_HEIGHT}"
+    ))
+    .style(Style::default().fg(theme.warning))
+    .alignment(Alignment::Center)
+    .wrap(Wrap { trim: true });
+
+    // vertically center the (possibly wrapped) message
+    let top = area.height.saturating_sub(2) / 2;
+    let centered = Layout::vertical([
+        Constraint::Length(top),
+        Constraint::Min(1),
+    ])
+    .split(area);
+    f.render_widget(message, centered[1]);
 }
 
 /// Draws the progress dots at the top.
-/// Completed steps are green, current step is cyan, future steps are gray.
+/// Completed steps use the success color, the current step the accent, future steps muted.
 fn draw_progress(f: &mut Frame, area: Rect, app: &App) {
+    let theme = &app.theme;
     let step = app.step.index();
     let dots: Vec<Span> = (0..TOTAL_STEPS)
         .map(|i| {
             if i < step {
-                Span::styled(" ● ", Style::default().fg(Color::Green))
+                Span::styled(" ● ", Style::default().fg(theme.success))
             } else if i == step {
-                Span::styled(" ● ", Style::default().fg(Color::Cyan).bold())
+                Span::styled(" ● ", Style::default().fg(theme.accent).bold())
             } else {
-                Span::styled(" ○ ", Style::default().fg(Color::DarkGray))
+                Span::styled(" ○ ", Style::default().fg(theme.muted))
             }
         })
         .collect();
@@ -302,7 +1309,7 @@ fn draw_progress(f: &mut Frame, area: Rect, app: &App) {
         .block(
             Block::default()
                 .borders(Borders::BOTTOM)
-                .border_style(Style::default().fg(Color::DarkGray)),
+                .border_style(Style::default().fg(theme.muted)),
         );
     f.render_widget(progress, area);
 }
@@ -318,13 +1325,24 @@ fn draw_content(f: &mut Frame, area: Rect, app: &mut App) {
     ])
     .split(area);
 
+    // style steps get a preview panel on the right when there's room for it
+    let show_preview = matches!(app.step, Step::CommandStyle | Step::FolderStyle)
+        && area.width >= PREVIEW_MIN_WIDTH;
+
     // vertically center the content in the available space
     let content_height: u16 = match app.step {
         Step::Welcome => 10,
-        Step::Summary => 10,
+        // the summary's length depends on the choices (or the diff); +2 leaves a gap
+        // before the init preview below it
+        Step::Summary => summary_lines(app).len() as u16 + 2 + PREVIEW_LINES as u16 + 2,
+        Step::CustomMappings | Step::WriteError => 12,
+        _ if show_preview => PREVIEW_LINES as u16 + 2,
         _ => 8,
     };
-    let vertical_pad = padded[1].height.saturating_sub(content_height) / 2;
+    // never ask for more rows than exist — content taller than the area is
+    // clipped from the bottom instead of squeezing the padding into nothing
+    let content_height = content_height.min(padded[1].height);
+    let vertical_pad = (padded[1].height - content_height) / 2;
     let centered = Layout::vertical([
         Constraint::Length(vertical_pad),
         Constraint::Length(content_height),
@@ -332,60 +1350,149 @@ fn draw_content(f: &mut Frame, area: Rect, app: &mut App) {
     ])
     .split(padded[1]);
 
-    let content_area = centered[1];
+    let mut content_area = centered[1];
+
+    // split off the right half for the preview, leaving the list on the left
+    if show_preview {
+        let columns = Layout::horizontal([
+            Constraint::Percentage(50),
+            Constraint::Percentage(50),
+        ])
+        .split(content_area);
+        content_area = columns[0];
+        draw_preview(f, columns[1], app);
+    }
+
+    // list steps only show the options matching the filter
+    let visible = app.visible_options();
 
     // render the right content for the current step
     match app.step {
-        Step::Welcome => draw_welcome(f, content_area),
-        Step::Shell => draw_selection(
+        Step::Welcome => draw_welcome(f, content_area, &app.theme, &app.config_path),
+        Step::ColorTheme => app.list_area = draw_selection(
             f,
             content_area,
-            "Which shell do you use?",
-            &app.shells.iter().map(|s| (*s, "")).collect::<Vec<_>>(),
-            &mut app.shell_state,
+            "Pick a color theme:",
+            Selection {
+                options: &app.themes,
+                visible: &visible,
+                selected: app.theme_state.selected(),
+                filter: &app.filter,
+            },
+            &mut app.list_view,
+            &app.theme,
         ),
-        Step::CommandStyle => draw_selection(
+        Step::Shell => {
+            let options: Vec<(&str, &str)> = app.shells.iter().map(|&s| (s, "")).collect();
+            app.list_area = draw_selection(
+                f,
+                content_area,
+                "Which shell do you use most?",
+                Selection {
+                    options: &options,
+                    visible: &visible,
+                    selected: app.shell_state.selected(),
+                    filter: &app.filter,
+                },
+                &mut app.list_view,
+                &app.theme,
+            );
+        }
+        Step::EnabledShells => {
+            // checkbox per shell, with the default marked
+            let default = app.default_shell();
+            let names: Vec<String> = app
+                .shells
+                .iter()
+                .zip(&app.enabled_shells)
+                .map(|(s, &on)| format!("{} {s}", if on { "[x]" } else { "[ ]" }))
+                .collect();
+            let options: Vec<(&str, &str)> = names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| (name.as_str(), if i == default { "(default)" } else { "" }))
+                .collect();
+            app.list_area = draw_selection(
+                f,
+                content_area,
+                "Which other shells should semantic set up?",
+                Selection {
+                    options: &options,
+                    visible: &visible,
+                    selected: app.enabled_shells_state.selected(),
+                    filter: &app.filter,
+                },
+                &mut app.list_view,
+                &app.theme,
+            );
+        }
+        Step::Language => app.list_area = draw_selection(
+            f,
+            content_area,
+            "Which language should the natural style use?",
+            Selection {
+                options: &app.languages,
+                visible: &visible,
+                selected: app.language_state.selected(),
+                filter: &app.filter,
+            },
+            &mut app.list_view,
+            &app.theme,
+        ),
+        Step::CommandStyle => app.list_area = draw_selection(
             f,
             content_area,
             "Pick a command style:",
-            &[
-                ("natural", "goto, list, install, delete"),
-                ("traditional", "cd, ls, pacman, rm"),
-                ("verbose", "go-to, list-files, install-package"),
-            ],
-            &mut app.command_style_state,
+            Selection {
+                options: &borrowed(&app.command_style_options()),
+                visible: &visible,
+                selected: app.command_style_state.selected(),
+                filter: &app.filter,
+            },
+            &mut app.list_view,
+            &app.theme,
         ),
-        Step::FolderStyle => draw_selection(
+        Step::FolderStyle => app.list_area = draw_selection(
             f,
             content_area,
             "Pick a folder style:",
-            &[
-                ("natural", "/apps, /settings, /logs"),
-                ("traditional", "/usr/bin, /etc, /var/log"),
-                ("verbose", "/user/applications, /configuration"),
-            ],
-            &mut app.folder_style_state,
+            Selection {
+                options: &borrowed(&app.folder_style_options()),
+                visible: &visible,
+                selected: app.folder_style_state.selected(),
+                filter: &app.filter,
+            },
+            &mut app.list_view,
+            &app.theme,
         ),
-        Step::NewShellBehavior => draw_selection(
+        Step::NewShellBehavior => app.list_area = draw_selection(
             f,
             content_area,
             "When a new shell is installed:",
-            &app.new_shell_options.to_vec(),
-            &mut app.new_shell_state,
+            Selection {
+                options: &app.new_shell_options,
+                visible: &visible,
+                selected: app.new_shell_state.selected(),
+                filter: &app.filter,
+            },
+            &mut app.list_view,
+            &app.theme,
         ),
+        Step::CustomMappings => draw_custom_mappings(f, content_area, app),
         Step::Summary => draw_summary(f, content_area, app),
+        Step::WriteError => draw_write_error(f, content_area, app, &visible),
         Step::Done => {}
     }
 }
 
 /// Draws the welcome screen — title, description, config path hint.
-fn draw_welcome(f: &mut Frame, area: Rect) {
+fn draw_welcome(f: &mut Frame, area: Rect, theme: &Theme, config_path: &Path) {
     let text = vec![
         Line::from(""),
         Line::from(Span::styled(
             "SemanticOS",
             Style::default()
-                .fg(Color::Cyan)
+                .fg(theme.accent)
                 .bold()
                 .add_modifier(Modifier::UNDERLINED),
         )),
@@ -395,13 +1502,13 @@ fn draw_welcome(f: &mut Frame, area: Rect) {
         Line::from("This will configure how you interact with your system."),
         Line::from("You can change everything later in:"),
         Line::from(Span::styled(
-            "  ~/.config/semantic/config.toml",
-            Style::default().fg(Color::Yellow),
+            format!("  {}", config::display_path(config_path)),
+            Style::default().fg(theme.warning),
         )),
         Line::from(""),
         Line::from(Span::styled(
             "Press Enter to get started.",
-            Style::default().fg(Color::DarkGray),
+            Style::default().fg(theme.muted),
         )),
     ];
 
@@ -409,16 +1516,59 @@ fn draw_welcome(f: &mut Frame, area: Rect) {
     f.render_widget(paragraph, area);
 }
 
+/// What a selection list should show: all options, which of them pass the
+/// filter, and which one is selected (as an index into `options`).
+struct Selection<'a> {
+    options: &'a [(&'a str, &'a str)],
+    visible: &'a [usize],
+    selected: Option<usize>,
+    filter: &'a str,
+}
+
+/// A style's description with the names the user's `[styles]` table sets for
+/// it: "goto, list, install, delete (yours: install)".
+fn with_own(desc: &str, own: HashMap<String, String>) -> String {
+    if own.is_empty() {
+        return desc.to_string();
+    }
+    let mut names: Vec<_> = own.into_keys().collect();
+    names.sort();
+    format!("{desc} (yours: {})", names.join(", "))
+}
+
+/// Whether `text` contains the characters of `filter` in order, not necessarily
+/// next to each other ("gbx" finds "gruvbox"), ignoring case.
+fn fuzzy_match(filter: &str, text: &str) -> bool {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    filter.chars().flat_map(char::to_lowercase).all(|c| text.any(|t| t == c))
+}
+
+/// Options with owned descriptions, as `Selection` takes them.
+fn borrowed<'a>(options: &'a [(&'a str, String)]) -> Vec<(&'a str, &'a str)> {
+    options.iter().map(|(name, desc)| (*name, desc.as_str())).collect()
+}
+
 /// Draws a selection list with a prompt.
 /// Each option has a name and an optional description.
-/// The selected item gets a cyan background with dark text.
+/// Only the visible (filtered) options are listed, and the active filter
+/// text is shown under the prompt.
+/// The selected item gets an accent-colored background with dark text.
+/// Returns where the list rows were drawn, or None if nothing matched.
 fn draw_selection(
     f: &mut Frame,
     area: Rect,
     prompt: &str,
-    options: &[(&str, &str)],
-    state: &mut ListState,
-) {
+    selection: Selection,
+    view: &mut ListState,
+    theme: &Theme,
+) -> Option<Rect> {
+    let Selection {
+        options,
+        visible,
+        selected,
+        filter,
+    } = selection;
+
     // split into prompt area and list area
     let layout = Layout::vertical([
         Constraint::Length(3), // prompt text
@@ -426,33 +1576,59 @@ fn draw_selection(
     ])
     .split(area);
 
-    // render the prompt
-    let prompt_widget = Paragraph::new(prompt)
-        .style(Style::default().fg(Color::White).bold())
-        .alignment(Alignment::Left);
+    // render the prompt, with the filter underneath while one is being typed
+    let mut prompt_lines = vec![Line::from(Span::styled(
+        prompt,
+        Style::default().fg(theme.text).bold(),
+    ))];
+    if !filter.is_empty() {
+        prompt_lines.push(Line::from(vec![
+            Span::styled("Filter: ", Style::default().fg(theme.muted)),
+            Span::styled(filter, Style::default().fg(theme.warning)),
+        ]));
+    }
+    let prompt_widget = Paragraph::new(prompt_lines).alignment(Alignment::Left);
     f.render_widget(prompt_widget, layout[0]);
 
-    let selected = state.selected().unwrap_or(0);
+    if visible.is_empty() {
+        let empty = Paragraph::new(Span::styled(
+            "    No matches — Backspace or Esc to edit the filter.",
+            Style::default().fg(theme.muted),
+        ));
+        f.render_widget(empty, layout[1]);
+        return None;
+    }
+
+    let selected = selected.unwrap_or(0);
 
     // build list items with selection styling
-    let items: Vec<ListItem> = options
+    let items: Vec<ListItem> = visible
         .iter()
-        .enumerate()
+        .map(|&i| (i, &options[i]))
         .map(|(i, (name, desc))| {
             let is_selected = i == selected;
 
             // arrow marker for the selected item, padding for the rest
-            let marker = if is_selected { "  ▸ " } else { "    " };
+            let marker = if is_selected { " ▸ " } else { "   " };
+
+            // number key hint for the first nine options
+            let number = if i < 9 { format!("{}", i + 1) } else { " ".to_string() };
+            let number_style = if is_selected {
+                Style::default().fg(theme.selected)
+            } else {
+                Style::default().fg(theme.muted)
+            };
 
             // selected item: dark text on colored background
             // unselected: white text, no background
             let name_style = if is_selected {
-                Style::default().fg(Color::Black).bold()
+                Style::default().fg(theme.selected).bold()
             } else {
-                Style::default().fg(Color::White)
+                Style::default().fg(theme.text)
             };
 
             let mut spans = vec![
+                Span::styled(number, number_style),
                 Span::styled(marker, name_style),
                 Span::styled(*name, name_style),
             ];
@@ -460,9 +1636,9 @@ fn draw_selection(
             // add description text if present (e.g. example commands)
             if !desc.is_empty() {
                 let desc_style = if is_selected {
-                    Style::default().fg(Color::Black)
+                    Style::default().fg(theme.selected)
                 } else {
-                    Style::default().fg(Color::DarkGray)
+                    Style::default().fg(theme.muted)
                 };
                 spans.push(Span::styled(format!("  {desc}"), desc_style));
             }
@@ -470,19 +1646,393 @@ fn draw_selection(
             // apply background color to the entire row if selected
             let item = ListItem::new(Line::from(spans));
             if is_selected {
-                item.style(Style::default().bg(Color::Cyan))
+                item.style(theme.selected_row())
             } else {
                 item
             }
         })
         .collect();
 
+    // the list widget indexes into the visible items, not the full option list.
+    // Its offset persists in `view`, so it scrolls once there are more rows than fit.
+    view.select(visible.iter().position(|&i| i == selected));
+
     let list = List::new(items).highlight_style(Style::default());
-    f.render_stateful_widget(list, layout[1], state);
+    f.render_stateful_widget(list, layout[1], view);
+
+    // rendering scrolled the list to keep the selection in view; mark the
+    // right edge where rows are hidden above or below it
+    let area = layout[1];
+    if area.height > 0 && area.width > 1 {
+        let marker_style = Style::default().fg(theme.muted);
+        let column = area.right() - 2;
+        if view.offset() > 0 {
+            let above = Paragraph::new(Span::styled("▲", marker_style));
+            f.render_widget(above, Rect::new(column, area.y, 1, 1));
+        }
+        if view.offset() + usize::from(area.height) < visible.len() {
+            let below = Paragraph::new(Span::styled("▼", marker_style));
+            f.render_widget(below, Rect::new(column, area.bottom() - 1, 1, 1));
+        }
+    }
+    Some(area)
+}
+
+/// Draws a live preview of what the highlighted style would set up.
+/// Uses the shell picked earlier, so it updates with both the highlight and the shell.
+fn draw_preview(f: &mut Frame, area: Rect, app: &App) {
+    let theme = &app.theme;
+    let mut lines: Vec<Line> = preview_lines(app)
+        .iter()
+        .map(|line| highlight_line(line, theme))
+        .collect();
+
+    // traditional style maps commands to themselves, so nothing gets generated
+    if lines.is_empty() {
+        let message = if app.step == Step::FolderStyle {
+            "No virtual paths — real paths are used as-is."
+        } else {
+            "No aliases — real commands are used as-is."
+        };
+        lines.push(Line::from(Span::styled(message, Style::default().fg(theme.muted))));
+    }
+
+    let preview = Paragraph::new(lines).block(
+        Block::default()
+            .title(format!(" {} preview ", app.selected_shell()))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.muted)),
+    );
+    f.render_widget(preview, area);
+}
+
+/// Builds the first few lines of `semantic init` output for the current selections.
+/// On the folder step only cd functions are shown, since that's where paths appear.
+/// On the command step each command is listed with what it does instead, from
+/// the same descriptions `semantic help` prints (`config::COMMAND_DOCS`).
+fn preview_lines(app: &App) -> Vec<String> {
+    let config = app.build_config();
+
+    if app.step == Step::CommandStyle {
+        let rank = |real: &str| {
+            config::COMMAND_DOCS
+                .iter()
+                .position(|doc| doc.real == real)
+                .unwrap_or(config::COMMAND_DOCS.len())
+        };
+        let mut commands: Vec<_> = config.commands.iter().filter(|(name, real)| name != real).collect();
+        commands.sort_by(|a, b| rank(a.1).cmp(&rank(b.1)).then(a.0.cmp(b.0)));
+        return commands
+            .into_iter()
+            .take(PREVIEW_LINES)
+            .map(|(name, real)| format!("{name}  # {}", config::describe_command(real)))
+            .collect();
+    }
+
+    let mut commands = config.commands.clone();
+    if app.step == Step::FolderStyle {
+        commands.retain(|_, real| real == "cd");
+
+        // bash, zsh and fish look paths up when cd runs, so there's no table
+        // in their init code; show what the cd command would do instead
+        if !matches!(app.selected_shell(), "nu" | "pwsh" | "powershell") {
+            let goto = commands.keys().min().map_or("cd", String::as_str);
+            let mut paths: Vec<_> = config.paths.iter().collect();
+            paths.sort();
+            return paths
+                .into_iter()
+                .take(PREVIEW_LINES)
+                .map(|(virtual_path, real_path)| format!("{goto} {virtual_path}  # cd {real_path}"))
+                .collect();
+        }
+    }
+
+    let mut lines = init_lines(&config, &commands, app.selected_shell());
+    lines.truncate(PREVIEW_LINES);
+    lines
+}
+
+/// What `semantic init` prints for `shell`, with `commands` in place of the
+/// config's own, minus the header comment, the init marker and blank separators.
+/// Nothing for a shell semantic can't generate code for.
+fn init_lines(config: &SemanticConfig, commands: &HashMap<String, String>, shell: &str) -> Vec<String> {
+    shell::generate_init(
+        commands,
+        &config.composite_commands,
+        &config.paths,
+        shell,
+        &shell::InitOptions {
+            guard: false,
+            mode: config.integration_mode(),
+            shell_mode: &config.shell_mode_commands,
+            bin_dir: config.bin_dir().as_deref(),
+        },
+    )
+    .unwrap_or_default()
+    .lines()
+    .filter(|line| !line.trim().is_empty() && !line.starts_with('#') && !line.contains(INIT_MARKER))
+    .map(String::from)
+    .collect()
+}
+
+/// Minimal syntax highlighting: shell keywords in the accent color, quoted strings in the warning color.
+fn highlight_line(line: &str, theme: &Theme) -> Line<'static> {
+    const KEYWORDS: &[&str] = &[
+        "function", "end", "local", "set", "if", "test", "case", "esac", "then", "fi",
+    ];
+
+    let indent = line.len() - line.trim_start().len();
+    let mut spans = vec![Span::raw(line[..indent].to_string())];
+
+    for (i, token) in line.trim_start().split(' ').enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(" "));
+        }
+        let style = if KEYWORDS.contains(&token) {
+            Style::default().fg(theme.accent)
+        } else if token.starts_with('"') {
+            Style::default().fg(theme.warning)
+        } else {
+            Style::default().fg(theme.text)
+        };
+        spans.push(Span::styled(token.to_string(), style));
+    }
+
+    Line::from(spans)
+}
+
+/// Draws the custom mappings step: two inputs side by side and the pairs added so far.
+fn draw_custom_mappings(f: &mut Frame, area: Rect, app: &App) {
+    let theme = &app.theme;
+    let layout = Layout::vertical([
+        Constraint::Length(2), // prompt
+        Constraint::Length(3), // input fields
+        Constraint::Min(1),    // added entries / hint
+    ])
+    .split(area);
+
+    let prompt = Paragraph::new(Span::styled(
+        "Add your own commands (optional):",
+        Style::default().fg(theme.text).bold(),
+    ));
+    f.render_widget(prompt, layout[0]);
+
+    let fields = Layout::horizontal([
+        Constraint::Percentage(40),
+        Constraint::Percentage(60),
+    ])
+    .split(layout[1]);
+
+    let inputs = [
+        (" Semantic name ", &app.custom_name, CustomField::Name),
+        (" Real command ", &app.custom_command, CustomField::Command),
+    ];
+    for ((title, value, field), area) in inputs.into_iter().zip(fields.iter()) {
+        let focused = app.custom_focus == field;
+        let border = if focused { theme.accent } else { theme.muted };
+        // a trailing block stands in for the cursor in the focused field
+        let text = if focused { format!("{value}█") } else { value.to_string() };
+        let input = Paragraph::new(text).block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(border)),
+        );
+        f.render_widget(input, *area);
+    }
+
+    let mut lines: Vec<Line> = app
+        .custom_commands
+        .iter()
+        .map(|(name, command)| {
+            Line::from(vec![
+                Span::styled(format!("  {name}"), Style::default().fg(theme.accent)),
+                Span::styled(" → ", Style::default().fg(theme.muted)),
+                Span::raw(command.as_str()),
+            ])
+        })
+        .collect();
+    if lines.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "  Fill in both fields and press Enter to add. Leave both empty and press Enter to skip.",
+            Style::default().fg(theme.muted),
+        )));
+    }
+    f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), layout[2]);
 }
 
 /// Draws the summary screen — shows all selections for review before saving.
 fn draw_summary(f: &mut Frame, area: Rect, app: &App) {
+    let lines = summary_lines(app);
+
+    // the init code for the default shell goes below, in whatever room is left
+    let layout = Layout::vertical([
+        Constraint::Length(lines.len() as u16 + 1),
+        Constraint::Min(0),
+    ])
+    .split(area);
+    f.render_widget(Paragraph::new(lines), layout[0]);
+    if layout[1].height >= 3 {
+        draw_init_preview(f, layout[1], app);
+    }
+}
+
+/// Draws the write error step: why saving failed, then the recovery options,
+/// or the directory field once "Change directory" is picked.
+fn draw_write_error(f: &mut Frame, area: Rect, app: &mut App, visible: &[usize]) {
+    let theme = app.theme;
+    let layout = Layout::vertical([
+        Constraint::Length(3), // the error, wrapped
+        Constraint::Min(4),
+    ])
+    .split(area);
+
+    let error = app.write_error.as_deref().unwrap_or("Failed to write config");
+    let message = Paragraph::new(Span::styled(error, Style::default().fg(theme.error).bold()))
+        .wrap(Wrap { trim: false });
+    f.render_widget(message, layout[0]);
+
+    let Some(input) = &app.dir_input else {
+        app.list_area = draw_selection(
+            f,
+            layout[1],
+            "What now?",
+            Selection {
+                options: WRITE_ERROR_OPTIONS,
+                visible,
+                selected: app.write_error_state.selected(),
+                filter: &app.filter,
+            },
+            &mut app.list_view,
+            &theme,
+        );
+        return;
+    };
+
+    let rows = Layout::vertical([
+        Constraint::Length(2), // prompt
+        Constraint::Length(3), // input
+        Constraint::Min(0),    // what's wrong with it
+    ])
+    .split(layout[1]);
+    f.render_widget(
+        Paragraph::new(Span::styled(
+            "Save config.toml in which directory?",
+            Style::default().fg(theme.text).bold(),
+        )),
+        rows[0],
+    );
+    let field = Paragraph::new(format!("{input}█")).block(
+        Block::default()
+            .title(" Directory ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.accent)),
+    );
+    f.render_widget(field, rows[1]);
+    if let Some(err) = &app.dir_input_error {
+        f.render_widget(Paragraph::new(Span::styled(err.as_str(), Style::default().fg(theme.error))), rows[2]);
+    }
+}
+
+/// Draws what `semantic init` will print for the default shell with the current
+/// choices, as much of it as fits, noting how many lines didn't.
+fn draw_init_preview(f: &mut Frame, area: Rect, app: &App) {
+    let theme = &app.theme;
+    let config = app.build_config();
+    let shell = app.selected_shell();
+    let code = init_lines(&config, &config.commands_for(shell), shell);
+
+    let room = usize::from(area.height - 2);
+    let mut lines: Vec<Line> = code.iter().take(room).map(|line| highlight_line(line, theme)).collect();
+    if lines.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "No aliases — real commands are used as-is.",
+            Style::default().fg(theme.muted),
+        )));
+    }
+
+    let mut block = Block::default()
+        .title(format!(" semantic init {shell} "))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.muted));
+    let hidden = code.len().saturating_sub(room);
+    if hidden > 0 {
+        block = block.title_bottom(Line::from(format!(" ▼ {hidden} more lines ")).right_aligned());
+    }
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// The summary text, without any write error.
+/// Re-running the wizard over an existing config shows what would change instead
+/// of the plain list of choices.
+fn summary_lines(app: &App) -> Vec<Line<'_>> {
+    let theme = &app.theme;
+    let mut lines = match app.pending_changes() {
+        Some(diff) => diff_summary_lines(diff, theme),
+        None => choices_summary_lines(app),
+    };
+
+    let carried = &app.carried;
+    if !carried.is_empty() || !carried.removed_commands.is_empty() || !carried.removed_paths.is_empty() {
+        let count = carried.len();
+        let mut note = format!(
+            "Preserving {count} custom {}",
+            if count == 1 { "mapping" } else { "mappings" }
+        );
+        let removed = carried.removed_commands.len() + carried.removed_paths.len();
+        if removed > 0 {
+            note.push_str(&format!(
+                ", and leaving out {removed} {} you deleted",
+                if removed == 1 { "default" } else { "defaults" }
+            ));
+        }
+        note.push('.');
+        lines.insert(1, Line::from(Span::styled(note, Style::default().fg(theme.muted))));
+    }
+
+    // names that would hide a real program or builtin, before it's too late
+    let shadowed = app.build_config().shadowed_commands(app.selected_shell());
+    if !shadowed.is_empty() {
+        lines.push(Line::from(""));
+        for (name, hidden) in shadowed {
+            lines.push(Line::from(Span::styled(
+                format!("  ! `{name}` hides {hidden}"),
+                Style::default().fg(theme.warning),
+            )));
+        }
+    }
+
+    // the init code puts this on $PATH, so a wrong guess is worth fixing here
+    lines.push(Line::from(""));
+    lines.push(Line::from(match &app.bin_dir_input {
+        Some(input) => vec![
+            Span::styled("  semantic is in: ", Style::default().fg(theme.muted)),
+            Span::styled(format!("{input}█"), Style::default().fg(theme.accent)),
+        ],
+        None if app.bin_dir.is_empty() => vec![
+            Span::styled("  semantic is in: ", Style::default().fg(theme.muted)),
+            Span::styled("$PATH (b to name its directory)", Style::default().fg(theme.muted)),
+        ],
+        None => vec![
+            Span::styled("  semantic is in: ", Style::default().fg(theme.muted)),
+            Span::styled(app.bin_dir.as_str(), Style::default().fg(theme.accent)),
+            Span::styled("  (added to $PATH; b to change)", Style::default().fg(theme.muted)),
+        ],
+    }));
+
+    lines.extend([
+        Line::from(""),
+        Line::from(Span::styled(
+            "Press Enter to save, or Backspace to go back.",
+            Style::default().fg(theme.muted),
+        )),
+    ]);
+    lines
+}
+
+/// The summary for a first install: every choice the user made.
+fn choices_summary_lines(app: &App) -> Vec<Line<'_>> {
+    let theme = &app.theme;
     let lines = vec![
         Line::from(Span::styled(
             "Review your choices:",
@@ -490,65 +2040,156 @@ fn draw_summary(f: &mut Frame, area: Rect, app: &App) {
         )),
         Line::from(""),
         Line::from(vec![
-            Span::styled("  Shell:          ", Style::default().fg(Color::DarkGray)),
-            Span::styled(app.selected_shell(), Style::default().fg(Color::Cyan)),
+            Span::styled("  Theme:          ", Style::default().fg(theme.muted)),
+            Span::styled(app.selected_theme(), Style::default().fg(theme.accent)),
+        ]),
+        Line::from(vec![
+            Span::styled("  Shells:         ", Style::default().fg(theme.muted)),
+            Span::styled(shell_summary(app), Style::default().fg(theme.accent)),
+        ]),
+        Line::from(vec![
+            Span::styled("  Language:       ", Style::default().fg(theme.muted)),
+            Span::styled(
+                config::language(app.selected_language()).name,
+                Style::default().fg(theme.accent),
+            ),
         ]),
         Line::from(vec![
-            Span::styled("  Command style:  ", Style::default().fg(Color::DarkGray)),
+            Span::styled("  Command style:  ", Style::default().fg(theme.muted)),
             Span::styled(
                 app.selected_command_style(),
-                Style::default().fg(Color::Cyan),
+                Style::default().fg(theme.accent),
             ),
         ]),
         Line::from(vec![
-            Span::styled("  Folder style:   ", Style::default().fg(Color::DarkGray)),
+            Span::styled("  Folder style:   ", Style::default().fg(theme.muted)),
             Span::styled(
                 app.selected_folder_style(),
-                Style::default().fg(Color::Cyan),
+                Style::default().fg(theme.accent),
             ),
         ]),
         Line::from(vec![
-            Span::styled("  New shell:      ", Style::default().fg(Color::DarkGray)),
-            Span::styled(app.selected_new_shell(), Style::default().fg(Color::Cyan)),
+            Span::styled("  New shell:      ", Style::default().fg(theme.muted)),
+            Span::styled(app.selected_new_shell(), Style::default().fg(theme.accent)),
         ]),
-        Line::from(""),
+    ];
+
+    // custom mappings get their own block, listed after the style choices
+    let mut lines = lines;
+    if !app.custom_commands.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            "  Custom commands:",
+            Style::default().fg(theme.muted),
+        )));
+        for (name, command) in &app.custom_commands {
+            lines.push(Line::from(vec![
+                Span::styled(format!("    {name}"), Style::default().fg(theme.accent)),
+                Span::styled(" → ", Style::default().fg(theme.muted)),
+                Span::raw(command.as_str()),
+            ]));
+        }
+    }
+
+    lines
+}
+
+/// The summary when a config already exists: the lines saving would add to or
+/// remove from it, each run of them under the `[table]` it's in.
+fn diff_summary_lines(diff: Vec<LineDiff>, theme: &Theme) -> Vec<Line<'static>> {
+    let mut lines = vec![
         Line::from(Span::styled(
-            "Press Enter to save, or Backspace to go back.",
-            Style::default().fg(Color::DarkGray),
+            "Changes to your existing config:",
+            Style::default().bold(),
         )),
+        Line::from(""),
     ];
 
-    // show error if config write failed
-    if let Some(ref err) = app.write_error {
-        let mut lines = lines;
-        lines.push(Line::from(""));
+    if diff.iter().all(|line| matches!(line, LineDiff::Same(_))) {
         lines.push(Line::from(Span::styled(
-            err.as_str(),
-            Style::default().fg(Color::Red).bold(),
+            "  No changes — there's nothing to save.",
+            Style::default().fg(theme.accent),
         )));
-        let paragraph = Paragraph::new(lines).wrap(Wrap { trim: false });
-        f.render_widget(paragraph, area);
-    } else {
-        let paragraph = Paragraph::new(lines);
-        f.render_widget(paragraph, area);
+        return lines;
+    }
+
+    // the table header above the next change, until it's been shown
+    let mut table: Option<String> = None;
+    for line in diff {
+        let (text, color) = match line {
+            LineDiff::Same(text) => {
+                if text.starts_with('[') {
+                    table = Some(text);
+                }
+                continue;
+            }
+            LineDiff::Added(text) => (format!("  + {text}"), theme.success),
+            LineDiff::Removed(text) => (format!("  - {text}"), theme.error),
+        };
+        // a table that's added or removed whole is its own header
+        let header = table.take().filter(|_| !text[4..].starts_with('['));
+        if let Some(header) = header {
+            lines.push(Line::from(Span::styled(
+                format!("    {header}"),
+                Style::default().fg(theme.muted),
+            )));
+        }
+        lines.push(Line::from(Span::styled(text, Style::default().fg(color))));
     }
+
+    lines
+}
+
+/// Formats the enabled shells for the summary, e.g. "fish (default), bash".
+fn shell_summary(app: &App) -> String {
+    app.selected_enabled_shells()
+        .iter()
+        .map(|s| {
+            if *s == app.selected_shell() {
+                format!("{s} (default)")
+            } else {
+                s.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 /// Draws the help bar at the bottom — shows available keybindings for the current step.
 fn draw_help(f: &mut Frame, area: Rect, app: &App) {
+    let theme = &app.theme;
     let help_text = match app.step {
-        Step::Welcome => "Enter: continue  •  q: quit",
-        Step::Summary => "Enter: save config  •  Backspace: back  •  q: quit",
-        _ => "↑/↓: select  •  Enter: continue  •  Backspace: back  •  q: quit",
+        Step::Welcome => {
+            "Enter: continue  •  1-9 or s/c/f/m/n: jump to step  •  q: quit"
+        }
+        Step::EnabledShells => {
+            "↑/↓/1-9: select  •  type: filter  •  Space: toggle  •  Enter: continue  •  Esc: quit"
+        }
+        Step::CustomMappings => {
+            "Tab: switch field  •  Enter: add / continue when empty  •  Backspace: back  •  Esc: quit"
+        }
+        Step::Summary if app.bin_dir_input.is_some() => {
+            "type: directory  •  Enter: keep it (empty: none)  •  Backspace on empty: cancel  •  Esc: quit"
+        }
+        Step::Summary => {
+            "Enter: save  •  b: bin dir  •  Backspace: back  •  1-9 or s/c/f/m/n: jump to step  •  q: quit"
+        }
+        Step::WriteError if app.dir_input.is_some() => {
+            "type: directory  •  Enter: save there  •  Backspace on empty: back to options  •  Esc: quit"
+        }
+        Step::WriteError => {
+            "↑/↓/1-3: select  •  Enter: confirm  •  Backspace: back to summary  •  Esc: quit"
+        }
+        _ => "↑/↓/1-9: select  •  type: filter  •  Enter: continue  •  Esc: quit",
     };
 
     let help = Paragraph::new(help_text)
-        .style(Style::default().fg(Color::DarkGray))
+        .style(Style::default().fg(theme.muted))
         .alignment(Alignment::Center)
         .block(
             Block::default()
                 .borders(Borders::TOP)
-                .border_style(Style::default().fg(Color::DarkGray)),
+                .border_style(Style::default().fg(theme.muted)),
         );
     f.render_widget(help, area);
 }
@@ -556,35 +2197,399 @@ fn draw_help(f: &mut Frame, area: Rect, app: &App) {
 // ============================================================
 // Event handling
 // ============================================================
-// Reads keyboard input and updates app state accordingly.
+// Turns keyboard and mouse input into changes to the app state.
 // Only responds to key press events (ignores key release/repeat).
 
-fn handle_event(app: &mut App) -> io::Result<()> {
-    if let Event::Key(key) = event::read()? {
+fn handle_event(app: &mut App, event: Event) {
+    match event {
         // ignore key release events (some terminals send both press and release)
-        if key.kind != KeyEventKind::Press {
-            return Ok(());
+        Event::Key(key) if key.kind == KeyEventKind::Press => handle_key(app, key.code),
+        Event::Mouse(mouse) => handle_mouse(app, mouse),
+        // nothing to update — returning lets the main loop redraw at the new size
+        _ => {}
+    }
+}
+
+/// What a key press does to the wizard's state, on whatever step it's on.
+fn handle_key(app: &mut App, key: KeyCode) {
+    // while the quit dialog is up, only its own keys do anything
+    if app.confirm_quit {
+        match key {
+            KeyCode::Char('y') | KeyCode::Char('Y') => app.should_quit = true,
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => app.confirm_quit = false,
+            _ => {}
         }
+        return;
+    }
 
-        match key.code {
-            KeyCode::Char('q') | KeyCode::Esc => {
-                app.should_quit = true;
-            }
-            KeyCode::Enter => {
-                app.advance();
+    // the custom mappings step is text entry, so it gets its own key handling
+    if app.step == Step::CustomMappings {
+        match key {
+            KeyCode::Esc => app.request_quit(),
+            KeyCode::Tab | KeyCode::BackTab => app.toggle_custom_focus(),
+            KeyCode::Enter => app.submit_custom(),
+            KeyCode::Backspace => app.backspace_custom(),
+            KeyCode::Char(c) if !c.is_control() => app.custom_field_mut().push(c),
+            _ => {}
+        }
+        return;
+    }
+
+    // and the summary's field for semantic's directory
+    if app.step == Step::Summary && app.bin_dir_input.is_some() {
+        match key {
+            KeyCode::Esc => app.request_quit(),
+            KeyCode::Enter => app.submit_bin_dir_input(),
+            KeyCode::Backspace => app.backspace_bin_dir_input(),
+            KeyCode::Char(c) if !c.is_control() => {
+                if let Some(input) = &mut app.bin_dir_input {
+                    input.push(c);
+                }
             }
-            KeyCode::Backspace => {
-                app.go_back();
+            _ => {}
+        }
+        return;
+    }
+
+    // so is the write error step's directory field
+    if app.step == Step::WriteError && app.dir_input.is_some() {
+        match key {
+            KeyCode::Esc => app.request_quit(),
+            KeyCode::Enter => app.submit_dir_input(),
+            KeyCode::Backspace => app.backspace_dir_input(),
+            KeyCode::Char(c) if !c.is_control() => {
+                if let Some(input) = &mut app.dir_input {
+                    input.push(c);
+                }
             }
-            KeyCode::Up | KeyCode::Char('k') => {
-                app.move_up();
+            _ => {}
+        }
+        return;
+    }
+
+    // list steps take typed characters as filter text
+    let filtering = app.current_list_state().is_some();
+
+    match key {
+        // Esc clears an active filter first, and only quits once it's empty
+        KeyCode::Esc if !app.filter.is_empty() => {
+            app.clear_filter();
+        }
+        KeyCode::Esc => {
+            app.request_quit();
+        }
+        KeyCode::Char('q') if !filtering => {
+            app.request_quit();
+        }
+        KeyCode::Backspace if !app.filter.is_empty() => {
+            app.pop_filter();
+        }
+        KeyCode::Enter => {
+            app.advance();
+        }
+        KeyCode::Backspace => {
+            app.go_back();
+        }
+        KeyCode::Up => {
+            app.move_up();
+        }
+        KeyCode::Down => {
+            app.move_down();
+        }
+        // on list steps, number keys pick an item: 1 = first option … 9 = ninth
+        KeyCode::Char(c @ '1'..='9') if filtering => {
+            app.select_number(c as usize - '1' as usize);
+        }
+        // elsewhere they jump to a step: 1 = Welcome … 9 = Summary
+        KeyCode::Char(c @ '1'..='9') => {
+            let index = c as usize - '1' as usize;
+            if let Some(step) = Step::from_index(index) {
+                app.jump_to(step);
             }
-            KeyCode::Down | KeyCode::Char('j') => {
-                app.move_down();
+        }
+        // and letters jump to the step they name: s = Shell, c = Command style, …
+        KeyCode::Char(c) if !filtering && Step::from_key(c).is_some() => {
+            if let Some(step) = Step::from_key(c) {
+                app.jump_to(step);
             }
-            _ => {}
         }
+        KeyCode::Char(' ') if app.step == Step::EnabledShells => {
+            app.toggle_shell();
+        }
+        KeyCode::Char('b') if app.step == Step::Summary => {
+            app.open_bin_dir_input();
+        }
+        KeyCode::Char(c) if filtering && !c.is_control() => {
+            app.push_filter(c);
+        }
+        _ => {}
     }
+}
 
-    Ok(())
+/// On list steps, the scroll wheel moves the selection and a left click picks
+/// the row under the pointer (see `App::click`). Ignored while the quit dialog is up.
+fn handle_mouse(app: &mut App, mouse: MouseEvent) {
+    if app.confirm_quit || app.current_list_state().is_none() {
+        return;
+    }
+    match mouse.kind {
+        MouseEventKind::ScrollUp => app.move_up(),
+        MouseEventKind::ScrollDown => app.move_down(),
+        MouseEventKind::Down(MouseButton::Left) => app.click(mouse.column, mouse.row),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Sandbox;
+    use std::fs;
+
+    /// A wizard on the command style step, which has four options.
+    fn on_command_style(wrap_navigation: Option<bool>) -> App {
+        let mut app = App::new(PathBuf::from("config.toml"));
+        app.step = Step::CommandStyle;
+        app.wrap_navigation = wrap_navigation;
+        app
+    }
+
+    #[test]
+    fn navigation_wraps_at_either_end_by_default() {
+        let mut app = on_command_style(None);
+        app.move_up();
+        assert_eq!(app.selected_command_style(), "emoji");
+        app.move_down();
+        assert_eq!(app.selected_command_style(), "natural");
+    }
+
+    #[test]
+    fn navigation_stops_at_either_end_without_wrapping() {
+        let mut app = on_command_style(Some(false));
+        app.move_up();
+        assert_eq!(app.selected_command_style(), "natural");
+
+        for _ in 0..5 {
+            app.move_down();
+        }
+        assert_eq!(app.selected_command_style(), "emoji");
+    }
+
+    #[test]
+    fn navigation_wraps_within_the_filtered_options() {
+        let mut app = on_command_style(Some(true));
+        // only traditional's pacman and verbose's install-package match
+        app.push_filter('p');
+        app.push_filter('a');
+        app.push_filter('c');
+        assert_eq!(app.selected_command_style(), "traditional");
+        app.move_down();
+        assert_eq!(app.selected_command_style(), "verbose");
+        app.move_down();
+        assert_eq!(app.selected_command_style(), "traditional");
+    }
+
+    #[test]
+    fn an_unchanged_summary_finishes_without_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let mut app = App::new(path.clone());
+        app.existing = Some(app.build_config());
+        app.step = Step::Summary;
+
+        app.advance();
+        assert!(app.step == Step::Done && app.unchanged);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn toggled_shells_are_saved_as_enabled_with_the_default() {
+        let sandbox = Sandbox::new();
+        let path = sandbox.path("config/config.toml");
+        let mut app = App::new(path.clone());
+        app.step = Step::Shell;
+
+        // bash as the default, which starts toggled on the next step
+        app.move_down();
+        app.advance();
+        assert!(app.step == Step::EnabledShells);
+        assert_eq!(app.enabled_shells[..3], [false, true, false]);
+
+        // zsh on; the default can't be toggled off
+        app.move_down();
+        app.move_down();
+        app.toggle_shell();
+        app.move_up();
+        app.toggle_shell();
+        assert_eq!(app.selected_enabled_shells(), ["bash", "zsh"]);
+
+        app.step = Step::Summary;
+        app.advance();
+        assert!(app.step == Step::Done);
+        let saved = SemanticConfig::load_from(&path).unwrap();
+        assert_eq!(saved.shells.default, "bash");
+        assert_eq!(saved.shells.enabled, ["bash", "zsh"]);
+    }
+
+    #[test]
+    fn an_existing_configs_shells_start_selected() {
+        let mut app = App::new(PathBuf::from("config.toml"));
+        app.select_shells("zsh", &["fish".to_string(), "zsh".to_string(), "tcsh".to_string()]);
+        assert_eq!(app.selected_shell(), "zsh");
+        assert_eq!(app.selected_enabled_shells(), ["fish", "zsh"]);
+    }
+
+    /// Press each key in turn, as the event loop would.
+    fn press(app: &mut App, keys: &[KeyCode]) {
+        for &key in keys {
+            handle_key(app, key);
+        }
+    }
+
+    /// Type `text` one character at a time.
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            handle_key(app, KeyCode::Char(c));
+        }
+    }
+
+    #[test]
+    fn a_first_run_walks_every_step_and_saves() {
+        let sandbox = Sandbox::new();
+        let path = sandbox.path("wizard/config.toml");
+        let (mut app, warnings) = App::open(&path, false);
+        assert!(warnings.is_empty() && app.existing.is_none());
+
+        // theme, then bash as the default shell with fish enabled too
+        press(&mut app, &[KeyCode::Enter, KeyCode::Enter, KeyCode::Down, KeyCode::Enter]);
+        assert!(app.step == Step::EnabledShells);
+        press(&mut app, &[KeyCode::Char(' '), KeyCode::Enter]);
+        // language, then the traditional command style and natural paths
+        press(&mut app, &[KeyCode::Enter, KeyCode::Down, KeyCode::Enter, KeyCode::Enter]);
+        assert!(app.step == Step::CustomMappings);
+        type_text(&mut app, "hi");
+        handle_key(&mut app, KeyCode::Tab);
+        type_text(&mut app, "echo hi");
+        press(&mut app, &[KeyCode::Enter, KeyCode::Enter, KeyCode::Enter]);
+        assert!(app.step == Step::Summary);
+        assert_eq!(app.furthest_step.index(), Step::Summary.index());
+
+        handle_key(&mut app, KeyCode::Enter);
+        assert!(app.step == Step::Done);
+        assert_eq!(app.written_to.as_deref(), Some(path.as_path()));
+        let saved = SemanticConfig::load_from(&path).unwrap();
+        assert_eq!(saved.general.command_style, "traditional");
+        assert_eq!(saved.general.folder_style, "natural");
+        assert_eq!(saved.shells.default, "bash");
+        assert_eq!(saved.shells.enabled, ["fish", "bash"]);
+        assert_eq!(saved.commands["hi"], "echo hi");
+    }
+
+    #[test]
+    fn backspace_steps_back_and_jumps_stop_at_unseen_steps() {
+        let (mut app, _) = App::open(Path::new("/nonexistent/semantic/config.toml"), false);
+        // a first run can't jump ahead of what it has seen
+        handle_key(&mut app, KeyCode::Char('c'));
+        assert!(app.step == Step::Welcome);
+
+        press(&mut app, &[KeyCode::Enter, KeyCode::Enter, KeyCode::Backspace]);
+        assert!(app.step == Step::ColorTheme);
+        press(&mut app, &[KeyCode::Backspace, KeyCode::Char('3')]);
+        assert!(app.step == Step::Shell);
+    }
+
+    #[test]
+    fn typing_filters_a_list_and_esc_clears_it_before_quitting() {
+        let (mut app, _) = App::open(Path::new("/nonexistent/semantic/config.toml"), false);
+        press(&mut app, &[KeyCode::Enter, KeyCode::Enter, KeyCode::Enter, KeyCode::Enter]);
+        press(&mut app, &[KeyCode::Enter]);
+        assert!(app.step == Step::CommandStyle);
+
+        type_text(&mut app, "emo");
+        assert_eq!(app.visible_options(), [3]);
+        assert_eq!(app.selected_command_style(), "emoji");
+        handle_key(&mut app, KeyCode::Esc);
+        assert!(app.filter.is_empty() && !app.confirm_quit);
+
+        // quitting past the welcome step asks first
+        handle_key(&mut app, KeyCode::Esc);
+        assert!(app.confirm_quit);
+        handle_key(&mut app, KeyCode::Char('n'));
+        assert!(!app.confirm_quit && !app.should_quit);
+        press(&mut app, &[KeyCode::Esc, KeyCode::Char('y')]);
+        assert!(app.should_quit);
+    }
+
+    #[test]
+    fn an_existing_config_starts_the_wizard_on_its_choices() {
+        let sandbox = Sandbox::new();
+        let path = sandbox.write_config("[commands]\nhi = \"echo hi\"");
+        let content = fs::read_to_string(&path).unwrap();
+        let general = "[general]\ntheme = \"nord\"\nwrap_navigation = false\n";
+        fs::write(&path, content.replacen("[general]\n", general, 1)).unwrap();
+
+        let (mut app, _) = App::open(&path, false);
+        assert!(app.existing.is_some());
+        assert_eq!(app.selected_theme(), "nord");
+        assert_eq!(app.wrap_navigation, Some(false));
+        assert_eq!(app.selected_shell(), "bash");
+
+        // any step can be jumped to over an existing config, and the summary
+        // compares against it
+        handle_key(&mut app, KeyCode::Char('9'));
+        assert!(app.step == Step::Summary);
+        let changes = app.pending_changes().unwrap();
+        assert!(changes.contains(&LineDiff::Same("hi = \"echo hi\"".to_string())));
+        assert!(changes.contains(&LineDiff::Removed("on_new_shell = \"ignore\"".to_string())));
+    }
+
+    #[test]
+    fn the_wizard_runs_on_any_backend() {
+        use crossterm::event::{KeyEvent, KeyModifiers};
+        use ratatui::backend::TestBackend;
+
+        let sandbox = Sandbox::new();
+        let path = sandbox.path("wizard/config.toml");
+        let (mut app, _) = App::open(&path, true);
+        let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+        let keys = [
+            // welcome, theme, bash as the default shell, no others
+            KeyCode::Enter, KeyCode::Enter, KeyCode::Down, KeyCode::Enter, KeyCode::Enter,
+            // language, styles, no custom mappings, bin dir, then the summary
+            KeyCode::Enter, KeyCode::Enter, KeyCode::Enter, KeyCode::Enter, KeyCode::Enter,
+            KeyCode::Enter,
+        ];
+        let mut events = keys.into_iter().map(|key| Event::Key(KeyEvent::new(key, KeyModifiers::NONE)));
+        let next_event = || events.next().ok_or_else(|| io::Error::other("out of keys"));
+
+        event_loop(&mut app, &mut terminal, next_event).unwrap();
+        assert!(app.step == Step::Done);
+        assert_eq!(SemanticConfig::load_from(&path).unwrap().shells.default, "bash");
+        // the last screen drawn was the summary
+        let cells = terminal.backend().buffer().content();
+        let screen: String = cells.iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("Review your choices:"), "{screen}");
+        assert!(screen.contains("Shells:         bash (default)"), "{screen}");
+    }
+
+    #[test]
+    fn saving_a_style_that_drifted_from_its_template_warns() {
+        let sandbox = Sandbox::new();
+        sandbox.write("config/templates/verbose.toml", crate::testutil::DRIFTED_VERBOSE_TEMPLATE);
+        let mut app = App::new(sandbox.path("config/config.toml"));
+        let verbose = app.command_styles.iter().position(|(style, _)| *style == "verbose");
+        app.command_style_state.select(verbose);
+        app.step = Step::Summary;
+
+        app.advance();
+        assert!(app.step == Step::Done);
+        assert_eq!(app.template_drift.len(), 3, "{:?}", app.template_drift);
+        assert!(app.template_drift[0].contains(r#"[commands] "list-files" is "ls -al" there"#));
+
+        // natural has no template here, so there's nothing to say
+        let mut app = App::new(sandbox.path("config/config.toml"));
+        app.step = Step::Summary;
+        app.advance();
+        assert!(app.step == Step::Done && app.template_drift.is_empty());
+    }
 }
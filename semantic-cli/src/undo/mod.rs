@@ -0,0 +1,267 @@
+// undo/mod.rs
+// Works out how to reverse a command from the history log, for `semantic undo`.
+// Inverses are looked up by the real program a semantic command ran rather than
+// by its name, so `move`, `mover` and `🚚` all undo the same way. A program whose
+// effects can't be reversed (rm) is refused with the reason; one semantic knows
+// nothing about is refused too, rather than guessed at.
+// Inverses are built from what's on disk now: a move is only undone if the moved
+// file is still where it went and nothing has taken its old place.
+
+use std::path::{Path, PathBuf};
+
+/// How to reverse one kind of command.
+enum Reverse {
+    /// Build the commands that undo it from its parsed argv, resolving relative
+    /// paths against the directory it ran in.
+    With(fn(&Operands, &Path) -> Result<Inverse, String>),
+    /// It can't be undone, for this reason.
+    Never(&'static str),
+}
+
+/// Every program `inverse` knows, by name.
+const REGISTRY: &[(&str, Reverse)] = &[
+    ("mv", Reverse::With(unmove)),
+    ("cp", Reverse::With(uncopy)),
+    ("mkdir", Reverse::With(unmkdir)),
+    ("rm", Reverse::Never("rm deletes for good; there's no copy left to restore")),
+    ("rmdir", Reverse::Never("the directory is gone, and so is what it was called with")),
+    ("shred", Reverse::Never("shred overwrites the file's contents before deleting it")),
+    ("truncate", Reverse::Never("truncate throws away what was past the new size")),
+];
+
+/// Short options that say where things went in a way `inverse` doesn't follow:
+/// a target directory (-t), no target directory (-T), backups (-b, -S).
+const UNFOLLOWED_SHORT: &[char] = &['t', 'T', 'b', 'S'];
+const UNFOLLOWED_LONG: &[&str] = &["--target-directory", "--no-target-directory", "--backup", "--suffix"];
+
+/// The commands that reverse one, each an argv, and anything the user should
+/// know before running them.
+#[derive(Debug)]
+pub struct Inverse {
+    pub steps: Vec<Vec<String>>,
+    pub warning: Option<&'static str>,
+}
+
+/// A command's argv split into its options and operands (paths, here), with
+/// whether it ran under sudo.
+struct Operands {
+    sudo: bool,
+    options: Vec<String>,
+    paths: Vec<String>,
+}
+
+impl Operands {
+    fn parse(argv: &[String]) -> Option<(String, Self)> {
+        let sudo = argv.first().is_some_and(|word| word == "sudo");
+        let mut words = argv.iter().skip(usize::from(sudo));
+        let program = words.next()?;
+        let program = Path::new(program).file_name()?.to_string_lossy().into_owned();
+
+        let mut options = Vec::new();
+        let mut paths = Vec::new();
+        let mut after_separator = false;
+        for word in words {
+            if after_separator || !word.starts_with('-') || word == "-" {
+                paths.push(word.clone());
+            } else if word == "--" {
+                after_separator = true;
+            } else {
+                options.push(word.clone());
+            }
+        }
+        Some((program, Operands { sudo, options, paths }))
+    }
+
+    /// Whether any option is `long` or a short one containing one of `short`.
+    fn has(&self, short: &[char], long: &[&str]) -> bool {
+        self.options.iter().any(|option| match option.strip_prefix("--") {
+            Some(_) => long.iter().any(|l| option == l || option.starts_with(&format!("{l}="))),
+            None => option[1..].chars().any(|c| short.contains(&c)),
+        })
+    }
+
+    /// The argv of an inverse step, under sudo if the original was.
+    fn command(&self, words: impl IntoIterator<Item = String>) -> Vec<String> {
+        let sudo = self.sudo.then(|| "sudo".to_string());
+        sudo.into_iter().chain(words).collect()
+    }
+}
+
+/// How to undo `argv` (a command from the history log, as run) that ran in `cwd`,
+/// or why it can't be.
+pub fn inverse(argv: &[String], cwd: &Path) -> Result<Inverse, String> {
+    let (program, operands) = Operands::parse(argv).ok_or("it ran nothing")?;
+    let Some((_, reverse)) = REGISTRY.iter().find(|(name, _)| *name == program) else {
+        return Err(format!("semantic doesn't know how to undo `{program}`"));
+    };
+    match reverse {
+        Reverse::Never(reason) => Err((*reason).to_string()),
+        Reverse::With(_) if operands.has(UNFOLLOWED_SHORT, UNFOLLOWED_LONG) => Err(format!(
+            "it used options ({}) that make where things went too hard to tell",
+            operands.options.join(" ")
+        )),
+        Reverse::With(generate) => generate(&operands, cwd),
+    }
+}
+
+/// Where each source of a `mv`/`cp` ended up: inside the destination when it's a
+/// directory holding something of that name (or there were several sources),
+/// otherwise the destination itself.
+fn destinations(operands: &Operands, cwd: &Path) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let [sources @ .., dest] = operands.paths.as_slice() else {
+        return Err("it wasn't given anything to work on".to_string());
+    };
+    if sources.is_empty() {
+        return Err("it was given only one path".to_string());
+    }
+    let dest = cwd.join(dest);
+    sources
+        .iter()
+        .map(|source| {
+            let source = cwd.join(source);
+            let name = source.file_name().ok_or_else(|| format!("{} has no file name", source.display()))?;
+            let inside = dest.join(name);
+            let landed = if sources.len() > 1 || (dest.is_dir() && inside.symlink_metadata().is_ok()) {
+                inside
+            } else {
+                dest.clone()
+            };
+            Ok((source, landed))
+        })
+        .collect()
+}
+
+/// `mv a b` → `mv b a`, for each source.
+fn unmove(operands: &Operands, cwd: &Path) -> Result<Inverse, String> {
+    let mut steps = Vec::new();
+    for (source, landed) in destinations(operands, cwd)? {
+        if landed.symlink_metadata().is_err() {
+            return Err(format!("{} isn't there anymore to move back", landed.display()));
+        }
+        if source.symlink_metadata().is_ok() {
+            return Err(format!("{} exists again; moving back would replace it", source.display()));
+        }
+        steps.push(operands.command([
+            "mv".to_string(),
+            "--".to_string(),
+            landed.display().to_string(),
+            source.display().to_string(),
+        ]));
+    }
+    Ok(Inverse { steps, warning: None })
+}
+
+/// `cp a b` → `rm b` (with -r if the copy was recursive).
+fn uncopy(operands: &Operands, cwd: &Path) -> Result<Inverse, String> {
+    let recursive = operands.has(&['r', 'R', 'a'], &["--recursive", "--archive"]);
+    let mut words = vec!["rm".to_string()];
+    if recursive {
+        words.push("-r".to_string());
+    }
+    words.push("--".to_string());
+    for (_, landed) in destinations(operands, cwd)? {
+        if landed.symlink_metadata().is_err() {
+            return Err(format!("{} isn't there anymore", landed.display()));
+        }
+        words.push(landed.display().to_string());
+    }
+    Ok(Inverse {
+        steps: vec![operands.command(words)],
+        warning: Some("if the copy replaced a file that was already there, that file is gone either way"),
+    })
+}
+
+/// `mkdir a b` → `rmdir b a`, which only removes them while they're empty.
+/// Parents made by -p are left alone.
+fn unmkdir(operands: &Operands, cwd: &Path) -> Result<Inverse, String> {
+    if operands.paths.is_empty() {
+        return Err("it wasn't given a directory".to_string());
+    }
+    let mut words = vec!["rmdir".to_string(), "--".to_string()];
+    for dir in operands.paths.iter().rev() {
+        let dir = cwd.join(dir);
+        if !dir.is_dir() {
+            return Err(format!("{} isn't there anymore", dir.display()));
+        }
+        words.push(dir.display().to_string());
+    }
+    Ok(Inverse {
+        steps: vec![operands.command(words)],
+        warning: operands
+            .has(&['p'], &["--parents"])
+            .then_some("parent directories mkdir -p made are left in place"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn argv(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn a_move_is_undone_by_moving_back() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("b.txt"), "moved").unwrap();
+
+        let inverse = inverse(&argv("mv a.txt b.txt"), dir.path()).unwrap();
+        let (from, to) = (dir.path().join("b.txt"), dir.path().join("a.txt"));
+        let expected = ["mv", "--", &from.display().to_string(), &to.display().to_string()];
+        assert_eq!(inverse.steps, [expected]);
+        assert!(inverse.warning.is_none());
+    }
+
+    #[test]
+    fn a_move_into_a_directory_moves_back_out_of_it() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("docs")).unwrap();
+        fs::write(dir.path().join("docs/a.txt"), "moved").unwrap();
+
+        let inverse = inverse(&argv("sudo mv a.txt docs"), dir.path()).unwrap();
+        let step = &inverse.steps[0];
+        assert_eq!(step[..3], ["sudo", "mv", "--"]);
+        assert_eq!(Path::new(&step[3]), dir.path().join("docs/a.txt"));
+    }
+
+    #[test]
+    fn a_move_isnt_undone_over_a_file_that_took_its_place() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "new").unwrap();
+        fs::write(dir.path().join("b.txt"), "moved").unwrap();
+
+        let error = inverse(&argv("mv a.txt b.txt"), dir.path()).unwrap_err();
+        assert!(error.contains("exists again"), "{error}");
+    }
+
+    #[test]
+    fn a_delete_is_refused_with_the_reason() {
+        let dir = tempfile::tempdir().unwrap();
+        let error = inverse(&argv("rm -rf build"), dir.path()).unwrap_err();
+        assert!(error.contains("deletes for good"), "{error}");
+    }
+
+    #[test]
+    fn unknown_programs_and_unfollowed_options_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let error = inverse(&argv("pacman -S git"), dir.path()).unwrap_err();
+        assert_eq!(error, "semantic doesn't know how to undo `pacman`");
+
+        fs::create_dir(dir.path().join("docs")).unwrap();
+        let error = inverse(&argv("mv -t docs a.txt"), dir.path()).unwrap_err();
+        assert!(error.contains("(-t)"), "{error}");
+    }
+
+    #[test]
+    fn a_recursive_copy_is_undone_by_removing_it() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("backup/src")).unwrap();
+
+        let inverse = inverse(&argv("cp -r src backup"), dir.path()).unwrap();
+        let target = dir.path().join("backup/src").display().to_string();
+        assert_eq!(inverse.steps, [["rm", "-r", "--", target.as_str()]]);
+        assert!(inverse.warning.is_some());
+    }
+}
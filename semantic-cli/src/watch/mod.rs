@@ -0,0 +1,336 @@
+// watch/mod.rs
+// The daemon behind `semantic watch`.
+// Watches every directory in $PATH (not recursively) for a shell binary that
+// wasn't there at startup. Depending on `[shells] on_new_shell`, a new shell is
+// either set up — enabled in the config, with a marked block in its rc file
+// that loads semantic — or only reported.
+// Runs in the foreground until interrupted, picking up a change to
+// on_new_shell without a restart.
+// `semantic scan-shells` is the one-shot version for a login hook or a timer: it
+// compares the installed shells (see `installed_shells`) with the enabled ones
+// instead of watching.
+
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, mpsc};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::config::{self, ConfigFile};
+use crate::shell;
+
+/// Shell binaries worth noticing. Each is also a shell `semantic init` supports.
+pub const WATCHED_SHELLS: &[&str] = &["bash", "zsh", "fish", "nu", "pwsh"];
+
+/// What to do when a new shell shows up, from `[shells] on_new_shell`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// "auto-setup": enable it and add semantic to its rc file.
+    AutoSetup,
+    /// "notify": just say so.
+    Notify,
+}
+
+impl Mode {
+    /// The mode for an `on_new_shell` value. None for "ignore" and anything unknown.
+    pub fn from_config(on_new_shell: &str) -> Option<Self> {
+        match on_new_shell {
+            "auto-setup" => Some(Mode::AutoSetup),
+            "notify" => Some(Mode::Notify),
+            _ => None,
+        }
+    }
+}
+
+/// What happens to new shells in `mode`, as a sentence.
+fn plan(mode: Option<Mode>) -> &'static str {
+    match mode {
+        Some(Mode::AutoSetup) => "New shells will be set up automatically.",
+        Some(Mode::Notify) => "New shells will be reported, not set up.",
+        None => "New shells will be ignored until on_new_shell is set again.",
+    }
+}
+
+/// Where the system lists its installed login shells.
+pub const ETC_SHELLS: &str = "/etc/shells";
+
+/// The shells we watch for that the shells file at `path` lists (one path per
+/// line, `#` comments), in the order listed, each once.
+pub fn listed_shells(path: &Path) -> io::Result<Vec<&'static str>> {
+    let content = fs::read_to_string(path)?;
+    let mut shells = Vec::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let Some(name) = Path::new(line).file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if let Some(shell) = WATCHED_SHELLS.iter().find(|s| **s == name)
+            && !shells.contains(shell)
+        {
+            shells.push(*shell);
+        }
+    }
+    Ok(shells)
+}
+
+/// The shells we watch for that are installed: those the shells file at `path`
+/// lists, then any others found in $PATH (fish and nu often aren't listed).
+/// A missing shells file just means there's only $PATH to go on.
+pub fn installed_shells(path: &Path) -> io::Result<Vec<&'static str>> {
+    let mut shells = match listed_shells(path) {
+        Ok(shells) => shells,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    for shell in WATCHED_SHELLS {
+        if !shells.contains(shell) && shell::find_in_path(shell).is_some() {
+            shells.push(shell);
+        }
+    }
+    Ok(shells)
+}
+
+/// The shells in `listed` that aren't in `enabled` yet.
+pub fn new_shells<'a>(listed: &[&'a str], enabled: &[String]) -> Vec<&'a str> {
+    listed.iter().copied().filter(|s| !enabled.iter().any(|e| e == s)).collect()
+}
+
+/// What `scan` did about one new shell.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Scanned {
+    /// Set up, with what was done (see `set_up`).
+    SetUp(String),
+    /// Set up failed, with why.
+    Failed(String),
+    /// Only to be reported, in notify mode.
+    Reported,
+}
+
+/// Compare the installed shells (see `installed_shells`, reading the shells file
+/// at `shells_file`) with `enabled` once, and set up each that's missing in
+/// auto-setup mode. No mode ("ignore") does nothing. Returns each new shell
+/// with what became of it.
+pub fn scan(
+    shells_file: &Path,
+    enabled: &[String],
+    mode: Option<Mode>,
+) -> io::Result<Vec<(&'static str, Scanned)>> {
+    let Some(mode) = mode else { return Ok(Vec::new()) };
+    let installed = installed_shells(shells_file)?;
+    Ok(new_shells(&installed, enabled)
+        .into_iter()
+        .map(|name| {
+            let scanned = match mode {
+                Mode::AutoSetup => match set_up(name) {
+                    Ok(message) => Scanned::SetUp(message),
+                    Err(e) => Scanned::Failed(e.to_string()),
+                },
+                Mode::Notify => Scanned::Reported,
+            };
+            (name, scanned)
+        })
+        .collect())
+}
+
+/// The existing directories in $PATH, in order, without duplicates.
+pub fn path_dirs() -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    env::var_os("PATH")
+        .map(|path| env::split_paths(&path).collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|dir| dir.is_dir() && seen.insert(dir.clone()))
+        .collect()
+}
+
+/// The shell a file is the binary of, if it's one we watch for and is executable.
+pub fn shell_of(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?;
+    let name = name.strip_suffix(env::consts::EXE_SUFFIX).unwrap_or(name);
+    let shell = WATCHED_SHELLS.iter().find(|s| **s == name)?;
+    is_executable(path).then_some(*shell)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Watch $PATH until interrupted, handling each new shell according to `mode`.
+/// Shells already installed at startup are left alone. Returns only on error.
+pub fn run(mode: Mode) -> Result<(), Box<dyn std::error::Error>> {
+    let dirs = path_dirs();
+    if dirs.is_empty() {
+        return Err("$PATH has no directories to watch".into());
+    }
+
+    // only shells installed from now on count as new
+    let mut known: HashSet<&str> = WATCHED_SHELLS
+        .iter()
+        .copied()
+        .filter(|s| shell::find_in_path(s).is_some())
+        .collect();
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    let mut watching = Vec::new();
+    for dir in &dirs {
+        match watcher.watch(dir, RecursiveMode::NonRecursive) {
+            Ok(()) => watching.push(dir),
+            Err(e) => eprintln!("semantic: can't watch {}: {e}", dir.display()),
+        }
+    }
+    if watching.is_empty() {
+        return Err("couldn't watch any directory in $PATH".into());
+    }
+
+    println!(
+        "semantic: watching {} {} for new shells ({}):",
+        watching.len(),
+        if watching.len() == 1 { "directory" } else { "directories" },
+        WATCHED_SHELLS.join(", ")
+    );
+    for dir in &watching {
+        println!("  {}", dir.display());
+    }
+    let mut installed: Vec<_> = known.iter().copied().collect();
+    installed.sort();
+    if !installed.is_empty() {
+        println!("Already installed (ignored): {}", installed.join(", "));
+    }
+    println!("{} Ctrl-C to stop.", plan(Some(mode)));
+
+    // follow on_new_shell as the config is edited
+    let current = Arc::new(Mutex::new(Some(mode)));
+    let followed = Arc::clone(&current);
+    let _config_watcher = config::SemanticConfig::watch(
+        move |config| {
+            let mode = Mode::from_config(&config.shells.on_new_shell);
+            let mut current = followed.lock().unwrap_or_else(|e| e.into_inner());
+            if *current != mode {
+                *current = mode;
+                println!("semantic: config changed. {}", plan(mode));
+            }
+        },
+        |e| eprintln!("semantic: config changed but can't be loaded, keeping the last good one: {e}"),
+    )
+    .map_err(|e| eprintln!("semantic: can't watch the config, restart to pick up changes: {e}"))
+    .ok();
+
+    for event in rx {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("semantic: watch error: {e}");
+                continue;
+            }
+        };
+        // installers create, rename into place or chmod; any of those can be the
+        // moment the binary becomes a usable shell
+        if matches!(event.kind, EventKind::Remove(_) | EventKind::Access(_)) {
+            continue;
+        }
+
+        for path in &event.paths {
+            let Some(name) = shell_of(path) else { continue };
+            if !known.insert(name) {
+                continue;
+            }
+            println!("semantic: new shell installed: {name} ({})", path.display());
+            let Some(mode) = *current.lock().unwrap_or_else(|e| e.into_inner()) else {
+                println!("  ignored: [shells] on_new_shell is no longer \"auto-setup\" or \"notify\"");
+                continue;
+            };
+            match mode {
+                Mode::AutoSetup => match set_up(name) {
+                    Ok(message) => println!("  {message}"),
+                    Err(e) => eprintln!("  couldn't set up {name}: {e}"),
+                },
+                Mode::Notify => {
+                    if let Some((rc, line)) = shell::rc_setup(name) {
+                        println!("  to use semantic in it, add `{line}` to {rc}");
+                        println!("  and `{name}` to [shells] enabled");
+                    }
+                }
+            }
+        }
+    }
+
+    Err("the file watcher stopped".into())
+}
+
+/// Enable `shell` in the config and add a semantic block to its rc file,
+/// unless the rc file already loads semantic. Returns what was done.
+pub fn set_up(shell: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let mut file = ConfigFile::load()?;
+    if !file.config.shells.enabled.iter().any(|s| s == shell) {
+        file.config.shells.enabled.push(shell.to_string());
+        file.save()?;
+    }
+
+    match shell::install_rc_block(shell, false)? {
+        Some(rc) => Ok(format!("enabled it and added semantic to {}; open a new {shell} to use it", rc.display())),
+        None => Ok("enabled it; its rc file already loads semantic".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Sandbox;
+
+    /// A sandbox whose shells file lists zsh and fish besides the enabled bash,
+    /// with nothing more to find in $PATH. Returns the shells file.
+    fn shells_sandbox() -> (Sandbox, PathBuf) {
+        let mut sandbox = Sandbox::new();
+        sandbox.write_config("");
+        sandbox.set("PATH", sandbox.path("empty-bin"));
+        let listing = "# comment\n/bin/bash\n/bin/zsh\n/usr/bin/fish\n/bin/sh\n";
+        let shells_file = sandbox.write("etc/shells", listing);
+        (sandbox, shells_file)
+    }
+
+    fn enabled() -> Vec<String> {
+        ConfigFile::load().unwrap().config.shells.enabled
+    }
+
+    #[test]
+    fn auto_setup_enables_each_new_shell() {
+        let (sandbox, shells_file) = shells_sandbox();
+        let scanned = scan(&shells_file, &enabled(), Mode::from_config("auto-setup")).unwrap();
+
+        let names: Vec<_> = scanned.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["zsh", "fish"]);
+        assert!(scanned.iter().all(|(_, scanned)| matches!(scanned, Scanned::SetUp(_))), "{scanned:?}");
+        assert_eq!(enabled(), ["bash", "zsh", "fish"]);
+        assert!(sandbox.path("home/.zshrc").exists());
+    }
+
+    #[test]
+    fn notify_reports_new_shells_and_changes_nothing() {
+        let (sandbox, shells_file) = shells_sandbox();
+        let scanned = scan(&shells_file, &enabled(), Mode::from_config("notify")).unwrap();
+
+        assert_eq!(scanned, [("zsh", Scanned::Reported), ("fish", Scanned::Reported)]);
+        assert_eq!(enabled(), ["bash"]);
+        assert!(!sandbox.path("home/.zshrc").exists());
+    }
+
+    #[test]
+    fn ignore_does_nothing() {
+        let (sandbox, shells_file) = shells_sandbox();
+        assert_eq!(Mode::from_config("ignore"), None);
+        assert_eq!(scan(&shells_file, &enabled(), Mode::from_config("ignore")).unwrap(), []);
+        assert_eq!(enabled(), ["bash"]);
+        assert!(!sandbox.path("home/.zshrc").exists());
+    }
+}
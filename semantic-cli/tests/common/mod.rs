@@ -0,0 +1,46 @@
+// tests/common/mod.rs
+// Running the built `semantic` for the integration tests: each test gets a
+// temporary config dir, home, cache and state dir, and an environment with
+// nothing else of the user's in it.
+
+use std::path::Path;
+use std::process::Command;
+
+use tempfile::TempDir;
+
+const BASE_CONFIG: &str = "\
+[general]
+command_style = \"natural\"
+folder_style = \"natural\"
+
+[shells]
+default = \"bash\"
+enabled = [\"bash\"]
+on_new_shell = \"ignore\"
+";
+
+/// A temporary setup with `extra` (usually a `[commands]` table) in its config.
+pub fn setup(extra: &str) -> TempDir {
+    let dir = tempfile::tempdir().expect("temporary directory");
+    std::fs::create_dir_all(dir.path().join("config")).expect("config dir");
+    std::fs::create_dir_all(dir.path().join("home")).expect("home dir");
+    std::fs::write(dir.path().join("config/config.toml"), format!("{BASE_CONFIG}\n{extra}"))
+        .expect("write config");
+    dir
+}
+
+/// `semantic` with `args`, using the setup in `dir` and nothing from our environment but $PATH.
+pub fn semantic(dir: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_semantic"));
+    command
+        .args(args)
+        .env_clear()
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("HOME", dir.join("home"))
+        .env("SEMANTIC_CONFIG_DIR", dir.join("config"))
+        .env("XDG_CACHE_HOME", dir.join("cache"))
+        .env("XDG_STATE_HOME", dir.join("state"))
+        .env("SEMANTIC_NO_LOCAL", "1")
+        .current_dir(dir.join("home"));
+    command
+}
@@ -0,0 +1,83 @@
+// tests/config.rs
+// `semantic config path` and `semantic config dir`, which scripts and editors
+// use to find the config, whether or not there is one yet, `semantic diff`,
+// which they use to check it for drift, and `semantic reset`'s warning about
+// templates the built-in styles have drifted from.
+
+mod common;
+
+use common::{semantic, setup};
+
+#[test]
+fn config_path_and_dir_follow_semantic_config_dir() {
+    let dir = setup("");
+    let elsewhere = dir.path().join("elsewhere");
+
+    let output = semantic(dir.path(), &["config", "path"])
+        .env("SEMANTIC_CONFIG_DIR", &elsewhere)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let expected = format!("{}\n", elsewhere.join("config.toml").display());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+
+    let output = semantic(dir.path(), &["config", "dir"])
+        .env("SEMANTIC_CONFIG_DIR", &elsewhere)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), format!("{}\n", elsewhere.display()));
+}
+
+#[test]
+fn config_path_doesnt_need_a_config_that_parses() {
+    let dir = setup("[commands\nbroken");
+
+    let output = semantic(dir.path(), &["config", "path"]).output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let expected = format!("{}\n", dir.path().join("config/config.toml").display());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+}
+
+#[test]
+fn diff_exits_0_clean_1_with_drift_and_2_without_a_config() {
+    let dir = setup("");
+    let diff = |dir: &std::path::Path| semantic(dir, &["diff"]).output().unwrap().status.code();
+    let reset = semantic(dir.path(), &["reset", "--yes"]).output().unwrap();
+    assert!(reset.status.success(), "{reset:?}");
+    assert_eq!(diff(dir.path()), Some(0));
+
+    let config = dir.path().join("config/config.toml");
+    let content = std::fs::read_to_string(&config).unwrap();
+    std::fs::write(&config, content.replacen("[commands]\n", "[commands]\nhi = \"echo hi\"\n", 1))
+        .unwrap();
+    assert_eq!(diff(dir.path()), Some(1));
+
+    std::fs::write(&config, "[commands]\nhi = [").unwrap();
+    assert_eq!(diff(dir.path()), Some(2));
+    std::fs::remove_file(&config).unwrap();
+    assert_eq!(diff(dir.path()), Some(2));
+}
+
+#[test]
+fn reset_warns_when_a_template_has_drifted() {
+    let dir = setup("");
+    let config = dir.path().join("config/config.toml");
+    let content = std::fs::read_to_string(&config).unwrap();
+    std::fs::write(&config, content.replace("\"natural\"", "\"verbose\"")).unwrap();
+
+    // no templates to compare with: nothing to say
+    let output = semantic(dir.path(), &["reset", "--yes"]).output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("warning"), "{output:?}");
+
+    std::fs::create_dir_all(dir.path().join("config/templates")).unwrap();
+    let template = "[commands]\ngo-to = \"cd -P\"\n";
+    std::fs::write(dir.path().join("config/templates/verbose.toml"), template).unwrap();
+    let output = semantic(dir.path(), &["reset", "--yes"]).output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let expected = r#"[commands] "go-to" is "cd -P" there but "cd" built in"#;
+    let warned = |line: &str| line.starts_with("semantic: warning: ") && line.ends_with(expected);
+    assert!(stderr.lines().any(warned), "{stderr}");
+}
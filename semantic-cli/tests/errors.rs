@@ -0,0 +1,107 @@
+// tests/errors.rs
+// What semantic prints and exits with when it can't do what was asked, for
+// the main ways that happens (see the exit code table in src/error/mod.rs).
+
+mod common;
+
+use std::process::Output;
+
+use common::{semantic, setup};
+
+/// The exit code and stderr of a `semantic` run that failed.
+fn fails(output: Output) -> (Option<i32>, String) {
+    assert!(!output.status.success(), "{output:?}");
+    (output.status.code(), String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+#[test]
+fn a_missing_config_exits_2() {
+    let dir = setup("");
+    std::fs::remove_file(dir.path().join("config/config.toml")).expect("remove config");
+
+    let (code, stderr) = fails(semantic(dir.path(), &["translate", "install"]).output().unwrap());
+    assert_eq!(code, Some(2));
+    assert!(stderr.contains("config.toml doesn't exist"), "{stderr}");
+}
+
+#[test]
+fn a_config_that_doesnt_parse_exits_2_with_its_line() {
+    let dir = setup("[commands]\ninstall = [");
+
+    let (code, stderr) = fails(semantic(dir.path(), &["translate", "install"]).output().unwrap());
+    assert_eq!(code, Some(2));
+    assert!(stderr.contains("config.toml:11:"), "{stderr}");
+}
+
+#[test]
+fn an_unknown_command_exits_3_with_a_suggestion() {
+    let dir = setup("[commands]\ninstall = \"sudo pacman -S\"");
+
+    let (code, stderr) = fails(semantic(dir.path(), &["translate", "instal"]).output().unwrap());
+    assert_eq!(code, Some(3));
+    assert!(stderr.contains("Unknown semantic command: instal"), "{stderr}");
+    assert!(stderr.contains("did you mean `install`?"), "{stderr}");
+}
+
+#[test]
+fn a_path_alias_used_as_a_command_exits_3() {
+    let dir = setup("[paths]\n\"/apps\" = \"/usr/bin\"");
+
+    let (code, stderr) = fails(semantic(dir.path(), &["translate", "/apps"]).output().unwrap());
+    assert_eq!(code, Some(3));
+    assert!(stderr.contains("is a path alias"), "{stderr}");
+}
+
+#[test]
+fn a_program_that_isnt_installed_exits_127() {
+    let dir = setup("[commands]\ninstall = \"semantic-test-no-such-program -S\"");
+
+    let (code, stderr) = fails(semantic(dir.path(), &["translate", "install"]).output().unwrap());
+    assert_eq!(code, Some(127));
+    assert!(stderr.contains("Failed to run `semantic-test-no-such-program -S`"), "{stderr}");
+}
+
+#[cfg(unix)]
+#[test]
+fn a_command_that_ran_passes_on_its_exit_code() {
+    let dir = setup("[commands]\nfail = \"sh fail.sh\"");
+    std::fs::write(dir.path().join("home/fail.sh"), "exit 42\n").expect("write script");
+
+    let (code, _) = fails(semantic(dir.path(), &["translate", "fail"]).output().unwrap());
+    assert_eq!(code, Some(42));
+}
+
+/// The commands that rewrite the user config, each with what it needs besides.
+const REWRITING: &[&[&str]] = &[
+    &["reset", "--yes"],
+    &["upgrade"],
+    &["convert", "--to", "json"],
+    &["profile", "save", "work"],
+    &["profile", "create", "work", "--copy"],
+    &["import", "extra.toml", "--merge", "--yes"],
+];
+
+#[test]
+fn commands_that_rewrite_the_config_exit_2_without_one() {
+    let dir = setup("");
+    std::fs::write(dir.path().join("home/extra.toml"), "[commands]\nhi = \"echo hi\"\n").unwrap();
+    std::fs::remove_file(dir.path().join("config/config.toml")).expect("remove config");
+
+    for args in REWRITING {
+        let (code, stderr) = fails(semantic(dir.path(), args).output().unwrap());
+        assert_eq!(code, Some(2), "{args:?}: {stderr}");
+        assert!(stderr.contains("config.toml doesn't exist"), "{args:?}: {stderr}");
+    }
+}
+
+#[test]
+fn commands_that_rewrite_the_config_exit_2_when_it_doesnt_parse() {
+    let dir = setup("[commands]\ninstall = [");
+    std::fs::write(dir.path().join("home/extra.toml"), "[commands]\nhi = \"echo hi\"\n").unwrap();
+
+    for args in REWRITING {
+        let (code, stderr) = fails(semantic(dir.path(), args).output().unwrap());
+        assert_eq!(code, Some(2), "{args:?}: {stderr}");
+        assert!(stderr.contains("config.toml:11:"), "{args:?}: {stderr}");
+    }
+}
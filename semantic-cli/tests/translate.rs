@@ -0,0 +1,144 @@
+// tests/translate.rs
+// `semantic translate` run as its own process, for what only shows from
+// outside: the child's stdin and stdout, and how it exits.
+
+mod common;
+
+use std::io::{Read, Write};
+use std::process::{Command, Output, Stdio};
+
+use common::{semantic, setup};
+
+/// Run `semantic` with `input` on its stdin, and collect what it printed.
+fn run_with_input(mut command: Command, input: &str) -> Output {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run semantic");
+    child.stdin.take().expect("stdin").write_all(input.as_bytes()).expect("write stdin");
+    child.wait_with_output().expect("wait for semantic")
+}
+
+#[cfg(unix)]
+#[test]
+fn piped_input_reaches_the_command() {
+    let dir = setup("[commands]\nshow = \"cat\"");
+
+    let output = run_with_input(semantic(dir.path(), &["translate", "show"]), "hello\nworld\n");
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello\nworld\n");
+
+    // without exec, semantic waits for the child, which still has the pipe
+    let output =
+        run_with_input(semantic(dir.path(), &["translate", "--timeout", "30", "show"]), "hello\n");
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn a_closed_pipe_ends_quietly_with_141() {
+    let dir = setup("[commands]\nspew = \"yes\"");
+
+    // --timeout keeps semantic waiting on the child instead of becoming it
+    let mut child = semantic(dir.path(), &["translate", "--timeout", "30", "spew"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run semantic");
+    let mut stdout = child.stdout.take().expect("stdout");
+    let mut first = [0; 4];
+    stdout.read_exact(&mut first).expect("some output");
+    assert_eq!(&first, b"y\ny\n");
+    drop(stdout);
+
+    let output = child.wait_with_output().expect("wait for semantic");
+    assert_eq!(output.status.code(), Some(141), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+/// Under a pseudo-terminal (util-linux `script`), the command sees the terminal
+/// semantic was given, as editors and other full-screen programs need.
+#[cfg(target_os = "linux")]
+#[test]
+fn the_command_gets_the_terminal() {
+    let dir = setup("[commands]\ncheck = \"sh check.sh\"");
+    let script = "test -t 0 && test -t 1 && echo interactive\n";
+    std::fs::write(dir.path().join("home/check.sh"), script).expect("write script");
+
+    // --timeout keeps semantic waiting on the child instead of becoming it
+    let inner = semantic(dir.path(), &["translate", "--timeout", "30", "check"]);
+    let words: Vec<String> = std::iter::once(inner.get_program())
+        .chain(inner.get_args())
+        .map(|word| format!("'{}'", word.to_string_lossy().replace('\'', r"'\''")))
+        .collect();
+    let mut command = Command::new("script");
+    command.args(["-qec", &words.join(" "), "/dev/null"]).env_clear();
+    for (var, value) in inner.get_envs() {
+        if let Some(value) = value {
+            command.env(var, value);
+        }
+    }
+    command.current_dir(dir.path().join("home")).stdin(Stdio::null());
+    let output = match command.output() {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return eprintln!("no `script`; skipped"),
+        Err(e) => panic!("run script: {e}"),
+    };
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("interactive"), "{output:?}");
+}
+
+/// The stdout of a `semantic` run that succeeded.
+fn stdout_of(mut command: Command) -> String {
+    let output = command.output().expect("run semantic");
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn arguments_after_the_separator_pass_through_untouched() {
+    let dir = setup("[commands]\ndelete = \"echo deleting\"\n[paths]\n\"/apps\" = \"/usr/bin\"");
+
+    let stdout = stdout_of(semantic(dir.path(), &["translate", "--", "delete", "--force"]));
+    assert_eq!(stdout, "deleting --force\n");
+    // nor are they virtual paths
+    let stdout = stdout_of(semantic(dir.path(), &["translate", "--", "delete", "/apps"]));
+    assert_eq!(stdout, "deleting /apps\n");
+    let stdout = stdout_of(semantic(dir.path(), &["translate", "delete", "/apps"]));
+    assert_eq!(stdout, "deleting /usr/bin\n");
+}
+
+#[test]
+fn flags_before_the_separator_are_still_semantics() {
+    let dir = setup("[commands]\ndelete = \"echo deleting\"");
+
+    let output = semantic(dir.path(), &["translate", "--dry-run", "--", "delete", "--force"])
+        .output()
+        .expect("run semantic");
+    assert!(output.status.success(), "{output:?}");
+    // shown, not run
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(r#"["echo", "deleting", "--force"]"#), "{stderr}");
+}
+
+/// With `[general] rewrite_output`, real paths in the command's output come back
+/// as virtual ones, even one the command writes in two parts.
+#[cfg(unix)]
+#[test]
+fn output_shows_real_paths_as_virtual_ones() {
+    let dir = setup("[commands]\nshow = \"sh show.sh\"\n[paths]\n\"/data\" = \"/srv/data\"");
+    let config = dir.path().join("config/config.toml");
+    let content = std::fs::read_to_string(&config).expect("read config");
+    std::fs::write(&config, content.replacen("[general]\n", "[general]\nrewrite_output = true\n", 1))
+        .expect("write config");
+    let script = "echo /srv/data/one\nprintf /srv/da\nsleep 0.2\necho ta/two\necho /srv/database\n";
+    std::fs::write(dir.path().join("home/show.sh"), script).expect("write script");
+
+    let stdout = stdout_of(semantic(dir.path(), &["translate", "show"]));
+    assert_eq!(stdout, "/data/one\n/data/two\n/srv/database\n");
+}
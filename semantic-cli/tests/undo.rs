@@ -0,0 +1,35 @@
+// tests/undo.rs
+// `semantic undo` after a real command: the history log it reads, the inverse
+// it works out from what's on disk, and running that.
+
+mod common;
+
+use std::fs;
+
+use common::{semantic, setup};
+
+#[cfg(unix)]
+#[test]
+fn undo_moves_a_moved_file_back() {
+    let dir = setup("[commands]\nmove = \"mv\"");
+    let config = dir.path().join("config/config.toml");
+    let content = fs::read_to_string(&config).unwrap();
+    fs::write(&config, content.replacen("[general]\n", "[general]\nhistory = true\n", 1)).unwrap();
+    let home = dir.path().join("home");
+    fs::write(home.join("a.txt"), "notes\n").unwrap();
+
+    let output = semantic(dir.path(), &["translate", "move", "a.txt", "b.txt"]).output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(!home.join("a.txt").exists());
+
+    let output = semantic(dir.path(), &["undo", "--yes"]).output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Undone."));
+    assert_eq!(fs::read_to_string(home.join("a.txt")).unwrap(), "notes\n");
+    assert!(!home.join("b.txt").exists());
+
+    // the undo isn't in the history itself, so a second one finds the move gone
+    let output = semantic(dir.path(), &["undo", "--yes"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("isn't there anymore"));
+}
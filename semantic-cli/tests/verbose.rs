@@ -0,0 +1,38 @@
+// tests/verbose.rs
+// The global -v/--verbose trace: on stderr with the flag, and nothing there without it.
+
+mod common;
+
+use common::{semantic, setup};
+
+const CONFIG: &str = "[commands]\nok = \"true\"\n[paths]\n\"/apps\" = \"/usr/bin\"";
+
+fn stderr_of(args: &[&str]) -> String {
+    let dir = setup(CONFIG);
+    let output = semantic(dir.path(), args).output().expect("run semantic");
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn translate_traces_each_step_with_the_flag() {
+    for flag in ["-v", "--verbose"] {
+        let stderr = stderr_of(&[flag, "translate", "ok", "/apps"]);
+        assert!(stderr.contains("semantic: config "), "{stderr}");
+        assert!(stderr.contains("semantic: ok → true\n"), "{stderr}");
+        assert!(stderr.contains("semantic: path /apps → /usr/bin\n"), "{stderr}");
+        assert!(stderr.contains("semantic: argv [\"true\", \"/usr/bin\"]\n"), "{stderr}");
+    }
+}
+
+#[test]
+fn translate_is_quiet_without_the_flag() {
+    assert_eq!(stderr_of(&["translate", "ok", "/apps"]), "");
+}
+
+#[test]
+fn init_traces_only_with_the_flag() {
+    let stderr = stderr_of(&["-v", "init", "bash"]);
+    assert!(stderr.contains("semantic: config "), "{stderr}");
+    assert_eq!(stderr_of(&["init", "bash"]), "");
+}